@@ -0,0 +1,72 @@
+/// The type affinity of a column, derived from its declared type.
+///
+/// See <https://www.sqlite.org/datatype3.html#determination_of_column_affinity>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Affinity {
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl Affinity {
+    pub(crate) fn from_declared_type(declared_type: &str) -> Self {
+        let declared_type = declared_type.to_ascii_uppercase();
+        let contains = |pattern: &str| declared_type.contains(pattern);
+
+        if contains("INT") {
+            Affinity::Integer
+        } else if contains("CHAR") || contains("CLOB") || contains("TEXT") {
+            Affinity::Text
+        } else if contains("BLOB") || declared_type.trim().is_empty() {
+            Affinity::Blob
+        } else if contains("REAL") || contains("FLOA") || contains("DOUB") {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    /// Returns an expression reading `column` with every value converted to a single
+    /// storage class, for outputs with typed columns.
+    ///
+    /// NUMERIC columns are read as reals, since they may hold both integers and reals.
+    #[cfg_attr(not(any(feature = "arrow", feature = "parquet")), allow(dead_code))]
+    pub(crate) fn cast(self, column: &str) -> String {
+        let storage_class = match self {
+            Affinity::Integer => "INTEGER",
+            Affinity::Real | Affinity::Numeric => "REAL",
+            Affinity::Text => "TEXT",
+            Affinity::Blob => "BLOB",
+        };
+
+        format!("CAST({column} AS {storage_class})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Affinity;
+
+    #[test]
+    fn test_from_declared_type() {
+        let cases = [
+            ("INTEGER", Affinity::Integer),
+            ("unsigned big int", Affinity::Integer),
+            ("VARCHAR(255)", Affinity::Text),
+            ("CLOB", Affinity::Text),
+            ("BLOB", Affinity::Blob),
+            ("", Affinity::Blob),
+            ("DOUBLE PRECISION", Affinity::Real),
+            ("float", Affinity::Real),
+            ("DECIMAL(10,5)", Affinity::Numeric),
+            ("BOOLEAN", Affinity::Numeric),
+            ("CHARINT", Affinity::Integer),
+            ("FLOATING POINT", Affinity::Integer),
+        ];
+        for (declared_type, expected) in cases {
+            assert_eq!(expected, Affinity::from_declared_type(declared_type));
+        }
+    }
+}
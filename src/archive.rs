@@ -0,0 +1,284 @@
+use std::{
+    fs,
+    io::{self, Seek as _},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time,
+};
+
+use crate::{Compression, ConnectionExt, DumpFormat, DumpOptions, Encryption};
+
+/// The size of the blocks making up a tar archive.
+const BLOCK_SIZE: usize = 512;
+
+/// The largest size the 11 octal digits of a header can hold, just under 8 GiB.
+const MAX_SIZE: u64 = 0o77777777777;
+
+/// Writes a tar archive holding `schema.sql` and one `data/{table}` file per table.
+///
+/// `schema.sql` holds the schema as SQL, while the data files hold the rows in the format of
+/// `options`. The files of a table are complete dumps on their own, so restoring `schema.sql`
+/// then any data file restores that table. Each file is spooled to a temporary file before it is
+/// added, since its size comes before it in the archive.
+pub(crate) fn dump(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    writer: &mut dyn io::Write,
+) -> Result<(), crate::Error> {
+    // Referenced rows are resolved across every table up front, since each file is a dump
+    // of its own tables only.
+    let mut options = crate::subset::close(conn, options)?.into_owned();
+    options.follow_foreign_keys = false;
+    // The database was checked once for the whole archive.
+    options.verify_source = false;
+    options.compression = Compression::None;
+    options.encryption = Encryption::None;
+    crate::blob::reset_manifest(&options)?;
+
+    // A deterministic archive is dated to the epoch, to be the same on every run.
+    let mtime = match options.deterministic {
+        true => 0,
+        false => time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+    };
+    let mut archive = Tar { writer, mtime };
+    let mut spool = Spool::new()?;
+
+    if !options.data_only {
+        let mut schema = options.clone().schema_only(true).format(DumpFormat::Sql);
+        schema.on_progress = None;
+        schema.on_metrics = None;
+
+        let size = spool.write(|file| conn.dump_with(&schema, file))?;
+        archive.append("schema.sql", size, &mut spool.file)?;
+    }
+
+    if !options.schema_only {
+        let extension = match options.format {
+            DumpFormat::Sql => "sql",
+            DumpFormat::Csv => "csv",
+            DumpFormat::Json => "json",
+            DumpFormat::Markdown => "md",
+            DumpFormat::Html => "html",
+            DumpFormat::Xml => "xml",
+        };
+        let tables = crate::dump::tables(conn, &options)?;
+
+        for table_name in crate::dump::data_tables(conn, &options)? {
+            // The rows of a virtual table are dumped as SQL from its shadow tables.
+            let shadow_tables = tables
+                .iter()
+                .filter(|(name, create_sql)| {
+                    options.format == DumpFormat::Sql
+                        && crate::dump::is_shadow_table(name, create_sql, &table_name)
+                })
+                .map(|(name, _)| name.clone());
+
+            let mut data = options.clone().data_only(true);
+            // Each file adds to the manifest of the BLOB values written by the others.
+            data.split = true;
+            data.include_tables = Some(
+                std::iter::once(table_name.clone())
+                    .chain(shadow_tables)
+                    .collect(),
+            );
+
+            let size = spool.write(|file| conn.dump_with(&data, file))?;
+            archive.append(
+                &format!("data/{}.{extension}", file_name(&table_name)),
+                size,
+                &mut spool.file,
+            )?;
+        }
+    }
+
+    archive.finish()?;
+    Ok(())
+}
+
+/// Replaces the characters of `table_name` that cannot appear in a file name.
+fn file_name(table_name: &str) -> String {
+    table_name.replace(['/', '\\', '\0'], "_")
+}
+
+/// A temporary file each file of the archive is written to in turn, removed when dropped.
+struct Spool {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl Spool {
+    fn new() -> io::Result<Self> {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "dumpqlite-archive-{}-{}.tmp",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut open = fs::OpenOptions::new();
+        open.read(true).write(true).create_new(true);
+        // The rows being archived are kept from the other users of the machine.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut open, 0o600);
+        let file = open.open(&path)?;
+
+        Ok(Self { path, file })
+    }
+
+    /// Replaces the contents of the file with what `write` writes to it, and returns their size
+    /// with the file rewound to be read.
+    fn write<F>(&mut self, write: F) -> Result<u64, crate::Error>
+    where
+        F: FnOnce(&mut fs::File) -> Result<(), crate::Error>,
+    {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        write(&mut self.file)?;
+
+        let size = self.file.stream_position()?;
+        self.file.rewind()?;
+        Ok(size)
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A writer of POSIX ustar archives.
+struct Tar<'a> {
+    writer: &'a mut dyn io::Write,
+    mtime: u64,
+}
+
+impl Tar<'_> {
+    /// Adds the file at `path` holding the `size` bytes read from `contents`.
+    fn append(&mut self, path: &str, size: u64, contents: &mut dyn io::Read) -> io::Result<()> {
+        // Paths and sizes that do not fit the header are stored in a PAX extended header.
+        let records = pax_records(path, size);
+        if !records.is_empty() {
+            self.write_entry(
+                &truncate(path, 100),
+                b'x',
+                records.len() as u64,
+                &mut records.as_bytes(),
+            )?;
+        }
+
+        self.write_entry(&truncate(path, 100), b'0', size, contents)
+    }
+
+    fn write_entry(
+        &mut self,
+        path: &str,
+        kind: u8,
+        size: u64,
+        contents: &mut dyn io::Read,
+    ) -> io::Result<()> {
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        write_octal(&mut header[100..108], 0o644);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        // The size in the extended header is read in place of that of the header.
+        write_octal(
+            &mut header[124..136],
+            if size > MAX_SIZE { 0 } else { size },
+        );
+        write_octal(&mut header[136..148], self.mtime);
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // The checksum is computed with its own field filled with spaces.
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|&byte| u64::from(byte)).sum::<u64>();
+        write_octal(&mut header[148..155], checksum);
+
+        self.writer.write_all(&header)?;
+        let copied = io::copy(&mut io::Read::take(contents, size), &mut self.writer)?;
+        if copied < size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let padding = (BLOCK_SIZE - (size % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE;
+        self.writer.write_all(&[0; BLOCK_SIZE][..padding])
+    }
+
+    /// Ends the archive with two empty blocks.
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.write_all(&[0; 2 * BLOCK_SIZE])?;
+        self.writer.flush()
+    }
+}
+
+/// Writes `value` as a NUL-terminated, zero-padded octal number filling `field`, which it must
+/// fit.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    debug_assert!(value < 1 << (3 * digits));
+    let octal = format!("{value:0digits$o}");
+    field[..digits].copy_from_slice(&octal.as_bytes()[octal.len() - digits..]);
+    field[digits] = 0;
+}
+
+/// Formats the PAX records of the file at `path` of `size` bytes that do not fit its header,
+/// if any.
+fn pax_records(path: &str, size: u64) -> String {
+    let mut records = String::new();
+    if path.len() > 100 {
+        records.push_str(&pax_record("path", path));
+    }
+    if size > MAX_SIZE {
+        records.push_str(&pax_record("size", &size.to_string()));
+    }
+    records
+}
+
+/// Formats a PAX record, whose length prefix counts the whole record including itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let rest = format!(" {key}={value}\n");
+    let mut len = rest.len() + 1;
+    while len.to_string().len() + rest.len() != len {
+        len = len.to_string().len() + rest.len();
+    }
+    format!("{len}{rest}")
+}
+
+/// Cuts `path` to at most `max` bytes without splitting a character.
+fn truncate(path: &str, max: usize) -> String {
+    let mut end = path.len().min(max);
+    while !path.is_char_boundary(end) {
+        end -= 1;
+    }
+    path[..end].to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_pax_record() {
+        assert_eq!("12 path=abc\n", super::pax_record("path", "abc"));
+        assert_eq!(
+            format!("101 path={}\n", "a".repeat(91)),
+            super::pax_record("path", &"a".repeat(91))
+        );
+    }
+
+    #[test]
+    fn test_pax_records() {
+        assert_eq!("", super::pax_records("schema.sql", super::MAX_SIZE));
+        assert_eq!(
+            "19 size=8589934592\n",
+            super::pax_records("schema.sql", super::MAX_SIZE + 1)
+        );
+        assert_eq!(
+            format!("111 path={}\n19 size=8589934592\n", "a".repeat(99) + "bb"),
+            super::pax_records(&("a".repeat(99) + "bb"), 1 << 33)
+        );
+    }
+}
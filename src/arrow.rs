@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use arrow_array::{
+    builder::{BinaryBuilder, Float64Builder, Int64Builder, StringBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+
+use rusqlite::types::ValueRef;
+
+use crate::{
+    affinity::Affinity, dump::Rewriter, CancellationToken, DumpOptions, Error, StatementKind,
+};
+
+/// An iterator over the rows of a table as Arrow [`RecordBatch`]es,
+/// returned by [`ConnectionExt::dump_arrow`](crate::ConnectionExt::dump_arrow).
+///
+/// Batches are read lazily, `batch_size` rows at a time. Each batch is read by its own query,
+/// so wrap the iteration in a transaction to get a consistent view of a table being written to.
+pub struct RecordBatches<'conn> {
+    conn: &'conn rusqlite::Connection,
+    schema: SchemaRef,
+    table_name: String,
+    sql: String,
+    rewriter: Rewriter,
+    cancellation: Option<CancellationToken>,
+    keyset: bool,
+    position: i64,
+    /// The number of rows read so far.
+    read: u64,
+    batch_size: usize,
+    done: bool,
+}
+
+impl<'conn> RecordBatches<'conn> {
+    pub(crate) fn new(
+        conn: &'conn rusqlite::Connection,
+        options: &DumpOptions,
+        table_name: &str,
+    ) -> Result<Self, crate::Error> {
+        let column_types = crate::dump::column_types(conn, options, table_name)?;
+        let rewriter = Rewriter::new(
+            options,
+            table_name,
+            column_types.iter().map(|(name, _)| name),
+        );
+
+        let (fields, expressions): (Vec<_>, Vec<_>) = column_types
+            .iter()
+            .map(|(name, declared_type)| {
+                let affinity = Affinity::from_declared_type(declared_type);
+                let data_type = match affinity {
+                    Affinity::Integer => DataType::Int64,
+                    Affinity::Real | Affinity::Numeric => DataType::Float64,
+                    Affinity::Text => DataType::Utf8,
+                    Affinity::Blob => DataType::Binary,
+                };
+
+                let column = crate::quote::identifier(name);
+                (Field::new(name, data_type, true), affinity.cast(&column))
+            })
+            .unzip();
+
+        let (sql, keyset) =
+            crate::dump::page_query(conn, options, table_name, &expressions.join(", "));
+
+        Ok(Self {
+            conn,
+            schema: Arc::new(Schema::new(fields)),
+            table_name: table_name.to_owned(),
+            sql,
+            rewriter,
+            cancellation: options.cancellation.clone(),
+            keyset,
+            position: if keyset { i64::MIN } else { 0 },
+            read: 0,
+            batch_size: options.batch_size,
+            done: options.schema_only,
+        })
+    }
+
+    /// The schema shared by every batch, with column types mapped from their SQLite affinities.
+    pub fn schema(&self) -> SchemaRef {
+        Arc::clone(&self.schema)
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>, crate::Error> {
+        enum Builder {
+            Int64(Int64Builder),
+            Float64(Float64Builder),
+            Utf8(StringBuilder),
+            Binary(BinaryBuilder),
+        }
+
+        let mut builders = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| match field.data_type() {
+                DataType::Int64 => Builder::Int64(Int64Builder::new()),
+                DataType::Float64 => Builder::Float64(Float64Builder::new()),
+                DataType::Utf8 => Builder::Utf8(StringBuilder::new()),
+                _ => Builder::Binary(BinaryBuilder::new()),
+            })
+            .collect::<Vec<_>>();
+
+        let table_name = &self.table_name;
+        let in_table = |row| Error::in_table(table_name, StatementKind::Data, row);
+
+        let mut stmt = self
+            .conn
+            .prepare_cached(&self.sql)
+            .map_err(in_table(None))?;
+        let mut rows = stmt
+            .query((self.position, self.batch_size as i64))
+            .map_err(in_table(None))?;
+        let mut count = 0;
+
+        loop {
+            let index = Some(self.read + count as u64);
+            let Some(row) = rows.next().map_err(in_table(index))? else {
+                break;
+            };
+
+            for (i, builder) in builders.iter_mut().enumerate() {
+                let value = row.get_ref(i).map_err(in_table(index))?;
+                let rewritten = self.rewriter.rewrite(i, value);
+                let value = rewritten.as_ref().map_or(value, ValueRef::from);
+
+                match (builder, value) {
+                    (Builder::Int64(builder), ValueRef::Integer(value)) => {
+                        builder.append_value(value)
+                    }
+                    (Builder::Int64(builder), _) => builder.append_null(),
+                    (Builder::Float64(builder), ValueRef::Real(value)) => {
+                        builder.append_value(value)
+                    }
+                    (Builder::Float64(builder), ValueRef::Integer(value)) => {
+                        builder.append_value(value as f64)
+                    }
+                    (Builder::Float64(builder), _) => builder.append_null(),
+                    (Builder::Utf8(builder), ValueRef::Text(text)) => {
+                        builder.append_value(String::from_utf8_lossy(text))
+                    }
+                    (Builder::Utf8(builder), _) => builder.append_null(),
+                    (Builder::Binary(builder), ValueRef::Blob(bytes)) => {
+                        builder.append_value(bytes)
+                    }
+                    (Builder::Binary(builder), _) => builder.append_null(),
+                }
+            }
+
+            if self.keyset {
+                self.position = row.get(builders.len()).map_err(in_table(index))?;
+            }
+            count += 1;
+        }
+
+        self.read += count as u64;
+        if !self.keyset {
+            self.position += count;
+        }
+        if count < self.batch_size as i64 {
+            self.done = true;
+        }
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let columns = builders
+            .into_iter()
+            .map(|builder| -> ArrayRef {
+                match builder {
+                    Builder::Int64(mut builder) => Arc::new(builder.finish()),
+                    Builder::Float64(mut builder) => Arc::new(builder.finish()),
+                    Builder::Utf8(mut builder) => Arc::new(builder.finish()),
+                    Builder::Binary(mut builder) => Arc::new(builder.finish()),
+                }
+            })
+            .collect();
+
+        Ok(Some(RecordBatch::try_new(self.schema(), columns)?))
+    }
+}
+
+impl Iterator for RecordBatches<'_> {
+    type Item = Result<RecordBatch, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let token = self.cancellation.clone();
+        let batch = crate::cancel::check(token.as_ref())
+            .and_then(|()| self.next_batch())
+            .map_err(|err| crate::cancel::map_interrupted(token.as_ref(), err));
+        if batch.is_err() {
+            self.done = true;
+        }
+
+        batch.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{cast::AsArray, types::Float64Type, types::Int64Type, Array};
+
+    use crate::{ConnectionExt, DumpOptions};
+
+    #[test]
+    fn test_dump_arrow() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB, score REAL);
+CREATE TABLE pairs (k TEXT PRIMARY KEY, v INTEGER) WITHOUT ROWID;
+INSERT INTO users VALUES (1, 'alice', X'cafe', 1.5), (5, NULL, NULL, NULL), (9, 42, 'text', '2.5');
+INSERT INTO pairs VALUES ('a', 1), ('b', 2), ('c', 3);"#,
+        )?;
+
+        let options = DumpOptions::new().batch_size(2);
+        let batches = conn
+            .dump_arrow(&options, "users")?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![2, 1],
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>()
+        );
+
+        let ids = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 5, 9], ids);
+
+        let names = batches[0].column(1).as_string::<i32>();
+        assert_eq!("alice", names.value(0));
+        assert!(names.is_null(1));
+        assert_eq!("42", batches[1].column(1).as_string::<i32>().value(0));
+        assert_eq!(b"text", batches[1].column(2).as_binary::<i32>().value(0));
+        assert_eq!(
+            2.5,
+            batches[1].column(3).as_primitive::<Float64Type>().value(0)
+        );
+
+        let batches = conn
+            .dump_arrow(&options, "pairs")?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![2, 1],
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_arrow_shadowed_rowid() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE t (rowid INTEGER, v TEXT);
+INSERT INTO t VALUES (7, 'a'), (7, 'b'), (3, 'c');"#,
+        )?;
+
+        let batches = conn
+            .dump_arrow(&DumpOptions::new().batch_size(1), "t")?
+            .collect::<Result<Vec<_>, _>>()?;
+        let values = batches
+            .iter()
+            .map(|batch| batch.column(1).as_string::<i32>().value(0).to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a", "b", "c"], values);
+
+        Ok(())
+    }
+}
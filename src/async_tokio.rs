@@ -0,0 +1,150 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use crate::{ConnectionExt, DumpOptions};
+
+/// The size of the chunks handed from the blocking dump to the async writer.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The number of chunks buffered before the dump waits for the writer to catch up.
+const CHANNEL_CAPACITY: usize = 4;
+
+/// Writes a dump to an async `writer`, as [`ConnectionExt::dump_with`] does to a blocking one.
+///
+/// The dump runs on tokio's blocking thread pool, so the connection is shared through a mutex
+/// that stays locked until the dump ends. The output is handed over in chunks, and the dump
+/// pauses while the writer is behind, so no runtime worker is blocked.
+pub async fn dump_async<W>(
+    conn: Arc<Mutex<rusqlite::Connection>>,
+    options: DumpOptions,
+    writer: &mut W,
+) -> Result<(), crate::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let (mut chunks, task) = spawn_dump(conn, options);
+
+    while let Some(chunk) = chunks.recv().await {
+        if let Err(err) = writer.write_all(&chunk).await {
+            // Dropping the receiver stops the dump at its next write.
+            drop(chunks);
+            let _ = task.await;
+            return Err(err.into());
+        }
+    }
+    writer.flush().await?;
+
+    join(task.await)
+}
+
+/// Starts a dump on the blocking thread pool, sending its output in chunks.
+pub(crate) fn spawn_dump(
+    conn: Arc<Mutex<rusqlite::Connection>>,
+    options: DumpOptions,
+) -> (
+    mpsc::Receiver<Vec<u8>>,
+    JoinHandle<Result<(), crate::Error>>,
+) {
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+    let task = tokio::task::spawn_blocking(move || {
+        let conn = conn
+            .lock()
+            .map_err(|_| io::Error::other("connection mutex poisoned"))?;
+
+        let mut writer = ChunkWriter {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+        };
+        conn.dump_with(&options, &mut writer)?;
+        io::Write::flush(&mut writer)?;
+
+        Ok(())
+    });
+
+    (receiver, task)
+}
+
+/// Turns the outcome of the task spawned by [`spawn_dump`] into the outcome of the dump.
+pub(crate) fn join(
+    result: Result<Result<(), crate::Error>, tokio::task::JoinError>,
+) -> Result<(), crate::Error> {
+    match result {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => Err(io::Error::other(err).into()),
+    }
+}
+
+/// A blocking writer sending what is written to it in chunks of about [`CHUNK_SIZE`] bytes.
+struct ChunkWriter {
+    sender: mpsc::Sender<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl io::Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender
+            .blocking_send(chunk)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{ConnectionExt, DumpOptions};
+
+    #[test]
+    fn test_dump_async() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+INSERT INTO users (name) SELECT 'user ' || i FROM n;"#,
+        )?;
+
+        let mut expected = Vec::new();
+        conn.dump(&mut expected)?;
+
+        let conn = Arc::new(Mutex::new(conn));
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+        let mut writer = Vec::new();
+        runtime.block_on(super::dump_async(
+            Arc::clone(&conn),
+            DumpOptions::new(),
+            &mut writer,
+        ))?;
+        assert_eq!(
+            std::str::from_utf8(&expected).unwrap(),
+            std::str::from_utf8(&writer).unwrap()
+        );
+
+        Ok(())
+    }
+}
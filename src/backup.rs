@@ -0,0 +1,215 @@
+use std::{fmt, sync::Arc, thread, time::Duration};
+
+use rusqlite::backup::{Backup, StepResult};
+
+use crate::{CancellationToken, DumpOptions};
+
+/// The number of pages copied by each step of a backup, unless chosen otherwise.
+const PAGES_PER_STEP: usize = 1024;
+
+/// How long a backup waits before retrying a step blocked by another connection.
+const BUSY_PAUSE: Duration = Duration::from_millis(50);
+
+/// Options controlling how [`ConnectionExt::backup_to`](crate::ConnectionExt::backup_to)
+/// copies a database with SQLite's online backup API.
+///
+/// The copy runs as [`rusqlite::backup::Backup::run_to_completion`] does, with the progress
+/// callback and the cancellation checked after each step.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dumpqlite::BackupOptions;
+///
+/// let options = BackupOptions::new()
+///     .pages_per_step(256)
+///     .pause(Duration::from_millis(10));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BackupOptions {
+    schema: String,
+    pages_per_step: usize,
+    pause: Duration,
+    on_progress: Option<OnBackupProgress>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self {
+            schema: "main".to_owned(),
+            pages_per_step: PAGES_PER_STEP,
+            pause: Duration::ZERO,
+            on_progress: None,
+            cancellation: None,
+        }
+    }
+}
+
+impl BackupOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects the attached schema to copy, such as `main` or the name given to
+    /// `ATTACH DATABASE`. Defaults to `main`.
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.schema = schema.into();
+        self
+    }
+
+    /// The number of pages copied by each step. The source is only locked while a step reads
+    /// its pages, so smaller steps block writers for less time. Defaults to 1024.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pages_per_step` is zero.
+    pub fn pages_per_step(mut self, pages_per_step: usize) -> Self {
+        assert!(pages_per_step > 0, "pages per step must be positive");
+        self.pages_per_step = pages_per_step;
+        self
+    }
+
+    /// How long to sleep between steps, leaving the source to other connections and
+    /// throttling the I/O of the backup. Defaults to no pause.
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Calls `f` after every step with the number of pages copied so far. `f` should return
+    /// quickly, as it runs on the thread performing the backup.
+    pub fn on_progress<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&BackupProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(OnBackupProgress(Arc::new(f)));
+        self
+    }
+
+    /// Aborts the backup with [`Error::Cancelled`](crate::Error::Cancelled) before its next
+    /// step once `token` is cancelled.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+}
+
+/// The progress of a backup, passed to the callback set with [`BackupOptions::on_progress`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct BackupProgress {
+    /// The number of pages copied so far.
+    pub pages_copied: u64,
+    /// The number of pages of the source database. It changes if the source grows or shrinks
+    /// during the backup.
+    pub page_count: u64,
+}
+
+/// A callback receiving the progress of a backup, see [`BackupOptions::on_progress`].
+#[derive(Clone)]
+struct OnBackupProgress(Arc<dyn Fn(&BackupProgress) + Send + Sync>);
+
+impl fmt::Debug for OnBackupProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnBackupProgress(..)")
+    }
+}
+
+/// Copies the schema of `conn` selected by `options` into the main schema of `dest`,
+/// replacing its content, through a [`Backup`] that `dest` is borrowed by until it is done.
+pub(crate) fn backup_to(
+    conn: &rusqlite::Connection,
+    dest: &mut rusqlite::Connection,
+    options: &BackupOptions,
+) -> Result<(), crate::Error> {
+    copy(
+        conn,
+        &options.schema,
+        dest,
+        options.pages_per_step,
+        options.pause,
+        options.cancellation.as_ref(),
+        |pages_copied, page_count| {
+            if let Some(callback) = &options.on_progress {
+                (callback.0)(&BackupProgress {
+                    pages_copied,
+                    page_count,
+                });
+            }
+        },
+    )
+}
+
+/// Copies the dumped schema of `conn` into a new in-memory database, if
+/// [`DumpOptions::backup_to_memory`] is enabled.
+pub(crate) fn to_memory(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Option<rusqlite::Connection>, crate::Error> {
+    if !options.backup_to_memory {
+        return Ok(None);
+    }
+
+    let mut copy = rusqlite::Connection::open_in_memory()?;
+    self::copy(
+        conn,
+        &options.schema,
+        &mut copy,
+        PAGES_PER_STEP,
+        Duration::ZERO,
+        options.cancellation.as_ref(),
+        |copied, pages| crate::progress::Progress::backup(options, copied, pages),
+    )?;
+
+    Ok(Some(copy))
+}
+
+/// Copies the schema `schema` of `conn` into the main schema of `dest`, `pages_per_step`
+/// pages at a time with a `pause` after each step, calling `on_step` with the number of pages
+/// copied so far and the number of pages to copy.
+///
+/// The pages of the database are copied a few at a time, so that `conn` is only locked while
+/// each step reads them. SQLite starts the copy over when another connection writes to the
+/// database in between, so the copy holds a single point in time.
+fn copy(
+    conn: &rusqlite::Connection,
+    schema: &str,
+    dest: &mut rusqlite::Connection,
+    pages_per_step: usize,
+    pause: Duration,
+    token: Option<&CancellationToken>,
+    mut on_step: impl FnMut(u64, u64),
+) -> Result<(), crate::Error> {
+    let pages_per_step = i32::try_from(pages_per_step).unwrap_or(i32::MAX);
+    let backup = Backup::new_with_names(conn, schema, dest, rusqlite::MAIN_DB)?;
+
+    // `Backup::run_to_completion` takes no state along with its progress callback, so the
+    // steps are run here to call `on_step` and check `token` in between.
+    loop {
+        crate::cancel::check(token)?;
+
+        let step = backup.step(pages_per_step)?;
+        let progress = backup.progress();
+        on_step(
+            (progress.pagecount - progress.remaining) as u64,
+            progress.pagecount as u64,
+        );
+
+        match step {
+            StepResult::Done => break,
+            StepResult::More if !pause.is_zero() => thread::sleep(pause),
+            StepResult::Busy | StepResult::Locked => thread::sleep(BUSY_PAUSE),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// The options dumping the in-memory copy made by [`to_memory`] in place of the database.
+pub(crate) fn copy_options(options: &DumpOptions) -> DumpOptions {
+    let mut options = options.clone().schema("main");
+    options.backup_to_memory = false;
+    options
+}
@@ -0,0 +1,496 @@
+use std::{fs, io, path::PathBuf, process::ExitCode};
+
+use dumpqlite::{
+    BlobEncoding, Codegen, Compression, ConnectionExt as _, Dialect, DumpFormat, DumpOptions,
+    OversizedBlob,
+};
+
+const USAGE: &str = "\
+Usage: dumpqlite [OPTIONS] <DATABASE>
+       dumpqlite codegen [--from-row] [OPTIONS] <DATABASE>
+
+Writes a dump of DATABASE, like the SQLite CLI's .dump command,
+or with codegen, Rust structs for its tables.
+
+Options:
+  -f, --format <FORMAT>   Output format: sql, csv, json, markdown, html or xml [default: sql]
+  -d, --dialect <NAME>    SQL dialect: sqlite, postgres, mysql, duckdb or tsql [default: sqlite]
+      --blob-encoding <ENCODING>
+                          BLOB encoding of the json and csv formats: base64 or hex [default: base64]
+  -t, --table <TABLE>     Only dump TABLE. Can be given more than once
+  -x, --exclude <TABLE>   Leave TABLE out of the dump. Can be given more than once
+  -o, --output <FILE>     Write to FILE instead of standard output
+  -z, --compress <CODEC>  Compress the output with CODEC: gzip or zstd
+      --level <LEVEL>     The compression level [default: 6 for gzip, 3 for zstd]
+      --schema-only       Only dump the schema
+      --data-only         Only dump the rows
+      --no-transaction    Do not wrap the dump in a transaction
+      --commit-every <N>  Commit and reopen the transaction every N inserted rows
+      --transaction-per-table
+                          Wrap the rows of each table in a transaction of their own
+      --snapshot          Read the whole database at a single point in time
+      --verify-source     Check the integrity of the database before dumping it
+      --foreign-key-check Warn about the rows breaking a foreign key before dumping
+      --backup-to-memory  Copy the database into memory first, then dump the copy
+      --deterministic     Dump tables by name and rows by rowid, for diffing
+      --preserve-rowids   Keep the rowid of tables without an INTEGER PRIMARY KEY
+      --header-pragmas    Also dump the user_version and application_id pragmas
+      --clean             Drop the tables, views, indexes and triggers before creating them
+      --if-not-exists     Create the tables, views, indexes and triggers only if missing
+      --pretty-schema     Reformat the statements creating tables, views, indexes and triggers
+      --metadata          Start the dump with comments recording how it was made
+      --newlines          Do not escape the newlines of TEXT values
+      --crlf              End the lines of the dump with CRLF
+      --terminator <TEXT> End statements with TEXT instead of ';'
+      --blank-lines       Write a blank line before the statements of each table
+      --checksum          End the dump with a comment holding its SHA-256 checksum
+      --max-blob-size <BYTES>
+                          Write a placeholder in place of the larger BLOB values
+      --blob-dir <DIR>    Write the larger BLOB values to files in DIR instead
+      --archive           Write a tar archive with schema.sql and one file per table
+      --manifest <FILE>   Write the byte range, row count and SHA-256 of the rows of
+                          each table within the output to FILE, as JSON
+      --split-size <MB>   Write the output in parts of at most MB megabytes each,
+                          to FILE.001, FILE.002 and so on
+      --lenient           Leave out the tables and rows that fail to read, with a warning
+      --from-row          Also generate TryFrom<&rusqlite::Row> impls for the codegen structs
+  -h, --help              Print this help
+  -V, --version           Print the version
+";
+
+/// The parsed command line.
+#[derive(Debug)]
+struct Args {
+    database: PathBuf,
+    output: Option<PathBuf>,
+    /// Where to write the manifest of the dump written to `output`.
+    manifest: Option<PathBuf>,
+    /// The largest size in bytes of each part of the dump written next to `output`.
+    split_size: Option<u64>,
+    archive: bool,
+    lenient: bool,
+    /// Warn about the rows breaking a foreign key before dumping.
+    foreign_key_check: bool,
+    /// Generate structs for the tables instead of dumping them.
+    codegen: Option<Codegen>,
+    options: DumpOptions,
+}
+
+/// What the command line asks for.
+#[derive(Debug)]
+enum Command {
+    Dump(Box<Args>),
+    Help,
+    Version,
+}
+
+fn parse_format(format: &str) -> Result<DumpFormat, String> {
+    match format.to_ascii_lowercase().as_str() {
+        "sql" => Ok(DumpFormat::Sql),
+        "csv" => Ok(DumpFormat::Csv),
+        "json" => Ok(DumpFormat::Json),
+        "markdown" | "md" => Ok(DumpFormat::Markdown),
+        "html" => Ok(DumpFormat::Html),
+        "xml" => Ok(DumpFormat::Xml),
+        _ => Err(format!("unknown format '{format}'")),
+    }
+}
+
+fn parse_dialect(dialect: &str) -> Result<Dialect, String> {
+    match dialect.to_ascii_lowercase().as_str() {
+        "sqlite" => Ok(Dialect::Sqlite),
+        "postgres" | "postgresql" | "pg" => Ok(Dialect::Postgres),
+        "mysql" | "mariadb" => Ok(Dialect::Mysql),
+        "duckdb" => Ok(Dialect::Duckdb),
+        "tsql" | "mssql" | "sqlserver" => Ok(Dialect::Tsql),
+        _ => Err(format!("unknown dialect '{dialect}'")),
+    }
+}
+
+fn parse_blob_encoding(encoding: &str) -> Result<BlobEncoding, String> {
+    match encoding.to_ascii_lowercase().as_str() {
+        "base64" => Ok(BlobEncoding::Base64),
+        "hex" => Ok(BlobEncoding::Hex),
+        _ => Err(format!("unknown blob encoding '{encoding}'")),
+    }
+}
+
+fn parse_rows(rows: &str) -> Result<usize, String> {
+    match rows.parse::<usize>() {
+        Ok(rows) if rows > 0 => Ok(rows),
+        _ => Err(format!("invalid row count '{rows}'")),
+    }
+}
+
+fn parse_bytes(bytes: &str) -> Result<usize, String> {
+    bytes
+        .parse::<usize>()
+        .map_err(|_| format!("invalid size '{bytes}'"))
+}
+
+fn parse_megabytes(megabytes: &str) -> Result<u64, String> {
+    match megabytes.parse::<u64>() {
+        Ok(megabytes @ 1..) => megabytes
+            .checked_mul(1024 * 1024)
+            .ok_or_else(|| format!("invalid size '{megabytes}'")),
+        _ => Err(format!("invalid size '{megabytes}'")),
+    }
+}
+
+fn parse_compression(codec: &str, level: Option<&str>) -> Result<Compression, String> {
+    let level = |default: i64| match level {
+        Some(level) => level
+            .parse::<i64>()
+            .map_err(|_| format!("invalid level '{level}'")),
+        None => Ok(default),
+    };
+
+    match codec.to_ascii_lowercase().as_str() {
+        "gzip" | "gz" => match level(6)? {
+            level @ 0..=9 => Ok(Compression::Gzip(level as u32)),
+            level => Err(format!("gzip level {level} is not between 0 and 9")),
+        },
+        "zstd" | "zst" => match level(3)? {
+            level @ 1..=22 => Ok(Compression::Zstd(level as i32)),
+            level => Err(format!("zstd level {level} is not between 1 and 22")),
+        },
+        _ => Err(format!("unknown codec '{codec}'")),
+    }
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    let mut codegen = args.next_if(|arg| arg == "codegen").map(|_| Codegen::new());
+    let mut database = None;
+    let mut output = None;
+    let mut manifest = None;
+    let mut split_size = None;
+    let mut codec = None;
+    let mut level = None;
+    let mut max_blob_size = None;
+    let mut blob_dir = None;
+    let mut archive = false;
+    let mut lenient = false;
+    let mut foreign_key_check = false;
+    let mut from_row = false;
+    let mut options = DumpOptions::new();
+
+    while let Some(arg) = args.next() {
+        // Both `--flag value` and `--flag=value` are accepted.
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for '{flag}'"))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-f" | "--format" => options = options.format(parse_format(&value()?)?),
+            "-d" | "--dialect" => options = options.dialect(parse_dialect(&value()?)?),
+            "--blob-encoding" => options = options.blob_encoding(parse_blob_encoding(&value()?)?),
+            "-t" | "--table" => options = options.include_tables([value()?]),
+            "-x" | "--exclude" => options = options.exclude_tables([value()?]),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-z" | "--compress" => codec = Some(value()?),
+            "--level" => level = Some(value()?),
+            "--schema-only" => options = options.schema_only(true),
+            "--data-only" => options = options.data_only(true),
+            "--no-transaction" => options = options.transaction(false),
+            "--transaction-per-table" => options = options.transaction_per_table(true),
+            "--commit-every" => options = options.commit_every(parse_rows(&value()?)?),
+            "--snapshot" => options = options.snapshot(true),
+            "--verify-source" => options = options.verify_source(true),
+            "--foreign-key-check" => foreign_key_check = true,
+            "--backup-to-memory" => options = options.backup_to_memory(true),
+            "--deterministic" => options = options.deterministic(true),
+            "--preserve-rowids" => options = options.preserve_rowids(true),
+            "--header-pragmas" => options = options.header_pragmas(true),
+            "--clean" => options = options.clean(true),
+            "--if-not-exists" => options = options.if_not_exists(true),
+            "--pretty-schema" => options = options.pretty_schema(true),
+            "--metadata" => options = options.metadata_header(true),
+            "--newlines" => options = options.newlines(true),
+            "--crlf" => options = options.crlf(true),
+            "--terminator" => options = options.statement_terminator(value()?),
+            "--blank-lines" => options = options.blank_lines(true),
+            "--checksum" => options = options.checksum(true),
+            "--max-blob-size" => max_blob_size = Some(parse_bytes(&value()?)?),
+            "--blob-dir" => blob_dir = Some(PathBuf::from(value()?)),
+            "--archive" => archive = true,
+            "--manifest" => manifest = Some(PathBuf::from(value()?)),
+            "--split-size" => split_size = Some(parse_megabytes(&value()?)?),
+            "--lenient" => lenient = true,
+            "--from-row" => from_row = true,
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option '{arg}'"));
+            }
+            _ if database.is_some() => return Err(format!("unexpected argument '{arg}'")),
+            _ => database = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let database = database.ok_or("missing DATABASE")?;
+    match (codec, level) {
+        (Some(codec), level) => {
+            options = options.compression(parse_compression(&codec, level.as_deref())?)
+        }
+        (None, Some(_)) => return Err("'--level' requires '--compress'".to_owned()),
+        (None, None) => {}
+    }
+    match (max_blob_size, blob_dir) {
+        (Some(size), Some(dir)) => {
+            options = options.max_blob_size(size, OversizedBlob::External(dir))
+        }
+        (Some(size), None) => options = options.max_blob_size(size, OversizedBlob::Placeholder),
+        (None, Some(_)) => return Err("'--blob-dir' requires '--max-blob-size'".to_owned()),
+        (None, None) => {}
+    }
+    if manifest.is_some() && output.is_none() {
+        return Err("'--manifest' requires '--output'".to_owned());
+    }
+    if split_size.is_some() {
+        if output.is_none() {
+            return Err("'--split-size' requires '--output'".to_owned());
+        }
+        if archive || manifest.is_some() {
+            return Err(
+                "'--split-size' cannot be used with '--archive' or '--manifest'".to_owned(),
+            );
+        }
+    }
+    match &mut codegen {
+        Some(codegen) => *codegen = codegen.clone().from_row(from_row),
+        None if from_row => return Err("'--from-row' requires 'codegen'".to_owned()),
+        None => {}
+    }
+    Ok(Command::Dump(Box::new(Args {
+        database,
+        output,
+        manifest,
+        split_size,
+        archive,
+        lenient,
+        foreign_key_check,
+        codegen,
+        options,
+    })))
+}
+
+fn run(args: &Args) -> Result<(), dumpqlite::Error> {
+    let conn = rusqlite::Connection::open_with_flags(
+        &args.database,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+
+    if args.foreign_key_check {
+        for violation in conn.foreign_key_check_with(&args.options)? {
+            match violation.rowid {
+                Some(rowid) => eprintln!(
+                    "dumpqlite: warning: row {rowid} of {} refers to a missing row of {}",
+                    violation.table, violation.parent
+                ),
+                None => eprintln!(
+                    "dumpqlite: warning: a row of {} refers to a missing row of {}",
+                    violation.table, violation.parent
+                ),
+            }
+        }
+    }
+
+    if let (Some(path), Some(max_bytes)) = (&args.output, args.split_size) {
+        let options = args.options.clone().strict(!args.lenient);
+        return conn.dump_in_parts(&options, max_bytes, |part| {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{part:03}"));
+            fs::File::create(name)
+        });
+    }
+
+    match &args.output {
+        Some(path) => dump(&conn, args, fs::File::create(path)?),
+        None => dump(&conn, args, io::stdout().lock()),
+    }
+}
+
+fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    args: &Args,
+    mut writer: W,
+) -> Result<(), dumpqlite::Error> {
+    if let Some(codegen) = &args.codegen {
+        let schema = conn.schema_with(&args.options.clone().strict(!args.lenient))?;
+        writer.write_all(codegen.generate(&schema).as_bytes())?;
+    } else if args.archive {
+        conn.dump_archive(&args.options.clone().strict(!args.lenient), &mut writer)?;
+    } else if let Some(path) = &args.manifest {
+        let options = args.options.clone().strict(!args.lenient);
+        let manifest = conn.dump_with_manifest(&options, &mut writer)?;
+        fs::write(path, manifest.to_json() + "\n")?;
+    } else if args.lenient {
+        for warning in conn.dump_with_warnings(&args.options, &mut writer)? {
+            match warning.rowid {
+                Some(rowid) => eprintln!(
+                    "dumpqlite: warning: skipped row {rowid} of {}: {}",
+                    warning.table, warning.cause
+                ),
+                None => eprintln!(
+                    "dumpqlite: warning: skipped {}: {}",
+                    warning.table, warning.cause
+                ),
+            }
+        }
+    } else {
+        conn.dump_with(&args.options, &mut writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Dump(args)) => args,
+        Ok(Command::Help) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Version) => {
+            println!("dumpqlite {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("dumpqlite: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // A closed pipe, as in `dumpqlite db.sqlite | head`, is not an error.
+        Err(dumpqlite::Error::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe => {
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("dumpqlite: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Command;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        super::parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let Ok(Command::Dump(args)) = parse(&[
+            "--format=csv",
+            "-t",
+            "users",
+            "--exclude",
+            "logs",
+            "--data-only",
+            "-o",
+            "out.csv",
+            "db.sqlite",
+        ]) else {
+            panic!("expected a dump command");
+        };
+        assert_eq!("db.sqlite", args.database.to_str().unwrap());
+        assert_eq!(
+            Some("out.csv"),
+            args.output.as_deref().and_then(|path| path.to_str())
+        );
+
+        assert!(matches!(parse(&["-h", "db.sqlite"]), Ok(Command::Help)));
+        assert!(matches!(
+            parse(&["--lenient", "db.sqlite"]),
+            Ok(Command::Dump(args)) if args.lenient
+        ));
+        assert!(matches!(
+            parse(&["--foreign-key-check", "db.sqlite"]),
+            Ok(Command::Dump(args)) if args.foreign_key_check
+        ));
+        assert!(matches!(
+            parse(&["-z", "gzip", "--level=9", "db.sqlite"]),
+            Ok(Command::Dump(args)) if format!("{:?}", args.options).contains("Gzip(9)")
+        ));
+        assert!(matches!(
+            parse(&["--compress", "zstd", "db.sqlite"]),
+            Ok(Command::Dump(args)) if format!("{:?}", args.options).contains("Zstd(3)")
+        ));
+        assert_eq!(
+            "gzip level 12 is not between 0 and 9",
+            parse(&["-z", "gzip", "--level", "12", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!(
+            "invalid row count '0'",
+            parse(&["--commit-every=0", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!(
+            "'--blob-dir' requires '--max-blob-size'",
+            parse(&["--blob-dir=blobs", "db.sqlite"]).unwrap_err()
+        );
+        assert!(matches!(
+            parse(&["codegen", "--from-row", "-t", "users", "db.sqlite"]),
+            Ok(Command::Dump(args)) if args.codegen.is_some()
+        ));
+        assert_eq!(
+            "'--from-row' requires 'codegen'",
+            parse(&["--from-row", "db.sqlite"]).unwrap_err()
+        );
+        assert!(matches!(
+            parse(&["--manifest", "out.json", "-o", "out.sql", "db.sqlite"]),
+            Ok(Command::Dump(args)) if args.manifest.is_some()
+        ));
+        assert_eq!(
+            "'--manifest' requires '--output'",
+            parse(&["--manifest=out.json", "db.sqlite"]).unwrap_err()
+        );
+        assert!(matches!(
+            parse(&["--split-size", "5000", "-o", "out.sql", "db.sqlite"]),
+            Ok(Command::Dump(args)) if args.split_size == Some(5000 * 1024 * 1024)
+        ));
+        assert_eq!(
+            "'--split-size' requires '--output'",
+            parse(&["--split-size=100", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!(
+            "invalid size '0'",
+            parse(&["--split-size=0", "-o", "out.sql", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!("missing DATABASE", parse(&["--schema-only"]).unwrap_err());
+        assert_eq!(
+            "unknown format 'yaml'",
+            parse(&["-f", "yaml", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!(
+            "unknown dialect 'oracle'",
+            parse(&["--dialect=oracle", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!(
+            "unknown blob encoding 'base32'",
+            parse(&["--blob-encoding", "base32", "db.sqlite"]).unwrap_err()
+        );
+        assert_eq!(
+            "missing value for '--table'",
+            parse(&["db.sqlite", "--table"]).unwrap_err()
+        );
+        assert_eq!(
+            "unexpected argument 'other.sqlite'",
+            parse(&["db.sqlite", "other.sqlite"]).unwrap_err()
+        );
+    }
+}
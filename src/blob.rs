@@ -0,0 +1,311 @@
+use std::{
+    fmt, fs,
+    io::{self, Read as _, Write as _},
+    path::Path,
+};
+
+use rusqlite::types::{Value, ValueRef};
+
+use crate::{quote, DumpOptions, Error, OversizedBlob, StatementKind};
+
+/// The size above which a BLOB value is not read with its row, but streamed on its own.
+pub(crate) const LARGE_BLOB: usize = 1024 * 1024;
+
+/// The number of bytes of a large BLOB read and written at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The name of the file listing the BLOB values written by [`OversizedBlob::External`].
+const MANIFEST: &str = "manifest.csv";
+
+/// The expressions reading `columns` with their large BLOB values left out, followed by the
+/// rowid, selected by `rowid`, and the flags read by [`Flags`].
+///
+/// The BLOB values larger than [`DumpOptions::max_blob_size`] are read as their length, and
+/// those to be streamed, if `stream` is set, as NULL. The type and length of a value are read
+/// without loading it, so the values left out are never read whole.
+pub(crate) fn expressions(
+    options: &DumpOptions,
+    columns: &[String],
+    rowid: &str,
+    stream: bool,
+) -> String {
+    let max = options.max_blob_size.as_ref().map(|(size, _)| *size);
+    let branches = |column: &str, oversized: &str, streamed: &str| {
+        let mut branches = String::new();
+        if let Some(max) = max {
+            branches.push_str(&format!(
+                " WHEN typeof({column}) = 'blob' AND length({column}) > {max} THEN {oversized}"
+            ));
+        }
+        if stream {
+            branches.push_str(&format!(
+                " WHEN typeof({column}) = 'blob' AND length({column}) > {LARGE_BLOB} THEN {streamed}"
+            ));
+        }
+        branches
+    };
+
+    let values = columns
+        .iter()
+        .map(|column| {
+            let column = quote::identifier(column);
+            let length = format!("length({column})");
+            format!(
+                "CASE{} ELSE {column} END",
+                branches(&column, &length, "NULL")
+            )
+        })
+        .collect::<Vec<_>>();
+    // The flags are concatenated to an empty string, so that they are read as text even for
+    // a single column.
+    let flags = std::iter::once("''".to_owned())
+        .chain(columns.iter().map(|column| {
+            let column = quote::identifier(column);
+            format!("CASE{} ELSE 0 END", branches(&column, "2", "1"))
+        }))
+        .collect::<Vec<_>>();
+
+    format!("{}, {rowid}, {}", values.join(", "), flags.join(" || "))
+}
+
+/// The BLOB values of a row left out of its values, as selected by [`expressions`].
+pub(crate) struct Flags {
+    pub(crate) rowid: i64,
+    /// A `0`, `1` when streamed, or `2` when oversized, for each column.
+    flags: String,
+}
+
+impl Flags {
+    /// Reads the rowid and flags following the `len` values of `row`, or `None` if no value
+    /// of the row was left out.
+    pub(crate) fn read(row: &rusqlite::Row<'_>, len: usize) -> rusqlite::Result<Option<Self>> {
+        let flags = row.get::<_, String>(len + 1)?;
+        if flags.bytes().all(|flag| flag == b'0') {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            rowid: row.get(len)?,
+            flags,
+        }))
+    }
+
+    /// Whether the value of column `i` is a large BLOB, read as NULL, to be streamed.
+    pub(crate) fn is_streamed(&self, i: usize) -> bool {
+        self.flags.as_bytes().get(i) == Some(&b'1')
+    }
+
+    /// Whether the value of column `i` is a BLOB larger than the maximum size, read as its
+    /// length.
+    fn is_oversized(&self, i: usize) -> bool {
+        self.flags.as_bytes().get(i) == Some(&b'2')
+    }
+}
+
+/// A BLOB value larger than [`DumpOptions::max_blob_size`], left out of a dump.
+#[derive(Debug)]
+struct Oversized {
+    size: usize,
+    max: usize,
+}
+
+impl fmt::Display for Oversized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BLOB of {} bytes is larger than {} bytes",
+            self.size, self.max
+        )
+    }
+}
+
+impl std::error::Error for Oversized {}
+
+/// The row of a table whose values are being dumped.
+pub(crate) struct Row<'a> {
+    pub(crate) table_name: &'a str,
+    pub(crate) rowid: Option<i64>,
+    /// The index of the row among those read, counting from zero.
+    pub(crate) index: u64,
+    pub(crate) flags: Option<&'a Flags>,
+}
+
+/// Returns what is written in place of `value`, read from column `i` named `column` of `row`,
+/// if it is a BLOB larger than [`DumpOptions::max_blob_size`].
+pub(crate) fn replace_oversized(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    row: &Row<'_>,
+    i: usize,
+    column: &str,
+    value: ValueRef<'_>,
+) -> Result<Option<Value>, crate::Error> {
+    let Some((max, policy)) = &options.max_blob_size else {
+        return Ok(None);
+    };
+    let stored = row.flags.filter(|flags| flags.is_oversized(i));
+    let size = match (stored, value) {
+        (Some(_), ValueRef::Integer(size)) => size as usize,
+        (None, ValueRef::Blob(bytes)) if bytes.len() > *max => bytes.len(),
+        _ => return Ok(None),
+    };
+
+    let replacement = match policy {
+        OversizedBlob::Null | OversizedBlob::Placeholder => {
+            let cause = rusqlite::Error::FromSqlConversionFailure(
+                i,
+                rusqlite::types::Type::Blob,
+                Box::new(Oversized { size, max: *max }),
+            );
+            crate::warning::warn(options, row.table_name, row.rowid, cause);
+
+            match policy {
+                OversizedBlob::Placeholder => Value::Text(format!("[BLOB of {size} bytes]")),
+                _ => Value::Null,
+            }
+        }
+        OversizedBlob::External(dir) => {
+            let key = match row.rowid {
+                Some(rowid) => rowid.to_string(),
+                None => format!("row{}", row.index),
+            };
+            let file_name = format!("{}.{}.{key}.bin", encode(row.table_name), encode(column));
+            fs::create_dir_all(dir)?;
+            let mut file = fs::File::create(dir.join(&file_name))?;
+            match (stored, value) {
+                (Some(flags), _) => copy(
+                    conn,
+                    options,
+                    row.table_name,
+                    column,
+                    flags.rowid,
+                    &mut file,
+                )?,
+                (None, value) => file.write_all(value.as_bytes().unwrap_or_default())?,
+            }
+            append_manifest(dir, &file_name, row.table_name, column, size)?;
+
+            Value::Text(file_name)
+        }
+    };
+
+    Ok(Some(replacement))
+}
+
+/// Percent-encodes the bytes of `name` other than ASCII letters, digits, `-` and `_`, so that
+/// it can be part of a file name.
+fn encode(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for b in name.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => encoded.push(char::from(b)),
+            b => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Adds the BLOB value written to `file_name` to the manifest of `dir`, creating it with its
+/// header if needed.
+fn append_manifest(
+    dir: &Path,
+    file_name: &str,
+    table_name: &str,
+    column: &str,
+    size: usize,
+) -> io::Result<()> {
+    let mut manifest = fs::File::options()
+        .create(true)
+        .append(true)
+        .open(dir.join(MANIFEST))?;
+    if manifest.metadata()?.len() == 0 {
+        crate::csv::write_record(&mut manifest, ["file", "table", "column", "size"])?;
+    }
+    crate::csv::write_record(
+        &mut manifest,
+        [file_name, table_name, column, &size.to_string()],
+    )
+}
+
+/// Removes the manifest of the directory of [`OversizedBlob::External`], if selected, at the
+/// start of a dump, so that it lists the files of that dump only, those of an earlier dump
+/// being written again.
+///
+/// The dumps of the tables of a split dump add to the manifest of the others.
+pub(crate) fn reset_manifest(options: &DumpOptions) -> io::Result<()> {
+    let Some((_, OversizedBlob::External(dir))) = &options.max_blob_size else {
+        return Ok(());
+    };
+    if options.split {
+        return Ok(());
+    }
+
+    match fs::remove_file(dir.join(MANIFEST)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the BLOB value of `column` in the row `rowid` of `table_name` a chunk at a time,
+/// passing each chunk to `f`.
+fn for_each_chunk<F>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    column: &str,
+    rowid: i64,
+    mut f: F,
+) -> Result<(), crate::Error>
+where
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut blob = conn
+        .blob_open(options.schema.as_str(), table_name, column, rowid, true)
+        .map_err(Error::in_table(table_name, StatementKind::Data, None))?;
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = blob.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(());
+        }
+        f(&chunk[..read])?;
+    }
+}
+
+/// Copies the BLOB value of `column` in the row `rowid` of `table_name` to `writer`.
+fn copy<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    column: &str,
+    rowid: i64,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    for_each_chunk(conn, options, table_name, column, rowid, |chunk| {
+        writer.write_all(chunk)
+    })
+}
+
+/// Writes the BLOB value of `column` in the row `rowid` of `table_name` as an SQL blob literal,
+/// reading and hex-encoding it a chunk at a time.
+pub(crate) fn write_literal<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    column: &str,
+    rowid: i64,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let mut hex = String::with_capacity(CHUNK_SIZE * 2);
+
+    writer.write_all(b"X'")?;
+    for_each_chunk(conn, options, table_name, column, rowid, |chunk| {
+        hex.clear();
+        crate::dump::write_hex(&mut hex, chunk);
+        writer.write_all(hex.as_bytes())
+    })?;
+    writer.write_all(b"'")?;
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// A handle that aborts the dumps it was given to, see [`DumpOptions::cancellation`].
+///
+/// Clones share the same state, so a clone can be moved to another thread to cancel a dump
+/// running on this one. A cancelled dump fails with [`Error::Cancelled`].
+///
+/// [`DumpOptions::cancellation`]: crate::DumpOptions::cancellation
+/// [`Error::Cancelled`]: crate::Error::Cancelled
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    interrupt: Option<rusqlite::InterruptHandle>,
+}
+
+impl CancellationToken {
+    /// Creates a token that stops a dump before its next row.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a token that also interrupts the statement running on `conn`,
+    /// so that a dump stuck in a slow query is cancelled as well.
+    ///
+    /// Like [`rusqlite::InterruptHandle::interrupt`], cancelling while no statement is running
+    /// may interrupt the next statement run on `conn`.
+    pub fn for_connection(conn: &rusqlite::Connection) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                interrupt: Some(conn.get_interrupt_handle()),
+            }),
+        }
+    }
+
+    /// Cancels the dumps using this token. Cannot be undone.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        if let Some(interrupt) = &self.inner.interrupt {
+            interrupt.interrupt();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Fails with [`Error::Cancelled`](crate::Error::Cancelled) once `token` is cancelled.
+pub(crate) fn check(token: Option<&CancellationToken>) -> Result<(), crate::Error> {
+    match token {
+        Some(token) if token.is_cancelled() => Err(crate::Error::Cancelled),
+        _ => Ok(()),
+    }
+}
+
+/// Reports the error of a statement interrupted by `token` as a cancellation.
+pub(crate) fn map_interrupted(
+    token: Option<&CancellationToken>,
+    err: crate::Error,
+) -> crate::Error {
+    let source = match &err {
+        crate::Error::Rusqlite(source) | crate::Error::Table { source, .. } => Some(source),
+        _ => None,
+    };
+    let interrupted = matches!(
+        source,
+        Some(rusqlite::Error::SqliteFailure(failure, _))
+            if failure.code == rusqlite::ErrorCode::OperationInterrupted
+    );
+
+    match token {
+        Some(token) if interrupted && token.is_cancelled() => crate::Error::Cancelled,
+        _ => err,
+    }
+}
@@ -0,0 +1,177 @@
+use rusqlite::types::Value;
+
+use crate::{dump, quote, DumpOptions, Error, StatementKind};
+
+/// How far the rows of each table had been dumped, as returned by
+/// [`ConnectionExt::checkpoint`](crate::ConnectionExt::checkpoint), so that the next dump
+/// given it through [`DumpOptions::since`] only holds the rows added since.
+///
+/// Each table is marked with the largest value of a column, its rowid unless another column
+/// is chosen with [`DumpOptions::incremental_column`]. The marks can be stored between runs
+/// and built again with [`mark`](Self::mark).
+///
+/// ```
+/// use dumpqlite::{Checkpoint, ConnectionExt as _, DumpOptions};
+///
+/// let conn = rusqlite::Connection::open_in_memory()?;
+/// conn.execute_batch("CREATE TABLE log (id INTEGER PRIMARY KEY, message TEXT);")?;
+/// conn.execute_batch("INSERT INTO log (message) VALUES ('first');")?;
+/// let checkpoint = conn.checkpoint()?;
+///
+/// conn.execute_batch("INSERT INTO log (message) VALUES ('second');")?;
+/// let options = DumpOptions::new().data_only(true).since(checkpoint);
+/// let mut delta = Vec::new();
+/// conn.dump_with(&options, &mut delta)?;
+///
+/// let delta = String::from_utf8(delta).unwrap();
+/// assert!(delta.contains("'second'") && !delta.contains("'first'"));
+/// # Ok::<(), dumpqlite::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Checkpoint {
+    data_version: Option<i64>,
+    marks: Vec<TableMark>,
+}
+
+/// The largest value of a column of a table when a [`Checkpoint`] was taken.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct TableMark {
+    pub table: String,
+    /// The column the rows of `table` are compared by, such as `rowid`.
+    pub column: String,
+    /// The largest value of `column`, or NULL when the table was empty.
+    pub value: Value,
+}
+
+impl Checkpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `table` as dumped up to the rows whose `column` is `value`. Marking the same table
+    /// again replaces its mark.
+    pub fn mark(
+        mut self,
+        table: impl Into<String>,
+        column: impl Into<String>,
+        value: impl Into<Value>,
+    ) -> Self {
+        let table = table.into();
+        self.marks
+            .retain(|mark| !mark.table.eq_ignore_ascii_case(&table));
+        self.marks.push(TableMark {
+            table,
+            column: column.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// The mark of every table, in the order they were dumped or marked.
+    pub fn marks(&self) -> &[TableMark] {
+        &self.marks
+    }
+
+    /// The `PRAGMA data_version` of the connection the checkpoint was taken on, if any.
+    ///
+    /// It only changes when another connection commits to the database, and is only
+    /// comparable with the value read on the same connection, as a cheap check that the next
+    /// dump would be empty.
+    pub fn data_version(&self) -> Option<i64> {
+        self.data_version
+    }
+
+    /// The condition matching the rows of `table_name` added since the checkpoint, if the
+    /// table was marked with a value.
+    pub(crate) fn condition(&self, table_name: &str) -> Option<String> {
+        let mark = self
+            .marks
+            .iter()
+            .find(|mark| mark.table.eq_ignore_ascii_case(table_name))?;
+        if mark.value == Value::Null {
+            return None;
+        }
+
+        let mut condition = format!("{} > ", quote::identifier(&mark.column));
+        dump::write_value(
+            &mut condition,
+            &DumpOptions::default(),
+            (&mark.value).into(),
+        );
+        Some(condition)
+    }
+}
+
+/// Marks each table selected by `options` with the largest value of its incremental column.
+///
+/// Virtual tables are left out, as are tables without a rowid and without an incremental
+/// column, which [`DumpOptions::since`] dumps whole.
+pub(crate) fn checkpoint(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Checkpoint, crate::Error> {
+    let data_version = conn.query_row(
+        &format!(
+            "PRAGMA {}.data_version;",
+            quote::identifier(&options.schema)
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    let mut checkpoint = Checkpoint {
+        data_version: Some(data_version),
+        marks: Vec::new(),
+    };
+
+    for (table_name, create_sql) in dump::tables(conn, options)? {
+        if create_sql.starts_with("CREATE VIRTUAL TABLE") {
+            continue;
+        }
+
+        let Some(column) = column(conn, options, &table_name).map_err(Error::in_table(
+            &table_name,
+            StatementKind::Schema,
+            None,
+        ))?
+        else {
+            continue;
+        };
+
+        let value = conn
+            .query_row(
+                &format!(
+                    "SELECT max({}) FROM {};",
+                    quote::identifier(&column),
+                    options.qualified(&table_name)
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .map_err(Error::in_table(&table_name, StatementKind::Data, None))?;
+        checkpoint.marks.push(TableMark {
+            table: table_name,
+            column,
+            value,
+        });
+    }
+
+    Ok(checkpoint)
+}
+
+/// The column `table_name` is marked by: its incremental column, or else its rowid.
+fn column(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> rusqlite::Result<Option<String>> {
+    if let Some(column) = options.marked_column(table_name) {
+        return Ok(Some(column.to_owned()));
+    }
+    if let Some(alias) = dump::rowid_alias(conn, options, table_name)? {
+        return Ok(Some(alias));
+    }
+
+    let columns = dump::read_columns(conn, options, table_name)?;
+    Ok(dump::rowid_column(conn, options, table_name, &columns)?.map(str::to_owned))
+}
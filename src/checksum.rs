@@ -0,0 +1,114 @@
+use std::io;
+
+use crate::{dump, sha256::Sha256};
+
+/// The start of the line ending an SQL dump written with [`DumpOptions::checksum`](crate::DumpOptions::checksum).
+const PREFIX: &str = "-- sha256: ";
+
+/// The longest length of the trailer line, with its 64 hex digits and its line ending.
+const TRAILER_LEN: usize = PREFIX.len() + 64 + 2;
+
+/// A writer hashing what is written through it.
+pub(crate) struct Hashed<'a, W> {
+    writer: &'a mut W,
+    hash: Sha256,
+}
+
+impl<'a, W: io::Write> Hashed<'a, W> {
+    pub(crate) fn new(writer: &'a mut W) -> Self {
+        Self {
+            writer,
+            hash: Sha256::new(),
+        }
+    }
+
+    /// Ends the dump with the trailer holding the hash of what was written before it, on a
+    /// line ended by `line_ending`.
+    pub(crate) fn finish(self, line_ending: &str) -> io::Result<()> {
+        write!(
+            self.writer,
+            "{PREFIX}{}{line_ending}",
+            dump::hex(&self.hash.finish())
+        )
+    }
+}
+
+impl<W: io::Write> io::Write for Hashed<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hash.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Checks the SQL dump read from `reader` against the SHA-256 checksum on its last line, as
+/// written with [`DumpOptions::checksum`](crate::DumpOptions::checksum), to find out whether
+/// an archived dump is intact.
+///
+/// The dump is read uncompressed, in a single pass. It fails with an
+/// [`io::ErrorKind::InvalidData`] error if the dump does not end with a checksum, or if its
+/// content does not match it.
+///
+/// ```
+/// use dumpqlite::{ConnectionExt as _, DumpOptions};
+///
+/// let conn = rusqlite::Connection::open_in_memory()?;
+/// conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")?;
+/// let mut dump = Vec::new();
+/// conn.dump_with(&DumpOptions::new().checksum(true), &mut dump)?;
+/// dumpqlite::verify_checksum(&mut dump.as_slice())?;
+///
+/// dump[60] ^= 1;
+/// assert!(dumpqlite::verify_checksum(&mut dump.as_slice()).is_err());
+/// # Ok::<(), dumpqlite::Error>(())
+/// ```
+pub fn verify_checksum<R: io::Read>(reader: &mut R) -> Result<(), crate::Error> {
+    let mut hash = Sha256::new();
+    // The last bytes read, held back from the hash until they are known not to be the trailer.
+    let mut tail = Vec::with_capacity(TRAILER_LEN + 64 * 1024);
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        tail.extend_from_slice(&buffer[..read]);
+        if tail.len() > TRAILER_LEN {
+            let hashed = tail.len() - TRAILER_LEN;
+            hash.update(&tail[..hashed]);
+            tail.drain(..hashed);
+        }
+    }
+
+    // The trailer ends with `\n`, or `\r\n` if the dump was written with CRLF line endings.
+    let trailer_len = match tail.ends_with(b"\r\n") {
+        true => TRAILER_LEN,
+        false => TRAILER_LEN - 1,
+    };
+    let (content, trailer) = tail.split_at(tail.len().saturating_sub(trailer_len));
+    hash.update(content);
+    let expected = std::str::from_utf8(trailer)
+        .ok()
+        .and_then(|trailer| trailer.strip_prefix(PREFIX)?.strip_suffix('\n'))
+        .map(|expected| expected.strip_suffix('\r').unwrap_or(expected))
+        .filter(|expected| expected.len() == 64)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the dump has no checksum"))?;
+
+    let actual = dump::hex(&hash.finish());
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("checksum mismatch: the dump says {expected}, its content hashes to {actual}"),
+        )
+        .into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+/// Opens the SQLCipher database at `path` and unlocks it with `key`, as [`apply_key`] does.
+///
+/// ```
+/// use dumpqlite::ConnectionExt as _;
+///
+/// let path = std::env::temp_dir().join(format!("dumpqlite-doc-cipher-{}.db", std::process::id()));
+/// let conn = dumpqlite::open_encrypted(&path, "secret")?;
+/// conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")?;
+/// drop(conn);
+///
+/// let conn = dumpqlite::open_encrypted(&path, "secret")?;
+/// assert!(conn.dump_to_string()?.contains("INSERT INTO t VALUES(1);"));
+/// assert!(dumpqlite::open_encrypted(&path, "wrong").is_err());
+/// # std::fs::remove_file(&path).unwrap();
+/// # Ok::<(), dumpqlite::Error>(())
+/// ```
+pub fn open_encrypted<P: AsRef<Path>>(
+    path: P,
+    key: &str,
+) -> Result<rusqlite::Connection, crate::Error> {
+    let conn = rusqlite::Connection::open(path)?;
+    apply_key(&conn, key)?;
+    Ok(conn)
+}
+
+/// Unlocks the SQLCipher database of `conn` with `key`, before anything else is run on it,
+/// failing with [`Error::WrongKey`](crate::Error::WrongKey) if the key does not decrypt it.
+///
+/// `key` is a passphrase, or a raw key written as SQLCipher takes it, such as `x'2DD29CA8...'`.
+/// A connection already unlocked with `PRAGMA key` needs no call: it dumps like any other,
+/// and writes the dump in plain text.
+///
+/// SQLCipher refuses to copy pages between databases not encrypted alike, so that
+/// [`DumpOptions::backup_to_memory`](crate::DumpOptions::backup_to_memory) fails on an
+/// encrypted database, and [`ConnectionExt::backup_to`](crate::ConnectionExt::backup_to) needs
+/// a destination unlocked with the same key. Use
+/// [`DumpOptions::snapshot`](crate::DumpOptions::snapshot) for a consistent dump instead.
+pub fn apply_key(conn: &rusqlite::Connection, key: &str) -> Result<(), crate::Error> {
+    conn.pragma_update(None, "key", key)?;
+
+    // The key is only checked once a page is read.
+    match conn.query_row("SELECT count(*) FROM sqlite_schema;", [], |_| Ok(())) {
+        Ok(()) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(failure, _))
+            if failure.code == rusqlite::ErrorCode::NotADatabase =>
+        {
+            Err(crate::Error::WrongKey)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
@@ -0,0 +1,254 @@
+use std::{collections::HashSet, fmt::Write as _};
+
+use crate::{affinity::Affinity, Column, Schema, Table};
+
+/// The keywords that cannot be used as identifiers in Rust, but as raw identifiers.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Generates Rust struct definitions from a [`Schema`], one per table with a field per column,
+/// to keep typed models in sync with a database.
+///
+/// Field types are mapped from the affinity of their column: `i64` for INTEGER, `f64` for REAL
+/// and NUMERIC, `String` for TEXT and `Vec<u8>` for BLOB, while columns declared without a type
+/// hold any [`rusqlite::types::Value`]. Among NUMERIC columns, booleans are mapped to `bool`,
+/// and dates and times to `String`, as SQLite applications usually store them as text. Fields
+/// are wrapped in an `Option` unless their column is `NOT NULL` or an `INTEGER PRIMARY KEY`.
+///
+/// Columns whose names give the same field name are told apart by a number, as in `name_2`.
+///
+/// ```
+/// use dumpqlite::{Codegen, ConnectionExt};
+///
+/// let conn = rusqlite::Connection::open_in_memory()?;
+/// conn.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);")?;
+///
+/// let code = Codegen::new().generate(&conn.schema()?);
+/// assert!(code.contains("pub struct Users {\n    pub id: i64,\n    pub name: String,\n}"));
+/// # Ok::<(), dumpqlite::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Codegen {
+    from_row: bool,
+}
+
+impl Codegen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also generates a `TryFrom<&rusqlite::Row>` implementation for each struct, reading its
+    /// fields by column name. Disabled by default.
+    pub fn from_row(mut self, from_row: bool) -> Self {
+        self.from_row = from_row;
+        self
+    }
+
+    /// Returns the Rust source of the structs of the tables of `schema`.
+    pub fn generate(&self, schema: &Schema) -> String {
+        let mut code = String::new();
+        for (i, table) in schema.tables.iter().enumerate() {
+            if i > 0 {
+                code.push('\n');
+            }
+            self.write_table(&mut code, table);
+        }
+        code
+    }
+
+    fn write_table(&self, code: &mut String, table: &Table) {
+        let name = type_name(&table.name);
+        let mut names = HashSet::new();
+        let fields = table
+            .columns
+            .iter()
+            .map(|column| {
+                let mut field = field_name(&column.name);
+                let mut n = 1;
+                while !names.insert(field.clone()) {
+                    n += 1;
+                    field = field_name(&format!("{}_{n}", column.name));
+                }
+                (field, column)
+            })
+            .collect::<Vec<_>>();
+
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(code, "/// A row of the {:?} table.", table.name);
+        let _ = writeln!(code, "#[derive(Debug, Clone, PartialEq)]");
+        let _ = writeln!(code, "pub struct {name} {{");
+        for (field, column) in &fields {
+            let _ = writeln!(code, "    pub {field}: {},", field_type(table, column));
+        }
+        let _ = writeln!(code, "}}");
+
+        if !self.from_row {
+            return;
+        }
+        let _ = writeln!(code);
+        let _ = writeln!(code, "impl TryFrom<&rusqlite::Row<'_>> for {name} {{");
+        let _ = writeln!(code, "    type Error = rusqlite::Error;");
+        let _ = writeln!(code);
+        let _ = writeln!(
+            code,
+            "    fn try_from(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {{"
+        );
+        let _ = writeln!(code, "        Ok(Self {{");
+        for (field, column) in &fields {
+            let _ = writeln!(code, "            {field}: row.get({:?})?,", column.name);
+        }
+        let _ = writeln!(code, "        }})");
+        let _ = writeln!(code, "    }}");
+        let _ = writeln!(code, "}}");
+    }
+}
+
+/// The Rust type of the values of `column` of `table`.
+fn field_type(table: &Table, column: &Column) -> String {
+    let declared_type = column.declared_type.to_ascii_uppercase();
+    let ty = match Affinity::from_declared_type(&declared_type) {
+        _ if declared_type.trim().is_empty() => "rusqlite::types::Value",
+        Affinity::Integer => "i64",
+        Affinity::Numeric if declared_type.contains("BOOL") => "bool",
+        Affinity::Numeric if declared_type.contains("DATE") || declared_type.contains("TIME") => {
+            "String"
+        }
+        Affinity::Real | Affinity::Numeric => "f64",
+        Affinity::Text => "String",
+        Affinity::Blob => "Vec<u8>",
+    };
+    // An `INTEGER PRIMARY KEY` column holds the rowid, which is never NULL.
+    let rowid = column.declared_type.eq_ignore_ascii_case("INTEGER")
+        && table.primary_key == [column.name.as_str()];
+
+    match column.not_null || rowid {
+        true => ty.to_owned(),
+        false => format!("Option<{ty}>"),
+    }
+}
+
+/// Splits `name` into its lowercase words, at non-alphanumeric characters and at the start of
+/// each uppercase run of a camel case name.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous = None;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+        } else {
+            if c.is_uppercase()
+                && previous.is_some_and(|p: char| p.is_lowercase() || p.is_numeric())
+            {
+                words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            }
+            word.extend(c.to_lowercase());
+        }
+        previous = Some(c);
+    }
+    words.extend((!word.is_empty()).then_some(word));
+    words
+}
+
+/// The name of the struct of the table `name`, in upper camel case.
+fn type_name(name: &str) -> String {
+    let name = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    identifier(name, "Table")
+}
+
+/// The name of the field of the column `name`, in snake case.
+fn field_name(name: &str) -> String {
+    identifier(words(name).join("_"), "column")
+}
+
+/// Turns `name` into a valid identifier, replacing an empty name with `fallback`.
+fn identifier(name: String, fallback: &str) -> String {
+    match name {
+        name if name.is_empty() => fallback.to_owned(),
+        name if name.starts_with(|c: char| c.is_numeric()) => format!("_{name}"),
+        name if name == "self" || name == "Self" || name == "super" || name == "crate" => {
+            format!("{name}_")
+        }
+        name if KEYWORDS.contains(&name.as_str()) => format!("r#{name}"),
+        name => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Codegen, ConnectionExt};
+
+    #[test]
+    fn test_generate() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE "user accounts" (
+    id INTEGER PRIMARY KEY,
+    userName VARCHAR(32) NOT NULL,
+    score REAL,
+    balance DECIMAL(10, 2),
+    avatar BLOB,
+    "type",
+    "2fa" BOOLEAN NOT NULL,
+    created_at DATETIME,
+    user_name TEXT,
+    "user name 2" TEXT
+);"#,
+        )?;
+
+        let expected = r#"/// A row of the "user accounts" table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserAccounts {
+    pub id: i64,
+    pub user_name: String,
+    pub score: Option<f64>,
+    pub balance: Option<f64>,
+    pub avatar: Option<Vec<u8>>,
+    pub r#type: Option<rusqlite::types::Value>,
+    pub _2fa: bool,
+    pub created_at: Option<String>,
+    pub user_name_2: Option<String>,
+    pub user_name_2_2: Option<String>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for UserAccounts {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            user_name: row.get("userName")?,
+            score: row.get("score")?,
+            balance: row.get("balance")?,
+            avatar: row.get("avatar")?,
+            r#type: row.get("type")?,
+            _2fa: row.get("2fa")?,
+            created_at: row.get("created_at")?,
+            user_name_2: row.get("user_name")?,
+            user_name_2_2: row.get("user name 2")?,
+        })
+    }
+}
+"#;
+        let schema = conn.schema()?;
+        assert_eq!(expected, Codegen::new().from_row(true).generate(&schema));
+
+        Ok(())
+    }
+}
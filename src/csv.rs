@@ -0,0 +1,119 @@
+use std::io;
+
+use crate::DumpOptions;
+
+/// Writes every table as an RFC 4180 CSV block.
+///
+/// Each block starts with a record holding the table name, followed by a header record with
+/// the column names and one record per row. Blocks are separated by an empty line.
+///
+/// A first `sqlite_metadata` block records how blobs are encoded, in the same layout.
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let tables = crate::dump::data_tables(conn, options)?;
+
+    write_record(writer, [crate::dump::METADATA])?;
+    write_record(writer, ["blob_encoding"])?;
+    write_record(writer, [options.blob_encoding.name()])?;
+
+    for table_name in &tables {
+        write!(writer, "\r\n")?;
+        write_record(writer, [table_name.as_str()])?;
+
+        let columns = crate::dump::columns(conn, options, table_name)?;
+        write_record(writer, columns.iter().map(String::as_str))?;
+
+        if options.schema_only {
+            continue;
+        }
+
+        crate::dump::for_each_row(
+            conn,
+            options,
+            progress,
+            table_name,
+            &columns,
+            None,
+            |values| {
+                let values = values
+                    .iter()
+                    .map(|value| match value {
+                        rusqlite::types::ValueRef::Blob(b) => options.blob_encoding.encode(b),
+                        value => crate::dump::format_plain_value(*value),
+                    })
+                    .collect::<Vec<String>>();
+                Ok(write_record(writer, values.iter().map(String::as_str))?)
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_record<'a, W: io::Write>(
+    writer: &mut W,
+    fields: impl IntoIterator<Item = &'a str>,
+) -> io::Result<()> {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            write!(writer, "{field}")?;
+        }
+    }
+
+    write!(writer, "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConnectionExt, DumpFormat, DumpOptions};
+
+    #[test]
+    fn test_dump_csv() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB, score REAL);
+CREATE TABLE tags (name TEXT);
+CREATE VIRTUAL TABLE docs USING fts5(body);
+INSERT INTO users VALUES (1, 'alice', X'cafe', 1.5), (2, 'bob "the builder", jr.', NULL, NULL);
+INSERT INTO tags VALUES ('multi
+line');
+INSERT INTO docs VALUES ('searchable');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = DumpOptions::new().format(DumpFormat::Csv);
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = "sqlite_metadata\r\n\
+            blob_encoding\r\n\
+            base64\r\n\
+            \r\n\
+            users\r\n\
+            id,name,avatar,score\r\n\
+            1,alice,yv4=,1.5\r\n\
+            2,\"bob \"\"the builder\"\", jr.\",,\r\n\
+            \r\n\
+            tags\r\n\
+            name\r\n\
+            \"multi\nline\"\r\n\
+            \r\n\
+            docs\r\n\
+            body\r\n\
+            searchable\r\n";
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+}
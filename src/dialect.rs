@@ -0,0 +1,674 @@
+use std::borrow::Cow;
+
+use rusqlite::types::ValueRef;
+
+use crate::{
+    dump::{Kind, Step, TableRows},
+    quote,
+    schema::{Column, ForeignKey, IndexOrigin},
+    warning::skip,
+    DumpOptions, Error, StatementKind,
+};
+
+/// The SQL dialect of the statements written by the SQL format.
+///
+/// Dialects other than SQLite translate the schema of every table from its columns and
+/// constraints, rather than copying its `CREATE` statement. Column types are mapped from the
+/// SQLite affinity of their declared type, and `INTEGER PRIMARY KEY` columns, which SQLite fills
+/// in when left out, become identity columns. `CHECK` constraints, generated columns, partial
+/// and expression indexes, views, triggers and virtual tables are left out, as are the columns of
+/// an expression index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// SQLite, as written by the SQLite CLI's `.dump` command.
+    #[default]
+    Sqlite,
+    /// PostgreSQL, with foreign keys added once every table holds its rows.
+    Postgres,
+    /// MySQL and MariaDB, with InnoDB tables in `utf8mb4` and foreign keys added once every
+    /// table holds its rows.
+    ///
+    /// TEXT and BLOB columns that are part of a key become `varchar(255)` and `varbinary(255)`,
+    /// which MySQL can index whole, and REAL values that are not finite are written as `NULL`.
+    Mysql,
+    /// DuckDB, where `INTEGER PRIMARY KEY` columns take their default from a sequence starting
+    /// past the dumped rows.
+    ///
+    /// Foreign keys are left out, as DuckDB cannot add them to existing tables, and NUMERIC
+    /// columns become `double`, as its decimals have a fixed scale.
+    Duckdb,
+    /// SQL Server, with a `GO` batch separator after every table and foreign keys added once
+    /// every table holds its rows.
+    ///
+    /// TEXT and BLOB columns that are part of a key become `nvarchar(450)` and `varbinary(900)`,
+    /// which SQL Server can index, and REAL values that are not finite are written as `NULL`.
+    /// SQL Server takes at most 1000 rows per `INSERT`, which
+    /// [`rows_per_insert`](crate::DumpOptions::rows_per_insert) should not exceed.
+    Tsql,
+}
+
+impl Dialect {
+    /// The name of the dialect, as recorded in the output.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "sqlite",
+            Dialect::Postgres => "postgres",
+            Dialect::Mysql => "mysql",
+            Dialect::Duckdb => "duckdb",
+            Dialect::Tsql => "tsql",
+        }
+    }
+
+    /// Quotes `name` as an identifier.
+    pub(crate) fn identifier(self, name: &str) -> Cow<'_, str> {
+        match self {
+            Dialect::Sqlite => quote::identifier(name),
+            // Names are always quoted, so that their case survives.
+            Dialect::Mysql => Cow::Owned(format!("`{}`", name.replace('`', "``"))),
+            Dialect::Tsql => Cow::Owned(format!("[{}]", name.replace(']', "]]"))),
+            Dialect::Postgres | Dialect::Duckdb => {
+                Cow::Owned(format!("\"{}\"", name.replace('"', "\"\"")))
+            }
+        }
+    }
+
+    /// Quotes every name of `names` as an identifier, separated by `separator`.
+    fn identifiers<S: AsRef<str>>(self, names: &[S], separator: &str) -> String {
+        names
+            .iter()
+            .map(|name| self.identifier(name.as_ref()))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    /// Translates the type a column is declared with in SQLite, where `keyed` tells whether the
+    /// column is part of a key or an index.
+    fn column_type(self, column_type: ColumnType, keyed: bool) -> &'static str {
+        match (self, column_type) {
+            (Dialect::Tsql, ColumnType::Boolean) => "bit",
+            (Dialect::Tsql, ColumnType::Text) if keyed => "nvarchar(450)",
+            (Dialect::Tsql, ColumnType::Text) => "nvarchar(max)",
+            (Dialect::Tsql, ColumnType::Blob) if keyed => "varbinary(900)",
+            (Dialect::Tsql, ColumnType::Blob) => "varbinary(max)",
+            (Dialect::Tsql, ColumnType::Real) => "float",
+            (Dialect::Tsql, ColumnType::Timestamp) => "datetime2",
+            (Dialect::Tsql, ColumnType::Numeric) => "decimal(38,10)",
+            (_, ColumnType::Boolean) => "boolean",
+            (_, ColumnType::Integer) => "bigint",
+            (_, ColumnType::Date) => "date",
+            (Dialect::Mysql, ColumnType::Text) if keyed => "varchar(255)",
+            (Dialect::Mysql, ColumnType::Text) => "longtext",
+            (Dialect::Mysql, ColumnType::Blob) if keyed => "varbinary(255)",
+            (Dialect::Mysql, ColumnType::Blob) => "longblob",
+            (Dialect::Mysql, ColumnType::Real) => "double",
+            (Dialect::Mysql, ColumnType::Timestamp) => "datetime",
+            (Dialect::Mysql, ColumnType::Numeric) => "decimal(65,30)",
+            (Dialect::Duckdb, ColumnType::Text) => "varchar",
+            (Dialect::Duckdb, ColumnType::Blob) => "blob",
+            (Dialect::Duckdb, ColumnType::Real | ColumnType::Numeric) => "double",
+            (_, ColumnType::Text) => "text",
+            (_, ColumnType::Blob) => "bytea",
+            (_, ColumnType::Real) => "double precision",
+            (_, ColumnType::Timestamp) => "timestamp",
+            (_, ColumnType::Numeric) => "numeric",
+        }
+    }
+
+    /// The definition of an `INTEGER PRIMARY KEY` column, after its name.
+    fn identity(self) -> &'static str {
+        match self {
+            Dialect::Mysql => "bigint NOT NULL AUTO_INCREMENT",
+            Dialect::Tsql => "bigint IDENTITY(1,1)",
+            _ => "bigint GENERATED BY DEFAULT AS IDENTITY",
+        }
+    }
+
+    /// The options following the column definitions of a `CREATE TABLE` statement.
+    fn table_options(self) -> &'static str {
+        match self {
+            Dialect::Mysql => " ENGINE=InnoDB DEFAULT CHARSET=utf8mb4",
+            _ => "",
+        }
+    }
+
+    /// The statement starting the transaction of the dump.
+    pub(crate) fn begin(self) -> &'static str {
+        match self {
+            Dialect::Sqlite | Dialect::Tsql => "BEGIN TRANSACTION;",
+            _ => "BEGIN;",
+        }
+    }
+
+    /// The line ending a batch of statements, if the dialect has one.
+    fn batch_separator(self) -> Option<&'static str> {
+        match self {
+            Dialect::Tsql => Some("GO"),
+            _ => None,
+        }
+    }
+
+    /// Formats `value` as a boolean literal.
+    fn boolean(self, value: bool) -> &'static str {
+        match (self, value) {
+            (Dialect::Tsql, false) => "0",
+            (Dialect::Tsql, true) => "1",
+            (_, false) => "FALSE",
+            (_, true) => "TRUE",
+        }
+    }
+
+    /// Formats `value` as a literal, for a column of `column_type`.
+    pub(crate) fn literal(self, column_type: ColumnType, value: ValueRef<'_>) -> String {
+        match value {
+            ValueRef::Null => "NULL".to_owned(),
+            ValueRef::Integer(i) if column_type == ColumnType::Boolean => {
+                self.boolean(i != 0).to_owned()
+            }
+            ValueRef::Integer(i) => i.to_string(),
+            ValueRef::Real(f)
+                if matches!(self, Dialect::Mysql | Dialect::Tsql) && !f.is_finite() =>
+            {
+                "NULL".to_owned()
+            }
+            ValueRef::Real(f) if f.is_nan() => "'NaN'".to_owned(),
+            ValueRef::Real(f64::INFINITY) => "'Infinity'".to_owned(),
+            ValueRef::Real(f64::NEG_INFINITY) => "'-Infinity'".to_owned(),
+            ValueRef::Real(f) => crate::dump::format_real(f),
+            // Bytes that are not valid UTF-8 are replaced, as only blobs could keep them.
+            ValueRef::Text(t) => self.text(&String::from_utf8_lossy(t)),
+            ValueRef::Blob(b) => {
+                let hex = crate::dump::hex(b);
+                match self {
+                    Dialect::Mysql => format!("X'{hex}'"),
+                    Dialect::Duckdb => format!("from_hex('{hex}')"),
+                    Dialect::Tsql => format!("0x{hex}"),
+                    _ => format!("'\\x{hex}'::bytea"),
+                }
+            }
+        }
+    }
+
+    /// Quotes `text` as a string literal.
+    fn text(self, text: &str) -> String {
+        match self {
+            // MySQL reads backslashes in string literals as escapes.
+            Dialect::Mysql => quote::text(&text.replace('\\', "\\\\").replace('\0', "\\0")),
+            // Without the `N` prefix, SQL Server reads text in the code page of the database.
+            Dialect::Tsql => format!("N{}", quote::text(text)),
+            _ => quote::text(text),
+        }
+    }
+
+    /// Translates the default value of a column, if it is a constant the dialect understands.
+    fn default(self, column_type: ColumnType, default: &str) -> Option<String> {
+        let mut default = default.trim();
+        while let Some(inner) = default.strip_prefix('(').and_then(|d| d.strip_suffix(')')) {
+            default = inner.trim();
+        }
+
+        let number = default.parse::<f64>().is_ok();
+        if column_type == ColumnType::Boolean && number {
+            return Some(self.boolean(default.parse::<f64>() != Ok(0.0)).to_owned());
+        }
+
+        let constant = [
+            "CURRENT_TIMESTAMP",
+            "CURRENT_DATE",
+            "CURRENT_TIME",
+            "TRUE",
+            "FALSE",
+        ]
+        .iter()
+        .any(|constant| constant.eq_ignore_ascii_case(default));
+        let text = default.len() >= 2 && default.starts_with('\'') && default.ends_with('\'');
+        if !(number || constant || text) {
+            return None;
+        }
+
+        if self == Dialect::Tsql {
+            match default.to_ascii_uppercase().as_str() {
+                "TRUE" => return Some(self.boolean(true).to_owned()),
+                "FALSE" => return Some(self.boolean(false).to_owned()),
+                // SQL Server only has `CURRENT_TIMESTAMP`.
+                "CURRENT_DATE" | "CURRENT_TIME" => return None,
+                _ => {}
+            }
+        }
+
+        // MySQL only takes the defaults of TEXT and BLOB columns, and those computing a date or
+        // a time, as expressions in parentheses.
+        let expression = matches!(column_type, ColumnType::Text | ColumnType::Blob)
+            || ["CURRENT_DATE", "CURRENT_TIME"]
+                .iter()
+                .any(|constant| constant.eq_ignore_ascii_case(default));
+        Some(match self {
+            Dialect::Mysql if expression => format!("({default})"),
+            _ => default.to_owned(),
+        })
+    }
+}
+
+/// The kind of a column, from the SQLite affinity of its declared type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColumnType {
+    Boolean,
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Timestamp,
+    Date,
+    Numeric,
+}
+
+impl ColumnType {
+    /// The kind of a column declared with `declared`, following the affinity rules of SQLite
+    /// except for booleans, dates and timestamps, which SQLite stores as numbers or text.
+    pub(crate) fn of(declared: &str) -> Self {
+        let declared = declared.to_ascii_uppercase();
+        let name = declared.split('(').next().unwrap_or_default().trim();
+
+        match () {
+            _ if name == "BOOLEAN" || name == "BOOL" => Self::Boolean,
+            _ if declared.contains("INT") => Self::Integer,
+            _ if ["CHAR", "CLOB", "TEXT"]
+                .iter()
+                .any(|t| declared.contains(t)) =>
+            {
+                Self::Text
+            }
+            _ if declared.contains("BLOB") => Self::Blob,
+            _ if ["REAL", "FLOA", "DOUB"]
+                .iter()
+                .any(|t| declared.contains(t)) =>
+            {
+                Self::Real
+            }
+            _ if name == "DATETIME" || name == "TIMESTAMP" => Self::Timestamp,
+            _ if name == "DATE" => Self::Date,
+            // Columns without a type hold any value, which text represents best.
+            _ if name.is_empty() => Self::Text,
+            _ => Self::Numeric,
+        }
+    }
+}
+
+/// An index of a table, as listed by `PRAGMA index_list`.
+struct Index {
+    name: String,
+    unique: bool,
+    /// The indexed columns, each with whether it is sorted in descending order.
+    columns: Vec<(String, bool)>,
+}
+
+/// The schema of a table, read from the pragmas describing it.
+struct Table {
+    name: String,
+    /// The columns holding values of their own, which leaves out generated columns.
+    columns: Vec<Column>,
+    primary_key: Vec<String>,
+    /// The `INTEGER PRIMARY KEY` column, if any.
+    identity: Option<String>,
+    /// The first value past the `INTEGER PRIMARY KEY` column of every dumped row.
+    next_identity: i64,
+    unique: Vec<Vec<String>>,
+    indexes: Vec<Index>,
+    foreign_keys: Vec<ForeignKey>,
+}
+
+impl Table {
+    fn read(
+        conn: &rusqlite::Connection,
+        options: &DumpOptions,
+        table_name: &str,
+        create_sql: &str,
+    ) -> rusqlite::Result<Self> {
+        let table = crate::schema::Table::read(conn, options, table_name, create_sql)?;
+
+        let mut unique = Vec::new();
+        let mut indexes = Vec::new();
+        for index in table.indexes {
+            if index.origin == IndexOrigin::PrimaryKey || index.partial {
+                continue;
+            }
+
+            // An index over expressions lists them without a name.
+            let Some(columns) = index
+                .columns
+                .into_iter()
+                .map(|column| column.name.map(|name| (name, column.descending)))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            match index.origin {
+                IndexOrigin::Unique => {
+                    unique.push(columns.into_iter().map(|(name, _)| name).collect())
+                }
+                _ => indexes.push(Index {
+                    name: index.name,
+                    unique: index.unique,
+                    columns,
+                }),
+            }
+        }
+        unique.sort();
+
+        let identity = crate::dump::rowid_alias(conn, options, table_name)?;
+        let next_identity = match &identity {
+            Some(identity) if !options.schema_only => conn.query_row(
+                &format!(
+                    "SELECT coalesce(max({}), 0) + 1 FROM {}.{};",
+                    quote::identifier(identity),
+                    quote::identifier(&options.schema),
+                    quote::identifier(table_name)
+                ),
+                [],
+                |row| row.get(0),
+            )?,
+            _ => 1,
+        };
+
+        Ok(Self {
+            name: table.name,
+            primary_key: table.primary_key,
+            identity,
+            next_identity,
+            columns: table
+                .columns
+                .into_iter()
+                .filter(|column| !column.generated)
+                .collect(),
+            unique,
+            indexes,
+            foreign_keys: table.foreign_keys,
+        })
+    }
+
+    /// Whether `column` is part of a key or an index of the table.
+    fn is_keyed(&self, column: &str) -> bool {
+        self.primary_key
+            .iter()
+            .chain(self.unique.iter().flatten())
+            .chain(
+                self.indexes
+                    .iter()
+                    .flat_map(|index| index.columns.iter().map(|(name, _)| name)),
+            )
+            .chain(self.foreign_keys.iter().flat_map(|key| &key.columns))
+            .any(|name| name == column)
+    }
+
+    /// The name of the sequence filling in the `INTEGER PRIMARY KEY` column `identity`.
+    fn sequence(&self, identity: &str) -> String {
+        format!("{}_{identity}_seq", self.name)
+    }
+
+    /// The statement creating the sequence of the identity of the table, for DuckDB.
+    fn create_sequence(&self, dialect: Dialect) -> Option<String> {
+        let identity = self
+            .identity
+            .as_ref()
+            .filter(|_| dialect == Dialect::Duckdb)?;
+
+        Some(format!(
+            "CREATE SEQUENCE {} START {};",
+            dialect.identifier(&self.sequence(identity)),
+            self.next_identity
+        ))
+    }
+
+    fn create_table(&self, dialect: Dialect) -> String {
+        let mut definitions = self
+            .columns
+            .iter()
+            .map(|column| {
+                let name = dialect.identifier(&column.name);
+                if self.identity.as_deref() == Some(&column.name) {
+                    if dialect == Dialect::Duckdb {
+                        let sequence = self.sequence(&column.name);
+                        return format!(
+                            "{name} bigint DEFAULT nextval({})",
+                            quote::text(&sequence)
+                        );
+                    }
+                    return format!("{name} {}", dialect.identity());
+                }
+
+                let column_type = ColumnType::of(&column.declared_type);
+                let mut definition = format!(
+                    "{name} {}",
+                    dialect.column_type(column_type, self.is_keyed(&column.name))
+                );
+                if column.not_null {
+                    definition.push_str(" NOT NULL");
+                }
+                if let Some(default) = column
+                    .default
+                    .as_deref()
+                    .and_then(|default| dialect.default(column_type, default))
+                {
+                    definition.push_str(&format!(" DEFAULT {default}"));
+                }
+                definition
+            })
+            .collect::<Vec<_>>();
+
+        if !self.primary_key.is_empty() {
+            definitions.push(format!(
+                "PRIMARY KEY ({})",
+                dialect.identifiers(&self.primary_key, ", ")
+            ));
+        }
+        for columns in &self.unique {
+            definitions.push(format!("UNIQUE ({})", dialect.identifiers(columns, ", ")));
+        }
+
+        format!(
+            "CREATE TABLE {} ({}){};",
+            dialect.identifier(&self.name),
+            definitions.join(", "),
+            dialect.table_options()
+        )
+    }
+
+    fn foreign_keys(&self, dialect: Dialect) -> impl Iterator<Item = String> + '_ {
+        let foreign_keys = match dialect {
+            Dialect::Duckdb => &[][..],
+            _ => &self.foreign_keys[..],
+        };
+        foreign_keys.iter().map(move |key| {
+            let mut sql = format!(
+                "ALTER TABLE {} ADD FOREIGN KEY ({}) REFERENCES {} ({})",
+                dialect.identifier(&self.name),
+                dialect.identifiers(&key.columns, ", "),
+                dialect.identifier(&key.parent),
+                dialect.identifiers(&key.parent_columns, ", "),
+            );
+            for (event, action) in [("UPDATE", &key.on_update), ("DELETE", &key.on_delete)] {
+                // SQL Server has no `RESTRICT`, which `NO ACTION` comes closest to.
+                let restrict = action == "RESTRICT" && dialect == Dialect::Tsql;
+                if action != "NO ACTION" && !restrict {
+                    sql.push_str(&format!(" ON {event} {action}"));
+                }
+            }
+            sql.push(';');
+            sql
+        })
+    }
+
+    fn create_indexes(&self, dialect: Dialect) -> impl Iterator<Item = String> + '_ {
+        self.indexes.iter().map(move |index| {
+            let columns = index
+                .columns
+                .iter()
+                // DuckDB indexes have no order.
+                .map(|(name, desc)| match *desc && dialect != Dialect::Duckdb {
+                    true => format!("{} DESC", dialect.identifier(name)),
+                    false => dialect.identifier(name).into_owned(),
+                })
+                .collect::<Vec<_>>();
+            format!(
+                "CREATE {}INDEX {} ON {} ({});",
+                if index.unique { "UNIQUE " } else { "" },
+                dialect.identifier(&index.name),
+                dialect.identifier(&self.name),
+                columns.join(", ")
+            )
+        })
+    }
+
+    /// The statement turning on or off the insertion of rows with their own identity, for
+    /// SQL Server.
+    fn identity_insert(&self, dialect: Dialect, on: bool) -> Option<String> {
+        self.identity
+            .as_ref()
+            .filter(|_| dialect == Dialect::Tsql)?;
+
+        Some(format!(
+            "SET IDENTITY_INSERT {} {};",
+            dialect.identifier(&self.name),
+            if on { "ON" } else { "OFF" }
+        ))
+    }
+
+    /// The statement moving the identity of the table past the rows inserted with their own.
+    ///
+    /// MySQL moves its `AUTO_INCREMENT` counter by itself, and DuckDB starts its sequences past
+    /// the dumped rows, so neither needs one.
+    fn reset_identity(&self, dialect: Dialect) -> Option<String> {
+        if dialect != Dialect::Postgres {
+            return None;
+        }
+        let identity = self.identity.as_ref()?;
+        let table = dialect.identifier(&self.name);
+
+        Some(format!(
+            "SELECT setval(pg_get_serial_sequence({}, {}), max({})) FROM {table} HAVING max({}) IS NOT NULL;",
+            quote::text(&table),
+            quote::text(identity),
+            dialect.identifier(identity),
+            dialect.identifier(identity),
+        ))
+    }
+}
+
+/// Lists the steps of the SQL dump of `conn` in a dialect other than SQLite, in order.
+pub(crate) fn plan(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<Step>, crate::Error> {
+    let dialect = options.dialect;
+    let schema = !options.data_only;
+    let data = !options.schema_only;
+
+    let mut steps = Vec::new();
+    if options.single_transaction() {
+        steps.push(Step::Statement(Kind::Prologue, dialect.begin().to_owned()));
+    }
+
+    let all_tables = crate::dump::tables(conn, options)?;
+    let virtual_tables = all_tables
+        .iter()
+        .filter(|(_, create_sql)| create_sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(table_name, _)| table_name)
+        .collect::<Vec<_>>();
+
+    let mut tables = Vec::new();
+    for (table_name, create_sql) in &all_tables {
+        let is_virtual = virtual_tables.contains(&table_name)
+            || virtual_tables.iter().any(|virtual_table| {
+                crate::dump::is_shadow_table(table_name, create_sql, virtual_table)
+            });
+        if is_virtual {
+            continue;
+        }
+
+        match Table::read(conn, options, table_name, create_sql) {
+            Ok(table) => tables.push(table),
+            Err(err) => skip(options, table_name, None, err).map_err(Error::in_table(
+                table_name,
+                StatementKind::Schema,
+                None,
+            ))?,
+        }
+    }
+
+    for table in &tables {
+        if schema {
+            steps.extend(
+                table
+                    .create_sequence(dialect)
+                    .into_iter()
+                    .chain([table.create_table(dialect)])
+                    .map(|sql| Step::Statement(Kind::Schema, sql)),
+            );
+        }
+        if data {
+            let columns = table
+                .columns
+                .iter()
+                .map(|column| column.name.clone())
+                .collect::<Vec<_>>();
+            steps.extend(
+                table
+                    .identity_insert(dialect, true)
+                    .map(|sql| Step::Statement(Kind::Data, sql)),
+            );
+            steps.push(Step::Rows(TableRows {
+                table_name: table.name.clone(),
+                insert: format!(
+                    "INSERT INTO {} ({})",
+                    dialect.identifier(&table.name),
+                    dialect.identifiers(&columns, ",")
+                ),
+                types: table
+                    .columns
+                    .iter()
+                    .map(|column| ColumnType::of(&column.declared_type))
+                    .collect(),
+                columns,
+            }));
+            steps.extend(
+                table
+                    .identity_insert(dialect, false)
+                    .map(|sql| Step::Statement(Kind::Data, sql)),
+            );
+        }
+        if let Some(separator) = dialect.batch_separator() {
+            steps.push(Step::Statement(Kind::Schema, separator.to_owned()));
+        }
+    }
+
+    if data {
+        for table in &tables {
+            steps.extend(
+                table
+                    .reset_identity(dialect)
+                    .map(|sql| Step::Statement(Kind::Data, sql)),
+            );
+        }
+    }
+    if schema {
+        let before = steps.len();
+        for table in &tables {
+            steps.extend(
+                table
+                    .foreign_keys(dialect)
+                    .chain(table.create_indexes(dialect))
+                    .map(|sql| Step::Statement(Kind::Schema, sql)),
+            );
+        }
+        if let Some(separator) = dialect.batch_separator().filter(|_| steps.len() > before) {
+            steps.push(Step::Statement(Kind::Schema, separator.to_owned()));
+        }
+    }
+
+    if options.single_transaction() {
+        steps.push(Step::Statement(Kind::Epilogue, "COMMIT;".to_owned()));
+    }
+
+    Ok(steps)
+}
@@ -0,0 +1,384 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    io::{self, Write as _},
+};
+
+use rusqlite::types::{Value, ValueRef};
+
+use crate::{dump, quote, DumpOptions, Error, StatementKind};
+
+/// Writes the statements turning the rows of the tables of `conn` into those of `other`.
+///
+/// Tables missing from `other` are dropped and those missing from `conn` are created, as are
+/// the tables whose columns or key differ, which are dropped and created again. The rows of the
+/// other tables are matched by rowid, or by primary key for tables without one, and written as
+/// `DELETE`, `UPDATE` and `INSERT` statements in the order of their key.
+///
+/// Virtual tables and their shadow tables are left out, as are indexes, views and triggers.
+pub(crate) fn diff<W: io::Write>(
+    conn: &rusqlite::Connection,
+    other: &rusqlite::Connection,
+    options: &DumpOptions,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let mut buffered = io::BufWriter::with_capacity(options.buffer_size, writer);
+    let mut output = Output {
+        options,
+        writer: &mut buffered,
+        started: false,
+    };
+
+    let own_tables = tables(conn, options)?;
+    let other_tables = tables(other, options)?;
+
+    for table_name in own_tables.keys() {
+        if !other_tables.contains_key(table_name) {
+            output.write(&format!("DROP TABLE {};", quote::identifier(table_name)))?;
+        }
+    }
+
+    for (table_name, create_sql) in &other_tables {
+        let shape = Shape::read(other, options, table_name)?;
+        let source = match own_tables.contains_key(table_name) {
+            true => Some(Shape::read(conn, options, table_name)?),
+            false => None,
+        };
+
+        match source {
+            Some(source) if source == shape => {
+                diff_rows(Some(conn), other, options, table_name, &shape, &mut output)?;
+            }
+            source => {
+                if source.is_some() {
+                    output.write(&format!("DROP TABLE {};", quote::identifier(table_name)))?;
+                }
+                output.write(&format!("{create_sql};"))?;
+                diff_rows(None, other, options, table_name, &shape, &mut output)?;
+            }
+        }
+    }
+
+    output.finish()?;
+    buffered.flush()?;
+    Ok(())
+}
+
+/// Returns the `CREATE TABLE` statement of every table to be compared, by name.
+fn tables(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<BTreeMap<String, String>, crate::Error> {
+    let tables = dump::tables(conn, options)?;
+    let virtual_tables = tables
+        .iter()
+        .filter(|(_, create_sql)| create_sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(table_name, _)| table_name.clone())
+        .collect::<Vec<_>>();
+
+    Ok(tables
+        .into_iter()
+        .filter(|(table_name, create_sql)| {
+            !virtual_tables.iter().any(|virtual_table| {
+                virtual_table == table_name
+                    || dump::is_shadow_table(table_name, create_sql, virtual_table)
+            })
+        })
+        .collect())
+}
+
+/// The columns of a table and the key identifying its rows, which must match for the rows of
+/// two tables to be compared.
+#[derive(Debug, PartialEq, Eq)]
+struct Shape {
+    columns: Vec<String>,
+    /// The rowid, under the first name that is not a column, or the primary key, or every
+    /// column for a table with neither.
+    key: Vec<String>,
+    /// Whether the key is the rowid, which is not one of the columns.
+    rowid: bool,
+}
+
+impl Shape {
+    fn read(
+        conn: &rusqlite::Connection,
+        options: &DumpOptions,
+        table_name: &str,
+    ) -> Result<Self, crate::Error> {
+        let columns = dump::columns(conn, options, table_name)?;
+        let read_key = || {
+            if let Some(rowid) = dump::rowid_column(conn, options, table_name, &columns)? {
+                return Ok((vec![rowid.to_owned()], true));
+            }
+            let primary_key = dump::primary_key(conn, options, table_name)?;
+            match primary_key.is_empty() {
+                true => Ok((columns.clone(), false)),
+                false => Ok((primary_key, false)),
+            }
+        };
+        let (key, rowid) =
+            read_key().map_err(Error::in_table(table_name, StatementKind::Schema, None))?;
+
+        Ok(Self {
+            columns,
+            key,
+            rowid,
+        })
+    }
+}
+
+/// Writes the statements turning the rows of `table_name` in `conn` into those in `other`,
+/// or inserting them all if `conn` is `None`.
+fn diff_rows<W: io::Write>(
+    conn: Option<&rusqlite::Connection>,
+    other: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    shape: &Shape,
+    output: &mut Output<'_, W>,
+) -> Result<(), crate::Error> {
+    let keys = shape
+        .key
+        .iter()
+        .map(|column| format!("{} COLLATE BINARY", quote::identifier(column)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // The query reads both databases, whose tables have the same shape.
+    let query = format!(
+        "{} ORDER BY {keys};",
+        dump::select(
+            conn.unwrap_or(other),
+            options,
+            table_name,
+            &format!(
+                "{}, {}",
+                quote::identifiers(&shape.key, ", "),
+                quote::identifiers(&shape.columns, ", ")
+            ),
+        )
+    );
+    let in_table = || Error::in_table(table_name, StatementKind::Data, None);
+
+    let mut source_stmt = match conn {
+        Some(conn) => Some(conn.prepare(&query).map_err(in_table())?),
+        None => None,
+    };
+    let mut source = match &mut source_stmt {
+        Some(stmt) => Some(stmt.query([]).map_err(in_table())?),
+        None => None,
+    };
+    let mut target_stmt = other.prepare(&query).map_err(in_table())?;
+    let mut target = target_stmt.query([]).map_err(in_table())?;
+
+    let width = shape.key.len() + shape.columns.len();
+    let next = |rows: Option<&mut rusqlite::Rows<'_>>| match rows {
+        Some(rows) => next_row(options, rows, width).map_err(in_table()),
+        None => Ok(None),
+    };
+
+    let mut source_row = next(source.as_mut())?;
+    let mut target_row = next(Some(&mut target))?;
+    loop {
+        let ordering = match (&source_row, &target_row) {
+            (None, None) => return Ok(()),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(source_row), Some(target_row)) => compare_keys(shape, source_row, target_row),
+        };
+
+        match (ordering, &source_row, &target_row) {
+            (Ordering::Less, Some(row), _) => {
+                output.write(&delete(options, table_name, shape, row))?;
+                source_row = next(source.as_mut())?;
+            }
+            (Ordering::Greater, _, Some(row)) => {
+                output.write(&insert(options, table_name, shape, row))?;
+                target_row = next(Some(&mut target))?;
+            }
+            (_, Some(source_values), Some(target_values)) => {
+                if let Some(sql) = update(options, table_name, shape, source_values, target_values)
+                {
+                    output.write(&sql)?;
+                }
+                source_row = next(source.as_mut())?;
+                target_row = next(Some(&mut target))?;
+            }
+            _ => unreachable!("rows are compared only while one is left"),
+        }
+    }
+}
+
+/// Reads the `width` values of the next row of `rows`.
+fn next_row(
+    options: &DumpOptions,
+    rows: &mut rusqlite::Rows<'_>,
+    width: usize,
+) -> rusqlite::Result<Option<Vec<Value>>> {
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+
+    (0..width)
+        .map(|i| dump::read_value(options, row, i).map(Value::from))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Compares the keys of two rows, read as the key followed by the columns of `shape`.
+fn compare_keys(shape: &Shape, a: &[Value], b: &[Value]) -> Ordering {
+    let len = shape.key.len();
+    a[..len]
+        .iter()
+        .zip(&b[..len])
+        .map(|(a, b)| compare(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Compares two values as SQLite sorts them with the `BINARY` collation: NULL first, then
+/// numbers, then TEXT and BLOB values byte by byte.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    let class = |value: &Value| match value {
+        Value::Null => 0,
+        Value::Integer(_) | Value::Real(_) => 1,
+        Value::Text(_) => 2,
+        Value::Blob(_) => 3,
+    };
+
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Real(a), Value::Real(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Value::Integer(i), Value::Real(r)) => compare_integer_real(*i, *r),
+        (Value::Real(r), Value::Integer(i)) => compare_integer_real(*i, *r).reverse(),
+        (Value::Text(a), Value::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+        (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+        (a, b) => class(a).cmp(&class(b)),
+    }
+}
+
+/// Compares an integer with a real exactly, although most large integers are not exactly
+/// representable as reals.
+fn compare_integer_real(i: i64, r: f64) -> Ordering {
+    // The smallest real larger than every i64.
+    const MAX: f64 = 9223372036854775808.0;
+
+    match (i as f64).partial_cmp(&r) {
+        Some(Ordering::Equal) if r < MAX => i.cmp(&(r as i64)),
+        Some(Ordering::Equal) => Ordering::Less,
+        ordering => ordering.unwrap_or(Ordering::Equal),
+    }
+}
+
+/// Appends `value` to `out` as an SQL literal.
+fn push_value(out: &mut String, options: &DumpOptions, value: &Value) {
+    dump::write_value(out, options, ValueRef::from(value));
+}
+
+/// The `WHERE` clause matching the row whose values, key first, are `row`.
+fn condition(options: &DumpOptions, shape: &Shape, row: &[Value]) -> String {
+    let mut condition = String::from(" WHERE ");
+    for (i, (column, value)) in shape.key.iter().zip(row).enumerate() {
+        if i > 0 {
+            condition.push_str(" AND ");
+        }
+        condition.push_str(&quote::identifier(column));
+        match value {
+            Value::Null => condition.push_str(" IS NULL"),
+            value => {
+                condition.push('=');
+                push_value(&mut condition, options, value);
+            }
+        }
+    }
+    condition
+}
+
+fn delete(options: &DumpOptions, table_name: &str, shape: &Shape, row: &[Value]) -> String {
+    format!(
+        "DELETE FROM {}{};",
+        quote::identifier(table_name),
+        condition(options, shape, row)
+    )
+}
+
+fn insert(options: &DumpOptions, table_name: &str, shape: &Shape, row: &[Value]) -> String {
+    let (names, values) = match shape.rowid {
+        true => (
+            [shape.key.as_slice(), shape.columns.as_slice()].concat(),
+            [&row[..1], &row[shape.key.len()..]].concat(),
+        ),
+        false => (shape.columns.clone(), row[shape.key.len()..].to_vec()),
+    };
+
+    let mut sql = format!(
+        "INSERT INTO {}({}) VALUES(",
+        quote::identifier(table_name),
+        quote::identifiers(&names, ",")
+    );
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            sql.push(',');
+        }
+        push_value(&mut sql, options, value);
+    }
+    sql.push_str(");");
+    sql
+}
+
+/// The `UPDATE` statement setting the columns of a row that differ between `source` and
+/// `target`, if any.
+fn update(
+    options: &DumpOptions,
+    table_name: &str,
+    shape: &Shape,
+    source: &[Value],
+    target: &[Value],
+) -> Option<String> {
+    let offset = shape.key.len();
+    let mut assignments = Vec::new();
+    for (i, column) in shape.columns.iter().enumerate() {
+        let (a, b) = (&source[offset + i], &target[offset + i]);
+        // Values sorting equally may still be stored differently, such as 1 and 1.0.
+        if compare(a, b).is_ne() || std::mem::discriminant(a) != std::mem::discriminant(b) {
+            let mut assignment = format!("{}=", quote::identifier(column));
+            push_value(&mut assignment, options, b);
+            assignments.push(assignment);
+        }
+    }
+    if assignments.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "UPDATE {} SET {}{};",
+        quote::identifier(table_name),
+        assignments.join(", "),
+        condition(options, shape, target)
+    ))
+}
+
+/// The statements of a diff, wrapped in a transaction once the first one is written, so that
+/// identical databases give an empty diff.
+struct Output<'a, W: io::Write> {
+    options: &'a DumpOptions,
+    writer: &'a mut W,
+    started: bool,
+}
+
+impl<W: io::Write> Output<'_, W> {
+    fn write(&mut self, sql: &str) -> io::Result<()> {
+        if !self.started && self.options.transaction {
+            writeln!(self.writer, "BEGIN TRANSACTION;")?;
+            writeln!(self.writer, "{}", dump::DEFER_FOREIGN_KEYS)?;
+        }
+        self.started = true;
+        writeln!(self.writer, "{sql}")
+    }
+
+    fn finish(self) -> io::Result<()> {
+        if self.started && self.options.transaction {
+            writeln!(self.writer, "COMMIT;")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1710 @@
+use std::{borrow::Cow, io};
+
+use crate::{
+    dialect::ColumnType,
+    options::{Redaction, Transform},
+    quote, views,
+    warning::skip,
+    Dialect, DumpOptions, Error, InvalidUtf8, OnConflict, StatementKind,
+};
+
+/// The part of a dump a statement belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Prologue,
+    Schema,
+    Data,
+    Epilogue,
+}
+
+/// A step of an SQL dump.
+pub(crate) enum Step {
+    /// A single statement, including its terminating semicolon, or the batch separator of a
+    /// dialect that has one.
+    Statement(Kind, String),
+    /// The `INSERT` statements adding the rows of a table.
+    Rows(TableRows),
+}
+
+impl Step {
+    fn statement(kind: Kind, sql: impl Into<String>) -> Self {
+        Self::Statement(kind, sql.into())
+    }
+}
+
+/// The rows of a table to be dumped as `INSERT` statements.
+pub(crate) struct TableRows {
+    pub(crate) table_name: String,
+    pub(crate) columns: Vec<String>,
+    /// The statement up to the `VALUES` keyword.
+    pub(crate) insert: String,
+    /// The kind of each column, for the dialects whose literals depend on it.
+    pub(crate) types: Vec<ColumnType>,
+}
+
+/// The statement keeping foreign keys from being checked before the transaction of a SQLite dump
+/// commits, which every commit turns off.
+pub(crate) const DEFER_FOREIGN_KEYS: &str = "PRAGMA defer_foreign_keys=ON;";
+
+/// Counts the rows inserted since the transaction of a dump was opened, to commit it every
+/// [`commit_every`](DumpOptions::commit_every) rows.
+#[derive(Debug, Default)]
+pub(crate) struct Chunks {
+    rows: usize,
+}
+
+impl Chunks {
+    /// Counts `rows` more inserted rows, returning the statements committing the transaction
+    /// and opening the next one once it holds enough of them.
+    pub(crate) fn add(&mut self, options: &DumpOptions, rows: usize) -> Vec<String> {
+        let Some(commit_every) = options.commit_every.filter(|_| options.transaction) else {
+            return Vec::new();
+        };
+
+        self.rows += rows;
+        if self.rows < commit_every {
+            return Vec::new();
+        }
+        self.rows = 0;
+
+        let mut statements = vec!["COMMIT;".to_owned()];
+        statements.extend(begin(options));
+        statements
+    }
+}
+
+/// The statements opening a transaction of the dump after the first one.
+pub(crate) fn begin(options: &DumpOptions) -> Vec<String> {
+    let mut statements = vec![options.dialect.begin().to_owned()];
+    if options.dialect == Dialect::Sqlite {
+        statements.push(DEFER_FOREIGN_KEYS.to_owned());
+    }
+    statements
+}
+
+/// Lists the steps of the SQL dump of `conn`, in order.
+///
+/// The rows themselves are read when the returned steps are carried out.
+pub(crate) fn plan(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<Step>, crate::Error> {
+    let mut steps = Vec::new();
+    if options.metadata_header {
+        for line in crate::metadata::header(conn, options)? {
+            steps.push(Step::Statement(Kind::Prologue, line));
+        }
+    }
+    steps.extend(match options.dialect {
+        Dialect::Sqlite => plan_sqlite(conn, options)?,
+        _ => crate::dialect::plan(conn, options)?,
+    });
+
+    match options.transaction && options.transaction_per_table {
+        true => Ok(wrap_tables(options, steps)),
+        false => Ok(steps),
+    }
+}
+
+/// Wraps the rows of every table of `steps` in a transaction of their own, announced by a
+/// comment naming the table.
+fn wrap_tables(options: &DumpOptions, steps: Vec<Step>) -> Vec<Step> {
+    let mut wrapped = Vec::with_capacity(steps.len());
+    for step in steps {
+        let Step::Rows(rows) = step else {
+            wrapped.push(step);
+            continue;
+        };
+
+        // A line break in the name would end the comment early.
+        let table_name = options
+            .dialect
+            .identifier(&rows.table_name)
+            .replace(['\r', '\n'], " ");
+        let mut begin = begin(options).into_iter();
+        if let Some(sql) = begin.next() {
+            wrapped.push(Step::Statement(
+                Kind::Data,
+                format!("-- Data for table {table_name}\n{sql}"),
+            ));
+        }
+        wrapped.extend(begin.map(|sql| Step::Statement(Kind::Data, sql)));
+        wrapped.push(Step::Rows(rows));
+        wrapped.push(Step::statement(Kind::Data, "COMMIT;"));
+    }
+    wrapped
+}
+
+/// Lists the steps of the SQL dump of `conn` in the SQLite dialect, in order.
+fn plan_sqlite(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<Step>, crate::Error> {
+    let schema = !options.data_only;
+    let data = !options.schema_only;
+    let transaction = options.single_transaction();
+
+    let mut steps = Vec::new();
+
+    // Dropping a table deletes its rows, which foreign keys would check.
+    if schema && (data || options.clean) {
+        steps.push(Step::statement(Kind::Prologue, "PRAGMA foreign_keys=OFF;"));
+    }
+    if transaction {
+        steps.push(Step::statement(Kind::Prologue, "BEGIN TRANSACTION;"));
+        if options.commit_every.is_some() {
+            steps.push(Step::statement(Kind::Prologue, DEFER_FOREIGN_KEYS));
+        }
+    }
+    if options.header_pragmas {
+        let schema = quote::identifier(&options.schema);
+        for pragma in ["user_version", "application_id"] {
+            let value: i64 =
+                conn.query_row(&format!("PRAGMA {schema}.{pragma};"), [], |row| row.get(0))?;
+            steps.push(Step::statement(
+                Kind::Prologue,
+                format!("PRAGMA {pragma}={value};"),
+            ));
+        }
+    }
+
+    let tables = tables(conn, options)?;
+    let stat_tables = stat_tables(conn, options)?;
+
+    if schema && options.clean {
+        steps.extend(
+            drops(conn, options, &tables, &stat_tables)?
+                .into_iter()
+                .map(|sql| Step::Statement(Kind::Schema, sql)),
+        );
+    }
+
+    // The index of a full-text search table is rebuilt from its content on restore,
+    // so the shadow tables holding the index are dumped without their rows.
+    let fts_tables = tables
+        .iter()
+        .filter_map(|(table_name, create_sql)| {
+            fts_index_shadow_tables(table_name, create_sql).map(|shadows| (table_name, shadows))
+        })
+        .collect::<Vec<_>>();
+
+    // Like the SQLite CLI, virtual tables are restored by inserting their schema rows directly,
+    // since `CREATE VIRTUAL TABLE` would also create shadow tables that the dump recreates itself.
+    let mut writable_schema = false;
+
+    for (table_name, create_sql) in &tables {
+        if create_sql.starts_with("CREATE VIRTUAL TABLE") {
+            if schema {
+                if !writable_schema {
+                    steps.push(Step::statement(Kind::Schema, "PRAGMA writable_schema=ON;"));
+                    writable_schema = true;
+                }
+
+                let name = quote::text(table_name);
+                let sql = quote::text(create_sql);
+                steps.push(Step::statement(
+                    Kind::Schema,
+                    match options.if_not_exists {
+                        true => format!(
+                            "INSERT INTO sqlite_schema(type,name,tbl_name,rootpage,sql)SELECT 'table',{name},{name},0,{sql} WHERE NOT EXISTS(SELECT 1 FROM sqlite_schema WHERE name={name});"
+                        ),
+                        false => format!(
+                            "INSERT INTO sqlite_schema(type,name,tbl_name,rootpage,sql)VALUES('table',{name},{name},0,{sql});"
+                        ),
+                    },
+                ));
+            }
+
+            // The rows of a virtual table live in its shadow tables, which are dumped on their own.
+            continue;
+        }
+
+        if schema {
+            let create_sql = create(options, create_sql);
+            match create_sql.strip_prefix("CREATE TABLE ") {
+                Some(rest) if rest.starts_with(['\'', '"']) => steps.push(Step::statement(
+                    Kind::Schema,
+                    format!("CREATE TABLE IF NOT EXISTS {rest};"),
+                )),
+                _ => steps.push(Step::statement(Kind::Schema, format!("{create_sql};"))),
+            }
+        }
+
+        let is_fts_index = fts_tables
+            .iter()
+            .flat_map(|(_, shadows)| shadows)
+            .any(|shadow| shadow.eq_ignore_ascii_case(table_name));
+
+        if !data || is_fts_index {
+            continue;
+        }
+
+        if let Some(rows) = table_rows(conn, options, table_name)? {
+            steps.push(Step::Rows(rows));
+        }
+    }
+
+    // Like the SQLite CLI, the statistics of the query planner are restored by creating their
+    // tables with `ANALYZE sqlite_schema`, which analyzes nothing, and inserting their rows.
+    if schema && !stat_tables.is_empty() {
+        steps.push(Step::statement(Kind::Schema, "ANALYZE sqlite_schema;"));
+    }
+    if data {
+        for table_name in &stat_tables {
+            if let Some(rows) = table_rows(conn, options, table_name)? {
+                steps.push(Step::Rows(rows));
+            }
+        }
+    }
+
+    // The table of the AUTOINCREMENT counters only exists once a table uses AUTOINCREMENT.
+    let sequence: bool = conn.query_row(
+        &format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE type == 'table' AND name == 'sqlite_sequence');",
+            options.qualified("sqlite_schema")
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+
+    if data && sequence {
+        // A part of a split dump only resets the counters of its own tables, which leaves out
+        // the table of the counters when none of them use AUTOINCREMENT.
+        if schema && !options.split {
+            steps.push(Step::statement(Kind::Data, "DELETE FROM sqlite_sequence;"));
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT name, seq FROM {}{};",
+            options.qualified("sqlite_sequence"),
+            match options.deterministic {
+                true => " ORDER BY name",
+                false => "",
+            },
+        ))?;
+        let sequences = stmt.query_map([], |row| {
+            let name: String = row.get(0)?;
+            let seq: i64 = row.get(1)?;
+
+            Ok((name, seq))
+        })?;
+        read_all(options, "sqlite_sequence", sequences)?
+            .into_iter()
+            .filter(|(name, _)| options.includes_table(name))
+            .for_each(|(name, seq)| {
+                let name = quote::text(&name);
+                if schema && options.split {
+                    steps.push(Step::statement(
+                        Kind::Data,
+                        format!("DELETE FROM sqlite_sequence WHERE name = {name};"),
+                    ));
+                }
+                steps.push(Step::statement(
+                    Kind::Data,
+                    format!("INSERT INTO sqlite_sequence VALUES({name},{seq});"),
+                ))
+            });
+    }
+
+    if schema {
+        let mut stmt = conn.prepare(&format!(
+            r#"
+                SELECT type, tbl_name, sql
+                FROM {schema}
+                WHERE sql NOT NULL
+                    AND type IN ({types})
+                ORDER BY type COLLATE NOCASE DESC{order};"#,
+            schema = options.qualified("sqlite_schema"),
+            types = object_types(options),
+            order = match options.deterministic {
+                true => ", name",
+                false => ", rowid",
+            },
+        ))?;
+        let entries = stmt.query_map([], |row| {
+            let kind: String = row.get(0)?;
+            let table_name: String = row.get(1)?;
+            let create_sql: String = row.get(2)?;
+
+            Ok((kind, table_name, create_sql))
+        })?;
+        let entries = read_all(options, "sqlite_schema", entries)?
+            .into_iter()
+            .filter(|(_, table_name, _)| options.includes_table(table_name))
+            .collect::<Vec<_>>();
+        // A view can only be created once the views it selects from are, which may have been
+        // created again after it.
+        views_first(entries)
+            .into_iter()
+            .for_each(|(_, _, create_sql)| {
+                steps.push(Step::statement(
+                    Kind::Schema,
+                    format!("{};", create(options, &create_sql)),
+                ))
+            });
+    }
+
+    let rebuild = data && !fts_tables.is_empty();
+
+    if writable_schema {
+        if rebuild || !data {
+            // Reloads the schema so that the virtual tables can be used by the rebuild below,
+            // or by the data loaded right after a schema-only dump.
+            steps.push(Step::statement(
+                Kind::Epilogue,
+                "PRAGMA writable_schema=RESET;",
+            ));
+        } else {
+            steps.push(Step::statement(
+                Kind::Epilogue,
+                "PRAGMA writable_schema=OFF;",
+            ));
+        }
+    }
+
+    if rebuild {
+        for (table_name, _) in &fts_tables {
+            let table_name = quote::identifier(table_name);
+            steps.push(Step::statement(
+                Kind::Epilogue,
+                format!("INSERT INTO {table_name}({table_name}) VALUES('rebuild');"),
+            ));
+        }
+    }
+
+    // Statistics inserted into their tables are only used once loaded again.
+    if options.analyze && data && !stat_tables.is_empty() {
+        steps.push(Step::statement(Kind::Epilogue, "ANALYZE sqlite_schema;"));
+    }
+
+    if transaction {
+        steps.push(Step::statement(Kind::Epilogue, "COMMIT;"));
+    }
+
+    Ok(steps)
+}
+
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    if !options.checksum {
+        return dump_with_tables(conn, options, progress, writer, |_, _, _| Ok(()));
+    }
+
+    let mut hashed = crate::checksum::Hashed::new(writer);
+    dump_with_tables(conn, options, progress, &mut hashed, |_, _, _| Ok(()))?;
+    hashed.finish(options.line_ending())?;
+    Ok(())
+}
+
+/// Where the rows of a table start or end in an SQL dump, see [`dump_with_tables`].
+pub(crate) enum TableBoundary {
+    Start,
+    /// The rows of the table have been written, as `rows` rows.
+    End {
+        rows: u64,
+    },
+}
+
+/// Writes the SQL dump of `conn` like [`dump`], calling `on_table` with the writer right
+/// before the `INSERT` statements of each table and right after them.
+pub(crate) fn dump_with_tables<W, F>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+    mut on_table: F,
+) -> Result<(), crate::Error>
+where
+    W: io::Write,
+    F: FnMut(&mut W, &str, TableBoundary) -> io::Result<()>,
+{
+    let mut chunks = Chunks::default();
+    for step in plan(conn, options)? {
+        if options.blank_lines && starts_table(options, &step) {
+            writer.write_all(options.line_ending().as_bytes())?;
+        }
+
+        match step {
+            Step::Statement(kind, sql) => {
+                write_line(writer, options, &sql)?;
+                progress.statement(kind, &sql);
+            }
+            Step::Rows(table) => {
+                on_table(writer, &table.table_name, TableBoundary::Start)?;
+                let mut written = 0;
+                // The buffer of the statement is reused, so that rows are formatted without
+                // allocating once it is large enough.
+                let mut insert = Insert::default();
+
+                // Large BLOB values are streamed to the writer rather than read whole, unless
+                // another dialect writes them.
+                read_rows(
+                    conn,
+                    options,
+                    progress,
+                    &table.table_name,
+                    &table.columns,
+                    None,
+                    options.dialect == Dialect::Sqlite,
+                    |values, flags| {
+                        match flags {
+                            Some(flags) => insert
+                                .push_large_row(conn, options, &table, values, flags, writer)?,
+                            None => insert.push_row(options, &table, values),
+                        }
+                        if insert.rows == options.rows_per_insert {
+                            let rows = insert.write(writer, options)?;
+                            written += rows as u64;
+                            write_chunk(writer, options, progress, chunks.add(options, rows))?;
+                        }
+                        Ok(())
+                    },
+                )?;
+
+                if insert.rows > 0 {
+                    let rows = insert.write(writer, options)?;
+                    written += rows as u64;
+                    write_chunk(writer, options, progress, chunks.add(options, rows))?;
+                }
+                on_table(
+                    writer,
+                    &table.table_name,
+                    TableBoundary::End { rows: written },
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// An `INSERT` statement being built one row at a time.
+#[derive(Default)]
+pub(crate) struct Insert {
+    sql: String,
+    /// The number of rows added so far.
+    pub(crate) rows: usize,
+}
+
+impl Insert {
+    /// Adds a row with `values` to the statement inserting into `table`.
+    pub(crate) fn push_row(
+        &mut self,
+        options: &DumpOptions,
+        table: &TableRows,
+        values: &[rusqlite::types::ValueRef<'_>],
+    ) {
+        self.start_row(table);
+        write_row(&mut self.sql, options, &table.types, values);
+        self.rows += 1;
+    }
+
+    /// Adds a row with `values` to the statement inserting into `table`, streaming its large
+    /// BLOB values to `writer` along with the statement so far.
+    fn push_large_row<W: io::Write>(
+        &mut self,
+        conn: &rusqlite::Connection,
+        options: &DumpOptions,
+        table: &TableRows,
+        values: &[rusqlite::types::ValueRef<'_>],
+        flags: &crate::blob::Flags,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        self.start_row(table);
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.sql.push(',');
+            }
+            if !flags.is_streamed(i) {
+                write_value(&mut self.sql, options, *value);
+                continue;
+            }
+
+            writer.write_all(self.sql.as_bytes())?;
+            self.sql.clear();
+            crate::blob::write_literal(
+                conn,
+                options,
+                &table.table_name,
+                &table.columns[i],
+                flags.rowid,
+                writer,
+            )?;
+        }
+        self.rows += 1;
+
+        Ok(())
+    }
+
+    /// Starts the statement, or the next tuple of its `VALUES`.
+    fn start_row(&mut self, table: &TableRows) {
+        match self.rows {
+            0 => {
+                self.sql.push_str(&table.insert);
+                self.sql.push_str(" VALUES(");
+            }
+            _ => self.sql.push_str("),("),
+        }
+    }
+
+    /// Ends the statement and takes it, leaving the builder empty.
+    pub(crate) fn take(&mut self) -> String {
+        self.sql.push_str(");");
+        self.rows = 0;
+        std::mem::take(&mut self.sql)
+    }
+
+    /// Ends the statement and writes it on a line of its own, keeping the buffer for the next
+    /// one. Returns the number of rows it adds.
+    fn write<W: io::Write>(&mut self, writer: &mut W, options: &DumpOptions) -> io::Result<usize> {
+        self.sql.push(')');
+        self.sql.push_str(&options.statement_terminator);
+        self.sql.push_str(options.line_ending());
+        writer.write_all(self.sql.as_bytes())?;
+        self.sql.clear();
+        Ok(std::mem::take(&mut self.rows))
+    }
+}
+
+/// Appends the values of a row to `out` as the comma-separated SQL literals of a `VALUES`
+/// tuple, where `types` are the kinds of its columns.
+fn write_row(
+    out: &mut String,
+    options: &DumpOptions,
+    types: &[ColumnType],
+    values: &[rusqlite::types::ValueRef<'_>],
+) {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        match options.dialect {
+            Dialect::Sqlite => write_value(out, options, *value),
+            dialect => out.push_str(&dialect.literal(types[i], *value)),
+        }
+    }
+}
+
+fn write_chunk<W: io::Write>(
+    writer: &mut W,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    statements: Vec<String>,
+) -> io::Result<()> {
+    statements.iter().try_for_each(|sql| {
+        write_line(writer, options, sql)?;
+        progress.statement(Kind::Data, sql);
+        Ok(())
+    })
+}
+
+/// Writes `sql` on a line of its own, ended by [`DumpOptions::statement_terminator`] in place of
+/// its semicolon, if any.
+fn write_line<W: io::Write>(writer: &mut W, options: &DumpOptions, sql: &str) -> io::Result<()> {
+    match sql.strip_suffix(';') {
+        Some(sql) => {
+            writer.write_all(sql.as_bytes())?;
+            writer.write_all(options.statement_terminator.as_bytes())?;
+        }
+        None => writer.write_all(sql.as_bytes())?,
+    }
+    writer.write_all(options.line_ending().as_bytes())
+}
+
+/// Whether `step` is the first of the statements of a table, which
+/// [`DumpOptions::blank_lines`] writes a blank line before.
+///
+/// The statements of a table start with its definition, or without the schema, with its rows.
+fn starts_table(options: &DumpOptions, step: &Step) -> bool {
+    let wrapped = options.transaction && options.transaction_per_table;
+    match step {
+        Step::Statement(Kind::Schema, sql) => {
+            sql.starts_with("CREATE TABLE ") || sql.starts_with("INSERT INTO sqlite_schema(")
+        }
+        Step::Statement(Kind::Data, sql) => {
+            options.data_only && wrapped && sql.starts_with("-- Data for table ")
+        }
+        Step::Rows(_) => options.data_only && !wrapped,
+        _ => false,
+    }
+}
+
+/// Orders the views leading `entries`, listed by type, name and `CREATE` statement, so that
+/// each view is created after the views it selects from.
+fn views_first(mut entries: Vec<(String, String, String)>) -> Vec<(String, String, String)> {
+    let others = entries.split_off(
+        entries
+            .iter()
+            .take_while(|(kind, _, _)| kind == "view")
+            .count(),
+    );
+    let mut entries = views::in_dependency_order(entries, |(_, name, sql)| (name, sql));
+    entries.extend(others);
+    entries
+}
+
+/// The types of the schema objects other than tables that are dumped, as SQL literals for an
+/// `IN` list.
+fn object_types(options: &DumpOptions) -> String {
+    let mut types = vec!["'view'"];
+    if options.indexes {
+        types.push("'index'");
+    }
+    if options.triggers {
+        types.push("'trigger'");
+    }
+
+    types.join(", ")
+}
+
+/// Lists the columns of `table_name` and builds the `INSERT` statements of its rows.
+///
+/// Returns `None` for a table whose columns cannot be read, if the dump is lenient.
+pub(crate) fn table_rows(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> Result<Option<TableRows>, crate::Error> {
+    let columns = match read_columns(conn, options, table_name) {
+        Ok(columns) => columns,
+        Err(err) => {
+            skip(options, table_name, None, err).map_err(Error::in_table(
+                table_name,
+                StatementKind::Schema,
+                None,
+            ))?;
+            return Ok(None);
+        }
+    };
+    // The rowid is dumped as an extra first column, unless it is already the value of an
+    // `INTEGER PRIMARY KEY` column.
+    let rowid = match options.preserve_rowids {
+        true => rowid_column(conn, options, table_name, &columns).map_err(Error::in_table(
+            table_name,
+            StatementKind::Schema,
+            None,
+        ))?,
+        false => None,
+    };
+    let columns = match rowid {
+        Some(rowid) => std::iter::once(rowid.to_owned()).chain(columns).collect(),
+        None => columns,
+    };
+    let insert = match options.on_conflict {
+        OnConflict::Abort => "INSERT",
+        OnConflict::Ignore => "INSERT OR IGNORE",
+        OnConflict::Replace => "INSERT OR REPLACE",
+    };
+    let table = quote::identifier(table_name);
+    let insert = if options.insert_column_names || rowid.is_some() {
+        format!(
+            "{insert} INTO {table}({})",
+            quote::identifiers(&columns, ",")
+        )
+    } else {
+        format!("{insert} INTO {table}")
+    };
+
+    Ok(Some(TableRows {
+        table_name: table_name.to_owned(),
+        columns,
+        insert,
+        types: Vec::new(),
+    }))
+}
+
+/// Returns `create_sql` as [`pretty`] writes it, with `IF NOT EXISTS` after the type of the
+/// object it creates if [`DumpOptions::if_not_exists`] is set.
+///
+/// SQLite stores the statements creating objects with their leading keywords normalized and
+/// without the clause, so the object type is matched as is.
+fn create<'a>(options: &DumpOptions, create_sql: &'a str) -> Cow<'a, str> {
+    const PREFIXES: [&str; 5] = [
+        "CREATE TABLE ",
+        "CREATE INDEX ",
+        "CREATE UNIQUE INDEX ",
+        "CREATE VIEW ",
+        "CREATE TRIGGER ",
+    ];
+
+    let create_sql = pretty(options, create_sql);
+    if !options.if_not_exists {
+        return create_sql;
+    }
+    let rewritten = PREFIXES.iter().find_map(|prefix| {
+        let rest = create_sql.strip_prefix(prefix)?;
+        Some(format!("{prefix}IF NOT EXISTS {rest}"))
+    });
+    rewritten.map_or(create_sql, Cow::Owned)
+}
+
+/// Returns `create_sql` reformatted if [`DumpOptions::pretty_schema`] is set.
+fn pretty<'a>(options: &DumpOptions, create_sql: &'a str) -> Cow<'a, str> {
+    match options.pretty_schema {
+        true => crate::pretty::create(create_sql),
+        false => Cow::Borrowed(create_sql),
+    }
+}
+
+/// The statements dropping every object the dump creates from the database it is restored into,
+/// see [`DumpOptions::clean`].
+///
+/// They all come before the first `CREATE` statement: a virtual table dropped later would take
+/// the shadow tables created before it along.
+fn drops(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    tables: &[(String, String)],
+    stat_tables: &[String],
+) -> Result<Vec<String>, crate::Error> {
+    let mut stmt = conn.prepare(&format!(
+        r#"
+            SELECT type, name, sql
+            FROM {}
+            WHERE sql NOT NULL
+                AND type IN ({})
+            ORDER BY type COLLATE NOCASE DESC, rowid DESC;"#,
+        options.qualified("sqlite_schema"),
+        object_types(options),
+    ))?;
+    let entries = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    let entries = read_all(options, "sqlite_schema", entries)?
+        .into_iter()
+        .filter(|(_, name, _)| options.includes_table(name))
+        .collect::<Vec<_>>();
+    // Views are dropped before the views they select from.
+    let mut entries = views_first(entries);
+    let views = entries
+        .iter()
+        .take_while(|(kind, _, _)| kind == "view")
+        .count();
+    entries[..views].reverse();
+    let mut drops = entries
+        .into_iter()
+        .map(|(kind, name, _)| {
+            format!(
+                "DROP {} IF EXISTS {};",
+                kind.to_ascii_uppercase(),
+                quote::identifier(&name)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Virtual tables first, which drop their shadow tables along.
+    let (virtual_tables, tables): (Vec<_>, Vec<_>) = tables
+        .iter()
+        .partition(|(_, create_sql)| create_sql.starts_with("CREATE VIRTUAL TABLE"));
+    drops.extend(
+        virtual_tables
+            .into_iter()
+            .chain(tables)
+            .map(|(table_name, _)| table_name)
+            .chain(stat_tables.iter().filter(|_| !options.schema_only))
+            .map(|table_name| format!("DROP TABLE IF EXISTS {};", quote::identifier(table_name))),
+    );
+
+    Ok(drops)
+}
+
+/// Returns the names of the statistics tables of the query planner, such as `sqlite_stat1`.
+fn stat_tables(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<String>, crate::Error> {
+    let mut stmt = conn.prepare(&format!(
+        r#"
+            SELECT name
+            FROM {}
+            WHERE type == 'table'
+                AND name GLOB 'sqlite_stat[0-9]'
+            ORDER BY name;"#,
+        options.qualified("sqlite_schema"),
+    ))?;
+    let tables = stmt.query_map([], |row| row.get(0))?;
+    let tables = read_all(options, "sqlite_schema", tables)?
+        .into_iter()
+        .filter(|table_name: &String| options.includes_table(table_name))
+        .collect();
+
+    Ok(tables)
+}
+
+/// Returns the name and `CREATE` statement of every table to be dumped,
+/// including virtual tables and their shadow tables.
+pub(crate) fn tables(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<(String, String)>, crate::Error> {
+    let mut stmt = conn.prepare(&format!(
+        r#"
+            SELECT name, sql
+            FROM {schema}
+            WHERE sql NOT NULL
+                AND type == 'table'
+                AND name NOT LIKE 'sqlite_%'{order};"#,
+        schema = options.qualified("sqlite_schema"),
+        order = match options.deterministic {
+            true => " ORDER BY name",
+            false => "",
+        },
+    ))?;
+    let tables = stmt.query_map([], |row| {
+        let table_name: String = row.get(0)?;
+        let create_sql: String = row.get(1)?;
+
+        Ok((table_name, create_sql))
+    })?;
+    let tables = read_all(options, "sqlite_schema", tables)?
+        .into_iter()
+        .filter(|(table_name, _)| options.includes_table(table_name))
+        .collect();
+
+    Ok(tables)
+}
+
+/// Collects the rows of `table` read by `rows`, leaving out those that fail to read unless
+/// the dump is [`strict`](DumpOptions::strict).
+fn read_all<T, I>(options: &DumpOptions, table: &str, rows: I) -> Result<Vec<T>, crate::Error>
+where
+    I: Iterator<Item = rusqlite::Result<T>>,
+{
+    let mut read = Vec::new();
+    for row in rows {
+        match row {
+            Ok(row) => read.push(row),
+            Err(err) => skip(options, table, None, err).map_err(Error::in_table(
+                table,
+                StatementKind::Schema,
+                None,
+            ))?,
+        }
+    }
+
+    Ok(read)
+}
+
+/// Returns the names of the tables whose rows are exported by the non-SQL formats.
+///
+/// Virtual tables are read like any other table, while their shadow tables are left out.
+/// Shadow tables are named after their virtual table and created with a quoted name.
+pub(crate) fn data_tables(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<String>, crate::Error> {
+    let tables = tables(conn, options)?;
+
+    let virtual_tables = tables
+        .iter()
+        .filter(|(_, create_sql)| create_sql.starts_with("CREATE VIRTUAL TABLE"))
+        .map(|(table_name, _)| table_name)
+        .collect::<Vec<_>>();
+
+    let mut data_tables = Vec::new();
+    for (table_name, create_sql) in &tables {
+        if virtual_tables
+            .iter()
+            .any(|virtual_table| is_shadow_table(table_name, create_sql, virtual_table))
+        {
+            continue;
+        }
+
+        // A lenient dump leaves out the tables that cannot be read, such as the virtual tables
+        // of a module that is not loaded, before anything is written about them.
+        if !options.strict {
+            let probe = format!("SELECT * FROM {} LIMIT 0;", options.qualified(table_name));
+            if let Err(err) = conn.prepare(&probe) {
+                skip(options, table_name, None, err).map_err(Error::in_table(
+                    table_name,
+                    StatementKind::Data,
+                    None,
+                ))?;
+                continue;
+            }
+        }
+
+        data_tables.push(table_name.clone());
+    }
+
+    Ok(data_tables)
+}
+
+/// Whether the table `table_name`, created by `create_sql`, is a shadow table of `virtual_table`.
+pub(crate) fn is_shadow_table(table_name: &str, create_sql: &str, virtual_table: &str) -> bool {
+    let quoted = create_sql
+        .strip_prefix("CREATE TABLE ")
+        .is_some_and(|rest| rest.starts_with(['\'', '"']));
+    let prefix = format!("{}_", virtual_table.to_ascii_lowercase());
+
+    quoted && table_name.to_ascii_lowercase().starts_with(&prefix)
+}
+
+pub(crate) fn columns(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> Result<Vec<String>, crate::Error> {
+    read_columns(conn, options, table_name).map_err(Error::in_table(
+        table_name,
+        StatementKind::Schema,
+        None,
+    ))
+}
+
+/// Reads the columns of `table_name` whose values are dumped.
+///
+/// Generated columns are left out, as their values cannot be inserted and are computed again
+/// on restore, and so are the hidden columns of virtual tables.
+pub(crate) fn read_columns(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_xinfo(?1, ?2) WHERE hidden = 0;")?;
+    let columns = stmt
+        .query_map([table_name, &options.schema], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(columns)
+}
+
+/// The modulus of the hash used for sampling, the Mersenne prime 2^31 - 1.
+const SAMPLE_MODULUS: i64 = 2147483647;
+/// The modulus divided by the golden ratio.
+const SAMPLE_MULTIPLIER: i64 = 1327217884;
+
+/// The condition selecting the rows of `table_name` to be dumped, if not all of them.
+pub(crate) fn filter(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> Option<String> {
+    let mut conditions = Vec::new();
+    if let Some(predicate) = options.predicate(table_name) {
+        conditions.push(format!("({predicate})"));
+    }
+    if let Some(condition) = options
+        .since
+        .as_ref()
+        .and_then(|checkpoint| checkpoint.condition(table_name))
+    {
+        conditions.push(condition);
+    }
+
+    // Rows are sampled by a Fibonacci hash of their rowid, which spreads consecutive rowids
+    // evenly, so that the same rows are picked on every run. Tables without a rowid are dumped
+    // in full.
+    let sampled = (options.sample_fraction.is_some() || options.max_rows_per_table.is_some())
+        && has_rowid(conn, options, table_name);
+    if !sampled {
+        return (!conditions.is_empty()).then(|| conditions.join(" AND "));
+    }
+
+    let seed = i64::from(options.sample_seed) % SAMPLE_MODULUS;
+    let hash = format!(
+        "(abs(rowid % {SAMPLE_MODULUS}) + {seed}) % {SAMPLE_MODULUS} * {SAMPLE_MULTIPLIER} % {SAMPLE_MODULUS}"
+    );
+
+    if let Some(fraction) = options.sample_fraction {
+        let threshold = (fraction * SAMPLE_MODULUS as f64) as i64;
+        conditions.push(format!("{hash} < {threshold}"));
+    }
+
+    if let Some(max_rows) = options.max_rows_per_table {
+        let table = options.qualified(table_name);
+        let condition = match conditions.is_empty() {
+            true => String::new(),
+            false => format!(" WHERE {}", conditions.join(" AND ")),
+        };
+        conditions = vec![format!(
+            "rowid IN (SELECT rowid FROM {table}{condition} ORDER BY {hash}, rowid LIMIT {max_rows})"
+        )];
+    }
+
+    (!conditions.is_empty()).then(|| conditions.join(" AND "))
+}
+
+/// Whether `table_name` has a rowid, unlike `WITHOUT ROWID` tables and some virtual tables.
+pub(crate) fn has_rowid(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> bool {
+    conn.prepare(&format!(
+        "SELECT rowid FROM {} LIMIT 0;",
+        options.qualified(table_name)
+    ))
+    .is_ok()
+}
+
+/// Returns the columns of the primary key of `table_name`, in the order of the key.
+pub(crate) fn primary_key(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "PRAGMA {}.table_info({});",
+        quote::identifier(&options.schema),
+        quote::identifier(table_name)
+    ))?;
+    let mut columns = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>("pk")?, row.get::<_, String>("name")?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    columns.retain(|(pk, _)| *pk > 0);
+    columns.sort();
+
+    Ok(columns.into_iter().map(|(_, name)| name).collect())
+}
+
+/// Returns the name under which the rowid of `table_name`, made of `columns`, is dumped to be
+/// preserved, if the table has a rowid that is not the value of an `INTEGER PRIMARY KEY` column.
+///
+/// Like the SQLite CLI, the rowid is named by the first of `rowid`, `_rowid_` and `oid` that is
+/// not the name of a column, and is not preserved if all three are.
+pub(crate) fn rowid_column(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    columns: &[String],
+) -> rusqlite::Result<Option<&'static str>> {
+    if !has_rowid(conn, options, table_name) || rowid_alias(conn, options, table_name)?.is_some() {
+        return Ok(None);
+    }
+
+    Ok(["rowid", "_rowid_", "oid"].into_iter().find(|name| {
+        !columns
+            .iter()
+            .any(|column| column.eq_ignore_ascii_case(name))
+    }))
+}
+
+/// Returns the `INTEGER PRIMARY KEY` column of `table_name` whose values are its rowids, if any.
+pub(crate) fn rowid_alias(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> rusqlite::Result<Option<String>> {
+    if !has_rowid(conn, options, table_name) {
+        return Ok(None);
+    }
+
+    let schema = quote::identifier(&options.schema);
+    let table = quote::identifier(table_name);
+    let mut stmt = conn.prepare(&format!("PRAGMA {schema}.table_info({table});"))?;
+    let key = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>("pk")?,
+                row.get::<_, String>("name")?,
+                row.get::<_, String>("type")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|(pk, _, _)| *pk > 0)
+        .collect::<Vec<_>>();
+
+    // A single `INTEGER PRIMARY KEY` column is the rowid, except when declared `DESC` in a
+    // column constraint, in which case SQLite backs it with an index of its own.
+    let [(_, name, declared_type)] = key.as_slice() else {
+        return Ok(None);
+    };
+    if !declared_type.eq_ignore_ascii_case("INTEGER") {
+        return Ok(None);
+    }
+    let mut stmt = conn.prepare(&format!("PRAGMA {schema}.index_list({table});"))?;
+    let indexed = stmt
+        .query_map([], |row| row.get::<_, String>("origin"))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|origin| origin == "pk");
+
+    Ok((!indexed).then(|| name.clone()))
+}
+
+/// Returns the name the rowid of `table_name` is selected by, if it has a rowid: its
+/// `INTEGER PRIMARY KEY` column, or else the first of `rowid`, `_rowid_` and `oid` that is not
+/// the name of a column, as a bare `rowid` selects a column of that name instead.
+pub(crate) fn rowid_name(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> rusqlite::Result<Option<String>> {
+    if let Some(alias) = rowid_alias(conn, options, table_name)? {
+        return Ok(Some(alias));
+    }
+
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_xinfo(?1, ?2);")?;
+    let columns = stmt
+        .query_map([table_name, &options.schema], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(rowid_column(conn, options, table_name, &columns)?.map(str::to_owned))
+}
+
+/// The `ORDER BY` clause reading the rows of `table_name` in a stable order, if the dump is
+/// [`deterministic`](DumpOptions::deterministic), and an empty string otherwise.
+///
+/// Rows are ordered by rowid when the table has one, and by primary key otherwise.
+pub(crate) fn order_by(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> String {
+    match (options.deterministic, has_rowid(conn, options, table_name)) {
+        (false, _) => String::new(),
+        (true, true) => " ORDER BY rowid".to_owned(),
+        (true, false) => primary_key_order(conn, options, table_name),
+    }
+}
+
+/// Returns the query reading the rows of `table_name` a page of `?2` rows at a time, selecting
+/// `expressions`, and whether it pages them by rowid.
+///
+/// Rows are paged by rowid when the table has one, so that each page is a cheap range scan:
+/// the query then selects the rowid after `expressions` and reads the rows past rowid `?1`.
+/// Otherwise, they are paged by offset `?1` in primary key order.
+pub(crate) fn page_query(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    expressions: &str,
+) -> (String, bool) {
+    let Some(rowid) = rowid_name(conn, options, table_name).ok().flatten() else {
+        let select = select(conn, options, table_name, expressions);
+        let order = primary_key_order(conn, options, table_name);
+        return (format!("{select}{order} LIMIT ?2 OFFSET ?1;"), false);
+    };
+
+    let table = options.qualified(table_name);
+    let rowid = quote::identifier(&rowid);
+    let filter = filter(conn, options, table_name)
+        .map(|filter| format!(" AND {filter}"))
+        .unwrap_or_default();
+    let sql = format!(
+        "SELECT {expressions}, {rowid} FROM {table} WHERE {rowid} > ?1{filter} ORDER BY {rowid} LIMIT ?2;"
+    );
+    (sql, true)
+}
+
+/// The `ORDER BY` clause reading the rows of `table_name` by primary key, as those of a
+/// `WITHOUT ROWID` table, or an empty string if the table has no primary key.
+pub(crate) fn primary_key_order(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> String {
+    // A table whose primary key cannot be read cannot be read at all, which the query reading
+    // its rows reports.
+    match primary_key(conn, options, table_name) {
+        Ok(columns) if !columns.is_empty() => {
+            format!(" ORDER BY {}", quote::identifiers(&columns, ", "))
+        }
+        _ => String::new(),
+    }
+}
+
+/// Builds the query reading `expressions` from the rows of `table_name` to be dumped.
+pub(crate) fn select(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    expressions: &str,
+) -> String {
+    let table = options.qualified(table_name);
+    match filter(conn, options, table_name) {
+        Some(filter) => format!("SELECT {expressions} FROM {table} WHERE {filter}"),
+        None => format!("SELECT {expressions} FROM {table}"),
+    }
+}
+
+/// Rewrites the values read from the columns of a table with the transform and redactions
+/// of the options.
+#[derive(Clone)]
+pub(crate) struct Rewriter {
+    table_name: String,
+    columns: Vec<(String, Option<Redaction>)>,
+    transform: Option<Transform>,
+}
+
+impl Rewriter {
+    pub(crate) fn new<'a>(
+        options: &DumpOptions,
+        table_name: &str,
+        columns: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        let columns = columns
+            .into_iter()
+            .map(|column| {
+                let redaction = options.redaction(table_name, column).cloned();
+                (column.clone(), redaction)
+            })
+            .collect();
+
+        Self {
+            table_name: table_name.to_owned(),
+            columns,
+            transform: options.transform.clone(),
+        }
+    }
+
+    /// Whether no value is ever rewritten, so that rows can be passed through as they are.
+    pub(crate) fn is_identity(&self) -> bool {
+        self.transform.is_none()
+            && self
+                .columns
+                .iter()
+                .all(|(_, redaction)| redaction.is_none())
+    }
+
+    /// Returns the replacement for each of the `values` of `row`, if it is rewritten or is a
+    /// BLOB larger than [`DumpOptions::max_blob_size`].
+    pub(crate) fn rewrite_row(
+        &self,
+        conn: &rusqlite::Connection,
+        options: &DumpOptions,
+        row: &crate::blob::Row<'_>,
+        values: &[rusqlite::types::ValueRef<'_>],
+    ) -> Result<Vec<Option<rusqlite::types::Value>>, crate::Error> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let rewritten = self.rewrite(i, *value);
+                let value = rewritten
+                    .as_ref()
+                    .map_or(*value, rusqlite::types::ValueRef::from);
+                let column = &self.columns[i].0;
+                let oversized =
+                    crate::blob::replace_oversized(conn, options, row, i, column, value)?;
+                Ok(oversized.or(rewritten))
+            })
+            .collect()
+    }
+
+    /// Returns the replacement for `value` read from column `i`, if it is rewritten.
+    pub(crate) fn rewrite(
+        &self,
+        i: usize,
+        value: rusqlite::types::ValueRef<'_>,
+    ) -> Option<rusqlite::types::Value> {
+        let (column, redaction) = &self.columns[i];
+
+        let transformed = self
+            .transform
+            .as_ref()
+            .and_then(|transform| (transform.0)(&self.table_name, column, value));
+
+        match redaction {
+            Some(redaction) => Some(
+                redaction.apply(
+                    transformed
+                        .as_ref()
+                        .map_or(value, rusqlite::types::ValueRef::from),
+                ),
+            ),
+            None => transformed,
+        }
+    }
+}
+
+/// Calls `f` with the values of every row of `table_name`, in the order of `columns`,
+/// stopping after `limit` rows if given.
+pub(crate) fn for_each_row<F>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    table_name: &str,
+    columns: &[String],
+    limit: Option<usize>,
+    mut f: F,
+) -> Result<(), crate::Error>
+where
+    F: FnMut(&[rusqlite::types::ValueRef<'_>]) -> Result<(), crate::Error>,
+{
+    read_rows(
+        conn,
+        options,
+        progress,
+        table_name,
+        columns,
+        limit,
+        false,
+        |values, _| f(values),
+    )
+}
+
+/// Like [`for_each_row`], but leaves the large BLOB values of a table with a rowid out of
+/// the values if `stream` is set, passing `f` where to stream them from instead.
+///
+/// Large BLOB values are read as they are when the table has no rowid, or only one its columns
+/// shadow, or when its values are rewritten.
+#[allow(clippy::too_many_arguments)]
+fn read_rows<F>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    table_name: &str,
+    columns: &[String],
+    limit: Option<usize>,
+    stream: bool,
+    mut f: F,
+) -> Result<(), crate::Error>
+where
+    F: FnMut(
+        &[rusqlite::types::ValueRef<'_>],
+        Option<&crate::blob::Flags>,
+    ) -> Result<(), crate::Error>,
+{
+    let order = order_by(conn, options, table_name);
+    let query = |expressions: &str| {
+        let mut sql = select(conn, options, table_name, expressions);
+        sql.push_str(&order);
+        if let Some(limit) = limit {
+            sql.push_str(&format!(" LIMIT {limit}"));
+        }
+        conn.prepare(&sql)
+    };
+
+    let rewriter = Rewriter::new(options, table_name, columns);
+    let identity = rewriter.is_identity();
+
+    // The rowid is read by a name no column shadows, and the BLOB values to be left out are
+    // left out by the query itself when the values are read as they are.
+    let rowid = rowid_name(conn, options, table_name)
+        .ok()
+        .flatten()
+        .map(|rowid| quote::identifier(&rowid).into_owned());
+    let leave_out = (stream || options.max_blob_size.is_some()) && identity && rowid.is_some();
+    // A lenient dump also reads the rowids, if any, to report the rows it leaves out, as does
+    // one leaving out BLOB values.
+    let expressions = quote::identifiers(columns, ", ");
+    let without_rowid = options.strict && options.max_blob_size.is_none();
+    let (stmt, with_rowid) = match (rowid, leave_out, without_rowid) {
+        (Some(rowid), true, _) => (
+            query(&crate::blob::expressions(options, columns, &rowid, stream)),
+            true,
+        ),
+        (Some(rowid), false, false) => (query(&format!("{expressions}, {rowid}")), true),
+        _ => (query(&expressions), false),
+    };
+
+    let in_table = |row| Error::in_table(table_name, StatementKind::Data, row);
+
+    // A table that cannot be read is left out of a lenient dump.
+    let mut stmt = match stmt {
+        Ok(stmt) => stmt,
+        Err(err) => return skip(options, table_name, None, err).map_err(in_table(None)),
+    };
+    let mut rows = stmt.query([]).map_err(in_table(None))?;
+    progress.start_table(table_name);
+
+    let mut index = 0;
+    loop {
+        // The rows past one that cannot be stepped over are left out as well.
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(err) => {
+                skip(options, table_name, None, err).map_err(in_table(Some(index)))?;
+                break;
+            }
+        };
+        crate::cancel::check(options.cancellation.as_ref())?;
+        index += 1;
+
+        let values = match (0..columns.len())
+            .map(|i| read_value(options, row, i))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(values) => values,
+            Err(err) => {
+                let rowid = with_rowid.then(|| row.get(columns.len()).ok()).flatten();
+                skip(options, table_name, rowid, err).map_err(in_table(Some(index - 1)))?;
+                continue;
+            }
+        };
+        let flags = match leave_out {
+            true => {
+                crate::blob::Flags::read(row, columns.len()).map_err(in_table(Some(index - 1)))?
+            }
+            false => None,
+        };
+
+        if identity && options.max_blob_size.is_none() {
+            f(&values, flags.as_ref())?;
+            progress.row();
+            continue;
+        }
+
+        let blob_row = crate::blob::Row {
+            table_name,
+            rowid: with_rowid.then(|| row.get(columns.len()).ok()).flatten(),
+            index: index - 1,
+            flags: flags.as_ref(),
+        };
+        let rewritten = rewriter.rewrite_row(conn, options, &blob_row, &values)?;
+        let values = values
+            .into_iter()
+            .zip(&rewritten)
+            .map(|(value, rewritten)| {
+                rewritten
+                    .as_ref()
+                    .map_or(value, rusqlite::types::ValueRef::from)
+            })
+            .collect::<Vec<_>>();
+
+        f(&values, flags.as_ref())?;
+        progress.row();
+    }
+    progress.end_table();
+
+    Ok(())
+}
+
+/// Returns the name and declared type of every column of `table_name` whose values are dumped.
+#[cfg(any(feature = "arrow", feature = "parquet"))]
+pub(crate) fn column_types(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> Result<Vec<(String, String)>, crate::Error> {
+    let columns = (|| {
+        let mut stmt =
+            conn.prepare("SELECT name, type FROM pragma_table_xinfo(?1, ?2) WHERE hidden = 0;")?;
+        let columns = stmt
+            .query_map([table_name, &options.schema], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<_, _>>();
+        columns
+    })();
+
+    columns.map_err(Error::in_table(table_name, StatementKind::Schema, None))
+}
+
+/// Returns the shadow tables that make up the index of an FTS4 or FTS5 table.
+///
+/// Returns `None` for other virtual tables, and for contentless FTS tables whose index
+/// cannot be rebuilt.
+fn fts_index_shadow_tables(table_name: &str, create_sql: &str) -> Option<Vec<String>> {
+    let create_sql = create_sql.to_ascii_lowercase();
+    if !create_sql.starts_with("create virtual table") {
+        return None;
+    }
+
+    let (_, module) = create_sql.split_once(" using ")?;
+    let (module, args) = module.split_once('(')?;
+
+    let args = args.replace(char::is_whitespace, "");
+    if args.contains("content=''") || args.contains("content=\"\"") {
+        return None;
+    }
+
+    let suffixes: &[&str] = match module.trim() {
+        "fts5" => &["data", "idx", "docsize"],
+        "fts4" => &["segments", "segdir", "docsize", "stat"],
+        _ => return None,
+    };
+
+    Some(
+        suffixes
+            .iter()
+            .map(|suffix| format!("{table_name}_{suffix}"))
+            .collect(),
+    )
+}
+
+pub(crate) fn write_value(
+    out: &mut String,
+    options: &DumpOptions,
+    value: rusqlite::types::ValueRef<'_>,
+) {
+    match value {
+        rusqlite::types::ValueRef::Null => out.push_str("NULL"),
+        rusqlite::types::ValueRef::Integer(i) => out.push_str(itoa::Buffer::new().format(i)),
+        rusqlite::types::ValueRef::Real(f) => write_real(out, f),
+        rusqlite::types::ValueRef::Text(t) => match std::str::from_utf8(t) {
+            Ok(t) => quote::push_escaped_text(out, t, options.newlines),
+            Err(_) if options.invalid_utf8 == InvalidUtf8::Cast => {
+                out.push_str("CAST(");
+                write_blob(out, t);
+                out.push_str(" AS TEXT)");
+            }
+            Err(_) => quote::push_escaped_text(out, &String::from_utf8_lossy(t), options.newlines),
+        },
+        rusqlite::types::ValueRef::Blob(b) => write_blob(out, b),
+    }
+}
+
+/// Appends `bytes` to `out` as an SQL blob literal.
+fn write_blob(out: &mut String, bytes: &[u8]) {
+    out.push_str("X'");
+    write_hex(out, bytes);
+    out.push('\'');
+}
+
+/// Formats `bytes` as lowercase hex digits.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 2);
+    write_hex(&mut output, bytes);
+    output
+}
+
+/// Appends `bytes` to `out` as lowercase hex digits.
+pub(crate) fn write_hex(out: &mut String, bytes: &[u8]) {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    out.reserve(bytes.len() * 2);
+    for b in bytes {
+        out.push(DIGITS[usize::from(b >> 4)] as char);
+        out.push(DIGITS[usize::from(b & 0xf)] as char);
+    }
+}
+
+/// Reads the value of column `i` of `row`, failing on TEXT that is not valid UTF-8 if the
+/// options ask for it.
+pub(crate) fn read_value<'row>(
+    options: &DumpOptions,
+    row: &'row rusqlite::Row<'_>,
+    i: usize,
+) -> rusqlite::Result<rusqlite::types::ValueRef<'row>> {
+    let value = row.get_ref(i)?;
+    if let (InvalidUtf8::Error, rusqlite::types::ValueRef::Text(t)) = (options.invalid_utf8, value)
+    {
+        std::str::from_utf8(t)?;
+    }
+
+    Ok(value)
+}
+
+/// Formats `f` as an SQL literal that reads back as the same REAL value, like the SQLite CLI.
+pub(crate) fn format_real(f: f64) -> String {
+    let mut out = String::new();
+    write_real(&mut out, f);
+    out
+}
+
+/// Appends `f` to `out` as an SQL literal that reads back as the same REAL value, like the
+/// SQLite CLI.
+///
+/// Whole numbers keep a `.0` fractional part, so that they are not restored as integers into
+/// columns without a type affinity, such as the `ANY` columns of `STRICT` tables. Other numbers
+/// are printed by SQLite itself with `%!.20g` when that reads back exactly, and infinities as
+/// out-of-range literals.
+///
+/// Numbers smaller than about `1e-80` may still not be restored bit for bit, as SQLite reads
+/// them back with a small error whatever their digits.
+fn write_real(out: &mut String, f: f64) {
+    // The range of i64, whose upper bound is not exactly representable as a double.
+    const WHOLE: std::ops::Range<f64> = -9223372036854775808.0..9223372036854775808.0;
+
+    match f {
+        f64::INFINITY => out.push_str("9.0e+999"),
+        f64::NEG_INFINITY => out.push_str("-9.0e+999"),
+        // SQLite stores NaN as NULL.
+        f if f.is_nan() => out.push_str("NULL"),
+        f if f.fract() == 0.0 && WHOLE.contains(&f) => {
+            out.push_str(itoa::Buffer::new().format(f as i64));
+            out.push_str(".0");
+        }
+        f => {
+            let mut buf = [0 as std::ffi::c_char; 50];
+            // SAFETY: `buf` is as large as the length passed along, and the format takes
+            // a single double.
+            let printed = unsafe {
+                rusqlite::ffi::sqlite3_snprintf(
+                    buf.len() as std::ffi::c_int,
+                    buf.as_mut_ptr(),
+                    c"%!.20g".as_ptr(),
+                    f,
+                );
+                std::ffi::CStr::from_ptr(buf.as_ptr())
+            };
+            let printed = printed.to_str().unwrap_or_default();
+
+            // SQLite only computes about 18 digits exactly, which do not always read back as
+            // the same value. The shortest digits that do are used then, in the same notation.
+            if printed.parse::<f64>() == Ok(f) {
+                out.push_str(printed);
+                return;
+            }
+            write_scientific(out, ryu::Buffer::new().format_finite(f));
+        }
+    }
+}
+
+/// Appends `number`, as printed by `ryu` in either notation, to `out` in the `d.ddde+XX`
+/// notation of SQLite.
+fn write_scientific(out: &mut String, number: &str) {
+    if let Some(rest) = number.strip_prefix('-') {
+        out.push('-');
+        return write_scientific(out, rest);
+    }
+
+    let (mantissa, exponent) = number.split_once('e').unwrap_or((number, "0"));
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = whole.bytes().chain(fraction.bytes());
+    let leading = digits.clone().take_while(|&d| d == b'0').count();
+
+    // `ryu` prints at most 17 significant digits, along with a few zeros.
+    let mut significant = [0; 32];
+    let mut len = 0;
+    for (slot, digit) in significant.iter_mut().zip(digits.skip(leading)) {
+        *slot = digit;
+        len += 1;
+    }
+    while len > 0 && significant[len - 1] == b'0' {
+        len -= 1;
+    }
+    let significant = &significant[..len];
+    let exponent =
+        exponent.parse::<i64>().unwrap_or_default() + whole.len() as i64 - 1 - leading as i64;
+
+    match significant.split_first() {
+        Some((first, [])) => {
+            out.push(char::from(*first));
+            out.push_str(".0");
+        }
+        Some((first, rest)) => {
+            out.push(char::from(*first));
+            out.push('.');
+            out.extend(rest.iter().copied().map(char::from));
+        }
+        None => return out.push_str("0.0e+00"),
+    }
+    out.push_str(if exponent < 0 { "e-" } else { "e+" });
+    if exponent.abs() < 10 {
+        out.push('0');
+    }
+    out.push_str(itoa::Buffer::new().format(exponent.abs()));
+}
+
+/// The name of the metadata written ahead of the tables by the JSON and CSV formats. Names
+/// starting with `sqlite_` are reserved by SQLite, so no table has it.
+pub(crate) const METADATA: &str = "sqlite_metadata";
+
+/// Formats `value` as plain text for the non-SQL formats.
+///
+/// NULL becomes an empty string and blobs are written as hex.
+pub(crate) fn format_plain_value(value: rusqlite::types::ValueRef<'_>) -> String {
+    match value {
+        rusqlite::types::ValueRef::Null => String::new(),
+        rusqlite::types::ValueRef::Integer(i) => i.to_string(),
+        rusqlite::types::ValueRef::Real(f) => f.to_string(),
+        rusqlite::types::ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+        rusqlite::types::ValueRef::Blob(b) => hex(b),
+    }
+}
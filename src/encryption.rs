@@ -0,0 +1,106 @@
+use std::{fmt, io, iter, str::FromStr};
+
+use age::secrecy::SecretString;
+
+use crate::Encryption;
+
+/// The key reading back a dump written with
+/// [`DumpOptions::encryption`](crate::DumpOptions::encryption), see [`decrypt`].
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Decryption {
+    /// The passphrase the dump was encrypted with.
+    Passphrase(String),
+    /// Age identities, such as `AGE-SECRET-KEY-1...`, one of which matches a recipient the
+    /// dump was encrypted to.
+    Identities(Vec<String>),
+}
+
+impl fmt::Debug for Decryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The keys are kept out of logs.
+        match self {
+            Decryption::Passphrase(_) => f.write_str("Passphrase(..)"),
+            Decryption::Identities(identities) => {
+                write!(f, "Identities([..; {}])", identities.len())
+            }
+        }
+    }
+}
+
+/// The age encryptor writing the output as `encryption` selects, which is not
+/// [`Encryption::None`].
+pub(crate) fn encryptor(encryption: &Encryption) -> Result<age::Encryptor, crate::Error> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    match encryption {
+        Encryption::None => unreachable!("the output is not encrypted"),
+        Encryption::Passphrase(passphrase) => Ok(age::Encryptor::with_user_passphrase(
+            SecretString::from(passphrase.clone()),
+        )),
+        Encryption::Recipients(recipients) => {
+            let recipients = recipients
+                .iter()
+                .map(|recipient| {
+                    age::x25519::Recipient::from_str(recipient).map_err(|err| {
+                        invalid(format!("invalid age recipient '{recipient}': {err}"))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            age::Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as _))
+                .map_err(|err| invalid(err.to_string()).into())
+        }
+    }
+}
+
+/// Reads the dump encrypted with [`DumpOptions::encryption`](crate::DumpOptions::encryption)
+/// from `reader`, returning a reader of its plain content, such as to pass to
+/// [`ConnectionExt::restore`](crate::ConnectionExt::restore).
+///
+/// Fails with [`Error::Decrypt`](crate::Error::Decrypt) if `key` does not open the dump, or if
+/// it is not an age file. The content is authenticated as it is read, so that a dump altered
+/// since it was written fails to read rather than restoring something else.
+///
+/// ```
+/// use dumpqlite::{ConnectionExt, Decryption, DumpOptions, Encryption};
+///
+/// let conn = rusqlite::Connection::open_in_memory()?;
+/// conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")?;
+/// let options = DumpOptions::new().encryption(Encryption::Recipients(vec![
+///     "age1pgs460cefrkf84e2spx65t0t2xs5lgnyp0075xwdhzpsfu3ykplqa0nvrt".to_owned(),
+/// ]));
+/// let mut dump = Vec::new();
+/// conn.dump_with(&options, &mut dump)?;
+///
+/// let key = Decryption::Identities(vec![
+///     "AGE-SECRET-KEY-19UK6NNG6NN0CDTNFYJYTXAJSH305372FZTJR6ZS5N4X7086UP35Q4KDWLE".to_owned(),
+/// ]);
+/// let mut restored = rusqlite::Connection::open_in_memory()?;
+/// ConnectionExt::restore(&mut restored, &mut dumpqlite::decrypt(dump.as_slice(), &key)?)?;
+/// assert_eq!(conn.dump_to_string()?, restored.dump_to_string()?);
+/// # Ok::<(), dumpqlite::Error>(())
+/// ```
+pub fn decrypt<R: io::Read>(reader: R, key: &Decryption) -> Result<impl io::Read, crate::Error> {
+    let decryptor = age::Decryptor::new(reader)?;
+
+    match key {
+        Decryption::Passphrase(passphrase) => {
+            let identity = age::scrypt::Identity::new(SecretString::from(passphrase.clone()));
+            Ok(decryptor.decrypt(iter::once(&identity as _))?)
+        }
+        Decryption::Identities(identities) => {
+            let identities = identities
+                .iter()
+                .map(|identity| {
+                    age::x25519::Identity::from_str(identity).map_err(|err| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("invalid age identity: {err}"),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(decryptor.decrypt(identities.iter().map(|identity| identity as _))?)
+        }
+    }
+}
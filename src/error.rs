@@ -0,0 +1,171 @@
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Io(std::io::Error),
+    Rusqlite(rusqlite::Error),
+    /// A statement reading a table failed.
+    Table {
+        /// The table being read.
+        table: String,
+        /// Whether the schema or the rows of `table` were being read.
+        kind: StatementKind,
+        /// The index of the row of `table` being read, counting from zero, if any.
+        ///
+        /// The index counts the rows read from the start of the table, after any filter.
+        row: Option<u64>,
+        source: rusqlite::Error,
+    },
+    /// A statement of a dump failed to prepare, as reported by [`validate`](crate::validate).
+    Statement {
+        /// The line the statement starts on, counting from one.
+        line: u64,
+        /// The text of the statement, along with any other statement on its lines.
+        sql: String,
+        source: rusqlite::Error,
+    },
+    /// The dumped database failed the check of
+    /// [`DumpOptions::verify_source`](crate::DumpOptions::verify_source).
+    Corrupt {
+        /// The problems reported by `PRAGMA integrity_check`, up to a hundred of them.
+        problems: Vec<String>,
+    },
+    /// The key given to [`apply_key`](crate::apply_key) does not decrypt the database.
+    #[cfg(feature = "sqlcipher")]
+    WrongKey,
+    /// The dump was cancelled through its [`CancellationToken`](crate::CancellationToken).
+    Cancelled,
+    /// The sink given to [`ConnectionExt::dump_with_sink`](crate::ConnectionExt::dump_with_sink)
+    /// failed.
+    Sink(Box<dyn core::error::Error + Send + Sync>),
+    /// An encrypted dump failed to decrypt, as read by [`decrypt`](crate::decrypt).
+    #[cfg(feature = "age")]
+    Decrypt(age::DecryptError),
+    #[cfg(feature = "parquet")]
+    Parquet(::parquet::errors::ParquetError),
+    #[cfg(feature = "arrow")]
+    Arrow(arrow_schema::ArrowError),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Io(err) => core::fmt::Display::fmt(err, f),
+            Error::Rusqlite(err) => core::fmt::Display::fmt(err, f),
+            Error::Table {
+                table,
+                kind,
+                row,
+                source,
+            } => match (kind, row) {
+                (_, Some(row)) => write!(f, "failed to read row {row} of table {table}: {source}"),
+                (StatementKind::Schema, None) => {
+                    write!(f, "failed to read the schema of table {table}: {source}")
+                }
+                (StatementKind::Data, None) => {
+                    write!(f, "failed to read the rows of table {table}: {source}")
+                }
+            },
+            Error::Statement { line, source, .. } => {
+                write!(f, "invalid statement at line {line}: {source}")
+            }
+            Error::Corrupt { problems } => match problems.as_slice() {
+                [] => f.write_str("the database failed its integrity check"),
+                [problem] => write!(f, "the database failed its integrity check: {problem}"),
+                [problem, rest @ ..] => write!(
+                    f,
+                    "the database failed its integrity check: {problem} (and {} more problems)",
+                    rest.len()
+                ),
+            },
+            #[cfg(feature = "sqlcipher")]
+            Error::WrongKey => f.write_str("the key does not decrypt the database"),
+            Error::Cancelled => f.write_str("dump cancelled"),
+            Error::Sink(err) => core::fmt::Display::fmt(err, f),
+            #[cfg(feature = "age")]
+            Error::Decrypt(err) => write!(f, "failed to decrypt the dump: {err}"),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(err) => core::fmt::Display::fmt(err, f),
+            #[cfg(feature = "arrow")]
+            Error::Arrow(err) => core::fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Rusqlite(err) => Some(err),
+            Error::Table { source, .. } | Error::Statement { source, .. } => Some(source),
+            Error::Corrupt { .. } | Error::Cancelled => None,
+            #[cfg(feature = "sqlcipher")]
+            Error::WrongKey => None,
+            Error::Sink(err) => Some(err.as_ref()),
+            #[cfg(feature = "age")]
+            Error::Decrypt(err) => Some(err),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(err) => Some(err),
+            #[cfg(feature = "arrow")]
+            Error::Arrow(err) => Some(err),
+        }
+    }
+}
+
+/// What a failed statement was reading, see [`Error::Table`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StatementKind {
+    /// The columns or the definition of a table.
+    Schema,
+    /// The rows of a table.
+    Data,
+}
+
+impl Error {
+    /// Returns a function wrapping the error of a statement reading `table` with where it failed.
+    pub(crate) fn in_table(
+        table: &str,
+        kind: StatementKind,
+        row: Option<u64>,
+    ) -> impl FnOnce(rusqlite::Error) -> Self + '_ {
+        move |source| Error::Table {
+            table: table.to_owned(),
+            kind,
+            row,
+            source,
+        }
+    }
+}
+
+impl core::convert::From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Error::Io(source)
+    }
+}
+
+impl core::convert::From<rusqlite::Error> for Error {
+    fn from(source: rusqlite::Error) -> Self {
+        Error::Rusqlite(source)
+    }
+}
+
+#[cfg(feature = "age")]
+impl core::convert::From<age::DecryptError> for Error {
+    fn from(source: age::DecryptError) -> Self {
+        Error::Decrypt(source)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl core::convert::From<::parquet::errors::ParquetError> for Error {
+    fn from(source: ::parquet::errors::ParquetError) -> Self {
+        Error::Parquet(source)
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl core::convert::From<arrow_schema::ArrowError> for Error {
+    fn from(source: arrow_schema::ArrowError) -> Self {
+        Error::Arrow(source)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{dump, quote, DumpOptions, Error, StatementKind};
+
+/// The size of a dump estimated before writing it, as returned by
+/// [`ConnectionExt::estimate`](crate::ConnectionExt::estimate), to set aside disk space or show
+/// how far a dump has gone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DumpEstimate {
+    /// The estimate of each table whose rows are dumped, in the order they are dumped in.
+    pub tables: Vec<TableEstimate>,
+    /// The number of rows of all the tables.
+    pub rows: u64,
+    /// The estimated size of the `INSERT` statements of all the tables, in bytes.
+    pub bytes: u64,
+}
+
+/// The estimated size of the rows of a table, see [`DumpEstimate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableEstimate {
+    /// The name of the table.
+    pub table: String,
+    /// The number of rows dumped, after [`DumpOptions::where_clause`] and sampling.
+    pub rows: u64,
+    /// The estimated size of the `INSERT` statements of the rows, in bytes.
+    pub bytes: u64,
+}
+
+/// Counts the rows of the tables selected by `options` and the bytes of their values written
+/// as SQL literals, reading each table once without writing anything.
+pub(crate) fn estimate(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<DumpEstimate, crate::Error> {
+    let mut tables = Vec::new();
+    for table_name in dump::data_tables(conn, options)? {
+        let Some(rows) = dump::table_rows(conn, options, &table_name)? else {
+            continue;
+        };
+        tables.push(
+            estimate_table(conn, options, &rows).map_err(Error::in_table(
+                &table_name,
+                StatementKind::Data,
+                None,
+            ))?,
+        );
+    }
+
+    Ok(DumpEstimate {
+        rows: tables.iter().map(|table| table.rows).sum(),
+        bytes: tables.iter().map(|table| table.bytes).sum(),
+        tables,
+    })
+}
+
+fn estimate_table(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    rows: &dump::TableRows,
+) -> rusqlite::Result<TableEstimate> {
+    // `quote()` writes values as the literals of a SQLite dump, but for the digits of some
+    // floating-point values. Their length is counted in bytes, as text is written in UTF-8.
+    let lengths = match rows.columns.is_empty() {
+        true => "0".to_owned(),
+        false => rows
+            .columns
+            .iter()
+            .map(|column| format!("length(CAST(quote({}) AS BLOB))", quote::identifier(column)))
+            .collect::<Vec<_>>()
+            .join(" + "),
+    };
+    let sql = dump::select(
+        conn,
+        options,
+        &rows.table_name,
+        &format!("count(*), total({lengths})"),
+    );
+    let (count, values): (i64, f64) =
+        conn.query_row(&sql, [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    // Each row is written as `{insert} VALUES({values});` on a line of its own.
+    let overhead =
+        rows.insert.len() + " VALUES(".len() + ");\n".len() + rows.columns.len().saturating_sub(1);
+    let count = count as u64;
+
+    Ok(TableEstimate {
+        table: rows.table_name.clone(),
+        rows: count,
+        bytes: count * overhead as u64 + values as u64,
+    })
+}
@@ -0,0 +1,49 @@
+use crate::{quote, DumpOptions};
+
+/// A row breaking a foreign key, as reported by `PRAGMA foreign_key_check` and returned by
+/// [`ConnectionExt::foreign_key_check`](crate::ConnectionExt::foreign_key_check).
+///
+/// A dump holding such a row fails to restore with foreign keys enforced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ForeignKeyViolation {
+    /// The table holding the row.
+    pub table: String,
+    /// The rowid of the row, or `None` for a `WITHOUT ROWID` table.
+    pub rowid: Option<i64>,
+    /// The table the row refers to without a matching row.
+    pub parent: String,
+    /// The index of the broken foreign key among those listed by
+    /// `PRAGMA foreign_key_list(table)`.
+    pub foreign_key: i64,
+}
+
+/// Runs `PRAGMA foreign_key_check` on the dumped schema, keeping the violations of the
+/// tables selected by `options`, ordered by table and rowid.
+pub(crate) fn check(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Vec<ForeignKeyViolation>, crate::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "PRAGMA {}.foreign_key_check;",
+        quote::identifier(&options.schema)
+    ))?;
+    let mut violations = stmt
+        .query_map([], |row| {
+            Ok(ForeignKeyViolation {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+                foreign_key: row.get(3)?,
+            })
+        })?
+        .filter(|violation| {
+            violation
+                .as_ref()
+                .map_or(true, |violation| options.includes_table(&violation.table))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    violations.sort_by(|a, b| (&a.table, a.rowid).cmp(&(&b.table, b.rowid)));
+
+    Ok(violations)
+}
@@ -0,0 +1,118 @@
+use rusqlite::types::ValueRef;
+
+use crate::{dump, quote, sha256::Sha256, DumpOptions, Error, StatementKind};
+
+/// Hashes the schema and rows of the tables selected by `options` in a canonical order.
+///
+/// The schema is hashed by type and name, without the pages it is stored in. The rows of
+/// each table are hashed by table name, and ordered by primary key, or by every column for
+/// tables without one, leaving out the rowids that `VACUUM` may change. Virtual tables are
+/// hashed through their shadow tables.
+pub(crate) fn content_hash(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<[u8; 32], crate::Error> {
+    let mut hash = Sha256::new();
+
+    let mut stmt = conn.prepare(&format!(
+        r#"
+            SELECT type, name, tbl_name, sql
+            FROM {}
+            WHERE sql NOT NULL
+                AND name NOT LIKE 'sqlite_%'
+            ORDER BY type, name;"#,
+        options.qualified("sqlite_schema")
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let table: String = row.get(2)?;
+        if !options.includes_table(&table) {
+            continue;
+        }
+
+        hash.update(b"S");
+        for i in 0..4 {
+            hash_value(&mut hash, row.get_ref(i)?);
+        }
+    }
+
+    let mut tables = dump::tables(conn, options)?;
+    tables.sort();
+    for (table_name, create_sql) in &tables {
+        if create_sql.starts_with("CREATE VIRTUAL TABLE") {
+            continue;
+        }
+
+        hash.update(b"D");
+        hash_value(&mut hash, ValueRef::Text(table_name.as_bytes()));
+        hash_rows(conn, options, table_name, &mut hash).map_err(Error::in_table(
+            table_name,
+            StatementKind::Data,
+            None,
+        ))?;
+    }
+
+    Ok(hash.finish())
+}
+
+/// Hashes the rows of `table_name`, in the order of their primary key or of their columns,
+/// and returns their number.
+pub(crate) fn hash_rows(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+    hash: &mut Sha256,
+) -> rusqlite::Result<u64> {
+    let columns = dump::read_columns(conn, options, table_name)?;
+    let primary_key = dump::primary_key(conn, options, table_name)?;
+    let order = match primary_key.is_empty() {
+        true => &columns,
+        false => &primary_key,
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "{} ORDER BY {};",
+        dump::select(
+            conn,
+            options,
+            table_name,
+            &quote::identifiers(&columns, ", ")
+        ),
+        quote::identifiers(order, ", ")
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut count = 0;
+    while let Some(row) = rows.next()? {
+        hash.update(b"R");
+        for i in 0..columns.len() {
+            hash_value(hash, row.get_ref(i)?);
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Hashes `value` with a tag for its type, and the length of TEXT and BLOB values so that
+/// adjacent values cannot be confused.
+fn hash_value(hash: &mut Sha256, value: ValueRef<'_>) {
+    match value {
+        ValueRef::Null => hash.update(b"N"),
+        ValueRef::Integer(i) => {
+            hash.update(b"I");
+            hash.update(&i.to_be_bytes());
+        }
+        ValueRef::Real(f) => {
+            hash.update(b"F");
+            hash.update(&f.to_bits().to_be_bytes());
+        }
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => {
+            hash.update(match value {
+                ValueRef::Text(_) => b"T",
+                _ => b"B",
+            });
+            hash.update(&(bytes.len() as u64).to_be_bytes());
+            hash.update(bytes);
+        }
+    }
+}
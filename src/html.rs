@@ -0,0 +1,149 @@
+use std::io;
+
+use crate::DumpOptions;
+
+/// Writes a standalone HTML document with one `<table>` per table, under a heading with its name.
+///
+/// NULL cells are marked with a `null` class so that they can be told apart from empty text.
+/// Tables longer than `max_display_rows` are cut short, with a note saying so.
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let tables = crate::dump::data_tables(conn, options)?;
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>")?;
+    writeln!(writer, "<head>")?;
+    writeln!(writer, "<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>Database dump</title>")?;
+    writeln!(writer, "</head>")?;
+    writeln!(writer, "<body>")?;
+
+    for table_name in &tables {
+        writeln!(writer, "<h2>{}</h2>", escape(table_name))?;
+        writeln!(writer, "<table>")?;
+
+        let columns = crate::dump::columns(conn, options, table_name)?;
+        write!(writer, "<thead><tr>")?;
+        for column in &columns {
+            write!(writer, "<th>{}</th>", escape(column))?;
+        }
+        writeln!(writer, "</tr></thead>")?;
+
+        writeln!(writer, "<tbody>")?;
+
+        // One more row than displayed is read to tell whether the table was cut short.
+        let limit = options.max_display_rows.map(|rows| rows + 1);
+        let mut rows = 0;
+        if !options.schema_only {
+            crate::dump::for_each_row(
+                conn,
+                options,
+                progress,
+                table_name,
+                &columns,
+                limit,
+                |values| {
+                    rows += 1;
+                    if Some(rows) == limit {
+                        return Ok(());
+                    }
+
+                    write!(writer, "<tr>")?;
+                    for &value in values {
+                        if value == rusqlite::types::ValueRef::Null {
+                            write!(writer, "<td class=\"null\"></td>")?;
+                        } else {
+                            let value = crate::dump::format_plain_value(value);
+                            write!(writer, "<td>{}</td>", escape(&value))?;
+                        }
+                    }
+                    writeln!(writer, "</tr>")?;
+
+                    Ok(())
+                },
+            )?;
+        }
+
+        writeln!(writer, "</tbody>")?;
+        writeln!(writer, "</table>")?;
+
+        if let Some(max_display_rows) = options.max_display_rows.filter(|_| Some(rows) == limit) {
+            writeln!(
+                writer,
+                "<p>Only the first {max_display_rows} rows are shown.</p>"
+            )?;
+        }
+    }
+
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")?;
+
+    Ok(())
+}
+
+/// Escapes the characters that are special in HTML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConnectionExt, DumpFormat, DumpOptions};
+
+    #[test]
+    fn test_dump_html() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users VALUES (1, '<script>alert("hi & bye")</script>'), (2, NULL), (3, '');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = DumpOptions::new()
+            .format(DumpFormat::Html)
+            .max_display_rows(2);
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Database dump</title>
+</head>
+<body>
+<h2>users</h2>
+<table>
+<thead><tr><th>id</th><th>name</th></tr></thead>
+<tbody>
+<tr><td>1</td><td>&lt;script&gt;alert(&quot;hi &amp; bye&quot;)&lt;/script&gt;</td></tr>
+<tr><td>2</td><td class="null"></td></tr>
+</tbody>
+</table>
+<p>Only the first 2 rows are shown.</p>
+</body>
+</html>
+"#;
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{quote, DumpOptions};
+
+/// The most problems reported by a failed check.
+const MAX_PROBLEMS: usize = 100;
+
+/// Runs `PRAGMA integrity_check` on the dumped schema if [`DumpOptions::verify_source`] is
+/// enabled, failing with [`Error::Corrupt`](crate::Error::Corrupt) if it reports a problem.
+pub(crate) fn check(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<(), crate::Error> {
+    if !options.verify_source {
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "PRAGMA {}.integrity_check({MAX_PROBLEMS});",
+        quote::identifier(&options.schema)
+    ))?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match problems.as_slice() {
+        [ok] if ok == "ok" => Ok(()),
+        _ => Err(crate::Error::Corrupt { problems }),
+    }
+}
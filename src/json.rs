@@ -0,0 +1,173 @@
+use std::io;
+
+use crate::DumpOptions;
+
+/// Writes the whole database as a JSON object mapping each table name to an array of rows,
+/// each row being an object keyed by column name.
+///
+/// Integers and reals become numbers, text becomes strings and blobs become strings in the
+/// encoding of `options`, which is recorded in a leading `sqlite_metadata` member.
+/// Non-finite reals, which JSON cannot represent, become `null` like NULL itself.
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let tables = crate::dump::data_tables(conn, options)?;
+
+    write!(
+        writer,
+        "{{\n  {}: {{\"blob_encoding\": {}}}",
+        quote_string(crate::dump::METADATA),
+        quote_string(options.blob_encoding.name())
+    )?;
+
+    for table_name in &tables {
+        write!(writer, ",\n  {}: [", quote_string(table_name))?;
+
+        let columns = crate::dump::columns(conn, options, table_name)?;
+        let keys = columns
+            .iter()
+            .map(|column| quote_string(column))
+            .collect::<Vec<_>>();
+
+        let mut rows = 0;
+        if !options.schema_only {
+            crate::dump::for_each_row(
+                conn,
+                options,
+                progress,
+                table_name,
+                &columns,
+                None,
+                |values| {
+                    let members = keys
+                        .iter()
+                        .zip(values.iter().map(|value| format_value(options, *value)))
+                        .map(|(key, value)| format!("{key}: {value}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    let separator = if rows > 0 { "," } else { "" };
+                    rows += 1;
+                    Ok(write!(writer, "{separator}\n    {{{members}}}")?)
+                },
+            )?;
+        }
+
+        if rows > 0 {
+            write!(writer, "\n  ")?;
+        }
+        write!(writer, "]")?;
+    }
+
+    writeln!(writer, "\n}}")?;
+
+    Ok(())
+}
+
+fn format_value(options: &DumpOptions, value: rusqlite::types::ValueRef<'_>) -> String {
+    match value {
+        rusqlite::types::ValueRef::Null => "null".to_owned(),
+        rusqlite::types::ValueRef::Integer(i) => i.to_string(),
+        rusqlite::types::ValueRef::Real(f) if f.is_finite() => format!("{f:?}"),
+        rusqlite::types::ValueRef::Real(_) => "null".to_owned(),
+        rusqlite::types::ValueRef::Text(t) => quote_string(&String::from_utf8_lossy(t)),
+        rusqlite::types::ValueRef::Blob(b) => quote_string(&options.blob_encoding.encode(b)),
+    }
+}
+
+/// Quotes `text` as a JSON string, escaping quotes, backslashes and control characters.
+pub(crate) fn quote_string(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 2);
+
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c < ' ' => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BlobEncoding, ConnectionExt, DumpFormat, DumpOptions};
+
+    #[test]
+    fn test_dump_json() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB, score REAL);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users VALUES (1, 'alice', X'cafe', 1.0), (2, 'bob "the" \builder', NULL, 9e999);
+INSERT INTO users VALUES (3, 'tab	and
+newline', X'', -0.25);"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = DumpOptions::new().format(DumpFormat::Json);
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"{
+  "sqlite_metadata": {"blob_encoding": "base64"},
+  "users": [
+    {"id": 1, "name": "alice", "avatar": "yv4=", "score": 1.0},
+    {"id": 2, "name": "bob \"the\" \\builder", "avatar": null, "score": null},
+    {"id": 3, "name": "tab\tand\nnewline", "avatar": "", "score": -0.25}
+  ],
+  "tags": []
+}
+"#;
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_json_blob_encoding() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE files (id INTEGER PRIMARY KEY, content BLOB);
+INSERT INTO files VALUES (1, X'00ff10'), (2, X'');"#,
+        )?;
+
+        for (blob_encoding, name, content) in [
+            (BlobEncoding::Base64, "base64", "AP8Q"),
+            (BlobEncoding::Hex, "hex", "00ff10"),
+        ] {
+            let mut writer = Vec::new();
+            let options = DumpOptions::new()
+                .format(DumpFormat::Json)
+                .blob_encoding(blob_encoding);
+            conn.dump_with(&options, &mut writer)?;
+
+            let expected = format!(
+                r#"{{
+  "sqlite_metadata": {{"blob_encoding": "{name}"}},
+  "files": [
+    {{"id": 1, "content": "{content}"}},
+    {{"id": 2, "content": ""}}
+  ]
+}}
+"#
+            );
+            assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+        }
+
+        Ok(())
+    }
+}
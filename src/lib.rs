@@ -1,130 +1,1094 @@
-use std::io;
+mod affinity;
+mod archive;
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "async-tokio")]
+mod async_tokio;
+mod backup;
+mod base64;
+mod blob;
+mod cancel;
+mod checkpoint;
+mod checksum;
+#[cfg(feature = "sqlcipher")]
+mod cipher;
+mod codegen;
+mod csv;
+mod dialect;
+mod diff;
+mod dump;
+#[cfg(feature = "age")]
+mod encryption;
+mod error;
+mod estimate;
+mod foreign_keys;
+mod hash;
+mod html;
+mod integrity;
+mod json;
+mod manifest;
+mod markdown;
+mod metadata;
+mod migration;
+mod options;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod parts;
+mod pretty;
+mod progress;
+mod query;
+mod quote;
+mod restore;
+mod schema;
+#[cfg(feature = "session")]
+mod session;
+mod sha256;
+mod snapshot;
+mod split;
+mod statements;
+mod stats;
+#[cfg(feature = "stream")]
+mod stream;
+mod subset;
+mod trace;
+mod vacuum;
+mod validate;
+mod verify;
+mod views;
+mod warning;
+mod xml;
 
-#[derive(Debug)]
-pub enum Error {
-    Io(std::io::Error),
-    Rusqlite(rusqlite::Error),
-}
+use std::io::{self, Write as _};
+
+#[cfg(feature = "arrow")]
+pub use arrow::RecordBatches;
+#[cfg(feature = "async-tokio")]
+pub use async_tokio::dump_async;
+pub use backup::{BackupOptions, BackupProgress};
+pub use cancel::CancellationToken;
+pub use checkpoint::{Checkpoint, TableMark};
+pub use checksum::verify_checksum;
+#[cfg(feature = "sqlcipher")]
+pub use cipher::{apply_key, open_encrypted};
+pub use codegen::Codegen;
+pub use dialect::Dialect;
+#[cfg(feature = "age")]
+pub use encryption::{decrypt, Decryption};
+pub use error::{Error, StatementKind};
+pub use estimate::{DumpEstimate, TableEstimate};
+pub use foreign_keys::ForeignKeyViolation;
+pub use manifest::{Manifest, ManifestTable};
+pub use options::{
+    BlobEncoding, Compression, DumpFormat, DumpOptions, Encryption, InvalidUtf8, OnConflict,
+    OversizedBlob, Redaction,
+};
+pub use progress::{DumpMetrics, DumpProgress};
+pub use schema::{
+    Column, ForeignKey, Index, IndexColumn, IndexOrigin, Schema, Table, Trigger, View,
+};
+#[cfg(feature = "session")]
+pub use session::Session;
+pub use statements::{DumpChunk, DumpStatements};
+pub use stats::DumpStats;
+#[cfg(feature = "stream")]
+pub use stream::{dump_stream, DumpStream};
+pub use validate::validate;
+pub use verify::{TableDifference, VerifyReport};
+pub use warning::DumpWarning;
+
+pub trait ConnectionExt {
+    fn dump<W: io::Write>(&self, writer: &mut W) -> Result<(), crate::Error> {
+        self.dump_with(&DumpOptions::default(), writer)
+    }
+
+    /// Returns the dump as a string, for tests and small databases.
+    fn dump_to_string(&self) -> Result<String, crate::Error> {
+        let mut writer = Vec::new();
+        self.dump(&mut writer)?;
+
+        String::from_utf8(writer)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into())
+    }
+
+    fn dump_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<(), crate::Error>;
+
+    /// Returns the schema of the database: its tables with their columns, keys and indexes,
+    /// its views and its triggers.
+    fn schema(&self) -> Result<Schema, crate::Error> {
+        self.schema_with(&DumpOptions::default())
+    }
+
+    /// Returns the schema of the tables selected by `options`, along with their views and
+    /// triggers.
+    fn schema_with(&self, options: &DumpOptions) -> Result<Schema, crate::Error>;
+
+    /// Returns the statements of the dump one by one, to filter, rewrite or route them.
+    fn dump_statements(&self) -> DumpStatements<'_> {
+        self.dump_statements_with(&DumpOptions::default())
+    }
 
-impl core::fmt::Display for Error {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match self {
-            Error::Io(err) => core::fmt::Display::fmt(err, f),
-            Error::Rusqlite(err) => core::fmt::Display::fmt(err, f),
+    /// Returns the statements of the dump one by one, whatever the format of `options`.
+    ///
+    /// The statements are not compressed, and the byte counts reported to
+    /// [`DumpOptions::on_progress`] stay at zero since nothing is written.
+    fn dump_statements_with(&self, options: &DumpOptions) -> DumpStatements<'_>;
+
+    /// Passes the statements of the dump one by one to `sink`, to stream them to a database
+    /// driver or a message queue rather than a writer.
+    ///
+    /// The dump stops at the first error of `sink`, which is returned as [`Error::Sink`].
+    fn dump_with_sink<F, E>(&self, options: &DumpOptions, mut sink: F) -> Result<(), crate::Error>
+    where
+        F: FnMut(DumpChunk<'_>) -> Result<(), E>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut statements = self.dump_statements_with(options);
+        while let Some(statement) = statements.next_with_kind() {
+            let (kind, sql) = statement?;
+            sink(DumpChunk::new(kind, &sql)).map_err(|err| crate::Error::Sink(err.into()))?;
         }
+
+        Ok(())
     }
-}
 
-impl core::error::Error for Error {
-    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
-        match self {
-            Error::Io(err) => Some(err),
-            Error::Rusqlite(err) => Some(err),
+    /// Writes a best-effort dump, leaving out the tables and rows that fail to read as with
+    /// [`strict(false)`](DumpOptions::strict), and returns a warning for each of them.
+    fn dump_with_warnings<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<Vec<DumpWarning>, crate::Error> {
+        let warnings = warning::Warnings::default();
+        let mut options = options.clone().strict(false);
+        options.warnings = Some(warnings.clone());
+
+        self.dump_with(&options, writer)?;
+        Ok(warnings.take())
+    }
+
+    /// Writes the dump and returns a [`DumpStats`] summary of it: the rows of each table, the
+    /// bytes written, how long it took and what was left out.
+    ///
+    /// Unlike [`dump_with_warnings`](Self::dump_with_warnings), the dump is only lenient if
+    /// `options` make it so.
+    fn dump_with_stats<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<DumpStats, crate::Error> {
+        stats::dump(self, options, writer)
+    }
+
+    /// Writes the SQL dump and returns a [`Manifest`] of the byte range, row count and hash
+    /// of the rows of each table within it, to store next to the dump.
+    ///
+    /// Offsets count from the first byte written to `writer`. The dump must be in
+    /// [`DumpFormat::Sql`] and uncompressed, so that they point into the file as it is stored.
+    fn dump_with_manifest<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<Manifest, crate::Error>;
+
+    /// Writes the dump to the file at `path`, replacing it only once the dump is complete.
+    ///
+    /// The dump is written to a temporary file next to `path`, which is renamed into place
+    /// on success and removed on failure, so a failed dump never leaves a truncated file
+    /// where the previous one was.
+    fn dump_to_path<P: AsRef<std::path::Path>>(
+        &self,
+        options: &DumpOptions,
+        path: P,
+    ) -> Result<(), crate::Error> {
+        let path = path.as_ref();
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = path.with_file_name(temp_name);
+
+        let result = (|| {
+            let mut file = std::fs::File::options()
+                .write(true)
+                .create_new(true)
+                .open(&temp_path)?;
+            self.dump_with(options, &mut file)?;
+            file.sync_all()?;
+            drop(file);
+
+            std::fs::rename(&temp_path, path)?;
+            Ok(())
+        })();
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
         }
+        result
     }
-}
 
-impl core::convert::From<std::io::Error> for Error {
-    fn from(source: std::io::Error) -> Self {
-        Error::Io(source)
+    /// Writes a compacted copy of the database to a new database file at `path` with
+    /// `VACUUM INTO`, a physical backup to take in place of a dump when the copy should
+    /// open as a database as it is.
+    ///
+    /// Fails if a file already exists at `path`, or if run within a transaction.
+    fn vacuum_into<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), crate::Error> {
+        self.vacuum_into_with(&DumpOptions::default(), path)
     }
-}
 
-impl core::convert::From<rusqlite::Error> for Error {
-    fn from(source: rusqlite::Error) -> Self {
-        Error::Rusqlite(source)
+    /// Writes a compacted copy of the schema selected by [`DumpOptions::schema`] to a new
+    /// database file at `path`.
+    ///
+    /// The copy holds every table of the schema: the options selecting tables and rows, and
+    /// those shaping the output of a dump, are ignored. The database is checked first with
+    /// [`DumpOptions::verify_source`], and the copy stopped with
+    /// [`DumpOptions::cancellation`].
+    fn vacuum_into_with<P: AsRef<std::path::Path>>(
+        &self,
+        options: &DumpOptions,
+        path: P,
+    ) -> Result<(), crate::Error>;
+
+    /// Copies the database into `dest` with SQLite's online backup API, replacing its content,
+    /// a physical backup of a live database.
+    ///
+    /// The pages are copied a step at a time, the database being locked only while a step
+    /// reads them, so writers are not blocked for long. SQLite starts the copy over when
+    /// another connection writes to the database in between, so the copy holds a single point
+    /// in time. `dest` can be opened on a file or in memory, and must not be within a
+    /// transaction.
+    fn backup_to(
+        &self,
+        dest: &mut rusqlite::Connection,
+        options: &BackupOptions,
+    ) -> Result<(), crate::Error>;
+
+    /// Writes a tar archive holding the schema as `schema.sql` and the rows of each table as
+    /// `data/{table}.sql`, or with the extension of the selected format.
+    ///
+    /// Each data file is a dump of its table on its own, so that a large dump can be partly
+    /// extracted and restored table by table after `schema.sql`. The archive as a whole is
+    /// compressed as selected by [`DumpOptions::compression`].
+    fn dump_archive<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<(), crate::Error>;
+
+    /// Writes a dump of each table to its own writer, returned by `factory` for the name of the
+    /// table, to write one file per table, upload them in parallel or restore them one by one.
+    ///
+    /// Each dump holds the definition of the table, its indexes, triggers and rows, and its
+    /// `AUTOINCREMENT` counter, so that it restores on its own. Views get a dump of their own
+    /// too, under their name. Formats other than SQL only hold the rows of each table.
+    ///
+    /// ```
+    /// use dumpqlite::{ConnectionExt as _, DumpOptions};
+    ///
+    /// let conn = rusqlite::Connection::open_in_memory()?;
+    /// conn.execute_batch("CREATE TABLE a (x); CREATE TABLE b (y);")?;
+    ///
+    /// let mut tables = Vec::new();
+    /// conn.dump_per_table(&DumpOptions::default(), |table| {
+    ///     tables.push(table.to_owned());
+    ///     // Such as `std::fs::File::create(format!("{table}.sql"))`.
+    ///     Ok(Vec::new())
+    /// })?;
+    /// assert_eq!(vec!["a", "b"], tables);
+    /// # Ok::<(), dumpqlite::Error>(())
+    /// ```
+    fn dump_per_table<W, F>(&self, options: &DumpOptions, factory: F) -> Result<(), crate::Error>
+    where
+        W: io::Write,
+        F: FnMut(&str) -> io::Result<W>;
+
+    /// Writes the dump of the table or view `name` alone, with its indexes and triggers, to
+    /// share or restore a single table.
+    fn dump_table<W: io::Write>(&self, name: &str, writer: &mut W) -> Result<(), crate::Error> {
+        self.dump_table_with(&DumpOptions::default(), name, writer)
     }
-}
 
-pub trait ConnectionExt {
-    fn dump<W: io::Write>(&self, writer: &mut W) -> Result<(), crate::Error>;
-}
+    /// Writes the dump of the table or view `name` alone as `options` select, as a dump of
+    /// [`dump_per_table`](Self::dump_per_table) does, failing with an [`io::Error`] of kind
+    /// [`NotFound`](io::ErrorKind::NotFound) if there is no such table.
+    ///
+    /// The tables included or excluded by `options` are ignored. Set
+    /// [`DumpOptions::indexes`] and [`DumpOptions::triggers`] to leave out the indexes and
+    /// triggers of the table.
+    ///
+    /// ```
+    /// use dumpqlite::{ConnectionExt as _, DumpOptions};
+    ///
+    /// let conn = rusqlite::Connection::open_in_memory()?;
+    /// conn.execute_batch(
+    ///     "CREATE TABLE a (x); CREATE INDEX a_x ON a (x); CREATE TABLE b (y); INSERT INTO a VALUES (1);",
+    /// )?;
+    ///
+    /// let mut dump = Vec::new();
+    /// conn.dump_table_with(&DumpOptions::new().indexes(false), "a", &mut dump)?;
+    /// let dump = String::from_utf8(dump).unwrap();
+    /// assert!(dump.contains("INSERT INTO a VALUES(1);"));
+    /// assert!(!dump.contains("CREATE INDEX") && !dump.contains("CREATE TABLE b"));
+    /// # Ok::<(), dumpqlite::Error>(())
+    /// ```
+    fn dump_table_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        name: &str,
+        writer: &mut W,
+    ) -> Result<(), crate::Error>;
 
-impl ConnectionExt for rusqlite::Connection {
-    fn dump<W: io::Write>(&self, writer: &mut W) -> Result<(), crate::Error> {
-        writeln!(writer, "PRAGMA foreign_keys=OFF;")?;
-        writeln!(writer, "BEGIN TRANSACTION;")?;
+    /// Writes the rows returned by the `SELECT` statement `sql` as the dump of a table named
+    /// `table_name`, for ad hoc extracts and denormalized exports.
+    fn dump_query<W: io::Write>(
+        &self,
+        sql: &str,
+        table_name: &str,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        self.dump_query_with(&DumpOptions::default(), sql, table_name, writer)
+    }
 
-        let mut stmt = self.prepare(
-            r#"
-                SELECT name, sql
-                FROM sqlite_schema
-                WHERE sql NOT NULL
-                    AND type == 'table'
-                    AND name NOT LIKE 'sqlite_%';"#,
-        )?;
-        let tables = stmt
-            .query_map([], |row| {
-                let table_name: String = row.get(0)?;
-                let create_sql: String = row.get(1)?;
+    /// Writes the rows returned by `sql` as the dump of a table named `table_name`, in the
+    /// format of `options`, failing with an [`io::Error`] of kind
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) if `sql` is not a read-only query.
+    ///
+    /// The table is declared with the columns of the result, typed as the table columns they
+    /// read, if any, and with repeated names numbered as `CREATE TABLE ... AS SELECT` does. The
+    /// rows are held in memory until they are written, and `options` apply to them as to the
+    /// rows of a table named `table_name`.
+    ///
+    /// ```
+    /// use dumpqlite::{ConnectionExt as _, DumpOptions};
+    ///
+    /// let conn = rusqlite::Connection::open_in_memory()?;
+    /// conn.execute_batch("CREATE TABLE t (x INTEGER, y TEXT); INSERT INTO t VALUES (1, 'a'), (2, 'b');")?;
+    ///
+    /// let mut dump = Vec::new();
+    /// conn.dump_query_with(
+    ///     &DumpOptions::new().transaction(false),
+    ///     "SELECT y, x * 10 AS z FROM t WHERE x > 1",
+    ///     "extract",
+    ///     &mut dump,
+    /// )?;
+    /// assert_eq!(
+    ///     String::from_utf8(dump).unwrap(),
+    ///     "PRAGMA foreign_keys=OFF;\nCREATE TABLE extract(y TEXT,z);\nINSERT INTO extract VALUES('b',20);\n",
+    /// );
+    /// # Ok::<(), dumpqlite::Error>(())
+    /// ```
+    fn dump_query_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        sql: &str,
+        table_name: &str,
+        writer: &mut W,
+    ) -> Result<(), crate::Error>;
+
+    /// Writes the SQL dump in parts of at most `max_bytes` each, to the writers `factory`
+    /// returns for parts 1, 2 and so on, for storage that limits the size of a single object.
+    ///
+    /// Parts are cut between statements, so a statement too large for `max_bytes` makes a part
+    /// of its own past the limit. Each part after the first starts with a comment naming the
+    /// part it continues, then reopens the transaction the previous part committed, so the
+    /// parts restore one after another, or concatenated in order. The dump must be in
+    /// [`DumpFormat::Sql`] and uncompressed; compress each part in the writer for it instead.
+    ///
+    /// ```
+    /// use dumpqlite::{ConnectionExt as _, DumpOptions};
+    ///
+    /// let conn = rusqlite::Connection::open_in_memory()?;
+    /// conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (randomblob(1000));")?;
+    /// conn.execute_batch("INSERT INTO t SELECT x FROM t; INSERT INTO t SELECT x FROM t;")?;
+    ///
+    /// let mut parts = Vec::new();
+    /// conn.dump_in_parts(&DumpOptions::default(), 4096, |part| {
+    ///     parts.push(part);
+    ///     // Such as `std::fs::File::create(format!("dump.sql.{part:03}"))`.
+    ///     Ok(Vec::new())
+    /// })?;
+    /// assert!(parts.len() > 1);
+    /// # Ok::<(), dumpqlite::Error>(())
+    /// ```
+    fn dump_in_parts<W, F>(
+        &self,
+        options: &DumpOptions,
+        max_bytes: u64,
+        factory: F,
+    ) -> Result<(), crate::Error>
+    where
+        W: io::Write,
+        F: FnMut(usize) -> io::Result<W>,
+    {
+        if options.format != DumpFormat::Sql
+            || options.compression != Compression::None
+            || options.encryption != Encryption::None
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "parts need an uncompressed, unencrypted SQL dump",
+            )
+            .into());
+        }
+
+        parts::dump(
+            self.dump_statements_with(options),
+            options,
+            max_bytes,
+            factory,
+        )
+    }
+
+    /// Writes every table to its own `{table}.parquet` file inside `dir`, creating `dir` if needed.
+    ///
+    /// Column types are mapped from their SQLite affinities.
+    #[cfg(feature = "parquet")]
+    fn dump_parquet<P: AsRef<std::path::Path>>(
+        &self,
+        options: &DumpOptions,
+        dir: P,
+    ) -> Result<(), crate::Error>;
+
+    /// Reads the rows of `table_name` as Arrow record batches of `batch_size` rows.
+    ///
+    /// Column types are mapped from their SQLite affinities.
+    #[cfg(feature = "arrow")]
+    fn dump_arrow(
+        &self,
+        options: &DumpOptions,
+        table_name: &str,
+    ) -> Result<RecordBatches<'_>, crate::Error>;
+
+    /// Writes the SQL statements turning the rows of this database into those of `other`, like
+    /// the `sqldiff` utility, to reconcile replicas or build incremental sync scripts.
+    ///
+    /// Rows are matched by rowid, or by primary key for `WITHOUT ROWID` tables, and written as
+    /// `DELETE`, `UPDATE` and `INSERT` statements inside a transaction. Tables added or removed,
+    /// or whose columns changed, are created or dropped whole. Nothing is written when the
+    /// rows are the same.
+    fn diff<W: io::Write>(
+        &self,
+        other: &rusqlite::Connection,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        self.diff_with(&DumpOptions::default(), other, writer)
+    }
+
+    /// Writes the SQL statements turning the rows of the tables selected by `options` into
+    /// those of `other`.
+    ///
+    /// Virtual tables are left out, as are indexes, views and triggers.
+    fn diff_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        other: &rusqlite::Connection,
+        writer: &mut W,
+    ) -> Result<(), crate::Error>;
+
+    /// Returns a SHA-256 hash of the schema and rows of the database, like the `dbhash`
+    /// utility, to check that replicas or snapshots hold the same content.
+    ///
+    /// Tables are hashed by name and their rows by primary key, or by all of their columns,
+    /// so that databases with the same content hash equal whatever their page layout, the
+    /// order their rows were inserted in, or the rowids of tables without an
+    /// `INTEGER PRIMARY KEY`.
+    fn content_hash(&self) -> Result<[u8; 32], crate::Error> {
+        self.content_hash_with(&DumpOptions::default())
+    }
+
+    /// Returns a SHA-256 hash of the schema and rows of the tables selected by `options`, the
+    /// rows being those [`dump_with`](Self::dump_with) writes with the same options.
+    fn content_hash_with(&self, options: &DumpOptions) -> Result<[u8; 32], crate::Error>;
+
+    /// Records how far the rows of every table go, so that a later dump given the checkpoint
+    /// through [`DumpOptions::since`] only holds the rows added since.
+    ///
+    /// To take the checkpoint at the same point in time as a dump, take both within one
+    /// transaction.
+    fn checkpoint(&self) -> Result<Checkpoint, crate::Error> {
+        self.checkpoint_with(&DumpOptions::default())
+    }
+
+    /// Records how far the rows of the tables selected by `options` go, by their
+    /// [`incremental_column`](DumpOptions::incremental_column) or rowid.
+    fn checkpoint_with(&self, options: &DumpOptions) -> Result<Checkpoint, crate::Error>;
+
+    /// Runs `PRAGMA foreign_key_check` and returns the rows referring to a parent row that
+    /// does not exist, to find out before or after a dump whether restoring it with foreign
+    /// keys enforced will succeed.
+    fn foreign_key_check(&self) -> Result<Vec<ForeignKeyViolation>, crate::Error> {
+        self.foreign_key_check_with(&DumpOptions::default())
+    }
+
+    /// Returns the rows of the tables selected by `options` that break a foreign key.
+    ///
+    /// Rows are checked as they are in the database: rows left out of a dump by
+    /// [`DumpOptions::where_clause`] or a sample are still taken as there, and so are the
+    /// rows of the parent tables left out of it.
+    fn foreign_key_check_with(
+        &self,
+        options: &DumpOptions,
+    ) -> Result<Vec<ForeignKeyViolation>, crate::Error>;
+
+    /// Counts the rows of the tables selected by `options` and estimates the size of their
+    /// `INSERT` statements before dumping them, to set aside disk space or show how long a dump
+    /// has left.
+    ///
+    /// Each table is read once, without writing its values. The bytes count one statement per
+    /// row as a SQLite dump writes them, leaving out the schema, which is usually small next to
+    /// the rows. Compression, encryption and other formats write a different size.
+    fn estimate(&self, options: &DumpOptions) -> Result<DumpEstimate, crate::Error>;
+
+    /// Restores `dump`, an SQL dump of this database, into an in-memory database and compares
+    /// it with this one table by table, as a check that a backup restores what it should.
+    fn verify_dump(&self, dump: &str) -> Result<VerifyReport, crate::Error> {
+        self.verify_dump_with(&DumpOptions::default(), dump)
+    }
 
-                Ok((table_name, create_sql))
-            })?
-            .filter_map(Result::ok);
+    /// Checks `dump` against the tables selected by `options`, which should be the options the
+    /// dump was written with.
+    ///
+    /// Only SQLite dumps can be restored. Options rewriting values, such as
+    /// [`DumpOptions::redact`], make the tables they change differ.
+    fn verify_dump_with(
+        &self,
+        options: &DumpOptions,
+        dump: &str,
+    ) -> Result<VerifyReport, crate::Error>;
 
-        for (table_name, create_sql) in tables {
-            writeln!(writer, "{create_sql};")?;
+    /// Writes the statements migrating the schema of this database to that of `other`, as
+    /// returned by [`Schema::migration`].
+    ///
+    /// To compare two dumps, restore each of them into an in-memory database first.
+    fn schema_diff<W: io::Write>(
+        &self,
+        other: &rusqlite::Connection,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        self.schema_diff_with(&DumpOptions::default(), other, writer)
+    }
+
+    /// Writes the statements migrating the schema of the tables selected by `options` to that
+    /// of the same tables of `other`.
+    fn schema_diff_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        other: &rusqlite::Connection,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        let migration = self
+            .schema_with(options)?
+            .migration(&other.schema_with(options)?);
+        for statement in migration {
+            writeln!(writer, "{statement}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Starts recording the changes made to the tables of this database, to take them as a
+    /// changeset for an incremental backup once the database has been dumped in full.
+    #[cfg(feature = "session")]
+    fn session(&self) -> Result<Session<'_>, crate::Error> {
+        self.session_with(&DumpOptions::default())
+    }
 
-            let (columns, column_count) = {
-                let mut stmt = self.prepare(&format!("PRAGMA table_info({table_name});"))?;
-                let columns: Vec<String> = stmt
-                    .query_map([], |row| row.get(1))?
-                    .filter_map(Result::ok)
-                    .collect();
+    /// Starts recording the changes made to the tables selected by `options`.
+    #[cfg(feature = "session")]
+    fn session_with(&self, options: &DumpOptions) -> Result<Session<'_>, crate::Error>;
 
-                (columns.join(", "), columns.len())
+    /// Applies a changeset or patchset taken from a [`Session`], such as to a database restored
+    /// from the dump it follows.
+    ///
+    /// Nothing is applied if any change conflicts with the rows of the database, such as a row
+    /// to update that differs from the one the change was recorded on.
+    #[cfg(feature = "session")]
+    fn apply_changeset(&self, changeset: &[u8]) -> Result<(), crate::Error>;
+
+    /// Executes the SQL read from `reader`, such as a dump, inside a single transaction.
+    ///
+    /// Nothing is applied if any statement fails.
+    ///
+    /// The `backup` feature of `rusqlite` adds a `Connection::restore` method of its own, which
+    /// takes precedence over this one: call it as `ConnectionExt::restore(&mut conn, reader)`.
+    fn restore<R: io::Read>(&mut self, reader: &mut R) -> Result<(), crate::Error>;
+}
+
+impl ConnectionExt for rusqlite::Connection {
+    fn dump_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        let token = options.cancellation.as_ref();
+        let result = (|| {
+            cancel::check(token)?;
+            let copy = backup::to_memory(self, options)?;
+            let (conn, options) = match &copy {
+                Some(copy) => (copy, &backup::copy_options(options)),
+                None => (self, options),
             };
+            let _snapshot = snapshot::Snapshot::begin(conn, options)?;
+            integrity::check(conn, options)?;
+            let options = &subset::close(conn, options)?;
+            blob::reset_manifest(options)?;
+            let progress = &progress::Progress::new(conn, options)?;
+            let mut buffered = io::BufWriter::with_capacity(options.buffer_size, writer);
+            let writer = &mut progress.count(&mut buffered);
 
-            let mut stmt = self.prepare(&format!("SELECT {columns} FROM {table_name};"))?;
-            stmt.query_map([], |row| {
-                let values = (0..column_count)
-                    .map(|i| row.get_ref(i))
-                    .filter_map(Result::ok)
-                    .map(|value| match value {
-                        rusqlite::types::ValueRef::Null => "NULL".to_owned(),
-                        rusqlite::types::ValueRef::Integer(i) => i.to_string(),
-                        rusqlite::types::ValueRef::Real(f) => f.to_string(),
-                        rusqlite::types::ValueRef::Text(t) => {
-                            format!("'{}'", String::from_utf8_lossy(t))
-                        }
-
-                        rusqlite::types::ValueRef::Blob(_b) => {
-                            // let hex = _b.iter().fold(String::new(), |mut output, b| {
-                            //     let _ = fmt::Write::write_fmt(&mut output, format_args!("{b:02x}"));
-                            //     output
-                            // });
-                            // format!("X'{hex}'")
-
-                            todo!()
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join(",");
-                Ok(values)
-            })?
-            .filter_map(Result::ok)
-            .try_for_each(|values| {
-                writeln!(writer, "INSERT INTO {table_name} VALUES({values});")
+            encrypted(&options.encryption, writer, |mut writer| {
+                compressed(options.compression, &mut writer, |mut writer| {
+                    dump_format(conn, options, progress, &mut writer)
+                })
             })?;
+            buffered.flush()?;
+            progress.finish();
+            Ok(())
+        })();
+
+        result.map_err(|err| cancel::map_interrupted(token, err))
+    }
+
+    fn dump_with_manifest<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<Manifest, crate::Error> {
+        if options.format != DumpFormat::Sql
+            || options.compression != Compression::None
+            || options.encryption != Encryption::None
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a manifest needs an uncompressed, unencrypted SQL dump",
+            )
+            .into());
         }
 
-        writeln!(writer, "DELETE FROM sqlite_sequence;")?;
+        let token = options.cancellation.as_ref();
+        let result = (|| {
+            cancel::check(token)?;
+            let copy = backup::to_memory(self, options)?;
+            let (conn, options) = match &copy {
+                Some(copy) => (copy, &backup::copy_options(options)),
+                None => (self, options),
+            };
+            let _snapshot = snapshot::Snapshot::begin(conn, options)?;
+            integrity::check(conn, options)?;
+            let options = &subset::close(conn, options)?;
+            blob::reset_manifest(options)?;
+            let progress = &progress::Progress::new(conn, options)?;
+            let mut buffered = io::BufWriter::with_capacity(options.buffer_size, writer);
+            let manifest =
+                manifest::dump(conn, options, progress, &mut progress.count(&mut buffered))?;
+            buffered.flush()?;
+            progress.finish();
+            Ok(manifest)
+        })();
+
+        result.map_err(|err| cancel::map_interrupted(token, err))
+    }
+
+    fn schema_with(&self, options: &DumpOptions) -> Result<Schema, crate::Error> {
+        schema::read(self, options)
+    }
+
+    fn dump_statements_with(&self, options: &DumpOptions) -> DumpStatements<'_> {
+        DumpStatements::new(self, options)
+    }
+
+    fn content_hash_with(&self, options: &DumpOptions) -> Result<[u8; 32], crate::Error> {
+        let _snapshot = snapshot::Snapshot::begin(self, options)?;
+        let options = &subset::close(self, options)?;
+        hash::content_hash(self, options)
+    }
+
+    fn vacuum_into_with<P: AsRef<std::path::Path>>(
+        &self,
+        options: &DumpOptions,
+        path: P,
+    ) -> Result<(), crate::Error> {
+        vacuum::vacuum_into(self, options, path.as_ref())
+    }
+
+    fn backup_to(
+        &self,
+        dest: &mut rusqlite::Connection,
+        options: &BackupOptions,
+    ) -> Result<(), crate::Error> {
+        backup::backup_to(self, dest, options)
+    }
+
+    fn checkpoint_with(&self, options: &DumpOptions) -> Result<Checkpoint, crate::Error> {
+        checkpoint::checkpoint(self, options)
+    }
 
-        let mut stmt = self.prepare("SELECT name, seq FROM sqlite_sequence;")?;
-        stmt.query_map([], |row| {
-            let name: String = row.get(0)?;
-            let seq: i64 = row.get(1)?;
+    fn foreign_key_check_with(
+        &self,
+        options: &DumpOptions,
+    ) -> Result<Vec<ForeignKeyViolation>, crate::Error> {
+        let _snapshot = snapshot::Snapshot::begin(self, options)?;
+        foreign_keys::check(self, options)
+    }
 
-            let values = format!("'{name}',{seq}");
-            Ok(values)
-        })?
-        .filter_map(Result::ok)
-        .try_for_each(|values| writeln!(writer, "INSERT INTO sqlite_sequence VALUES({values});"))?;
+    fn estimate(&self, options: &DumpOptions) -> Result<DumpEstimate, crate::Error> {
+        let _snapshot = snapshot::Snapshot::begin(self, options)?;
+        let options = &subset::close(self, options)?;
+        estimate::estimate(self, options)
+    }
 
-        writeln!(writer, "COMMIT;")?;
+    fn verify_dump_with(
+        &self,
+        options: &DumpOptions,
+        dump: &str,
+    ) -> Result<VerifyReport, crate::Error> {
+        let _snapshot = snapshot::Snapshot::begin(self, options)?;
+        let options = &subset::close(self, options)?;
+        verify::verify(self, options, dump)
+    }
 
+    fn dump_archive<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        let copy = backup::to_memory(self, options)?;
+        let (conn, options) = match &copy {
+            Some(copy) => (copy, &backup::copy_options(options)),
+            None => (self, options),
+        };
+        let _snapshot = snapshot::Snapshot::begin(conn, options)?;
+        integrity::check(conn, options)?;
+        let mut buffered = io::BufWriter::with_capacity(options.buffer_size, writer);
+        encrypted(&options.encryption, &mut buffered, |mut writer| {
+            compressed(options.compression, &mut writer, |writer| {
+                archive::dump(conn, options, writer)
+            })
+        })?;
+        buffered.flush()?;
         Ok(())
     }
+
+    fn dump_per_table<W, F>(&self, options: &DumpOptions, factory: F) -> Result<(), crate::Error>
+    where
+        W: io::Write,
+        F: FnMut(&str) -> io::Result<W>,
+    {
+        let copy = backup::to_memory(self, options)?;
+        let (conn, options) = match &copy {
+            Some(copy) => (copy, &backup::copy_options(options)),
+            None => (self, options),
+        };
+        // A snapshot spans the dumps of every table, which are parts of one whole.
+        let _snapshot = snapshot::Snapshot::begin(conn, options)?;
+        integrity::check(conn, options)?;
+        split::dump(conn, options, factory)
+    }
+
+    fn dump_table_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        name: &str,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        split::dump_table(self, options, name, writer)
+    }
+
+    fn dump_query_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        sql: &str,
+        table_name: &str,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        query::dump(self, options, sql, table_name, writer)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn dump_parquet<P: AsRef<std::path::Path>>(
+        &self,
+        options: &DumpOptions,
+        dir: P,
+    ) -> Result<(), crate::Error> {
+        let token = options.cancellation.as_ref();
+        let result = (|| {
+            cancel::check(token)?;
+            let copy = backup::to_memory(self, options)?;
+            let (conn, options) = match &copy {
+                Some(copy) => (copy, &backup::copy_options(options)),
+                None => (self, options),
+            };
+            let _snapshot = snapshot::Snapshot::begin(conn, options)?;
+            integrity::check(conn, options)?;
+            let options = &subset::close(conn, options)?;
+            let progress = &progress::Progress::new(conn, options)?;
+            parquet::dump(conn, options, progress, dir.as_ref())?;
+            progress.finish();
+            Ok(())
+        })();
+
+        result.map_err(|err| cancel::map_interrupted(token, err))
+    }
+
+    #[cfg(feature = "arrow")]
+    fn dump_arrow(
+        &self,
+        options: &DumpOptions,
+        table_name: &str,
+    ) -> Result<RecordBatches<'_>, crate::Error> {
+        let options = &subset::close(self, options)?;
+        RecordBatches::new(self, options, table_name)
+    }
+
+    fn diff_with<W: io::Write>(
+        &self,
+        options: &DumpOptions,
+        other: &rusqlite::Connection,
+        writer: &mut W,
+    ) -> Result<(), crate::Error> {
+        diff::diff(self, other, options, writer)
+    }
+
+    #[cfg(feature = "session")]
+    fn session_with(&self, options: &DumpOptions) -> Result<Session<'_>, crate::Error> {
+        session::begin(self, options)
+    }
+
+    #[cfg(feature = "session")]
+    fn apply_changeset(&self, changeset: &[u8]) -> Result<(), crate::Error> {
+        session::apply(self, changeset)
+    }
+
+    fn restore<R: io::Read>(&mut self, reader: &mut R) -> Result<(), crate::Error> {
+        restore::restore(self, reader)
+    }
+}
+
+/// Implements [`ConnectionExt`] for a transaction type by dumping through the connection it
+/// derefs to, and restoring within a savepoint of it.
+macro_rules! impl_connection_ext_for_transaction {
+    ($($ty:ty),*) => {$(
+        impl ConnectionExt for $ty {
+            fn dump_with<W: io::Write>(
+                &self,
+                options: &DumpOptions,
+                writer: &mut W,
+            ) -> Result<(), crate::Error> {
+                (**self).dump_with(options, writer)
+            }
+
+            fn dump_with_manifest<W: io::Write>(
+                &self,
+                options: &DumpOptions,
+                writer: &mut W,
+            ) -> Result<Manifest, crate::Error> {
+                (**self).dump_with_manifest(options, writer)
+            }
+
+            fn schema_with(&self, options: &DumpOptions) -> Result<Schema, crate::Error> {
+                (**self).schema_with(options)
+            }
+
+            fn dump_statements_with(&self, options: &DumpOptions) -> DumpStatements<'_> {
+                (**self).dump_statements_with(options)
+            }
+
+            fn content_hash_with(&self, options: &DumpOptions) -> Result<[u8; 32], crate::Error> {
+                (**self).content_hash_with(options)
+            }
+
+            fn vacuum_into_with<P: AsRef<std::path::Path>>(
+                &self,
+                options: &DumpOptions,
+                path: P,
+            ) -> Result<(), crate::Error> {
+                (**self).vacuum_into_with(options, path)
+            }
+
+            fn backup_to(
+                &self,
+                dest: &mut rusqlite::Connection,
+                options: &BackupOptions,
+            ) -> Result<(), crate::Error> {
+                (**self).backup_to(dest, options)
+            }
+
+            fn checkpoint_with(&self, options: &DumpOptions) -> Result<Checkpoint, crate::Error> {
+                (**self).checkpoint_with(options)
+            }
+
+            fn foreign_key_check_with(
+                &self,
+                options: &DumpOptions,
+            ) -> Result<Vec<ForeignKeyViolation>, crate::Error> {
+                (**self).foreign_key_check_with(options)
+            }
+
+            fn estimate(&self, options: &DumpOptions) -> Result<DumpEstimate, crate::Error> {
+                (**self).estimate(options)
+            }
+
+            fn verify_dump_with(
+                &self,
+                options: &DumpOptions,
+                dump: &str,
+            ) -> Result<VerifyReport, crate::Error> {
+                (**self).verify_dump_with(options, dump)
+            }
+
+            fn dump_archive<W: io::Write>(
+                &self,
+                options: &DumpOptions,
+                writer: &mut W,
+            ) -> Result<(), crate::Error> {
+                (**self).dump_archive(options, writer)
+            }
+
+            fn dump_per_table<W, F>(
+                &self,
+                options: &DumpOptions,
+                factory: F,
+            ) -> Result<(), crate::Error>
+            where
+                W: io::Write,
+                F: FnMut(&str) -> io::Result<W>,
+            {
+                (**self).dump_per_table(options, factory)
+            }
+
+            fn dump_table_with<W: io::Write>(
+                &self,
+                options: &DumpOptions,
+                name: &str,
+                writer: &mut W,
+            ) -> Result<(), crate::Error> {
+                (**self).dump_table_with(options, name, writer)
+            }
+
+            fn dump_query_with<W: io::Write>(
+                &self,
+                options: &DumpOptions,
+                sql: &str,
+                table_name: &str,
+                writer: &mut W,
+            ) -> Result<(), crate::Error> {
+                (**self).dump_query_with(options, sql, table_name, writer)
+            }
+
+            #[cfg(feature = "parquet")]
+            fn dump_parquet<P: AsRef<std::path::Path>>(
+                &self,
+                options: &DumpOptions,
+                dir: P,
+            ) -> Result<(), crate::Error> {
+                (**self).dump_parquet(options, dir)
+            }
+
+            #[cfg(feature = "arrow")]
+            fn dump_arrow(
+                &self,
+                options: &DumpOptions,
+                table_name: &str,
+            ) -> Result<RecordBatches<'_>, crate::Error> {
+                (**self).dump_arrow(options, table_name)
+            }
+
+            fn diff_with<W: io::Write>(
+                &self,
+                options: &DumpOptions,
+                other: &rusqlite::Connection,
+                writer: &mut W,
+            ) -> Result<(), crate::Error> {
+                (**self).diff_with(options, other, writer)
+            }
+
+            #[cfg(feature = "session")]
+            fn session_with(&self, options: &DumpOptions) -> Result<Session<'_>, crate::Error> {
+                (**self).session_with(options)
+            }
+
+            #[cfg(feature = "session")]
+            fn apply_changeset(&self, changeset: &[u8]) -> Result<(), crate::Error> {
+                (**self).apply_changeset(changeset)
+            }
+
+            /// Executes the SQL read from `reader` inside a savepoint of this transaction,
+            /// which is rolled back if any statement fails.
+            ///
+            /// Foreign key checks are deferred until the outermost transaction commits.
+            fn restore<R: io::Read>(&mut self, reader: &mut R) -> Result<(), crate::Error> {
+                restore::restore_savepoint(self.savepoint()?, reader)
+            }
+        }
+    )*};
+}
+
+impl_connection_ext_for_transaction!(rusqlite::Transaction<'_>, rusqlite::Savepoint<'_>);
+
+/// Calls `f` with a writer compressing what it is given into `writer`.
+fn compressed<W, F>(compression: Compression, writer: &mut W, f: F) -> Result<(), crate::Error>
+where
+    W: io::Write,
+    F: FnOnce(&mut dyn io::Write) -> Result<(), crate::Error>,
+{
+    match compression {
+        Compression::None => f(writer),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip(level) => {
+            let level = flate2::Compression::new(level);
+            let mut encoder = flate2::write::GzEncoder::new(writer, level);
+            f(&mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => {
+            let mut encoder = zstd::Encoder::new(writer, level)?;
+            f(&mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
+    }
+}
+
+/// Calls `f` with a writer encrypting what it is given into `writer`.
+fn encrypted<W, F>(encryption: &Encryption, writer: &mut W, f: F) -> Result<(), crate::Error>
+where
+    W: io::Write,
+    F: FnOnce(&mut dyn io::Write) -> Result<(), crate::Error>,
+{
+    match encryption {
+        Encryption::None => f(writer),
+        #[cfg(feature = "age")]
+        encryption => {
+            let mut encryptor = encryption::encryptor(encryption)?.wrap_output(writer)?;
+            f(&mut encryptor)?;
+            encryptor.finish()?;
+            Ok(())
+        }
+    }
+}
+
+/// Writes the dump in the format selected by `options`.
+fn dump_format<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    match options.format {
+        DumpFormat::Sql => dump::dump(conn, options, progress, writer),
+        DumpFormat::Csv => csv::dump(conn, options, progress, writer),
+        DumpFormat::Json => json::dump(conn, options, progress, writer),
+        DumpFormat::Markdown => markdown::dump(conn, options, progress, writer),
+        DumpFormat::Html => html::dump(conn, options, progress, writer),
+        DumpFormat::Xml => xml::dump(conn, options, progress, writer),
+    }
 }
 
 #[cfg(test)]
@@ -207,4 +1171,4323 @@ COMMIT;
 
         Ok(())
     }
+
+    #[test]
+    fn test_dump_blob() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    data BLOB
+);"#,
+        )?;
+
+        let large = (0..=255u8).cycle().take(4096).collect::<Vec<u8>>();
+        let blobs: [&[u8]; 4] = [&[], &[0x00], &[0xde, 0xad, 0xbe, 0xef], &large];
+        for blob in blobs {
+            conn.execute("INSERT INTO files (data) VALUES (?1);", [blob])?;
+        }
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap().trim();
+
+        let large_hex = large.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let expected = format!(
+            r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    data BLOB
+);
+INSERT INTO files VALUES(1,X'');
+INSERT INTO files VALUES(2,X'00');
+INSERT INTO files VALUES(3,X'deadbeef');
+INSERT INTO files VALUES(4,X'{large_hex}');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('files',4);
+COMMIT;
+"#
+        );
+
+        assert_eq!(expected.trim(), result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let mut stmt = restored.prepare("SELECT data FROM files ORDER BY id;")?;
+        let restored_blobs = stmt
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(blobs.map(<[u8]>::to_vec).to_vec(), restored_blobs);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_text_escaping() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    body TEXT
+);"#,
+        )?;
+
+        let texts = [
+            "it's",
+            "''",
+            r"C:\path\to\file",
+            r"\'",
+            "日本語のテキスト 🦀",
+        ];
+        for text in texts {
+            conn.execute("INSERT INTO notes (body) VALUES (?1);", [text])?;
+        }
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap().trim();
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    body TEXT
+);
+INSERT INTO notes VALUES(1,'it''s');
+INSERT INTO notes VALUES(2,'''''');
+INSERT INTO notes VALUES(3,'C:\path\to\file');
+INSERT INTO notes VALUES(4,'\''');
+INSERT INTO notes VALUES(5,'日本語のテキスト 🦀');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('notes',5);
+COMMIT;
+"#
+        .trim();
+
+        assert_eq!(expected, result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let mut stmt = restored.prepare("SELECT body FROM notes ORDER BY id;")?;
+        let restored_texts = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(texts.map(str::to_owned).to_vec(), restored_texts);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_with_options() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL
+);
+INSERT INTO users (username) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let dump = |options: &crate::DumpOptions| -> Result<String, crate::Error> {
+            let mut writer = Vec::new();
+            conn.dump_with(options, &mut writer)?;
+            Ok(String::from_utf8(writer).unwrap())
+        };
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+        assert_eq!(
+            std::str::from_utf8(&writer).unwrap(),
+            dump(&crate::DumpOptions::default())?
+        );
+
+        let expected = r#"
+INSERT INTO users VALUES(1,'alice');
+INSERT INTO users VALUES(2,'bob');
+INSERT INTO sqlite_sequence VALUES('users',2);
+"#
+        .trim_start();
+        let options = crate::DumpOptions::new().data_only(true).transaction(false);
+        assert_eq!(expected, dump(&options)?);
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL
+);
+INSERT INTO users VALUES(1,'alice');
+INSERT INTO users VALUES(2,'bob');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',2);
+"#
+        .trim_start();
+        let options = crate::DumpOptions::new().transaction(false);
+        assert_eq!(expected, dump(&options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_table_filters() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, username TEXT);
+CREATE TABLE logs (id INTEGER PRIMARY KEY AUTOINCREMENT, message TEXT);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users (username) VALUES ('alice');
+INSERT INTO logs (message) VALUES ('started');
+INSERT INTO tags (name) VALUES ('rust');"#,
+        )?;
+
+        let dump = |options: &crate::DumpOptions| -> Result<String, crate::Error> {
+            let mut writer = Vec::new();
+            conn.dump_with(options, &mut writer)?;
+            Ok(String::from_utf8(writer).unwrap())
+        };
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, username TEXT);
+INSERT INTO users VALUES(1,'alice');
+CREATE TABLE tags (name TEXT);
+INSERT INTO tags VALUES('rust');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',1);
+COMMIT;
+"#
+        .trim_start();
+        let options = crate::DumpOptions::new().exclude_tables(["LOGS"]);
+        assert_eq!(expected, dump(&options)?);
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE logs (id INTEGER PRIMARY KEY AUTOINCREMENT, message TEXT);
+INSERT INTO logs VALUES(1,'started');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('logs',1);
+COMMIT;
+"#
+        .trim_start();
+        let options = crate::DumpOptions::new()
+            .include_tables(["logs", "tags"])
+            .exclude_tables(["tags"]);
+        assert_eq!(expected, dump(&options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_schema_only() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, username TEXT);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users (username) VALUES ('alice');
+INSERT INTO tags (name) VALUES ('rust');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .schema_only(true)
+            .transaction(false);
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, username TEXT);
+CREATE TABLE tags (name TEXT);
+"#
+        .trim_start();
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_indexes() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, email TEXT UNIQUE, name TEXT);
+CREATE INDEX users_name ON users (name);
+CREATE TABLE logs (id INTEGER PRIMARY KEY AUTOINCREMENT, message TEXT);
+CREATE INDEX logs_message ON logs (message);
+INSERT INTO users (email, name) VALUES ('alice@example.com', 'alice');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = crate::DumpOptions::new().exclude_tables(["logs"]);
+        conn.dump_with(&options, &mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, email TEXT UNIQUE, name TEXT);
+INSERT INTO users VALUES(1,'alice@example.com','alice');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',1);
+CREATE INDEX users_name ON users (name);
+COMMIT;
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let indexes: i64 = restored.query_row(
+            "SELECT count(*) FROM sqlite_schema WHERE type = 'index';",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(2, indexes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_views() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, active INTEGER);
+CREATE VIEW active_users AS SELECT id, name FROM users WHERE active;
+CREATE VIEW active_user_names AS SELECT name FROM active_users;
+CREATE INDEX users_name ON users (name);
+INSERT INTO users (name, active) VALUES ('alice', 1), ('bob', 0);"#,
+        )?;
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, active INTEGER);
+INSERT INTO users VALUES(1,'alice',1);
+INSERT INTO users VALUES(2,'bob',0);
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',2);
+CREATE VIEW active_users AS SELECT id, name FROM users WHERE active;
+CREATE VIEW active_user_names AS SELECT name FROM active_users;
+CREATE INDEX users_name ON users (name);
+COMMIT;
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let name: String =
+            restored.query_row("SELECT name FROM active_user_names;", [], |row| row.get(0))?;
+        assert_eq!("alice", name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_triggers() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE audit (id INTEGER PRIMARY KEY AUTOINCREMENT, user_id INTEGER);
+CREATE TRIGGER users_audit AFTER INSERT ON users BEGIN
+    INSERT INTO audit (user_id) VALUES (new.id);
+END;
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users VALUES(1,'alice');
+INSERT INTO users VALUES(2,'bob');
+CREATE TABLE audit (id INTEGER PRIMARY KEY AUTOINCREMENT, user_id INTEGER);
+INSERT INTO audit VALUES(1,1);
+INSERT INTO audit VALUES(2,2);
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('audit',2);
+INSERT INTO sqlite_sequence VALUES('users',2);
+CREATE TRIGGER users_audit AFTER INSERT ON users BEGIN
+    INSERT INTO audit (user_id) VALUES (new.id);
+END;
+COMMIT;
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let audits: i64 =
+            restored.query_row("SELECT count(*) FROM audit;", [], |row| row.get(0))?;
+        assert_eq!(2, audits);
+
+        restored.execute("INSERT INTO users (name) VALUES ('carol');", [])?;
+        let audits: i64 =
+            restored.query_row("SELECT count(*) FROM audit;", [], |row| row.get(0))?;
+        assert_eq!(3, audits);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_virtual_tables() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE VIRTUAL TABLE docs USING fts5(title, body);
+INSERT INTO docs (title, body) VALUES ('hello', 'world wide web'), ('rust', 'is great');
+CREATE TABLE notes (id INTEGER PRIMARY KEY AUTOINCREMENT, body TEXT);"#,
+        )?;
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+PRAGMA writable_schema=ON;
+INSERT INTO sqlite_schema(type,name,tbl_name,rootpage,sql)VALUES('table','docs','docs',0,'CREATE VIRTUAL TABLE docs USING fts5(title, body)');
+CREATE TABLE IF NOT EXISTS 'docs_data'(id INTEGER PRIMARY KEY, block BLOB);
+"#
+        .trim_start();
+        assert!(result.starts_with(expected));
+        assert!(!result.contains("INSERT INTO docs VALUES"));
+        assert!(result.ends_with(
+            "PRAGMA writable_schema=RESET;\nINSERT INTO docs(docs) VALUES('rebuild');\nCOMMIT;\n"
+        ));
+
+        let path = std::env::temp_dir().join(format!(
+            "dumpqlite-test-virtual-tables-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        rusqlite::Connection::open(&path)?.execute_batch(result)?;
+
+        let restored = rusqlite::Connection::open(&path)?;
+        let title: String = restored.query_row(
+            "SELECT title FROM docs WHERE docs MATCH 'great';",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!("rust", title);
+
+        drop(restored);
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_fts_rebuild() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE notes (id INTEGER PRIMARY KEY AUTOINCREMENT, body TEXT);
+CREATE VIRTUAL TABLE docs USING fts5(title, body);
+CREATE VIRTUAL TABLE notes_fts USING fts5(body, content='notes', content_rowid='id');
+CREATE VIRTUAL TABLE legacy USING fts4(body);
+CREATE VIRTUAL TABLE contentless USING fts5(body, content='');
+INSERT INTO notes (body) VALUES ('external content');
+INSERT INTO notes_fts (rowid, body) SELECT id, body FROM notes;
+INSERT INTO docs (title, body) VALUES ('hello', 'world wide web'), ('rust', 'is great');
+INSERT INTO legacy (body) VALUES ('still searchable');
+INSERT INTO contentless (body) VALUES ('kept as is');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        conn.dump(&mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        for shadow in ["docs_data", "docs_idx", "notes_fts_data", "legacy_segdir"] {
+            assert!(result.contains(&format!("CREATE TABLE IF NOT EXISTS '{shadow}'")));
+            assert!(!result.contains(&format!("INSERT INTO {shadow} VALUES")));
+        }
+        assert!(result.contains("INSERT INTO docs_content VALUES(1,'hello','world wide web');"));
+        assert!(result.contains("INSERT INTO contentless_data VALUES("));
+
+        let expected = r#"
+PRAGMA writable_schema=RESET;
+INSERT INTO docs(docs) VALUES('rebuild');
+INSERT INTO notes_fts(notes_fts) VALUES('rebuild');
+INSERT INTO legacy(legacy) VALUES('rebuild');
+COMMIT;
+"#;
+        assert!(result.ends_with(expected));
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let matches = |sql: &str| -> Result<i64, crate::Error> {
+            Ok(restored.query_row(sql, [], |row| row.get(0))?)
+        };
+        assert_eq!(
+            1,
+            matches("SELECT count(*) FROM docs WHERE docs MATCH 'rust';")?
+        );
+        assert_eq!(
+            1,
+            matches("SELECT count(*) FROM notes_fts WHERE notes_fts MATCH 'external';")?
+        );
+        assert_eq!(
+            1,
+            matches("SELECT count(*) FROM legacy WHERE legacy MATCH 'searchable';")?
+        );
+        assert_eq!(
+            1,
+            matches("SELECT count(*) FROM contentless WHERE contentless MATCH 'kept';")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_rows_per_insert() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob'), ('carol'), (NULL), ('it''s');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = crate::DumpOptions::new().rows_per_insert(2);
+        conn.dump_with(&options, &mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users VALUES(1,'alice'),(2,'bob');
+INSERT INTO users VALUES(3,'carol'),(4,NULL);
+INSERT INTO users VALUES(5,'it''s');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',5);
+COMMIT;
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+
+        let mut original = Vec::new();
+        conn.dump(&mut original)?;
+        let mut redump = Vec::new();
+        restored.dump(&mut redump)?;
+        assert_eq!(original, redump);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_insert_column_names() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .insert_column_names(true);
+        conn.dump_with(&options, &mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+
+        let expected = r#"
+INSERT INTO users(id,name) VALUES(1,'alice');
+INSERT INTO users(id,name) VALUES(2,'bob');
+INSERT INTO sqlite_sequence VALUES('users',2);
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, email TEXT, name TEXT);",
+        )?;
+        restored.execute_batch(result)?;
+
+        let name: String =
+            restored.query_row("SELECT name FROM users WHERE id = 2;", [], |row| row.get(0))?;
+        assert_eq!("bob", name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_on_conflict() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let dump = |on_conflict: crate::OnConflict| -> Result<String, crate::Error> {
+            let mut writer = Vec::new();
+            let options = crate::DumpOptions::new()
+                .data_only(true)
+                .transaction(false)
+                .on_conflict(on_conflict)
+                .insert_column_names(true);
+            conn.dump_with(&options, &mut writer)?;
+            Ok(String::from_utf8(writer).unwrap())
+        };
+
+        let ignore = dump(crate::OnConflict::Ignore)?;
+        assert!(ignore.starts_with("INSERT OR IGNORE INTO users(id,name) VALUES(1,'alice');\n"));
+
+        let replace = dump(crate::OnConflict::Replace)?;
+        assert!(replace.starts_with("INSERT OR REPLACE INTO users(id,name) VALUES(1,'alice');\n"));
+
+        let target = rusqlite::Connection::open_in_memory()?;
+        target.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('existing');"#,
+        )?;
+        assert!(target
+            .execute_batch(&dump(crate::OnConflict::Abort)?)
+            .is_err());
+
+        target.execute_batch(&ignore)?;
+        let name: String =
+            target.query_row("SELECT name FROM users WHERE id = 1;", [], |row| row.get(0))?;
+        assert_eq!("existing", name);
+
+        target.execute_batch(&replace)?;
+        let name: String =
+            target.query_row("SELECT name FROM users WHERE id = 1;", [], |row| row.get(0))?;
+        assert_eq!("alice", name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_where_clause() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE events (id INTEGER PRIMARY KEY AUTOINCREMENT, created_at TEXT);
+CREATE TABLE users (name TEXT);
+INSERT INTO events (created_at) VALUES ('2023-12-31'), ('2024-01-02'), ('2024-03-04');
+INSERT INTO users (name) VALUES ('alice');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .where_clause("events", "id = 1")
+            .where_clause("EVENTS", "created_at > '2024-01-01'");
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"
+INSERT INTO events VALUES(2,'2024-01-02');
+INSERT INTO events VALUES(3,'2024-03-04');
+INSERT INTO users VALUES('alice');
+INSERT INTO sqlite_sequence VALUES('events',3);
+"#
+        .trim_start();
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options.format(crate::DumpFormat::Csv), &mut writer)?;
+        assert!(!std::str::from_utf8(&writer).unwrap().contains("2023-12-31"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_sampling() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE events (id INTEGER PRIMARY KEY AUTOINCREMENT, kind TEXT);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+INSERT INTO events (kind) SELECT CASE i % 2 WHEN 0 THEN 'even' ELSE 'odd' END FROM n;
+CREATE TABLE kinds (name TEXT PRIMARY KEY) WITHOUT ROWID;
+INSERT INTO kinds VALUES ('even'), ('odd');"#,
+        )?;
+
+        let count = |options: &crate::DumpOptions| -> Result<(String, usize), crate::Error> {
+            let mut writer = Vec::new();
+            conn.dump_with(options, &mut writer)?;
+            let dump = String::from_utf8(writer).unwrap();
+            let rows = dump.matches("INSERT INTO events").count();
+            Ok((dump, rows))
+        };
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .sample_fraction(0.1);
+        let (dump, rows) = count(&options)?;
+        assert!((50..150).contains(&rows), "{rows} rows sampled");
+        assert_eq!(dump, count(&options)?.0);
+        assert_ne!(dump, count(&options.clone().sample_seed(7))?.0);
+        assert_eq!(0, count(&options.clone().sample_fraction(0.0))?.1);
+        assert_eq!(1000, count(&options.clone().sample_fraction(1.0))?.1);
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .max_rows_per_table(10)
+            .where_clause("events", "kind = 'even'");
+        let (dump, rows) = count(&options)?;
+        assert_eq!(10, rows);
+        assert!(!dump
+            .lines()
+            .any(|line| line.starts_with("INSERT INTO events") && line.contains("'odd'")));
+
+        // Tables without a rowid cannot be sampled, and are dumped in full.
+        assert!(dump.contains("INSERT INTO kinds VALUES('odd');"));
+        let (dump, _) = count(&crate::DumpOptions::new().sample_fraction(0.0))?;
+        assert_eq!(2, dump.matches("INSERT INTO kinds").count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_follow_foreign_keys() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE teams (code TEXT PRIMARY KEY, lead_id INTEGER REFERENCES users(id));
+CREATE TABLE tasks (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER REFERENCES users,
+    team TEXT REFERENCES teams(code),
+    title TEXT
+);
+INSERT INTO users (name) VALUES ('alice'), ('bob'), ('carol'), ('dave');
+INSERT INTO teams VALUES ('red', 3), ('blue', 4);
+INSERT INTO tasks VALUES (1, 1, NULL, 'one'), (2, 2, 'red', 'two'), (3, 1, 'blue', 'three');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .where_clause("tasks", "id = 2")
+            .where_clause("users", "0")
+            .where_clause("teams", "0")
+            .follow_foreign_keys(true);
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+        let dump = std::str::from_utf8(&writer).unwrap();
+
+        assert!(dump.contains("INSERT INTO tasks VALUES(2,2,'red','two');"));
+        assert!(dump.contains("INSERT INTO teams VALUES('red',3);"));
+        assert!(dump.contains("INSERT INTO users VALUES(2,'bob');"));
+        assert!(dump.contains("INSERT INTO users VALUES(3,'carol');"));
+        assert_eq!(4, dump.matches("INSERT INTO").count() - 1);
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch("PRAGMA foreign_keys=ON;")?;
+        crate::ConnectionExt::restore(&mut restored, &mut writer.as_slice())?;
+        let violations: i64 = restored.query_row(
+            "SELECT count(*) FROM pragma_foreign_key_check;",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(0, violations);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_redact() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, email TEXT, password_hash BLOB);
+CREATE TABLE invites (Email TEXT, note TEXT);
+INSERT INTO users (name, email, password_hash) VALUES ('alice', 'alice@example.com', X'cafe');
+INSERT INTO invites VALUES ('bob@example.com', 'hello');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .redact("users.password_hash", crate::Redaction::Null)
+            .redact(
+                "*.email",
+                crate::Redaction::Constant("redacted".to_owned().into()),
+            )
+            .redact(
+                "note",
+                crate::Redaction::with(|value| match value {
+                    rusqlite::types::ValueRef::Text(text) => text.len().to_string().into(),
+                    _ => rusqlite::types::Value::Null,
+                }),
+            );
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"
+INSERT INTO users VALUES(1,'alice','redacted',NULL);
+INSERT INTO invites VALUES('redacted','5');
+INSERT INTO sqlite_sequence VALUES('users',1);
+"#
+        .trim_start();
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options.format(crate::DumpFormat::Json), &mut writer)?;
+        assert!(!std::str::from_utf8(&writer)
+            .unwrap()
+            .contains("@example.com"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_transform() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE items (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, price_cents INTEGER);
+INSERT INTO items (name, price_cents) VALUES ('  Apple ', 150), ('pear', NULL);"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .transform(|table, column, value| match (table, column, value) {
+                ("items", "name", rusqlite::types::ValueRef::Text(text)) => {
+                    Some(String::from_utf8_lossy(text).trim().to_lowercase().into())
+                }
+                (_, "price_cents", rusqlite::types::ValueRef::Integer(cents)) => {
+                    Some(rusqlite::types::Value::Real(cents as f64 / 100.0))
+                }
+                _ => None,
+            })
+            .redact(
+                "items.name",
+                crate::Redaction::with(|value| match value {
+                    rusqlite::types::ValueRef::Text(text) => {
+                        format!("<{}>", String::from_utf8_lossy(text)).into()
+                    }
+                    _ => rusqlite::types::Value::Null,
+                }),
+            );
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"
+INSERT INTO items VALUES(1,'<apple>',1.5);
+INSERT INTO items VALUES(2,'<pear>',NULL);
+INSERT INTO sqlite_sequence VALUES('items',2);
+"#
+        .trim_start();
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_progress() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');
+INSERT INTO tags VALUES ('red');"#,
+        )?;
+
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = crate::DumpOptions::new().on_progress({
+            let reports = std::sync::Arc::clone(&reports);
+            move |progress| {
+                reports.lock().unwrap().push((
+                    progress.table.to_owned(),
+                    progress.table_rows_written,
+                    progress.rows_written,
+                    progress.tables_remaining,
+                    progress.bytes_written,
+                ))
+            }
+        });
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+
+        let reports = reports.lock().unwrap();
+        let summary = reports
+            .iter()
+            .map(|(table, table_rows, rows, remaining, _)| {
+                (table.as_str(), *table_rows, *rows, *remaining)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("users", 0, 0, 1),
+                ("users", 1, 1, 1),
+                ("users", 2, 2, 1),
+                ("tags", 0, 2, 0),
+                ("tags", 1, 3, 0),
+            ],
+            summary
+        );
+
+        let bytes = reports.iter().map(|report| report.4).collect::<Vec<_>>();
+        assert!(bytes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(bytes[4] < writer.len() as u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_cancellation() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob'), ('carol');"#,
+        )?;
+
+        // Cancels the dump from the progress callback once the first row is written.
+        let token = crate::CancellationToken::for_connection(&conn);
+        let options = crate::DumpOptions::new()
+            .cancellation(token.clone())
+            .on_progress({
+                let token = token.clone();
+                move |progress| {
+                    if progress.rows_written == 1 {
+                        token.cancel();
+                    }
+                }
+            });
+
+        let mut writer = Vec::new();
+        let result = conn.dump_with(&options, &mut writer);
+        assert!(matches!(result, Err(crate::Error::Cancelled)));
+        assert_eq!(
+            1,
+            std::str::from_utf8(&writer)
+                .unwrap()
+                .matches("INSERT")
+                .count()
+        );
+        assert!(token.is_cancelled());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_dump_gzip() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let mut expected = Vec::new();
+        conn.dump(&mut expected)?;
+
+        let options = crate::DumpOptions::new().compression(crate::Compression::Gzip(6));
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+        assert_eq!([0x1f, 0x8b], writer[..2]);
+
+        let mut decoded = Vec::new();
+        std::io::Read::read_to_end(
+            &mut flate2::read::GzDecoder::new(writer.as_slice()),
+            &mut decoded,
+        )?;
+        assert_eq!(expected, decoded);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_dump_zstd() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let mut expected = Vec::new();
+        conn.dump(&mut expected)?;
+
+        let options = crate::DumpOptions::new().compression(crate::Compression::Zstd(19));
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+
+        assert_eq!(expected, zstd::decode_all(writer.as_slice())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_archive() -> Result<(), crate::Error> {
+        let db =
+            std::env::temp_dir().join(format!("dumpqlite-test-archive-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&db);
+        let conn = rusqlite::Connection::open(&db)?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE tags (name TEXT);
+CREATE VIRTUAL TABLE notes USING fts5(body);
+INSERT INTO users (name) VALUES ('alice');
+INSERT INTO tags VALUES ('red');
+INSERT INTO notes VALUES ('hello world');"#,
+        )?;
+
+        let mut archive = Vec::new();
+        conn.dump_archive(&crate::DumpOptions::new(), &mut archive)?;
+        assert_eq!(0, archive.len() % 512);
+
+        // Reads the entries back from their headers.
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while archive[offset] != 0 {
+            let header = &archive[offset..offset + 512];
+            let name = std::str::from_utf8(&header[..100])
+                .unwrap()
+                .trim_end_matches('\0');
+            let size = std::str::from_utf8(&header[124..135]).unwrap();
+            let size = usize::from_str_radix(size, 8).unwrap();
+            let contents = &archive[offset + 512..offset + 512 + size];
+            entries.push((
+                name.to_owned(),
+                std::str::from_utf8(contents).unwrap().to_owned(),
+            ));
+            offset += 512 + size.div_ceil(512) * 512;
+        }
+
+        let names = entries
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "schema.sql",
+                "data/users.sql",
+                "data/tags.sql",
+                "data/notes.sql"
+            ],
+            names
+        );
+        assert_eq!(
+            "BEGIN TRANSACTION;\nINSERT INTO tags VALUES('red');\nCOMMIT;\n",
+            entries[2].1
+        );
+
+        // The schema followed by a single data file restores that table alone.
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut entries[0].1.as_bytes())?;
+        crate::ConnectionExt::restore(&mut restored, &mut entries[3].1.as_bytes())?;
+        let matches: i64 = restored.query_row(
+            "SELECT count(*) FROM notes WHERE notes MATCH 'hello';",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(1, matches);
+        let users: i64 = restored.query_row("SELECT count(*) FROM users;", [], |row| row.get(0))?;
+        assert_eq!(0, users);
+
+        // A deterministic archive is dated to the epoch, and so is the same on every run.
+        let options = crate::DumpOptions::new().deterministic(true);
+        let mut archive = Vec::new();
+        conn.dump_archive(&options, &mut archive)?;
+        assert_eq!(b"00000000000\0", &archive[136..148]);
+        let mut again = Vec::new();
+        conn.dump_archive(&options, &mut again)?;
+        assert_eq!(archive, again);
+
+        drop(conn);
+        std::fs::remove_file(&db)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_to_path() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice');"#,
+        )?;
+
+        let dir = std::env::temp_dir().join(format!("dumpqlite-test-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("dump.sql");
+        std::fs::write(&path, "previous")?;
+
+        let mut expected = Vec::new();
+        conn.dump(&mut expected)?;
+        conn.dump_to_path(&crate::DumpOptions::new(), &path)?;
+        assert_eq!(expected, std::fs::read(&path)?);
+
+        // A failed dump keeps the previous file and leaves no temporary file behind.
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let options = crate::DumpOptions::new().cancellation(token);
+        assert!(conn.dump_to_path(&options, &path).is_err());
+        assert_eq!(expected, std::fs::read(&path)?);
+        assert_eq!(1, std::fs::read_dir(&dir)?.count());
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_to_string() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice');"#,
+        )?;
+
+        let mut expected = Vec::new();
+        conn.dump(&mut expected)?;
+        assert_eq!(
+            std::str::from_utf8(&expected).unwrap(),
+            conn.dump_to_string()?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_statements() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE pairs (k TEXT PRIMARY KEY, v INTEGER) WITHOUT ROWID;
+CREATE INDEX users_name ON users (name);
+INSERT INTO users (name) VALUES ('alice'), ('bob'), ('carol'), (NULL), ('dave');
+INSERT INTO pairs VALUES ('a', 1), ('b', 2), ('c', 3);"#,
+        )?;
+
+        for options in [
+            crate::DumpOptions::new(),
+            crate::DumpOptions::new().rows_per_insert(2),
+            crate::DumpOptions::new()
+                .rows_per_insert(2)
+                .where_clause("users", "id > 1")
+                .redact("users.name", crate::Redaction::Null)
+                .insert_column_names(true),
+        ] {
+            let mut expected = Vec::new();
+            conn.dump_with(&options, &mut expected)?;
+
+            let statements = conn
+                .dump_statements_with(&options)
+                .collect::<Result<Vec<_>, _>>()?;
+            let actual = statements
+                .iter()
+                .map(|statement| format!("{statement}\n"))
+                .collect::<String>();
+            assert_eq!(std::str::from_utf8(&expected).unwrap(), actual);
+        }
+
+        let inserts = conn
+            .dump_statements()
+            .filter(
+                |statement| matches!(statement, Ok(sql) if sql.starts_with("INSERT INTO pairs")),
+            )
+            .count();
+        assert_eq!(3, inserts);
+
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let mut statements =
+            conn.dump_statements_with(&crate::DumpOptions::new().cancellation(token));
+        assert!(matches!(
+            statements.next(),
+            Some(Err(crate::Error::Cancelled))
+        ));
+        assert!(statements.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_statements_shadowed_rowid() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE t (rowid INTEGER, v TEXT);
+CREATE TABLE u (rowid INTEGER, _rowid_ INTEGER, oid INTEGER, v TEXT);
+INSERT INTO t VALUES (7, 'a'), (7, 'b'), (3, 'c');
+INSERT INTO u VALUES (7, 7, 7, 'a'), (7, 7, 7, 'b'), (3, 3, 3, 'c');"#,
+        )?;
+
+        for options in [
+            crate::DumpOptions::new(),
+            crate::DumpOptions::new().rows_per_insert(1),
+        ] {
+            let mut expected = Vec::new();
+            conn.dump_with(&options, &mut expected)?;
+
+            let actual = conn
+                .dump_statements_with(&options)
+                .map(|statement| statement.map(|statement| format!("{statement}\n")))
+                .collect::<Result<String, _>>()?;
+            assert_eq!(std::str::from_utf8(&expected).unwrap(), actual);
+            for row in [
+                "(7,'a')",
+                "(7,'b')",
+                "(3,'c')",
+                "(7,7,7,'b')",
+                "(3,3,3,'c')",
+            ] {
+                assert!(actual.contains(row));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_with_sink() -> Result<(), crate::Error> {
+        use crate::DumpChunk;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let mut chunks = Vec::new();
+        conn.dump_with_sink(&crate::DumpOptions::new(), |chunk| {
+            chunks.push(format!("{chunk:?}"));
+            Ok::<_, std::io::Error>(())
+        })?;
+        assert_eq!(
+            vec![
+                r#"Prologue("PRAGMA foreign_keys=OFF;")"#,
+                r#"Prologue("BEGIN TRANSACTION;")"#,
+                r#"Schema("CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);")"#,
+                r#"Data("INSERT INTO users VALUES(1,'alice');")"#,
+                r#"Data("INSERT INTO users VALUES(2,'bob');")"#,
+                r#"Data("DELETE FROM sqlite_sequence;")"#,
+                r#"Data("INSERT INTO sqlite_sequence VALUES('users',2);")"#,
+                r#"Epilogue("COMMIT;")"#,
+            ],
+            chunks
+        );
+
+        let mut schema = 0;
+        let result = conn.dump_with_sink(&crate::DumpOptions::new(), |chunk| match chunk {
+            DumpChunk::Data(sql) => Err(format!("refused {sql}")),
+            DumpChunk::Schema(_) => {
+                schema += 1;
+                Ok(())
+            }
+            _ => Ok(()),
+        });
+        assert!(matches!(
+            result,
+            Err(crate::Error::Sink(err)) if err.to_string() == "refused INSERT INTO users VALUES(1,'alice');"
+        ));
+        assert_eq!(1, schema);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_transaction() -> Result<(), crate::Error> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice');"#,
+        )?;
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+
+        let mut tx = conn.transaction()?;
+        tx.execute("INSERT INTO users (name) VALUES ('bob');", [])?;
+        let mut actual = Vec::new();
+        tx.dump(&mut actual)?;
+        assert!(std::str::from_utf8(&actual)
+            .unwrap()
+            .contains("INSERT INTO users VALUES(2,'bob');"));
+
+        let mut sp = tx.savepoint()?;
+        sp.execute_batch("DROP TABLE users; DELETE FROM sqlite_sequence;")?;
+        sp.restore(&mut dump.as_slice())?;
+        let mut restored = Vec::new();
+        sp.dump(&mut restored)?;
+        assert_eq!(dump, restored);
+
+        // A failed restore leaves the transaction as it was.
+        let invalid = "INSERT INTO users VALUES(3,'carol');\nINSERT INTO missing VALUES(1);\n";
+        assert!(sp.restore(&mut invalid.as_bytes()).is_err());
+        let mut after = Vec::new();
+        sp.dump(&mut after)?;
+        assert_eq!(restored, after);
+
+        drop(sp);
+        tx.rollback()?;
+
+        let mut rolled_back = Vec::new();
+        conn.dump(&mut rolled_back)?;
+        assert_eq!(dump, rolled_back);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_quoted_identifiers() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE "my table" (id INTEGER PRIMARY KEY AUTOINCREMENT, "order" TEXT, "say ""hi""" INTEGER);
+CREATE TABLE "select" ("group" INTEGER REFERENCES "my table" (id));
+INSERT INTO "my table" ("order", "say ""hi""") VALUES ('first', 1), ('second', 2);
+INSERT INTO "select" VALUES (2);"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .insert_column_names(true)
+            .max_rows_per_table(1)
+            .include_tables(["select", "my table"])
+            .follow_foreign_keys(true);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE IF NOT EXISTS "my table" (id INTEGER PRIMARY KEY AUTOINCREMENT, "order" TEXT, "say ""hi""" INTEGER);
+INSERT INTO "my table"(id,"order","say ""hi""") VALUES(2,'second',2);
+CREATE TABLE IF NOT EXISTS "select" ("group" INTEGER REFERENCES "my table" (id));
+INSERT INTO "select"("group") VALUES(2);
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('my table',2);
+COMMIT;
+"#;
+        assert_eq!(expected, std::str::from_utf8(&actual).unwrap());
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut actual.as_slice())?;
+        let mut dump = Vec::new();
+        restored.dump_with(&options, &mut dump)?;
+        assert_eq!(expected, std::str::from_utf8(&dump).unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_strict() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        // A virtual table whose module is not loaded cannot be read.
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice');
+PRAGMA writable_schema=ON;
+INSERT INTO sqlite_schema(type,name,tbl_name,rootpage,sql)
+    VALUES('table','broken','broken',0,'CREATE VIRTUAL TABLE broken USING missing(x)');
+PRAGMA writable_schema=RESET;"#,
+        )?;
+
+        let options = crate::DumpOptions::new().format(crate::DumpFormat::Csv);
+        let mut actual = Vec::new();
+        let err = conn.dump_with(&options, &mut actual).unwrap_err();
+        assert!(err.to_string().contains("no such module: missing"));
+
+        let mut actual = Vec::new();
+        conn.dump_with(&options.clone().strict(false), &mut actual)?;
+        assert_eq!(
+            "sqlite_metadata\r\nblob_encoding\r\nbase64\r\n\r\nusers\r\nid,name\r\n1,alice\r\n",
+            std::str::from_utf8(&actual).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_with_warnings() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice');
+PRAGMA writable_schema=ON;
+INSERT INTO sqlite_schema(type,name,tbl_name,rootpage,sql)
+    VALUES('table','broken','broken',0,'CREATE VIRTUAL TABLE broken USING missing(x)');
+PRAGMA writable_schema=RESET;"#,
+        )?;
+
+        // Tables read more than once, here to count them for the progress, are reported once.
+        let options = crate::DumpOptions::new()
+            .format(crate::DumpFormat::Json)
+            .on_progress(|_| {});
+        let mut actual = Vec::new();
+        let warnings = conn.dump_with_warnings(&options, &mut actual)?;
+        assert_eq!(1, warnings.len());
+        assert_eq!("broken", warnings[0].table);
+        assert_eq!(None, warnings[0].rowid);
+        assert_eq!("no such module: missing", warnings[0].cause.to_string());
+        assert!(!std::str::from_utf8(&actual).unwrap().contains("broken"));
+
+        let mut actual = Vec::new();
+        let warnings = conn.dump_with_warnings(&crate::DumpOptions::new(), &mut actual)?;
+        assert!(warnings.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_error_context() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('{}'), ('[]'), ('not json');"#,
+        )?;
+
+        // The filter fails on the third row, so the first two are read.
+        let options = crate::DumpOptions::new().where_clause("users", "json(name) IS NOT NULL");
+        let mut actual = Vec::new();
+        let err = conn.dump_with(&options, &mut actual).unwrap_err();
+        assert!(matches!(
+            &err,
+            crate::Error::Table {
+                table,
+                kind: crate::StatementKind::Data,
+                row: Some(2),
+                ..
+            } if table == "users"
+        ));
+        assert_eq!(
+            "failed to read row 2 of table users: malformed JSON",
+            err.to_string()
+        );
+
+        let err = conn
+            .dump_statements_with(&options.rows_per_insert(2))
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(
+            "failed to read row 2 of table users: malformed JSON",
+            err.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_deterministic() -> Result<(), crate::Error> {
+        let first = rusqlite::Connection::open_in_memory()?;
+        first.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE INDEX users_name ON users (name);
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+CREATE INDEX tags_color ON tags (color);
+INSERT INTO users VALUES (2, 'alice'), (1, 'bob');
+INSERT INTO tags VALUES ('b', 'red'), ('a', 'blue');"#,
+        )?;
+
+        let second = rusqlite::Connection::open_in_memory()?;
+        second.execute_batch(
+            r#"
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+CREATE INDEX tags_color ON tags (color);
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE INDEX users_name ON users (name);
+INSERT INTO tags VALUES ('a', 'blue'), ('b', 'red');
+INSERT INTO users VALUES (1, 'bob'), (2, 'alice');"#,
+        )?;
+
+        let dump = |conn: &rusqlite::Connection, options: &crate::DumpOptions| {
+            let mut output = Vec::new();
+            conn.dump_with(options, &mut output)?;
+            Ok::<_, crate::Error>(String::from_utf8(output).unwrap())
+        };
+
+        let options = crate::DumpOptions::new();
+        assert_ne!(dump(&first, &options)?, dump(&second, &options)?);
+
+        let options = options.deterministic(true);
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+INSERT INTO tags VALUES('a','blue');
+INSERT INTO tags VALUES('b','red');
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users VALUES(1,'bob');
+INSERT INTO users VALUES(2,'alice');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',2);
+CREATE INDEX tags_color ON tags (color);
+CREATE INDEX users_name ON users (name);
+COMMIT;
+"#;
+        assert_eq!(expected, dump(&first, &options)?);
+        assert_eq!(expected, dump(&second, &options)?);
+
+        let statements = |conn: &rusqlite::Connection| {
+            conn.dump_statements_with(&options)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        assert_eq!(statements(&first)?, statements(&second)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_preserve_rowids() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE notes (body TEXT);
+CREATE TABLE events (rowid TEXT, at INTEGER);
+INSERT INTO users (name) VALUES ('alice'), ('bob');
+INSERT INTO notes VALUES ('first'), ('second'), ('third');
+INSERT INTO events VALUES ('boot', 1), ('halt', 2);
+DELETE FROM notes WHERE body = 'first';
+DELETE FROM events WHERE at = 1;"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .preserve_rowids(true);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"INSERT INTO users VALUES(1,'alice');
+INSERT INTO users VALUES(2,'bob');
+INSERT INTO notes(rowid,body) VALUES(2,'second');
+INSERT INTO notes(rowid,body) VALUES(3,'third');
+INSERT INTO events(_rowid_,rowid,at) VALUES(2,'halt',2);
+INSERT INTO sqlite_sequence VALUES('users',2);
+"#;
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+
+        let mut dump = Vec::new();
+        conn.dump_with(&crate::DumpOptions::new().preserve_rowids(true), &mut dump)?;
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        let rowids = restored
+            .prepare("SELECT rowid FROM notes;")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<i64>, _>>()?;
+        assert_eq!(vec![2, 3], rowids);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_without_rowid() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE memberships (team TEXT, user INTEGER, role TEXT, PRIMARY KEY (user, team)) WITHOUT ROWID;
+CREATE INDEX memberships_role ON memberships (role, team, user);
+INSERT INTO memberships VALUES ('ops', 2, 'a'), ('dev', 2, 'c'), ('dev', 1, 'b');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .exclude_tables(["users"])
+            .deterministic(true)
+            .preserve_rowids(true);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"INSERT INTO memberships VALUES('dev',1,'b');
+INSERT INTO memberships VALUES('dev',2,'c');
+INSERT INTO memberships VALUES('ops',2,'a');
+"#;
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+
+        // Pages read by separate queries follow the primary key as well.
+        let statements = conn
+            .dump_statements_with(&options.rows_per_insert(2))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![
+                "INSERT INTO memberships VALUES('dev',1,'b'),('dev',2,'c');",
+                "INSERT INTO memberships VALUES('ops',2,'a');",
+            ],
+            statements
+        );
+
+        let mut dump = Vec::new();
+        conn.dump_with(&crate::DumpOptions::new().preserve_rowids(true), &mut dump)?;
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        let count: i64 =
+            restored.query_row("SELECT count(*) FROM memberships;", [], |row| row.get(0))?;
+        assert_eq!(3, count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_generated_columns() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT, greeting TEXT GENERATED ALWAYS AS ('hi ' || name) VIRTUAL, size INTEGER AS (length(name)) STORED);
+INSERT INTO users (name) VALUES ('alice');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .insert_column_names(true);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"INSERT INTO users(id,name) VALUES(1,'alice');
+INSERT INTO sqlite_sequence VALUES('users',1);
+"#;
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        let (greeting, size): (String, i64) =
+            restored.query_row("SELECT greeting, size FROM users;", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        assert_eq!(("hi alice".to_owned(), 5), (greeting, size));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_strict_tables() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE "values" (i INTEGER, r REAL, t TEXT, b BLOB, a ANY) STRICT;
+INSERT INTO "values" VALUES (NULL, NULL, NULL, NULL, NULL);
+INSERT INTO "values" VALUES (1, 1.0, '1', X'01', 1);
+INSERT INTO "values" VALUES (-7, 2.5e-30, 'x''y', X'', 1.0);
+INSERT INTO "values" VALUES (9223372036854775807, 1e300, '', X'00ff', '1');
+INSERT INTO "values" VALUES (0, 9e999, 'z', X'ab', X'01');
+INSERT INTO "values" VALUES (0, -9e999, 'z', X'ab', 0.1);"#,
+        )?;
+
+        let rows = |conn: &rusqlite::Connection| {
+            let mut stmt = conn.prepare(
+                r#"
+                    SELECT typeof(i), i, typeof(r), r, typeof(t), t, typeof(b), b, typeof(a), a
+                    FROM "values"
+                    ORDER BY rowid;"#,
+            )?;
+            let rows = stmt
+                .query_map([], |row| {
+                    (0..10)
+                        .map(|i| row.get::<_, rusqlite::types::Value>(i))
+                        .collect::<Result<Vec<_>, _>>()
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok::<_, crate::Error>(rows)
+        };
+
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        assert_eq!(rows(&conn)?, rows(&restored)?);
+
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.contains(r#"INSERT INTO "values" VALUES(1,1.0,'1',X'01',1);"#));
+        assert!(dump.contains(r#"INSERT INTO "values" VALUES(0,9.0e+999,'z',X'ab',X'01');"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_schema() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+ATTACH DATABASE ':memory:' AS aux;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users (name) VALUES ('alice');
+CREATE TEMP TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO temp.users (name) VALUES ('temporary');
+CREATE TABLE aux.logs (id INTEGER PRIMARY KEY AUTOINCREMENT, message TEXT, level TEXT);
+CREATE INDEX aux.logs_level ON logs (level);
+INSERT INTO aux.logs (message, level) VALUES ('started', 'info'), ('stopped', 'warn');"#,
+        )?;
+
+        let dump = |options: &crate::DumpOptions| {
+            let mut output = Vec::new();
+            conn.dump_with(options, &mut output)?;
+            Ok::<_, crate::Error>(String::from_utf8(output).unwrap())
+        };
+
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE logs (id INTEGER PRIMARY KEY AUTOINCREMENT, message TEXT, level TEXT);
+INSERT INTO logs VALUES(1,'started','info');
+INSERT INTO logs VALUES(2,'stopped','warn');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('logs',2);
+CREATE INDEX logs_level ON logs (level);
+COMMIT;
+"#;
+        let options = crate::DumpOptions::new().schema("aux");
+        assert_eq!(expected, dump(&options)?);
+        assert_eq!(
+            expected.lines().collect::<Vec<_>>(),
+            conn.dump_statements_with(&options)
+                .collect::<Result<Vec<_>, _>>()?
+        );
+
+        // The temporary table does not hide the table of the main schema.
+        let main = dump(&crate::DumpOptions::new().data_only(true))?;
+        assert!(main.contains("INSERT INTO users VALUES(1,'alice');"));
+        assert!(!main.contains("temporary"));
+
+        let temp = dump(&crate::DumpOptions::new().schema("temp").data_only(true))?;
+        assert!(temp.contains("INSERT INTO users VALUES(1,'temporary');"));
+        assert!(!temp.contains("alice"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_header_pragmas() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+PRAGMA user_version=42;
+PRAGMA application_id=1179208007;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);"#,
+        )?;
+
+        let options = crate::DumpOptions::new().header_pragmas(true);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+PRAGMA user_version=42;
+PRAGMA application_id=1179208007;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+DELETE FROM sqlite_sequence;
+COMMIT;
+"#;
+        assert_eq!(expected, String::from_utf8(actual.clone()).unwrap());
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut actual.as_slice())?;
+        let pragmas: (i64, i64) = restored.query_row(
+            "SELECT user_version, application_id FROM pragma_user_version, pragma_application_id;",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((42, 1179208007), pragmas);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_stat_tables() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE INDEX users_name ON users (name);
+INSERT INTO users (name) VALUES ('alice'), ('alice'), ('bob');
+ANALYZE;"#,
+        )?;
+
+        // The samples of sqlite_stat4 depend on how SQLite was built.
+        let options = crate::DumpOptions::new()
+            .exclude_tables(["sqlite_stat4"])
+            .analyze(true);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users VALUES(1,'alice');
+INSERT INTO users VALUES(2,'alice');
+INSERT INTO users VALUES(3,'bob');
+ANALYZE sqlite_schema;
+INSERT INTO sqlite_stat1 VALUES('users','users_name','3 2');
+DELETE FROM sqlite_sequence;
+INSERT INTO sqlite_sequence VALUES('users',3);
+CREATE INDEX users_name ON users (name);
+ANALYZE sqlite_schema;
+COMMIT;
+"#;
+        assert_eq!(expected, String::from_utf8(actual.clone()).unwrap());
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut actual.as_slice())?;
+        let stat: String = restored.query_row(
+            "SELECT stat FROM sqlite_stat1 WHERE idx = 'users_name';",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!("3 2", stat);
+
+        let options = crate::DumpOptions::new().exclude_tables(["sqlite_stat1", "sqlite_stat4"]);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+        assert!(!String::from_utf8(actual).unwrap().contains("sqlite_stat"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_without_sqlite_sequence() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users (name) VALUES ('alice');"#,
+        )?;
+
+        let mut actual = Vec::new();
+        conn.dump(&mut actual)?;
+
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users VALUES(1,'alice');
+COMMIT;
+"#;
+        assert_eq!(expected, String::from_utf8(actual.clone()).unwrap());
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut actual.as_slice())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_real_values() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE points (x REAL);
+INSERT INTO points VALUES (1.0), (-2.5), (0.1), (1e300), (5e-324), (9e999), (-9e999);
+INSERT INTO points VALUES (3.0251373381551392e240), (-3.2731802469906564e-254);"#,
+        )?;
+
+        let options = crate::DumpOptions::new().data_only(true).transaction(false);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+
+        let expected = r#"INSERT INTO points VALUES(1.0);
+INSERT INTO points VALUES(-2.5);
+INSERT INTO points VALUES(0.1000000000000000055);
+INSERT INTO points VALUES(1.0e+300);
+INSERT INTO points VALUES(4.940656458412465441e-324);
+INSERT INTO points VALUES(9.0e+999);
+INSERT INTO points VALUES(-9.0e+999);
+INSERT INTO points VALUES(3.0251373381551392e+240);
+INSERT INTO points VALUES(-3.2731802469906564e-254);
+"#;
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+
+        let bits = |conn: &rusqlite::Connection| {
+            let mut stmt = conn.prepare("SELECT x FROM points ORDER BY rowid;")?;
+            let bits = stmt
+                .query_map([], |row| row.get::<_, f64>(0))?
+                .map(|x| x.map(f64::to_bits))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok::<_, crate::Error>(bits)
+        };
+
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        assert_eq!(bits(&conn)?, bits(&restored)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_invalid_utf8() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE notes (body TEXT);
+INSERT INTO notes VALUES ('fine'), (CAST(X'61ff62' AS TEXT));"#,
+        )?;
+
+        let dump = |invalid_utf8| {
+            let options = crate::DumpOptions::new()
+                .data_only(true)
+                .transaction(false)
+                .invalid_utf8(invalid_utf8);
+            let mut output = Vec::new();
+            conn.dump_with(&options, &mut output)?;
+            Ok::<_, crate::Error>(String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(
+            "INSERT INTO notes VALUES('fine');\nINSERT INTO notes VALUES('a\u{fffd}b');\n",
+            dump(crate::InvalidUtf8::Replace)?
+        );
+
+        let cast = dump(crate::InvalidUtf8::Cast)?;
+        assert_eq!(
+            "INSERT INTO notes VALUES('fine');\nINSERT INTO notes VALUES(CAST(X'61ff62' AS TEXT));\n",
+            cast
+        );
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(&format!("CREATE TABLE notes (body TEXT);\n{cast}"))?;
+        let (kind, hex): (String, String) = restored.query_row(
+            "SELECT typeof(body), hex(body) FROM notes WHERE rowid = 2;",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!(("text".to_owned(), "61FF62".to_owned()), (kind, hex));
+
+        let err = dump(crate::InvalidUtf8::Error).unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::Table {
+                row: Some(1),
+                source: rusqlite::Error::Utf8Error(_),
+                ..
+            }
+        ));
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .invalid_utf8(crate::InvalidUtf8::Error);
+        let mut output = Vec::new();
+        let warnings = conn.dump_with_warnings(&options, &mut output)?;
+        assert_eq!(
+            "INSERT INTO notes VALUES('fine');\n",
+            String::from_utf8(output).unwrap()
+        );
+        assert_eq!(1, warnings.len());
+        assert_eq!(Some(2), warnings[0].rowid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_escaped_text() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute("CREATE TABLE notes (body TEXT);", [])?;
+        let texts = [
+            "two\nlines",
+            "windows\r\nline",
+            "nul\0inside",
+            "\\n and \\012 next to\na newline",
+            "\\000\0\\x00",
+        ];
+        for text in texts {
+            conn.execute("INSERT INTO notes VALUES (?1);", [text])?;
+        }
+
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.contains("INSERT INTO notes VALUES(replace('two\\nlines','\\n',char(10)));\n"));
+        // Every statement fits on its single line.
+        assert_eq!(
+            dump.lines().count(),
+            dump.lines().filter(|line| line.ends_with(';')).count()
+        );
+        assert!(!dump.contains('\0'));
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_bytes())?;
+        let mut stmt = restored.prepare("SELECT body FROM notes ORDER BY rowid;")?;
+        let restored_texts = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(texts.to_vec(), restored_texts);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_postgres() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+            CREATE TABLE users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name VARCHAR(20) NOT NULL UNIQUE,
+                active BOOLEAN DEFAULT (1),
+                avatar BLOB
+            );
+            CREATE TABLE posts (
+                post_id INTEGER,
+                user_id INTEGER REFERENCES users ON DELETE CASCADE,
+                score REAL,
+                PRIMARY KEY (post_id, user_id)
+            );
+            CREATE INDEX posts_score ON posts (score DESC);
+            CREATE INDEX posts_expression ON posts (score + 1);
+            INSERT INTO users (name, active, avatar) VALUES ('Ann', 1, X'00ff'), ('O''Brien', 0, NULL);
+            INSERT INTO posts VALUES (1, 1, 1.5), (1, 2, NULL);
+            CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+            INSERT INTO tags VALUES ('red', '#f00');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump_with(
+            &crate::DumpOptions::new().dialect(crate::Dialect::Postgres),
+            &mut dump,
+        )?;
+        assert_eq!(
+            r#"BEGIN;
+CREATE TABLE "users" ("id" bigint GENERATED BY DEFAULT AS IDENTITY, "name" text NOT NULL, "active" boolean DEFAULT TRUE, "avatar" bytea, PRIMARY KEY ("id"), UNIQUE ("name"));
+INSERT INTO "users" ("id","name","active","avatar") VALUES(1,'Ann',TRUE,'\x00ff'::bytea);
+INSERT INTO "users" ("id","name","active","avatar") VALUES(2,'O''Brien',FALSE,NULL);
+CREATE TABLE "posts" ("post_id" bigint, "user_id" bigint, "score" double precision, PRIMARY KEY ("post_id", "user_id"));
+INSERT INTO "posts" ("post_id","user_id","score") VALUES(1,1,1.5);
+INSERT INTO "posts" ("post_id","user_id","score") VALUES(1,2,NULL);
+CREATE TABLE "tags" ("name" text NOT NULL, "color" text, PRIMARY KEY ("name"));
+INSERT INTO "tags" ("name","color") VALUES('red','#f00');
+SELECT setval(pg_get_serial_sequence('"users"', 'id'), max("id")) FROM "users" HAVING max("id") IS NOT NULL;
+ALTER TABLE "posts" ADD FOREIGN KEY ("user_id") REFERENCES "users" ("id") ON DELETE CASCADE;
+CREATE INDEX "posts_score" ON "posts" ("score" DESC);
+COMMIT;
+"#,
+            String::from_utf8(dump).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_mysql() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+            CREATE TABLE users (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                bio TEXT DEFAULT 'none',
+                avatar BLOB,
+                ratio REAL
+            );
+            CREATE INDEX users_ratio ON users (ratio);
+            INSERT INTO users VALUES (3, 'C:\dir', 'it''s', X'00ff', 1e999);
+            CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+            INSERT INTO tags VALUES ('red', '#f00');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump_with(
+            &crate::DumpOptions::new().dialect(crate::Dialect::Mysql),
+            &mut dump,
+        )?;
+        assert_eq!(
+            r#"BEGIN;
+CREATE TABLE `users` (`id` bigint NOT NULL AUTO_INCREMENT, `name` varchar(255) NOT NULL, `bio` longtext DEFAULT ('none'), `avatar` longblob, `ratio` double, PRIMARY KEY (`id`), UNIQUE (`name`)) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `users` (`id`,`name`,`bio`,`avatar`,`ratio`) VALUES(3,'C:\\dir','it''s',X'00ff',NULL);
+CREATE TABLE `tags` (`name` varchar(255) NOT NULL, `color` longtext, PRIMARY KEY (`name`)) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `tags` (`name`,`color`) VALUES('red','#f00');
+CREATE INDEX `users_ratio` ON `users` (`ratio`);
+COMMIT;
+"#,
+            String::from_utf8(dump).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_duckdb() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+            CREATE TABLE users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT,
+                avatar BLOB,
+                balance NUMERIC
+            );
+            CREATE TABLE posts (
+                user_id INTEGER REFERENCES users,
+                score REAL
+            );
+            CREATE INDEX posts_score ON posts (score DESC);
+            INSERT INTO users VALUES (7, 'Ann', X'00ff', 1.5);
+            CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+            INSERT INTO tags VALUES ('red', '#f00');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump_with(
+            &crate::DumpOptions::new().dialect(crate::Dialect::Duckdb),
+            &mut dump,
+        )?;
+        assert_eq!(
+            r#"BEGIN;
+CREATE SEQUENCE "users_id_seq" START 8;
+CREATE TABLE "users" ("id" bigint DEFAULT nextval('users_id_seq'), "name" varchar, "avatar" blob, "balance" double, PRIMARY KEY ("id"));
+INSERT INTO "users" ("id","name","avatar","balance") VALUES(7,'Ann',from_hex('00ff'),1.5);
+CREATE TABLE "posts" ("user_id" bigint, "score" double);
+CREATE TABLE "tags" ("name" varchar NOT NULL, "color" varchar, PRIMARY KEY ("name"));
+INSERT INTO "tags" ("name","color") VALUES('red','#f00');
+CREATE INDEX "posts_score" ON "posts" ("score");
+COMMIT;
+"#,
+            String::from_utf8(dump).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_tsql() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+            CREATE TABLE [users] (
+                id INTEGER PRIMARY KEY,
+                name TEXT UNIQUE,
+                active BOOLEAN DEFAULT TRUE,
+                avatar BLOB
+            );
+            CREATE TABLE posts (
+                user_id INTEGER REFERENCES users ON DELETE RESTRICT,
+                body TEXT
+            );
+            INSERT INTO users VALUES (1, 'Zoë', 0, X'00ff');
+            INSERT INTO posts VALUES (1, 'it''s');
+            CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+            INSERT INTO tags VALUES ('red', '#f00');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump_with(
+            &crate::DumpOptions::new().dialect(crate::Dialect::Tsql),
+            &mut dump,
+        )?;
+        assert_eq!(
+            r#"BEGIN TRANSACTION;
+CREATE TABLE [users] ([id] bigint IDENTITY(1,1), [name] nvarchar(450), [active] bit DEFAULT 1, [avatar] varbinary(max), PRIMARY KEY ([id]), UNIQUE ([name]));
+SET IDENTITY_INSERT [users] ON;
+INSERT INTO [users] ([id],[name],[active],[avatar]) VALUES(1,N'Zoë',0,0x00ff);
+SET IDENTITY_INSERT [users] OFF;
+GO
+CREATE TABLE [posts] ([user_id] bigint, [body] nvarchar(max));
+INSERT INTO [posts] ([user_id],[body]) VALUES(1,N'it''s');
+GO
+CREATE TABLE [tags] ([name] nvarchar(450) NOT NULL, [color] nvarchar(max), PRIMARY KEY ([name]));
+INSERT INTO [tags] ([name],[color]) VALUES(N'red',N'#f00');
+GO
+ALTER TABLE [posts] ADD FOREIGN KEY ([user_id]) REFERENCES [users] ([id]);
+GO
+COMMIT;
+"#,
+            String::from_utf8(dump).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_commit_every() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob'), ('carol');"#,
+        )?;
+
+        let options = crate::DumpOptions::new().commit_every(2).rows_per_insert(2);
+        let mut writer = Vec::new();
+        conn.dump_with(&options, &mut writer)?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users VALUES(1,'alice'),(2,'bob');
+COMMIT;
+BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+INSERT INTO users VALUES(3,'carol');
+COMMIT;
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let statements = conn
+            .dump_statements_with(&options)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(expected, format!("{}\n", statements.join("\n")));
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(result)?;
+        let count: i64 = restored.query_row("SELECT count(*) FROM users;", [], |row| row.get(0))?;
+        assert_eq!(3, count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_transaction_per_table() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE posts (user_id INTEGER, body TEXT);
+INSERT INTO users (name) VALUES ('alice');
+INSERT INTO posts VALUES (1, 'hello');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        conn.dump_with(
+            &crate::DumpOptions::new().transaction_per_table(true),
+            &mut writer,
+        )?;
+
+        let result = std::str::from_utf8(&writer).unwrap();
+        let expected = r#"
+PRAGMA foreign_keys=OFF;
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+-- Data for table users
+BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+INSERT INTO users VALUES(1,'alice');
+COMMIT;
+CREATE TABLE posts (user_id INTEGER, body TEXT);
+-- Data for table posts
+BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+INSERT INTO posts VALUES(1,'hello');
+COMMIT;
+"#
+        .trim_start();
+        assert_eq!(expected, result);
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut result.as_bytes())?;
+        let mut original = Vec::new();
+        conn.dump(&mut original)?;
+        let mut copy = Vec::new();
+        restored.dump(&mut copy)?;
+        assert_eq!(original, copy);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_snapshot() -> Result<(), crate::Error> {
+        let path =
+            std::env::temp_dir().join(format!("dumpqlite-test-snapshot-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let conn = rusqlite::Connection::open(&path)?;
+        conn.execute_batch(
+            r#"
+PRAGMA journal_mode=WAL;
+CREATE TABLE a (x INTEGER);
+CREATE TABLE b (x INTEGER);
+INSERT INTO a VALUES (1);
+INSERT INTO b VALUES (1);"#,
+        )?;
+        let writer = rusqlite::Connection::open(&path)?;
+
+        for snapshot in [true, false] {
+            let options = crate::DumpOptions::new()
+                .data_only(true)
+                .transaction(false)
+                .snapshot(snapshot);
+            let mut statements = conn.dump_statements_with(&options);
+            assert_eq!(
+                "INSERT INTO a VALUES(1);",
+                statements.next().transpose()?.unwrap()
+            );
+
+            writer.execute_batch("INSERT INTO b VALUES (2);")?;
+            let rest = statements.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(
+                snapshot,
+                !rest.contains(&"INSERT INTO b VALUES(2);".to_owned())
+            );
+            assert!(conn.is_autocommit());
+        }
+
+        drop((conn, writer));
+        std::fs::remove_file(&path)?;
+        let _ = std::fs::remove_file(path.with_extension("db-wal"));
+        let _ = std::fs::remove_file(path.with_extension("db-shm"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_backup_to_memory() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+ATTACH ':memory:' AS other;
+CREATE TABLE other.users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE INDEX other.users_name ON users (name);
+INSERT INTO other.users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let pages = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = crate::DumpOptions::new().schema("other");
+        let copied = options.clone().backup_to_memory(true).on_progress({
+            let pages = std::sync::Arc::clone(&pages);
+            move |progress| {
+                if progress.page_count > 0 {
+                    pages
+                        .lock()
+                        .unwrap()
+                        .push((progress.pages_copied, progress.page_count));
+                }
+            }
+        });
+
+        let mut expected = Vec::new();
+        conn.dump_with(&options, &mut expected)?;
+        let mut dump = Vec::new();
+        conn.dump_with(&copied, &mut dump)?;
+        assert_eq!(
+            String::from_utf8(expected.clone()).unwrap(),
+            String::from_utf8(dump).unwrap()
+        );
+
+        let statements = conn
+            .dump_statements_with(&copied)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            String::from_utf8(expected).unwrap(),
+            format!("{}\n", statements.join("\n"))
+        );
+
+        let pages = pages.lock().unwrap();
+        let &(copied, count) = pages.last().unwrap();
+        assert!(count > 0);
+        assert_eq!(count, copied);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_buffer_size() {
+        struct CountingWriter(usize);
+
+        impl std::io::Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0 += 1;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE numbers (n INTEGER);
+WITH RECURSIVE seq(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM seq WHERE n < 1000)
+INSERT INTO numbers SELECT n FROM seq;
+"#,
+        )
+        .unwrap();
+
+        let mut writer = CountingWriter(0);
+        conn.dump(&mut writer).unwrap();
+        assert_eq!(1, writer.0);
+
+        let mut writer = CountingWriter(0);
+        let options = crate::DumpOptions::new().buffer_size(0);
+        conn.dump_with(&options, &mut writer).unwrap();
+        assert!(writer.0 > 1000);
+    }
+
+    #[test]
+    fn test_dump_large_blobs() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE files (name TEXT, content BLOB, thumbnail BLOB);
+INSERT INTO files VALUES ('small', x'0102', NULL);
+INSERT INTO files VALUES ('large', zeroblob(1048577), x'ff');
+INSERT INTO files VALUES ('both', zeroblob(1048577), randomblob(2097152));
+"#,
+        )
+        .unwrap();
+        let thumbnail: Vec<u8> = conn
+            .query_row(
+                "SELECT thumbnail FROM files WHERE name = 'both';",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+
+        let zeros = "00".repeat(1048577);
+        let expected = format!(
+            r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE files (name TEXT, content BLOB, thumbnail BLOB);
+INSERT INTO files VALUES('small',X'0102',NULL),('large',X'{zeros}',X'ff');
+INSERT INTO files VALUES('both',X'{zeros}',X'{}');
+COMMIT;
+"#,
+            crate::dump::hex(&thumbnail)
+        );
+
+        let mut actual = Vec::new();
+        let options = crate::DumpOptions::new().rows_per_insert(2);
+        conn.dump_with(&options, &mut actual).unwrap();
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+
+        let mut actual = Vec::new();
+        let options = options.strict(false).buffer_size(0);
+        conn.dump_with(&options, &mut actual).unwrap();
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+    }
+
+    #[test]
+    fn test_dump_large_blobs_shadowed_rowid() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE t (rowid INTEGER, content BLOB);
+CREATE TABLE u (rowid TEXT, content BLOB);
+CREATE TABLE v (rowid INTEGER, _rowid_ INTEGER, oid INTEGER, content BLOB);
+INSERT INTO t VALUES (7, zeroblob(1048577));
+INSERT INTO u VALUES ('a', zeroblob(1048577));
+INSERT INTO v VALUES (7, 7, 7, zeroblob(1048577));
+"#,
+        )?;
+
+        let zeros = "00".repeat(1048577);
+        let expected = format!(
+            r#"INSERT INTO t VALUES(7,X'{zeros}');
+INSERT INTO u VALUES('a',X'{zeros}');
+INSERT INTO v VALUES(7,7,7,X'{zeros}');
+"#
+        );
+
+        for options in [
+            crate::DumpOptions::new(),
+            crate::DumpOptions::new().strict(false),
+        ] {
+            let mut actual = Vec::new();
+            let options = options.data_only(true).transaction(false);
+            conn.dump_with(&options, &mut actual)?;
+            assert_eq!(expected, String::from_utf8(actual).unwrap());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_max_blob_size() -> Result<(), crate::Error> {
+        use crate::OversizedBlob;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE files (id INTEGER PRIMARY KEY, content BLOB);
+INSERT INTO files VALUES (1, x'0102'), (2, x'0102030405');
+CREATE TABLE "key files" (name TEXT PRIMARY KEY, content BLOB) WITHOUT ROWID;
+INSERT INTO "key files" VALUES ('a', x'0102030405');
+"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .max_blob_size(4, OversizedBlob::Null);
+        let mut actual = Vec::new();
+        let warnings = conn.dump_with_warnings(&options, &mut actual)?;
+        let expected = r#"INSERT INTO files VALUES(1,X'0102');
+INSERT INTO files VALUES(2,NULL);
+INSERT INTO "key files" VALUES('a',NULL);
+"#;
+        assert_eq!(expected, String::from_utf8(actual).unwrap());
+        assert_eq!(2, warnings.len());
+        assert_eq!(
+            ("files", Some(2)),
+            (warnings[0].table.as_str(), warnings[0].rowid)
+        );
+        assert!(warnings[0].cause.to_string().contains("BLOB of 5 bytes"));
+        assert_eq!(
+            ("key files", None),
+            (warnings[1].table.as_str(), warnings[1].rowid)
+        );
+
+        let options = options
+            .max_blob_size(4, OversizedBlob::Placeholder)
+            .format(crate::DumpFormat::Csv)
+            .blob_encoding(crate::BlobEncoding::Hex)
+            .include_tables(["files"]);
+        let mut actual = Vec::new();
+        conn.dump_with(&options, &mut actual)?;
+        assert_eq!(
+            "sqlite_metadata\r\nblob_encoding\r\nhex\r\n\r\n\
+             files\r\nid,content\r\n1,0102\r\n2,[BLOB of 5 bytes]\r\n",
+            String::from_utf8(actual).unwrap()
+        );
+
+        let dir = std::env::temp_dir().join(format!("dumpqlite-test-blobs-{}", std::process::id()));
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .max_blob_size(4, OversizedBlob::External(dir.clone()));
+        let statements = conn
+            .dump_statements_with(&options)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            vec![
+                "INSERT INTO files VALUES(1,X'0102');",
+                "INSERT INTO files VALUES(2,'files.content.2.bin');",
+                r#"INSERT INTO "key files" VALUES('a','key%20files.content.row0.bin');"#,
+            ],
+            statements
+        );
+        assert_eq!(
+            vec![1, 2, 3, 4, 5],
+            std::fs::read(dir.join("files.content.2.bin"))?
+        );
+        assert_eq!(
+            "file,table,column,size\r\n\
+             files.content.2.bin,files,content,5\r\n\
+             key%20files.content.row0.bin,key files,content,5\r\n",
+            std::fs::read_to_string(dir.join("manifest.csv"))?
+        );
+
+        // Dumping again lists every file once, as does a dump split per table.
+        conn.dump_with(&options, &mut Vec::new())?;
+        conn.dump_per_table(&options, |_| Ok(Vec::new()))?;
+        assert_eq!(
+            "file,table,column,size\r\n\
+             files.content.2.bin,files,content,5\r\n\
+             key%20files.content.row0.bin,key files,content,5\r\n",
+            std::fs::read_to_string(dir.join("manifest.csv"))?
+        );
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema() -> Result<(), crate::Error> {
+        use crate::{IndexColumn, IndexOrigin};
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE, name TEXT DEFAULT 'x');
+CREATE TABLE posts (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER REFERENCES users ON DELETE CASCADE,
+    title TEXT,
+    slug TEXT GENERATED ALWAYS AS (lower(title))
+);
+CREATE INDEX posts_title ON posts (title DESC);
+CREATE VIEW titles AS SELECT title FROM posts;
+CREATE TRIGGER users_delete AFTER DELETE ON users BEGIN DELETE FROM posts; END;
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+"#,
+        )?;
+
+        let schema = conn.schema()?;
+        let names = |tables: &[crate::Table]| {
+            tables
+                .iter()
+                .map(|table| table.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["users", "posts", "tags"], names(&schema.tables));
+
+        let users = &schema.tables[0];
+        assert_eq!(vec!["id"], users.primary_key);
+        assert_eq!(
+            ("email", "TEXT", true, None),
+            (
+                users.columns[1].name.as_str(),
+                users.columns[1].declared_type.as_str(),
+                users.columns[1].not_null,
+                users.columns[1].default.as_deref()
+            )
+        );
+        assert_eq!(Some("'x'"), users.columns[2].default.as_deref());
+        assert_eq!(1, users.indexes.len());
+        assert_eq!(IndexOrigin::Unique, users.indexes[0].origin);
+        assert_eq!(None, users.indexes[0].sql);
+
+        let posts = &schema.tables[1];
+        assert!(posts.columns[3].generated);
+        assert_eq!(1, posts.foreign_keys.len());
+        assert_eq!(
+            (
+                vec!["user_id".to_owned()],
+                "users",
+                vec!["id".to_owned()],
+                "CASCADE"
+            ),
+            (
+                posts.foreign_keys[0].columns.clone(),
+                posts.foreign_keys[0].parent.as_str(),
+                posts.foreign_keys[0].parent_columns.clone(),
+                posts.foreign_keys[0].on_delete.as_str()
+            )
+        );
+        assert_eq!(
+            vec![IndexColumn {
+                name: Some("title".to_owned()),
+                descending: true
+            }],
+            posts.indexes[0].columns
+        );
+        assert_eq!(
+            Some("CREATE INDEX posts_title ON posts (title DESC)"),
+            posts.indexes[0].sql.as_deref()
+        );
+
+        let tags = &schema.tables[2];
+        assert_eq!(vec!["name"], tags.primary_key);
+        assert_eq!(1, tags.indexes.len());
+        assert_eq!(IndexOrigin::PrimaryKey, tags.indexes[0].origin);
+        assert_eq!(None, tags.indexes[0].sql);
+
+        assert_eq!(1, schema.views.len());
+        assert_eq!("titles", schema.views[0].name);
+        assert_eq!(1, schema.triggers.len());
+        assert_eq!(
+            ("users_delete", "users"),
+            (
+                schema.triggers[0].name.as_str(),
+                schema.triggers[0].table.as_str()
+            )
+        );
+
+        let options = crate::DumpOptions::new().include_tables(["posts"]);
+        let schema = conn.schema_with(&options)?;
+        assert_eq!(vec!["posts"], names(&schema.tables));
+        assert!(schema.views.is_empty());
+        assert!(schema.triggers.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<(), crate::Error> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let other = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, score REAL);
+INSERT INTO users VALUES(1, 'alice', 1.5);
+INSERT INTO users VALUES(2, 'bob', 2.0);
+INSERT INTO users VALUES(3, 'carol', NULL);
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT) WITHOUT ROWID;
+INSERT INTO tags VALUES('red', '#f00');
+CREATE TABLE logs (message TEXT);
+INSERT INTO logs VALUES('started');
+INSERT INTO logs VALUES('stopped');
+CREATE TABLE legacy (x);
+"#,
+        )?;
+        other.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, score REAL);
+INSERT INTO users VALUES(1, 'alice', 1.5);
+INSERT INTO users VALUES(3, 'carol', 3.0);
+INSERT INTO users VALUES(4, 'dave', NULL);
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT, weight INTEGER) WITHOUT ROWID;
+INSERT INTO tags VALUES('blue', '#00f', 1);
+CREATE TABLE logs (message TEXT);
+INSERT INTO logs VALUES('started');
+INSERT INTO logs VALUES('restarted');
+CREATE TABLE notes (body TEXT);
+INSERT INTO notes VALUES('hello');
+"#,
+        )?;
+
+        let mut diff = Vec::new();
+        conn.diff(&other, &mut diff)?;
+        let expected = r#"BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+DROP TABLE legacy;
+UPDATE logs SET message='restarted' WHERE rowid=2;
+CREATE TABLE notes (body TEXT);
+INSERT INTO notes(rowid,body) VALUES(1,'hello');
+DROP TABLE tags;
+CREATE TABLE tags (name TEXT PRIMARY KEY, color TEXT, weight INTEGER) WITHOUT ROWID;
+INSERT INTO tags(name,color,weight) VALUES('blue','#00f',1);
+DELETE FROM users WHERE id=2;
+UPDATE users SET score=3.0 WHERE id=3;
+INSERT INTO users(id,name,score) VALUES(4,'dave',NULL);
+COMMIT;
+"#;
+        assert_eq!(expected, std::str::from_utf8(&diff).unwrap());
+
+        // Applying the diff makes the databases identical.
+        crate::ConnectionExt::restore(&mut conn, &mut diff.as_slice())?;
+        let mut diff = Vec::new();
+        conn.diff(&other, &mut diff)?;
+        assert!(diff.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_diff() -> Result<(), crate::Error> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+        let other = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users VALUES(1, 'alice');
+CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER, body TEXT, draft INTEGER);
+INSERT INTO posts VALUES(1, 1, 'hello', 0);
+CREATE INDEX posts_user ON posts(user_id);
+CREATE TABLE logs (message TEXT);
+INSERT INTO logs VALUES('started');
+CREATE TABLE old (x);
+CREATE VIEW names AS SELECT name FROM users;
+"#,
+        )?;
+        other.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, email TEXT);
+CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users, body TEXT NOT NULL);
+CREATE INDEX posts_user ON posts(user_id);
+CREATE TABLE logs (message TEXT);
+CREATE TABLE tags (name TEXT PRIMARY KEY);
+CREATE VIEW names AS SELECT name FROM users;
+"#,
+        )?;
+
+        let mut diff = Vec::new();
+        conn.schema_diff(&other, &mut diff)?;
+        let expected = r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+DROP VIEW names;
+DROP TABLE old;
+CREATE TABLE tags (name TEXT PRIMARY KEY);
+ALTER TABLE users ADD COLUMN email TEXT;
+CREATE TABLE new_posts(id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users, body TEXT NOT NULL);
+INSERT INTO new_posts (id, user_id, body) SELECT id, user_id, body FROM posts;
+DROP TABLE posts;
+ALTER TABLE new_posts RENAME TO posts;
+CREATE INDEX posts_user ON posts(user_id);
+CREATE VIEW names AS SELECT name FROM users;
+PRAGMA foreign_key_check;
+COMMIT;
+PRAGMA foreign_keys=ON;
+"#;
+        assert_eq!(expected, std::str::from_utf8(&diff).unwrap());
+
+        // Applying the migration keeps the rows, turns foreign keys on again and leaves nothing
+        // to migrate.
+        crate::ConnectionExt::restore(&mut conn, &mut diff.as_slice())?;
+        let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys;", [], |row| row.get(0))?;
+        assert!(foreign_keys);
+        let body: String =
+            conn.query_row("SELECT body FROM posts WHERE id = 1", [], |row| row.get(0))?;
+        assert_eq!("hello", body);
+        let schema = conn.schema()?;
+        let target = other.schema()?;
+        for table in &target.tables {
+            let migrated = schema.tables.iter().find(|t| t.name == table.name).unwrap();
+            assert_eq!(table.columns, migrated.columns);
+            assert_eq!(table.foreign_keys, migrated.foreign_keys);
+        }
+        assert!(schema.migration(&target).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_hash() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let other = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE logs (message TEXT, data BLOB);
+INSERT INTO users VALUES(1, 'alice');
+INSERT INTO users VALUES(2, 'bob');
+INSERT INTO logs VALUES('started', X'00');
+INSERT INTO logs VALUES('stopped', NULL);
+"#,
+        )?;
+        // The same content, inserted in another order and with other rowids.
+        other.execute_batch(
+            r#"
+CREATE TABLE logs (message TEXT, data BLOB);
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO logs(rowid, message, data) VALUES(10, 'stopped', NULL);
+INSERT INTO logs(rowid, message, data) VALUES(20, 'started', X'00');
+INSERT INTO users VALUES(2, 'bob');
+INSERT INTO users VALUES(1, 'alice');
+"#,
+        )?;
+        assert_eq!(conn.content_hash()?, other.content_hash()?);
+
+        other.execute("UPDATE users SET name = 'Bob' WHERE id = 2", [])?;
+        assert_ne!(conn.content_hash()?, other.content_hash()?);
+
+        // The rows hash equal again once restored, while an index changes the schema.
+        other.execute_batch("DELETE FROM logs; UPDATE users SET name = 'bob' WHERE id = 2;")?;
+        other.execute_batch(
+            r#"
+INSERT INTO logs VALUES('started', X'00');
+INSERT INTO logs VALUES('stopped', NULL);
+"#,
+        )?;
+        assert_eq!(conn.content_hash()?, other.content_hash()?);
+        other.execute("CREATE INDEX users_name ON users(name)", [])?;
+        assert_ne!(conn.content_hash()?, other.content_hash()?);
+
+        let options = crate::DumpOptions::new().include_tables(["logs"]);
+        assert_eq!(
+            conn.content_hash_with(&options)?,
+            other.content_hash_with(&options)?
+        );
+
+        // The rows hashed are those dumped, including the rows referenced by those selected.
+        conn.execute_batch(
+            r#"
+CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+INSERT INTO orders VALUES(1, 1);
+"#,
+        )?;
+        let options = crate::DumpOptions::new()
+            .include_tables(["users", "orders"])
+            .where_clause("users", "0")
+            .follow_foreign_keys(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        assert_eq!(
+            conn.content_hash_with(&options)?,
+            restored.content_hash_with(&crate::DumpOptions::new())?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_dump() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users VALUES(1, 'alice');
+INSERT INTO users VALUES(2, 'bob');
+CREATE TABLE logs (message TEXT);
+INSERT INTO logs VALUES('started');
+"#,
+        )?;
+
+        let dump = conn.dump_to_string()?;
+        assert!(conn.verify_dump(&dump)?.is_ok());
+
+        let tampered = dump
+            .replace("'bob'", "'eve'")
+            .replace(
+                "CREATE TABLE logs (message TEXT);",
+                "CREATE TABLE notes (message TEXT);",
+            )
+            .replace("INSERT INTO logs", "INSERT INTO notes");
+        let report = conn.verify_dump(&tampered)?;
+        assert_eq!(
+            vec![
+                crate::TableDifference::Missing {
+                    table: "logs".to_owned()
+                },
+                crate::TableDifference::Unexpected {
+                    table: "notes".to_owned()
+                },
+                crate::TableDifference::Rows {
+                    table: "users".to_owned(),
+                    expected_rows: 2,
+                    actual_rows: 2,
+                },
+            ],
+            report.differences
+        );
+
+        let options = crate::DumpOptions::new().include_tables(["users"]);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(conn.verify_dump_with(&options, &dump)?.is_ok());
+        assert!(!conn.verify_dump(&dump)?.is_ok());
+
+        // The rows referenced by those dumped are expected too.
+        conn.execute_batch(
+            r#"
+CREATE TABLE orders (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+INSERT INTO orders VALUES(1, 1);
+"#,
+        )?;
+        let options = crate::DumpOptions::new()
+            .include_tables(["users", "orders"])
+            .where_clause("users", "0")
+            .follow_foreign_keys(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.contains("INSERT INTO users VALUES(1,'alice');"));
+        assert!(conn.verify_dump_with(&options, &dump)?.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users VALUES(1, 'alice');
+CREATE INDEX users_name ON users(name);
+CREATE VIEW names AS SELECT name FROM users;
+CREATE VIRTUAL TABLE docs USING fts5(body);
+INSERT INTO docs VALUES('hello');
+"#,
+        )?;
+
+        let options = crate::DumpOptions::new().transaction_per_table(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        crate::validate(&mut dump.as_slice())?;
+
+        // The statistics tables are created by `ANALYZE` before their rows are inserted.
+        conn.execute_batch("ANALYZE;")?;
+        let mut analyzed = Vec::new();
+        conn.dump(&mut analyzed)?;
+        assert!(String::from_utf8_lossy(&analyzed).contains("INSERT INTO sqlite_stat1"));
+        crate::validate(&mut analyzed.as_slice())?;
+
+        let dump = String::from_utf8(dump).unwrap();
+        let broken = dump.replace(
+            "INSERT INTO users VALUES(1,'alice');",
+            "INSERT INTO users VALUES(1,'alice',2);",
+        );
+        let line = broken
+            .lines()
+            .position(|line| line.starts_with("INSERT INTO users"))
+            .unwrap() as u64
+            + 1;
+        match crate::validate(&mut broken.as_bytes()) {
+            Err(crate::Error::Statement {
+                line: reported,
+                sql,
+                ..
+            }) => {
+                assert_eq!(line, reported);
+                assert_eq!("INSERT INTO users VALUES(1,'alice',2);\n", sql);
+            }
+            result => panic!("expected an invalid statement, got {result:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "session")]
+    #[test]
+    fn test_session_changeset() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE events (name TEXT);
+INSERT INTO users VALUES (1, 'alice'), (2, 'bob');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+        let mut session = conn.session()?;
+        assert!(session.is_empty());
+
+        conn.execute_batch(
+            r#"
+UPDATE users SET name = 'carol' WHERE id = 1;
+DELETE FROM users WHERE id = 2;
+INSERT INTO users VALUES (3, 'dave');
+INSERT INTO events VALUES ('not recorded');"#,
+        )?;
+        let changeset = session.changeset()?;
+        assert!(session.patchset()?.len() < changeset.len());
+        drop(session);
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+        restored.apply_changeset(&changeset)?;
+        let users = restored
+            .prepare("SELECT id, name FROM users ORDER BY id;")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(i64, String)>, _>>()?;
+        assert_eq!(vec![(1, "carol".to_owned()), (3, "dave".to_owned())], users);
+
+        // Applying the changes twice conflicts, and leaves the rows alone.
+        let err = restored.apply_changeset(&changeset).unwrap_err();
+        assert!(err.to_string().contains("table users"), "{err}");
+        let count: i64 = restored.query_row("SELECT count(*) FROM users;", [], |row| row.get(0))?;
+        assert_eq!(2, count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_since() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE log (id INTEGER PRIMARY KEY, message TEXT);
+CREATE TABLE events (name TEXT);
+CREATE TABLE items (name TEXT PRIMARY KEY, updated_at TEXT) WITHOUT ROWID;
+INSERT INTO log (message) VALUES ('first');
+INSERT INTO events VALUES ('opened');
+INSERT INTO items VALUES ('apple', '2024-01-01'), ('pear', '2024-01-02');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .on_conflict(crate::OnConflict::Replace)
+            .incremental_column("items", "updated_at");
+        let checkpoint = conn.checkpoint_with(&options)?;
+        let marks = checkpoint
+            .marks()
+            .iter()
+            .map(|mark| {
+                (
+                    mark.table.as_str(),
+                    mark.column.as_str(),
+                    mark.value.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("log", "id", rusqlite::types::Value::Integer(1)),
+                ("events", "rowid", rusqlite::types::Value::Integer(1)),
+                (
+                    "items",
+                    "updated_at",
+                    rusqlite::types::Value::Text("2024-01-02".to_owned())
+                ),
+            ],
+            marks
+        );
+
+        conn.execute_batch(
+            r#"
+INSERT INTO log (message) VALUES ('second');
+INSERT INTO events VALUES ('closed');
+UPDATE items SET updated_at = '2024-02-01' WHERE name = 'apple';
+CREATE TABLE later (a);
+INSERT INTO later VALUES (1);"#,
+        )?;
+
+        let mut writer = Vec::new();
+        conn.dump_with(&options.clone().since(checkpoint.clone()), &mut writer)?;
+        let expected = r#"INSERT OR REPLACE INTO log VALUES(2,'second');
+INSERT OR REPLACE INTO events VALUES('closed');
+INSERT OR REPLACE INTO items VALUES('apple','2024-02-01');
+INSERT OR REPLACE INTO later VALUES(1);
+"#;
+        assert_eq!(expected, String::from_utf8(writer).unwrap());
+
+        // A checkpoint built from stored marks selects the same rows.
+        let stored = crate::Checkpoint::new().mark("log", "id", 1);
+        let mut writer = Vec::new();
+        let options = crate::DumpOptions::new()
+            .data_only(true)
+            .transaction(false)
+            .include_tables(["log"])
+            .since(stored);
+        conn.dump_with(&options, &mut writer)?;
+        assert_eq!(
+            "INSERT INTO log VALUES(2,'second');\n",
+            String::from_utf8(writer).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_with_manifest() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE tasks (id INTEGER PRIMARY KEY, title TEXT);
+CREATE TABLE empty (a);
+INSERT INTO users VALUES (1, 'alice'), (2, 'bob');
+INSERT INTO tasks VALUES (1, 'write'), (2, 'read'), (3, 'sleep');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        let manifest = conn.dump_with_manifest(&crate::DumpOptions::default(), &mut dump)?;
+        let names = manifest
+            .tables
+            .iter()
+            .map(|table| (table.name.as_str(), table.rows))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("users", 2), ("tasks", 3), ("empty", 0)], names);
+
+        let tasks = &manifest.tables[1];
+        let range = tasks.offset as usize..(tasks.offset + tasks.length) as usize;
+        assert_eq!(
+            "INSERT INTO tasks VALUES(1,'write');\nINSERT INTO tasks VALUES(2,'read');\nINSERT INTO tasks VALUES(3,'sleep');\n",
+            std::str::from_utf8(&dump[range.clone()]).unwrap()
+        );
+        let mut hash = crate::sha256::Sha256::new();
+        hash.update(&dump[range.clone()]);
+        assert_eq!(hash.finish(), tasks.sha256);
+        assert_eq!(0, manifest.tables[2].length);
+
+        // A single table restores from its definition and its range alone.
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        let sql = format!(
+            "{};\n{}",
+            tasks.sql,
+            std::str::from_utf8(&dump[range]).unwrap()
+        );
+        crate::ConnectionExt::restore(&mut restored, &mut sql.as_bytes())?;
+        let count: i64 = restored.query_row("SELECT count(*) FROM tasks;", [], |row| row.get(0))?;
+        assert_eq!(3, count);
+
+        assert!(manifest.to_json().starts_with(
+            r#"{"tables":[{"name":"users","sql":"CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)","offset":"#
+        ));
+
+        let options = crate::DumpOptions::new().format(crate::DumpFormat::Json);
+        assert!(conn.dump_with_manifest(&options, &mut Vec::new()).is_err());
+
+        Ok(())
+    }
+
+    /// A writer into a buffer shared with the test, for the dumps written to the writers a
+    /// factory returns.
+    struct Writer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Writer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_dump_per_table() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE INDEX users_name ON users (name);
+CREATE TABLE tasks (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+CREATE VIEW names AS SELECT name FROM users;
+INSERT INTO users (name) VALUES ('alice');
+INSERT INTO tasks VALUES (1, 1);"#,
+        )?;
+
+        let mut writers = Vec::new();
+        conn.dump_per_table(&crate::DumpOptions::default(), |table| {
+            let writer = std::rc::Rc::default();
+            writers.push((table.to_owned(), std::rc::Rc::clone(&writer)));
+            Ok(Writer(writer))
+        })?;
+
+        let dumps = writers
+            .into_iter()
+            .map(|(table, dump)| (table, String::from_utf8(dump.take()).unwrap()))
+            .collect::<Vec<_>>();
+        let expected = vec![
+            (
+                "users".to_owned(),
+                r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+INSERT INTO users VALUES(1,'alice');
+DELETE FROM sqlite_sequence WHERE name = 'users';
+INSERT INTO sqlite_sequence VALUES('users',1);
+CREATE INDEX users_name ON users (name);
+COMMIT;
+"#
+                .to_owned(),
+            ),
+            (
+                "tasks".to_owned(),
+                r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE tasks (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+INSERT INTO tasks VALUES(1,1);
+COMMIT;
+"#
+                .to_owned(),
+            ),
+            (
+                "names".to_owned(),
+                r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE VIEW names AS SELECT name FROM users;
+COMMIT;
+"#
+                .to_owned(),
+            ),
+        ];
+        assert_eq!(expected, dumps);
+
+        // Every dump restores on its own, here in reverse order.
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        for (_, dump) in dumps.iter().rev() {
+            crate::ConnectionExt::restore(&mut restored, &mut dump.as_bytes())?;
+        }
+        let name: String = restored.query_row("SELECT name FROM names;", [], |row| row.get(0))?;
+        assert_eq!("alice", name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_in_parts() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE t (id INTEGER PRIMARY KEY, x TEXT);
+INSERT INTO t VALUES (1, 'aaaaaaaaaa'), (2, 'bbbbbbbbbb'), (3, 'cccccccccc');"#,
+        )?;
+
+        let mut parts = Vec::new();
+        conn.dump_in_parts(&crate::DumpOptions::default(), 170, |part| {
+            assert_eq!(parts.len() + 1, part);
+            parts.push(std::rc::Rc::new(std::cell::RefCell::new(Vec::new())));
+            Ok(Writer(std::rc::Rc::clone(parts.last().unwrap())))
+        })?;
+
+        let parts = parts
+            .into_iter()
+            .map(|part| String::from_utf8(part.take()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE t (id INTEGER PRIMARY KEY, x TEXT);
+INSERT INTO t VALUES(1,'aaaaaaaaaa');
+COMMIT;
+"#,
+                r#"-- Part 2 of the dump, continuing part 1
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+INSERT INTO t VALUES(2,'bbbbbbbbbb');
+COMMIT;
+"#,
+                r#"-- Part 3 of the dump, continuing part 2
+PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+PRAGMA defer_foreign_keys=ON;
+INSERT INTO t VALUES(3,'cccccccccc');
+COMMIT;
+"#,
+            ],
+            parts
+        );
+        assert!(parts.iter().all(|part| part.len() <= 170));
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        for part in &parts {
+            restored.execute_batch(part)?;
+        }
+        assert_eq!(conn.dump_to_string()?, restored.dump_to_string()?);
+
+        let options = crate::DumpOptions::default().format(crate::DumpFormat::Csv);
+        assert!(conn
+            .dump_in_parts(&options, 100, |_| Ok(Vec::new()))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES ('hello');")?;
+
+        let mut dump = Vec::new();
+        conn.dump_with(&crate::DumpOptions::new().checksum(true), &mut dump)?;
+        let text = String::from_utf8(dump.clone()).unwrap();
+        let (content, trailer) = text.rsplit_once("-- sha256: ").unwrap();
+        assert_eq!(conn.dump_to_string()?, content);
+        let mut hash = crate::sha256::Sha256::new();
+        hash.update(content.as_bytes());
+        assert_eq!(format!("{}\n", crate::dump::hex(&hash.finish())), trailer);
+        crate::verify_checksum(&mut dump.as_slice())?;
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(&text)?;
+        assert_eq!(conn.dump_to_string()?, restored.dump_to_string()?);
+
+        let mut tampered = text.replace("'hello'", "'hallo'").into_bytes();
+        let err = crate::verify_checksum(&mut tampered.as_slice()).unwrap_err();
+        assert!(err.to_string().starts_with("checksum mismatch"));
+
+        tampered.truncate(content.len());
+        let err = crate::verify_checksum(&mut tampered.as_slice()).unwrap_err();
+        assert_eq!("the dump has no checksum", err.to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_header() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE t (x); INSERT INTO t VALUES (1);")?;
+
+        let options = crate::DumpOptions::new()
+            .metadata_header(true)
+            .deterministic(true)
+            .exclude_tables(["a \"b\"\nc"]);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+
+        let sqlite_version: String =
+            conn.query_row("SELECT sqlite_version();", [], |row| row.get(0))?;
+        let header = format!(
+            r#"-- dumpqlite: {}
+-- sqlite_version: {sqlite_version}
+-- page_size: 4096
+-- encoding: UTF-8
+-- options: {{"dialect":"sqlite","schema":"main","data_only":false,"schema_only":false,"transaction":true,"commit_every":null,"transaction_per_table":false,"deterministic":true,"preserve_rowids":false,"rows_per_insert":1,"on_conflict":"abort","include_tables":null,"exclude_tables":["a \"b\"\nc"],"filtered_tables":[],"incremental":false}}
+"#,
+            env!("CARGO_PKG_VERSION")
+        );
+        let body = dump.strip_prefix(&header).unwrap();
+        assert_eq!(conn.dump_to_string()?, body);
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_bytes())?;
+        assert_eq!(conn.dump_to_string()?, restored.dump_to_string()?);
+
+        let options = crate::DumpOptions::new().metadata_header(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        let generated_at = dump.lines().nth(1).unwrap();
+        assert!(generated_at.starts_with("-- generated_at: 20"));
+        assert!(generated_at.ends_with('Z'));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_clean() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE INDEX users_name ON users (name);
+CREATE TABLE tasks (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+CREATE TRIGGER tasks_user AFTER INSERT ON tasks BEGIN SELECT 1; END;
+CREATE VIEW names AS SELECT name FROM users;
+CREATE VIRTUAL TABLE docs USING fts5(body);
+INSERT INTO users VALUES (1, 'alice');
+INSERT INTO tasks VALUES (1, 1);
+INSERT INTO docs VALUES ('hello');"#,
+        )?;
+
+        let options = crate::DumpOptions::new().clean(true).deterministic(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.starts_with(
+            r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+DROP VIEW IF EXISTS names;
+DROP TRIGGER IF EXISTS tasks_user;
+DROP INDEX IF EXISTS users_name;
+DROP TABLE IF EXISTS docs;
+DROP TABLE IF EXISTS docs_config;
+"#
+        ));
+
+        // Restoring twice, into a database holding other rows, gives the same database.
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(
+            r#"
+PRAGMA foreign_keys=ON;
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, age INTEGER);
+INSERT INTO users VALUES (2, 'bob', 42);
+CREATE TABLE tasks (id INTEGER PRIMARY KEY, user_id INTEGER REFERENCES users(id));
+INSERT INTO tasks VALUES (2, 2);"#,
+        )?;
+        restored.execute_batch(&dump)?;
+        restored.execute_batch(&dump)?;
+        let deterministic = |conn: &rusqlite::Connection| -> Result<Vec<u8>, crate::Error> {
+            let mut dump = Vec::new();
+            conn.dump_with(&crate::DumpOptions::new().deterministic(true), &mut dump)?;
+            Ok(dump)
+        };
+        assert_eq!(deterministic(&conn)?, deterministic(&restored)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_if_not_exists() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE UNIQUE INDEX users_name ON users (name);
+CREATE VIEW names AS SELECT name FROM users;
+CREATE TRIGGER users_insert AFTER INSERT ON users BEGIN SELECT 1; END;
+CREATE VIRTUAL TABLE docs USING fts5(body);
+INSERT INTO users VALUES (1, 'alice');"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .if_not_exists(true)
+            .on_conflict(crate::OnConflict::Ignore);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(
+            dump.contains("CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY, name TEXT);")
+        );
+        assert!(dump.contains("CREATE UNIQUE INDEX IF NOT EXISTS users_name ON users (name);"));
+        assert!(dump.contains("CREATE VIEW IF NOT EXISTS names AS SELECT name FROM users;"));
+        assert!(dump.contains("CREATE TRIGGER IF NOT EXISTS users_insert AFTER INSERT ON users"));
+
+        // The dump restores into a database holding part of its schema, and again over itself.
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE UNIQUE INDEX users_name ON users (name);
+INSERT INTO users VALUES (1, 'alice');"#,
+        )?;
+        restored.execute_batch(&dump)?;
+        restored.execute_batch(&dump)?;
+        assert_eq!(conn.dump_to_string()?, restored.dump_to_string()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_formatting() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE a (x);
+CREATE TRIGGER a_insert AFTER INSERT ON a BEGIN SELECT 1; END;
+CREATE TABLE b (y);
+INSERT INTO a VALUES (1);
+INSERT INTO b VALUES (2);"#,
+        )?;
+
+        let options = crate::DumpOptions::new()
+            .crlf(true)
+            .statement_terminator(";;")
+            .blank_lines(true)
+            .checksum(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        let (content, _) = dump.rsplit_once("-- sha256: ").unwrap();
+        assert_eq!(
+            "PRAGMA foreign_keys=OFF;;\r\n\
+             BEGIN TRANSACTION;;\r\n\
+             \r\n\
+             CREATE TABLE a (x);;\r\n\
+             INSERT INTO a VALUES(1);;\r\n\
+             \r\n\
+             CREATE TABLE b (y);;\r\n\
+             INSERT INTO b VALUES(2);;\r\n\
+             CREATE TRIGGER a_insert AFTER INSERT ON a BEGIN SELECT 1; END;;\r\n\
+             COMMIT;;\r\n",
+            content
+        );
+        assert!(dump.ends_with("\r\n") && !dump.ends_with("\r\n\r\n"));
+        crate::verify_checksum(&mut dump.as_bytes())?;
+
+        let options = crate::DumpOptions::new().data_only(true).blank_lines(true);
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        assert_eq!(
+            "BEGIN TRANSACTION;\n\nINSERT INTO a VALUES(1);\n\nINSERT INTO b VALUES(2);\nCOMMIT;\n",
+            String::from_utf8(dump).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_pretty_schema() -> Result<(), crate::Error> {
+        let schema = |sql: &str| -> Result<String, crate::Error> {
+            let conn = rusqlite::Connection::open_in_memory()?;
+            conn.execute_batch(sql)?;
+            let options = crate::DumpOptions::new()
+                .pretty_schema(true)
+                .schema_only(true)
+                .transaction(false);
+            let mut dump = Vec::new();
+            conn.dump_with(&options, &mut dump)?;
+            Ok(String::from_utf8(dump).unwrap())
+        };
+
+        let expected = r#"CREATE TABLE users (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL DEFAULT 'a  b',
+    age INTEGER CHECK (age >= 0),
+    UNIQUE (name, age)
+) WITHOUT ROWID;
+CREATE VIEW adults AS SELECT name FROM users WHERE age >= 18;
+CREATE TRIGGER users_insert AFTER INSERT ON users BEGIN
+    SELECT 1;
+    SELECT max(1, 2);
+END;
+CREATE INDEX users_age ON users (age, name);
+"#;
+        assert_eq!(
+            expected,
+            schema(
+                r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL DEFAULT 'a  b', age INTEGER CHECK (age >= 0), UNIQUE (name, age)) WITHOUT ROWID;
+CREATE VIEW adults AS SELECT name FROM users WHERE age >= 18;
+CREATE TRIGGER users_insert AFTER INSERT ON users BEGIN SELECT 1; SELECT max(1, 2); END;
+CREATE INDEX users_age ON users (age, name);"#
+            )?
+        );
+        assert_eq!(
+            expected,
+            schema(
+                r#"
+CREATE TABLE users (
+  id    INTEGER PRIMARY KEY
+, name  TEXT NOT NULL DEFAULT 'a  b'
+, age   INTEGER CHECK ( age >= 0 )
+, UNIQUE(name,age)
+)   WITHOUT ROWID;
+CREATE VIEW adults AS
+    SELECT name
+    FROM users
+    WHERE age >= 18;
+CREATE TRIGGER users_insert AFTER INSERT ON users
+BEGIN
+  SELECT 1;
+  SELECT max(1,2);
+END;
+CREATE INDEX users_age ON users ( age , name );"#
+            )?
+        );
+
+        // The reformatted schema restores, and formats the same again.
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(expected)?;
+        assert_eq!(expected, schema(expected)?);
+
+        // Comments are kept along with the rest of their statement.
+        let commented = "CREATE TABLE t (\n  a, -- the first\n  b\n);\n";
+        assert_eq!(commented, schema(commented)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_verify_source() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE t (a, b);
+CREATE INDEX t_a ON t (a);
+INSERT INTO t VALUES (1, 2), (3, 4);"#,
+        )?;
+        let options = crate::DumpOptions::new().verify_source(true);
+        conn.dump_with(&options, &mut std::io::sink())?;
+
+        // An index whose definition no longer matches its entries.
+        conn.execute_batch(
+            r#"
+PRAGMA writable_schema=ON;
+UPDATE sqlite_schema SET sql = 'CREATE INDEX t_a ON t (b)' WHERE name = 't_a';
+PRAGMA writable_schema=RESET;"#,
+        )?;
+        let err = conn.dump_with(&options, &mut std::io::sink()).unwrap_err();
+        let crate::Error::Corrupt { problems } = &err else {
+            panic!("unexpected error: {err}");
+        };
+        assert!(!problems.is_empty());
+        assert!(err
+            .to_string()
+            .starts_with("the database failed its integrity check: "));
+
+        let mut statements = conn.dump_statements_with(&options);
+        assert!(matches!(
+            statements.next(),
+            Some(Err(crate::Error::Corrupt { .. }))
+        ));
+        conn.dump_with(&crate::DumpOptions::new(), &mut std::io::sink())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_key_check() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+PRAGMA foreign_keys=OFF;
+CREATE TABLE parent (id INTEGER PRIMARY KEY);
+CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id REFERENCES parent (id));
+CREATE TABLE other (id INTEGER PRIMARY KEY, parent_id REFERENCES parent (id));
+INSERT INTO parent VALUES (1);
+INSERT INTO child VALUES (1, 1), (2, 2), (3, NULL);
+INSERT INTO other VALUES (1, 3);"#,
+        )?;
+
+        let violations = conn.foreign_key_check()?;
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0],
+            crate::ForeignKeyViolation {
+                table: "child".to_owned(),
+                rowid: Some(2),
+                parent: "parent".to_owned(),
+                foreign_key: 0,
+            }
+        );
+        assert_eq!(violations[1].table, "other");
+
+        let options = crate::DumpOptions::new().exclude_tables(["other"]);
+        assert_eq!(conn.foreign_key_check_with(&options)?.len(), 1);
+
+        conn.execute_batch("DELETE FROM child WHERE id = 2; DELETE FROM other;")?;
+        assert!(conn.foreign_key_check()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacuum_into() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE INDEX users_name ON users (name);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+
+        let dir =
+            std::env::temp_dir().join(format!("dumpqlite-test-vacuum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("copy.db");
+        conn.vacuum_into(&path)?;
+
+        let copy = rusqlite::Connection::open(&path)?;
+        assert_eq!(conn.dump_to_string()?, copy.dump_to_string()?);
+
+        // VACUUM INTO never overwrites a file.
+        assert!(conn.vacuum_into(&path).is_err());
+
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let options = crate::DumpOptions::new().cancellation(token);
+        assert!(matches!(
+            conn.vacuum_into_with(&options, dir.join("cancelled.db")),
+            Err(crate::Error::Cancelled)
+        ));
+
+        drop(copy);
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup_to() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+PRAGMA page_size=512;
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200)
+INSERT INTO users (name) SELECT printf('user %d', i) FROM n;"#,
+        )?;
+
+        let steps = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = crate::BackupOptions::new()
+            .pages_per_step(2)
+            .pause(std::time::Duration::from_millis(1))
+            .on_progress({
+                let steps = steps.clone();
+                move |progress| {
+                    steps
+                        .lock()
+                        .unwrap()
+                        .push((progress.pages_copied, progress.page_count))
+                }
+            });
+        let mut dest = rusqlite::Connection::open_in_memory()?;
+        conn.backup_to(&mut dest, &options)?;
+        assert_eq!(conn.dump_to_string()?, dest.dump_to_string()?);
+
+        let steps = steps.lock().unwrap();
+        assert!(steps.len() > 1);
+        let &(copied, pages) = steps.last().unwrap();
+        assert_eq!(copied, pages);
+
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let options = crate::BackupOptions::new().cancellation(token);
+        assert!(matches!(
+            conn.backup_to(&mut rusqlite::Connection::open_in_memory()?, &options),
+            Err(crate::Error::Cancelled)
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_dump_encrypted() -> Result<(), crate::Error> {
+        let path =
+            std::env::temp_dir().join(format!("dumpqlite-test-cipher-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let conn = crate::open_encrypted(&path, "secret")?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+CREATE INDEX users_name ON users (name);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+        drop(conn);
+
+        // The file holds none of the rows in plain text.
+        let file = std::fs::read(&path)?;
+        assert!(!file.windows(5).any(|window| window == b"alice"));
+
+        let conn = crate::open_encrypted(&path, "secret")?;
+        let dump = conn.dump_to_string()?;
+        assert!(dump.contains("INSERT INTO users VALUES(1,'alice');"));
+        let options = crate::DumpOptions::new().snapshot(true).verify_source(true);
+        let mut snapshot = Vec::new();
+        conn.dump_with(&options, &mut snapshot)?;
+        assert_eq!(dump.as_bytes(), snapshot);
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_bytes())?;
+        assert_eq!(dump, restored.dump_to_string()?);
+
+        // A connection keyed beforehand dumps the same.
+        let keyed = rusqlite::Connection::open(&path)?;
+        keyed.execute_batch("PRAGMA key = 'secret';")?;
+        assert_eq!(dump, keyed.dump_to_string()?);
+
+        assert!(matches!(
+            crate::open_encrypted(&path, "wrong"),
+            Err(crate::Error::WrongKey)
+        ));
+        assert!(rusqlite::Connection::open(&path)?.dump_to_string().is_err());
+
+        drop((conn, keyed));
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn test_dump_encrypted_output() -> Result<(), crate::Error> {
+        use std::io::Read as _;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+INSERT INTO users (name) VALUES ('alice'), ('bob');"#,
+        )?;
+        let expected = conn.dump_to_string()?;
+
+        let recipient = "age1pgs460cefrkf84e2spx65t0t2xs5lgnyp0075xwdhzpsfu3ykplqa0nvrt";
+        let identity = "AGE-SECRET-KEY-19UK6NNG6NN0CDTNFYJYTXAJSH305372FZTJR6ZS5N4X7086UP35Q4KDWLE";
+        let options = crate::DumpOptions::new()
+            .encryption(crate::Encryption::Recipients(vec![recipient.to_owned()]));
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+        assert!(dump.starts_with(b"age-encryption.org/v1\n"));
+        assert!(!dump.windows(5).any(|window| window == b"alice"));
+
+        let key = crate::Decryption::Identities(vec![identity.to_owned()]);
+        let mut decrypted = String::new();
+        crate::decrypt(dump.as_slice(), &key)?.read_to_string(&mut decrypted)?;
+        assert_eq!(expected, decrypted);
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        crate::ConnectionExt::restore(&mut restored, &mut crate::decrypt(dump.as_slice(), &key)?)?;
+        assert_eq!(expected, restored.dump_to_string()?);
+
+        let passphrase = crate::DumpOptions::new()
+            .encryption(crate::Encryption::Passphrase("secret".to_owned()));
+        let mut dump = Vec::new();
+        conn.dump_with(&passphrase, &mut dump)?;
+        let mut decrypted = String::new();
+        let key = crate::Decryption::Passphrase("secret".to_owned());
+        crate::decrypt(dump.as_slice(), &key)?.read_to_string(&mut decrypted)?;
+        assert_eq!(expected, decrypted);
+
+        let wrong = crate::Decryption::Passphrase("wrong".to_owned());
+        assert!(matches!(
+            crate::decrypt(dump.as_slice(), &wrong),
+            Err(crate::Error::Decrypt(_))
+        ));
+        let invalid = crate::DumpOptions::new().encryption(crate::Encryption::Recipients(vec![
+            "age1invalid".to_owned(),
+        ]));
+        assert!(conn.dump_with(&invalid, &mut std::io::sink()).is_err());
+        assert!(conn
+            .dump_with_manifest(&options, &mut std::io::sink())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_metrics() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE a (x);
+CREATE TABLE b (y);
+INSERT INTO a VALUES (1), (2), (3);
+INSERT INTO b VALUES ('one');"#,
+        )?;
+
+        let metrics = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let options = crate::DumpOptions::new().deterministic(true).on_metrics({
+            let metrics = metrics.clone();
+            move |m| {
+                assert!(m.rows_per_second() >= 0.0 && m.bytes_per_second() >= 0.0);
+                assert!(m.table_duration <= m.elapsed);
+                metrics.lock().unwrap().push((
+                    m.table.to_owned(),
+                    m.table_rows,
+                    m.table_bytes,
+                    m.rows_written,
+                    m.bytes_written,
+                ));
+            }
+        });
+        let mut dump = Vec::new();
+        conn.dump_with(&options, &mut dump)?;
+
+        let metrics = metrics.lock().unwrap();
+        let insert_bytes = |table: &str| {
+            std::str::from_utf8(&dump)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with(&format!("INSERT INTO {table} ")))
+                .map(|line| line.len() as u64 + 1)
+                .sum::<u64>()
+        };
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(
+            (
+                metrics[0].0.as_str(),
+                metrics[0].1,
+                metrics[0].2,
+                metrics[0].3
+            ),
+            ("a", 3, insert_bytes("a"), 3)
+        );
+        assert_eq!(
+            (
+                metrics[1].0.as_str(),
+                metrics[1].1,
+                metrics[1].2,
+                metrics[1].3
+            ),
+            ("b", 1, insert_bytes("b"), 4)
+        );
+        assert!(metrics[1].4 < dump.len() as u64);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_dump_tracing() -> Result<(), crate::Error> {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        };
+
+        /// Records the names of the spans and the messages of the events.
+        struct Recorder {
+            next_id: AtomicU64,
+            spans: Arc<Mutex<Vec<String>>>,
+            events: Arc<Mutex<Vec<String>>>,
+        }
+
+        struct Message<'a>(&'a mut String);
+
+        impl tracing::field::Visit for Message<'_> {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    *self.0 = format!("{value:?}");
+                }
+            }
+        }
+
+        impl tracing::Subscriber for Recorder {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+                self.spans
+                    .lock()
+                    .unwrap()
+                    .push(span.metadata().name().to_owned());
+                tracing::span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+            }
+
+            fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+            fn event(&self, event: &tracing::Event<'_>) {
+                let mut message = String::new();
+                event.record(&mut Message(&mut message));
+                self.events.lock().unwrap().push(message);
+            }
+
+            fn enter(&self, _: &tracing::span::Id) {}
+
+            fn exit(&self, _: &tracing::span::Id) {}
+        }
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE a (x);
+CREATE TABLE b (y);
+INSERT INTO a VALUES (1), (2);"#,
+        )?;
+
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            next_id: AtomicU64::new(1),
+            spans: spans.clone(),
+            events: events.clone(),
+        };
+        tracing::subscriber::with_default(recorder, || {
+            conn.dump_with(&crate::DumpOptions::new(), &mut std::io::sink())
+        })?;
+
+        assert_eq!(*spans.lock().unwrap(), ["dump", "table", "table"]);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "table written",
+                "table written",
+                "schema written",
+                "commit",
+                "dump finished"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_with_stats() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+CREATE TABLE a (x);
+CREATE TABLE b (y);
+INSERT INTO a VALUES (1), (2), (x'0102030405');"#,
+        )?;
+
+        let tables = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let options = crate::DumpOptions::new()
+            .max_blob_size(4, crate::OversizedBlob::Placeholder)
+            .on_metrics({
+                let tables = tables.clone();
+                move |_| {
+                    tables.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                }
+            });
+        let mut dump = Vec::new();
+        let stats = conn.dump_with_stats(&options, &mut dump)?;
+
+        assert_eq!(stats.tables, 2);
+        assert_eq!(stats.rows_per_table["a"], 3);
+        assert_eq!(stats.rows_per_table["b"], 0);
+        assert_eq!(stats.bytes_written, dump.len() as u64);
+        assert_eq!(stats.warnings.len(), 1);
+        assert_eq!(stats.warnings[0].table, "a");
+        assert_eq!(tables.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+        let stats = conn.dump_with_stats(
+            &crate::DumpOptions::new().schema_only(true),
+            &mut std::io::sink(),
+        )?;
+        assert_eq!(stats.tables, 0);
+        assert!(stats.bytes_written > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate() -> Result<(), crate::Error> {
+        use crate::ConnectionExt as _;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+                CREATE TABLE a (x INTEGER, y TEXT, z BLOB);
+                INSERT INTO a VALUES (1, 'one', x'0102'), (2, NULL, NULL), (3, 'it''s', x'');
+                CREATE TABLE b (x);
+            "#,
+        )?;
+
+        let dump_size = |options: &crate::DumpOptions| -> Result<u64, crate::Error> {
+            let options = options.clone().data_only(true).transaction(false);
+            let mut dump = Vec::new();
+            conn.dump_with(&options, &mut dump)?;
+            Ok(dump.len() as u64)
+        };
+
+        let options = crate::DumpOptions::new();
+        let estimate = conn.estimate(&options)?;
+        assert_eq!(estimate.tables.len(), 2);
+        assert_eq!(estimate.tables[0].table, "a");
+        assert_eq!(estimate.tables[0].rows, 3);
+        assert_eq!(estimate.tables[1].rows, 0);
+        assert_eq!(estimate.tables[1].bytes, 0);
+        assert_eq!(estimate.rows, 3);
+        assert_eq!(estimate.bytes, dump_size(&options)?);
+
+        let options = crate::DumpOptions::new()
+            .where_clause("a", "x > 1")
+            .insert_column_names(true);
+        let estimate = conn.estimate(&options)?;
+        assert_eq!(estimate.tables[0].rows, 2);
+        assert_eq!(estimate.bytes, dump_size(&options)?);
+
+        // Text is counted in bytes, and the rows referenced by those selected are counted too.
+        conn.execute_batch(
+            r#"
+                CREATE TABLE c (id INTEGER PRIMARY KEY, y TEXT);
+                INSERT INTO c VALUES (1, 'ééé'), (2, NULL);
+                CREATE TABLE d (c_id INTEGER REFERENCES c (id));
+                INSERT INTO d VALUES (1);
+            "#,
+        )?;
+        let options = crate::DumpOptions::new()
+            .include_tables(["c", "d"])
+            .where_clause("c", "0")
+            .follow_foreign_keys(true);
+        let estimate = conn.estimate(&options)?;
+        assert_eq!(estimate.tables[0].rows, 1);
+        assert_eq!(estimate.rows, 2);
+        assert_eq!(estimate.bytes, dump_size(&options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_table() -> Result<(), crate::Error> {
+        use crate::ConnectionExt as _;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+                CREATE TABLE a (id INTEGER PRIMARY KEY AUTOINCREMENT, x);
+                CREATE INDEX a_x ON a (x);
+                CREATE TRIGGER a_t AFTER INSERT ON a BEGIN SELECT 1; END;
+                CREATE TABLE b (y);
+                CREATE VIEW v AS SELECT x FROM a;
+                INSERT INTO a (x) VALUES (1);
+                INSERT INTO b VALUES (2);
+            "#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump_table("A", &mut dump)?;
+        assert_eq!(
+            String::from_utf8(dump).unwrap(),
+            r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE a (id INTEGER PRIMARY KEY AUTOINCREMENT, x);
+INSERT INTO a VALUES(1,1);
+DELETE FROM sqlite_sequence WHERE name = 'a';
+INSERT INTO sqlite_sequence VALUES('a',1);
+CREATE TRIGGER a_t AFTER INSERT ON a BEGIN SELECT 1; END;
+CREATE INDEX a_x ON a (x);
+COMMIT;
+"#
+        );
+
+        let options = crate::DumpOptions::new()
+            .indexes(false)
+            .triggers(false)
+            .include_tables(["b"]);
+        let mut dump = Vec::new();
+        conn.dump_table_with(&options, "a", &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.contains("INSERT INTO a VALUES(1,1);"));
+        assert!(!dump.contains("CREATE INDEX") && !dump.contains("CREATE TRIGGER"));
+
+        let mut dump = Vec::new();
+        conn.dump_table("v", &mut dump)?;
+        assert!(String::from_utf8(dump).unwrap().contains("CREATE VIEW v"));
+
+        let err = conn.dump_table("c", &mut Vec::new()).unwrap_err();
+        assert!(matches!(err, crate::Error::Io(err) if err.kind() == std::io::ErrorKind::NotFound));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_query() -> Result<(), crate::Error> {
+        use crate::ConnectionExt as _;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+                CREATE TABLE a (id INTEGER PRIMARY KEY, name TEXT);
+                CREATE TABLE b (a_id INTEGER, name TEXT, data BLOB);
+                INSERT INTO a VALUES (1, 'one'), (2, 'two');
+                INSERT INTO b VALUES (1, 'x', x'00ff'), (2, 'y', NULL), (2, 'z', NULL);
+            "#,
+        )?;
+        let sql = "SELECT a.name, b.name, b.data FROM a JOIN b ON b.a_id = a.id ORDER BY b.name";
+
+        let mut dump = Vec::new();
+        conn.dump_query(sql, "joined rows", &mut dump)?;
+        let dump = String::from_utf8(dump).unwrap();
+        assert_eq!(
+            dump,
+            r#"PRAGMA foreign_keys=OFF;
+BEGIN TRANSACTION;
+CREATE TABLE IF NOT EXISTS "joined rows"(name TEXT,"name:1" TEXT,data BLOB);
+INSERT INTO "joined rows" VALUES('one','x',X'00ff');
+INSERT INTO "joined rows" VALUES('two','y',NULL);
+INSERT INTO "joined rows" VALUES('two','z',NULL);
+COMMIT;
+"#
+        );
+
+        let restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch(&dump)?;
+        let count: i64 =
+            restored.query_row(r#"SELECT count(*) FROM "joined rows";"#, [], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(count, 3);
+
+        let mut json = Vec::new();
+        conn.dump_query_with(
+            &crate::DumpOptions::new().format(crate::DumpFormat::Json),
+            "SELECT count(*) AS n FROM b",
+            "counts",
+            &mut json,
+        )?;
+        assert!(String::from_utf8(json)
+            .unwrap()
+            .contains("\"counts\": [\n    {\"n\": 3}\n  ]"));
+
+        let err = conn
+            .dump_query("DELETE FROM a", "t", &mut Vec::new())
+            .unwrap_err();
+        assert!(
+            matches!(err, crate::Error::Io(err) if err.kind() == std::io::ErrorKind::InvalidInput)
+        );
+        assert_eq!(
+            conn.query_row("SELECT count(*) FROM a;", [], |row| row.get::<_, i64>(0))?,
+            2
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dump_views_in_dependency_order() -> Result<(), crate::Error> {
+        use crate::ConnectionExt as _;
+
+        let conn = rusqlite::Connection::open_in_memory()?;
+        conn.execute_batch(
+            r#"
+                CREATE TABLE t (x);
+                CREATE VIEW c AS SELECT x FROM t;
+                CREATE VIEW b AS SELECT x FROM c;
+                CREATE VIEW a AS SELECT x FROM "B" WHERE x != 'c';
+                DROP VIEW c;
+                CREATE VIEW c AS SELECT x FROM t;
+                DROP VIEW b;
+                CREATE VIEW b AS SELECT x FROM [c];
+            "#,
+        )?;
+
+        let dump = |options: &crate::DumpOptions| -> Result<String, crate::Error> {
+            let mut dump = Vec::new();
+            conn.dump_with(options, &mut dump)?;
+            Ok(String::from_utf8(dump).unwrap())
+        };
+        let views = |dump: &str| {
+            dump.lines()
+                .filter_map(|line| line.strip_prefix("CREATE VIEW "))
+                .map(|line| &line[..1])
+                .collect::<String>()
+        };
+        assert_eq!(views(&conn.dump_to_string()?), "cba");
+        let options = crate::DumpOptions::new().deterministic(true);
+        assert_eq!(views(&dump(&options)?), "cba");
+
+        let drops = dump(&crate::DumpOptions::new().clean(true))?;
+        let drops = drops
+            .lines()
+            .filter_map(|line| line.strip_prefix("DROP VIEW IF EXISTS "))
+            .collect::<String>();
+        assert_eq!(drops, "a;b;c;");
+
+        let schema = conn.schema()?;
+        let names = schema
+            .views
+            .iter()
+            .map(|view| view.name.as_str())
+            .collect::<String>();
+        assert_eq!(names, "cba");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,136 @@
+use std::{collections::HashMap, io};
+
+use crate::{
+    dump::{self, TableBoundary},
+    json::quote_string,
+    progress::Progress,
+    sha256::Sha256,
+    DumpOptions,
+};
+
+/// Where the rows of each table are in an SQL dump, as returned by
+/// [`ConnectionExt::dump_with_manifest`](crate::ConnectionExt::dump_with_manifest), so that
+/// tools can extract or restore a single table by seeking to it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Manifest {
+    /// The tables whose rows were dumped, in the order of the dump.
+    pub tables: Vec<ManifestTable>,
+}
+
+/// The rows of a table within an SQL dump, see [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ManifestTable {
+    pub name: String,
+    /// The definition of the table in `sqlite_schema`, to create it before restoring its rows
+    /// alone.
+    pub sql: String,
+    /// The offset of the first byte of the `INSERT` statements of the table, from the start of
+    /// the dump.
+    pub offset: u64,
+    /// The number of bytes of the `INSERT` statements of the table, along with any statement
+    /// committing them every [`DumpOptions::commit_every`] rows.
+    pub length: u64,
+    pub rows: u64,
+    /// The SHA-256 hash of the `length` bytes at `offset`.
+    pub sha256: [u8; 32],
+}
+
+impl Manifest {
+    /// Formats the manifest as a JSON object holding a `tables` array, with the hashes as hex
+    /// strings.
+    ///
+    /// ```json
+    /// {"tables":[{"name":"users","sql":"CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)","offset":103,"length":70,"rows":2,"sha256":"..."}]}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let tables = self
+            .tables
+            .iter()
+            .map(|table| {
+                format!(
+                    r#"{{"name":{},"sql":{},"offset":{},"length":{},"rows":{},"sha256":"{}"}}"#,
+                    quote_string(&table.name),
+                    quote_string(&table.sql),
+                    table.offset,
+                    table.length,
+                    table.rows,
+                    dump::hex(&table.sha256)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(r#"{{"tables":[{}]}}"#, tables.join(","))
+    }
+}
+
+/// A writer tracking its offset and hashing what is written to it while a table is open.
+struct Tracked<'a, W> {
+    writer: &'a mut W,
+    offset: u64,
+    table: Option<(u64, Sha256)>,
+}
+
+impl<W: io::Write> io::Write for Tracked<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.offset += written as u64;
+        if let Some((_, hash)) = &mut self.table {
+            hash.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes the SQL dump of `conn` to `writer`, recording where the rows of each table are.
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &Progress,
+    writer: &mut W,
+) -> Result<Manifest, crate::Error> {
+    let definitions = dump::tables(conn, options)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut manifest = Manifest::default();
+    let mut tracked = Tracked {
+        writer,
+        offset: 0,
+        table: None,
+    };
+    dump::dump_with_tables(
+        conn,
+        options,
+        progress,
+        &mut tracked,
+        |tracked, table_name, boundary| {
+            match boundary {
+                TableBoundary::Start => tracked.table = Some((tracked.offset, Sha256::new())),
+                TableBoundary::End { rows } => {
+                    let Some((offset, hash)) = tracked.table.take() else {
+                        return Ok(());
+                    };
+                    manifest.tables.push(ManifestTable {
+                        name: table_name.to_owned(),
+                        // The statistics tables of `ANALYZE` are created by the dump as a
+                        // whole.
+                        sql: definitions.get(table_name).cloned().unwrap_or_default(),
+                        offset,
+                        length: tracked.offset - offset,
+                        rows,
+                        sha256: hash.finish(),
+                    });
+                }
+            }
+            Ok(())
+        },
+    )?;
+
+    Ok(manifest)
+}
@@ -0,0 +1,128 @@
+use std::io;
+
+use crate::DumpOptions;
+
+/// Writes every table as a GitHub-flavored Markdown table under a heading with its name.
+///
+/// Tables longer than `max_display_rows` are cut short, with a note saying so.
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let tables = crate::dump::data_tables(conn, options)?;
+
+    for (i, table_name) in tables.iter().enumerate() {
+        if i > 0 {
+            writeln!(writer)?;
+        }
+        writeln!(writer, "## {}", escape(table_name))?;
+        writeln!(writer)?;
+
+        let columns = crate::dump::columns(conn, options, table_name)?;
+        write_row(writer, columns.iter().map(String::as_str))?;
+        write_row(writer, columns.iter().map(|_| "---"))?;
+
+        if options.schema_only {
+            continue;
+        }
+
+        // One more row than displayed is read to tell whether the table was cut short.
+        let limit = options.max_display_rows.map(|rows| rows + 1);
+        let mut rows = 0;
+        crate::dump::for_each_row(
+            conn,
+            options,
+            progress,
+            table_name,
+            &columns,
+            limit,
+            |values| {
+                rows += 1;
+                if Some(rows) == limit {
+                    return Ok(());
+                }
+
+                let values = values
+                    .iter()
+                    .copied()
+                    .map(crate::dump::format_plain_value)
+                    .collect::<Vec<String>>();
+                Ok(write_row(writer, values.iter().map(String::as_str))?)
+            },
+        )?;
+
+        if let Some(max_display_rows) = options.max_display_rows.filter(|_| Some(rows) == limit) {
+            writeln!(writer)?;
+            writeln!(
+                writer,
+                "*Only the first {max_display_rows} rows are shown.*"
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_row<'a, W: io::Write>(
+    writer: &mut W,
+    cells: impl IntoIterator<Item = &'a str>,
+) -> io::Result<()> {
+    write!(writer, "|")?;
+    for cell in cells {
+        write!(writer, " {} |", escape(cell))?;
+    }
+    writeln!(writer)
+}
+
+/// Escapes pipes, which would end a cell, and line breaks, which would end the table.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\r', '\n'], "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConnectionExt, DumpFormat, DumpOptions};
+
+    #[test]
+    fn test_dump_markdown() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users VALUES (1, 'alice | bob', X'cafe'), (2, 'multi
+line', NULL), (3, 'carol', NULL);
+INSERT INTO tags VALUES ('rust');"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = DumpOptions::new()
+            .format(DumpFormat::Markdown)
+            .max_display_rows(2);
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = r#"## users
+
+| id | name | avatar |
+| --- | --- | --- |
+| 1 | alice \| bob | cafe |
+| 2 | multi<br>line |  |
+
+*Only the first 2 rows are shown.*
+
+## tags
+
+| name |
+| --- |
+| rust |
+"#;
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+}
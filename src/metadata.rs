@@ -0,0 +1,95 @@
+use crate::{json::quote_string, quote, DumpOptions, OnConflict};
+
+/// The comment lines starting a dump written with
+/// [`DumpOptions::metadata_header`](crate::DumpOptions::metadata_header), each of the form
+/// `-- key: value`.
+pub(crate) fn header(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> rusqlite::Result<Vec<String>> {
+    let (generated_at, sqlite_version) = conn.query_row(
+        "SELECT strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), sqlite_version();",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+    )?;
+    let page_size: i64 = conn.query_row(
+        &format!("PRAGMA {}.page_size;", quote::identifier(&options.schema)),
+        [],
+        |row| row.get(0),
+    )?;
+    let encoding: String = conn.query_row("PRAGMA encoding;", [], |row| row.get(0))?;
+
+    let mut lines = vec![format!("-- dumpqlite: {}", env!("CARGO_PKG_VERSION"))];
+    // A deterministic dump stays the same as long as the database does.
+    if !options.deterministic {
+        lines.push(format!("-- generated_at: {generated_at}"));
+    }
+    lines.extend([
+        format!("-- sqlite_version: {sqlite_version}"),
+        format!("-- page_size: {page_size}"),
+        format!("-- encoding: {encoding}"),
+        format!("-- options: {}", options_json(options)),
+    ]);
+    Ok(lines)
+}
+
+/// The options shaping the SQL of the dump, as a JSON object on a single line.
+fn options_json(options: &DumpOptions) -> String {
+    let names = |names: &[String]| {
+        let names = names
+            .iter()
+            .map(|name| quote_string(name))
+            .collect::<Vec<_>>();
+        format!("[{}]", names.join(","))
+    };
+    let filtered = options
+        .where_clauses
+        .iter()
+        .map(|(table_name, _)| table_name.clone())
+        .collect::<Vec<_>>();
+
+    let fields = [
+        ("dialect", quote_string(options.dialect.name())),
+        ("schema", quote_string(&options.schema)),
+        ("data_only", options.data_only.to_string()),
+        ("schema_only", options.schema_only.to_string()),
+        ("transaction", options.transaction.to_string()),
+        (
+            "commit_every",
+            options
+                .commit_every
+                .map_or_else(|| "null".to_owned(), |rows| rows.to_string()),
+        ),
+        (
+            "transaction_per_table",
+            options.transaction_per_table.to_string(),
+        ),
+        ("deterministic", options.deterministic.to_string()),
+        ("preserve_rowids", options.preserve_rowids.to_string()),
+        ("rows_per_insert", options.rows_per_insert.to_string()),
+        (
+            "on_conflict",
+            quote_string(match options.on_conflict {
+                OnConflict::Abort => "abort",
+                OnConflict::Ignore => "ignore",
+                OnConflict::Replace => "replace",
+            }),
+        ),
+        (
+            "include_tables",
+            options
+                .include_tables
+                .as_deref()
+                .map_or_else(|| "null".to_owned(), names),
+        ),
+        ("exclude_tables", names(&options.exclude_tables)),
+        ("filtered_tables", names(&filtered)),
+        ("incremental", options.since.is_some().to_string()),
+    ];
+
+    let fields = fields
+        .iter()
+        .map(|(name, value)| format!("{}:{value}", quote_string(name)))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", fields.join(","))
+}
@@ -0,0 +1,640 @@
+use std::collections::HashSet;
+
+use crate::{quote, Column, Schema, Table, Trigger};
+
+impl Schema {
+    /// Returns the statements migrating this schema to `target`, or nothing if they are the
+    /// same.
+    ///
+    /// Tables, indexes, views and triggers are created, dropped or created again as needed.
+    /// A table whose columns were only added or only dropped is changed in place with
+    /// `ALTER TABLE`, when SQLite allows it. Any other change of a table is made with the
+    /// table rebuild described in the SQLite documentation. The rows are copied into a new
+    /// table, which then takes the place of the old one, with foreign keys turned off, and
+    /// turned on again once committed.
+    ///
+    /// The `PRAGMA foreign_key_check` run before committing a rebuild is advisory: it returns
+    /// the rows violating a foreign key but does not fail the migration, so check its rows, or
+    /// call [`ConnectionExt::foreign_key_check`](crate::ConnectionExt::foreign_key_check) after
+    /// the migration, to find them.
+    ///
+    /// Columns are matched by name, so a renamed column is dropped and added again without its
+    /// values. Changed virtual tables are created again without their rows.
+    ///
+    /// ```
+    /// use dumpqlite::ConnectionExt;
+    ///
+    /// let source = rusqlite::Connection::open_in_memory()?;
+    /// source.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY);")?;
+    /// let target = rusqlite::Connection::open_in_memory()?;
+    /// target.execute_batch("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);")?;
+    ///
+    /// assert_eq!(
+    ///     vec![
+    ///         "BEGIN TRANSACTION;",
+    ///         "ALTER TABLE users ADD COLUMN name TEXT;",
+    ///         "COMMIT;",
+    ///     ],
+    ///     source.schema()?.migration(&target.schema()?),
+    /// );
+    /// # Ok::<(), dumpqlite::Error>(())
+    /// ```
+    pub fn migration(&self, target: &Schema) -> Vec<String> {
+        let source_tables = real_tables(&self.tables);
+        let target_tables = real_tables(&target.tables);
+        // Every name in use, to pick the name of the new tables of rebuilds.
+        let mut names = self
+            .tables
+            .iter()
+            .chain(&target.tables)
+            .map(|table| table.name.to_ascii_lowercase())
+            .collect::<HashSet<_>>();
+
+        let mut dropped = Vec::new();
+        let mut created = Vec::new();
+        let mut changed = Vec::new();
+        for table in &source_tables {
+            if find(&target_tables, &table.name).is_none() {
+                dropped.push(*table);
+            }
+        }
+        for table in &target_tables {
+            match find(&source_tables, &table.name) {
+                None => created.push(*table),
+                Some(source) if source.sql != table.sql => {
+                    let change = match is_virtual(table) || is_virtual(source) {
+                        true => Change::Recreate,
+                        false => alter(source, table).unwrap_or(Change::Rebuild),
+                    };
+                    changed.push((source, *table, change));
+                }
+                Some(_) => {}
+            }
+        }
+        let rebuilt = changed
+            .iter()
+            .filter(|(_, _, change)| matches!(change, Change::Rebuild | Change::Recreate))
+            .map(|(_, table, _)| table.name.to_ascii_lowercase())
+            .collect::<HashSet<_>>();
+        let is_rebuilt = |name: &str| rebuilt.contains(&name.to_ascii_lowercase());
+        let is_dropped = |name: &str| {
+            dropped
+                .iter()
+                .any(|table| table.name.eq_ignore_ascii_case(name))
+        };
+        // Renaming a table checks every view and trigger, which fail while the table they
+        // read is missing, so all of them are created again around rebuilds.
+        let rebuild = !rebuilt.is_empty();
+        let dropped_views = self
+            .views
+            .iter()
+            .filter(|view| rebuild || !target.views.iter().any(|other| other.sql == view.sql))
+            .map(|view| view.name.to_ascii_lowercase())
+            .collect::<HashSet<_>>();
+        // Triggers go with the view they fire on, so they are created again with it.
+        let is_changed = |trigger: &Trigger, others: &[Trigger]| {
+            rebuild
+                || !others.iter().any(|other| other.sql == trigger.sql)
+                || dropped_views.contains(&trigger.table.to_ascii_lowercase())
+        };
+
+        let mut statements = Vec::new();
+
+        for trigger in &self.triggers {
+            if is_changed(trigger, &target.triggers) && !is_dropped(&trigger.table) {
+                statements.push(format!(
+                    "DROP TRIGGER {};",
+                    quote::identifier(&trigger.name)
+                ));
+            }
+        }
+        for view in &self.views {
+            if dropped_views.contains(&view.name.to_ascii_lowercase()) {
+                statements.push(format!("DROP VIEW {};", quote::identifier(&view.name)));
+            }
+        }
+        for table in &source_tables {
+            if is_dropped(&table.name) || is_rebuilt(&table.name) {
+                continue;
+            }
+            let target_table = find(&target_tables, &table.name);
+            for index in table.indexes.iter().filter(|index| index.sql.is_some()) {
+                let kept = target_table.is_some_and(|target| {
+                    target
+                        .indexes
+                        .iter()
+                        .any(|other| other.name == index.name && other.sql == index.sql)
+                });
+                if !kept {
+                    statements.push(format!("DROP INDEX {};", quote::identifier(&index.name)));
+                }
+            }
+        }
+        for table in &dropped {
+            statements.push(format!("DROP TABLE {};", quote::identifier(&table.name)));
+        }
+        for table in &created {
+            statements.push(format!("{};", table.sql));
+        }
+
+        for (source, table, change) in &changed {
+            match change {
+                Change::Alter(alters) => statements.extend(alters.iter().cloned()),
+                Change::Recreate => {
+                    statements.push(format!("DROP TABLE {};", quote::identifier(&source.name)));
+                    statements.push(format!("{};", table.sql));
+                }
+                Change::Rebuild => {
+                    let mut new_name = format!("new_{}", table.name);
+                    while !names.insert(new_name.to_ascii_lowercase()) {
+                        new_name.insert(0, '_');
+                    }
+                    statements.extend(rebuild_table(source, table, &new_name));
+                }
+            }
+        }
+
+        for table in &target_tables {
+            let source_table =
+                find(&source_tables, &table.name).filter(|_| !is_rebuilt(&table.name));
+            for index in &table.indexes {
+                let Some(sql) = &index.sql else {
+                    continue;
+                };
+                let kept = source_table.is_some_and(|source| {
+                    source
+                        .indexes
+                        .iter()
+                        .any(|other| other.name == index.name && other.sql == index.sql)
+                });
+                if !kept {
+                    statements.push(format!("{sql};"));
+                }
+            }
+        }
+        for view in &target.views {
+            let kept = self.views.iter().any(|other| other.sql == view.sql);
+            if rebuild || !kept {
+                statements.push(format!("{};", view.sql));
+            }
+        }
+        for trigger in &target.triggers {
+            if is_changed(trigger, &self.triggers) {
+                statements.push(format!("{};", trigger.sql));
+            }
+        }
+
+        if statements.is_empty() {
+            return statements;
+        }
+        let mut migration = Vec::new();
+        if rebuild {
+            migration.push("PRAGMA foreign_keys=OFF;".to_owned());
+        }
+        migration.push("BEGIN TRANSACTION;".to_owned());
+        migration.extend(statements);
+        if rebuild {
+            migration.push("PRAGMA foreign_key_check;".to_owned());
+        }
+        migration.push("COMMIT;".to_owned());
+        if rebuild {
+            migration.push("PRAGMA foreign_keys=ON;".to_owned());
+        }
+        migration
+    }
+}
+
+/// How a table is changed into its target.
+enum Change {
+    /// By the `ALTER TABLE` statements adding or dropping its columns.
+    Alter(Vec<String>),
+    /// By copying its rows into a new table that takes its place.
+    Rebuild,
+    /// By dropping and creating it again, for virtual tables.
+    Recreate,
+}
+
+/// The tables of `tables` other than the shadow tables of virtual tables, which are created
+/// and dropped along with them.
+fn real_tables(tables: &[Table]) -> Vec<&Table> {
+    tables
+        .iter()
+        .filter(|table| {
+            !tables.iter().any(|virtual_table| {
+                is_virtual(virtual_table)
+                    && crate::dump::is_shadow_table(&table.name, &table.sql, &virtual_table.name)
+            })
+        })
+        .collect()
+}
+
+/// Finds the table named `name` among `tables`, whatever the case of the name.
+fn find<'a>(tables: &[&'a Table], name: &str) -> Option<&'a Table> {
+    tables
+        .iter()
+        .find(|table| table.name.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+fn is_virtual(table: &Table) -> bool {
+    table.sql.starts_with("CREATE VIRTUAL TABLE")
+}
+
+/// Returns the `ALTER TABLE` statements changing `source` into `target`, if only columns
+/// were added, or only dropped, and SQLite can do it in place.
+fn alter(source: &Table, target: &Table) -> Option<Change> {
+    let source_definition = Definition::parse(&source.sql)?;
+    let target_definition = Definition::parse(&target.sql)?;
+    if !same_tokens(&source_definition.tail, &target_definition.tail) {
+        return None;
+    }
+    let table = quote::identifier(&target.name);
+
+    let (added, dropped) = match target_definition.items.len() > source_definition.items.len() {
+        true => (
+            removed(&target_definition.items, &source_definition.items)?,
+            Vec::new(),
+        ),
+        false => (
+            Vec::new(),
+            removed(&source_definition.items, &target_definition.items)?,
+        ),
+    };
+
+    // Added columns are appended, so they must follow the columns kept.
+    if let Some(first) = target_definition
+        .items
+        .iter()
+        .position(|item| added.contains(&item))
+    {
+        let kept_after = target_definition.items[first..]
+            .iter()
+            .any(|item| column_name(item).is_some() && !added.contains(&item));
+        if kept_after {
+            return None;
+        }
+    }
+
+    let mut statements = Vec::new();
+    for item in added {
+        let name = column_name(item)?;
+        let column = target.columns.iter().find(|column| column.name == name)?;
+        if !can_add(target, column) {
+            return None;
+        }
+        statements.push(format!(
+            "ALTER TABLE {table} ADD COLUMN {};",
+            Definition::text(&target.sql, item)
+        ));
+    }
+    for item in &dropped {
+        let name = column_name(item)?;
+        let others = source_definition
+            .items
+            .iter()
+            .filter(|other| !dropped.contains(other));
+        if !can_drop(source, &name, others) {
+            return None;
+        }
+        statements.push(format!(
+            "ALTER TABLE {table} DROP COLUMN {};",
+            quote::identifier(&name)
+        ));
+    }
+
+    // Statements differing only in their spacing, comments or case need no change at all.
+    Some(Change::Alter(statements))
+}
+
+/// Returns the items of `items` missing from `kept`, if the others are in the same order and
+/// only columns are missing.
+fn removed<'a, 'sql>(items: &'a [Item<'sql>], kept: &[Item<'sql>]) -> Option<Vec<&'a Item<'sql>>> {
+    let mut removed = Vec::new();
+    let mut kept = kept.iter().peekable();
+    for item in items {
+        match kept.peek() {
+            Some(other) if same_tokens(item, other) => {
+                kept.next();
+            }
+            _ => removed.push(item),
+        }
+    }
+
+    match kept.next() {
+        None if removed.iter().all(|item| column_name(item).is_some()) => Some(removed),
+        _ => None,
+    }
+}
+
+/// Whether SQLite can add `column` of `target` with `ALTER TABLE ADD COLUMN`.
+///
+/// The column must not be part of a key or an index, and must have a constant default value
+/// unless it can be NULL.
+fn can_add(target: &Table, column: &Column) -> bool {
+    let default = column.default.as_deref().map(str::trim);
+    let null_default = default.is_none_or(|default| default.eq_ignore_ascii_case("NULL"));
+    let constant_default = default.is_none_or(|default| {
+        !default.starts_with('(') && !default.to_ascii_uppercase().starts_with("CURRENT_")
+    });
+    let indexed = target.indexes.iter().any(|index| {
+        index
+            .columns
+            .iter()
+            .any(|indexed| indexed.name.as_deref() == Some(column.name.as_str()))
+    });
+    let referencing = target
+        .foreign_keys
+        .iter()
+        .any(|key| key.columns.contains(&column.name));
+
+    let blocked = column.generated
+        || target.primary_key.contains(&column.name)
+        || indexed
+        || !constant_default;
+    // A NOT NULL column needs a default value for the existing rows, while a column with a
+    // foreign key must default to NULL.
+    let default_allowed = match (column.not_null, referencing) {
+        (true, true) => false,
+        (true, false) => !null_default,
+        (false, true) => null_default,
+        (false, false) => true,
+    };
+
+    !blocked && default_allowed
+}
+
+/// Whether SQLite can drop the column `name` of `source` with `ALTER TABLE DROP COLUMN`,
+/// given the `others` items of its definition that are kept.
+///
+/// The column must not be part of a key or an index, nor be mentioned by other columns or
+/// constraints, such as generated columns or checks.
+fn can_drop<'a, 'sql: 'a>(
+    source: &Table,
+    name: &str,
+    mut others: impl Iterator<Item = &'a Item<'sql>>,
+) -> bool {
+    let indexed = source.indexes.iter().any(|index| {
+        index.partial
+            || index.columns.iter().any(|indexed| {
+                indexed
+                    .name
+                    .as_deref()
+                    .is_none_or(|indexed| indexed == name)
+            })
+    });
+    let referencing = source
+        .foreign_keys
+        .iter()
+        .any(|key| key.columns.iter().any(|column| column == name));
+    let mentioned = others.any(|item| {
+        item.iter()
+            .any(|(_, token)| unquote(token).eq_ignore_ascii_case(name))
+    });
+
+    !source.primary_key.iter().any(|column| column == name)
+        && !indexed
+        && !referencing
+        && !mentioned
+}
+
+/// Returns the statements rebuilding `source` into `target` through a new table named
+/// `new_name`, following the steps of the SQLite documentation.
+fn rebuild_table(source: &Table, target: &Table, new_name: &str) -> Vec<String> {
+    let Some(definition) = Definition::parse(&target.sql) else {
+        // A statement that cannot be parsed cannot be renamed, so the table is created again.
+        return vec![
+            format!("DROP TABLE {};", quote::identifier(&source.name)),
+            format!("{};", target.sql),
+        ];
+    };
+    let new_table = quote::identifier(new_name);
+    let old_table = quote::identifier(&source.name);
+
+    // The values of the columns kept are copied, along with the rowids of tables that have one
+    // under a name that is not a column.
+    let mut columns = target
+        .columns
+        .iter()
+        .filter(|column| !column.generated)
+        .filter(|column| {
+            source
+                .columns
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&column.name))
+        })
+        .map(|column| quote::identifier(&column.name).into_owned())
+        .collect::<Vec<_>>();
+    let without_rowid = |definition: &Definition<'_>| {
+        definition
+            .tail
+            .iter()
+            .any(|(_, token)| token.eq_ignore_ascii_case("WITHOUT"))
+    };
+    let rowid = ["rowid", "_rowid_", "oid"];
+    let shadowed = source.columns.iter().chain(&target.columns).any(|column| {
+        rowid
+            .iter()
+            .any(|name| column.name.eq_ignore_ascii_case(name))
+    });
+    let source_without_rowid = Definition::parse(&source.sql)
+        .as_ref()
+        .is_none_or(without_rowid);
+    // An `INTEGER PRIMARY KEY` column of the target already holds the rowid.
+    let rowid_alias = match target.primary_key.as_slice() {
+        [key] => target.columns.iter().any(|column| {
+            column.name == *key && column.declared_type.eq_ignore_ascii_case("INTEGER")
+        }),
+        _ => false,
+    };
+    if !without_rowid(&definition) && !source_without_rowid && !shadowed && !rowid_alias {
+        columns.insert(0, "rowid".to_owned());
+    }
+    let columns = columns.join(", ");
+
+    vec![
+        format!(
+            "CREATE TABLE {new_table}{};",
+            &target.sql[definition.open..]
+        ),
+        format!("INSERT INTO {new_table} ({columns}) SELECT {columns} FROM {old_table};"),
+        format!("DROP TABLE {old_table};"),
+        format!(
+            "ALTER TABLE {new_table} RENAME TO {};",
+            quote::identifier(&target.name)
+        ),
+    ]
+}
+
+/// A token of an SQL statement and its byte offset.
+pub(crate) type Token<'sql> = (usize, &'sql str);
+
+/// A column or constraint of a `CREATE TABLE` statement, as its tokens.
+type Item<'sql> = Vec<Token<'sql>>;
+
+/// A `CREATE TABLE` statement split into its column and constraint definitions.
+struct Definition<'sql> {
+    /// The byte offset of the parenthesis opening the definitions.
+    open: usize,
+    items: Vec<Item<'sql>>,
+    /// The table options following the definitions, such as `WITHOUT ROWID`.
+    tail: Vec<Token<'sql>>,
+}
+
+impl<'sql> Definition<'sql> {
+    fn parse(sql: &'sql str) -> Option<Self> {
+        let tokens = tokenize(sql)?;
+        let open = tokens.iter().position(|(_, token)| *token == "(")?;
+
+        let mut items = vec![Vec::new()];
+        let mut depth = 0;
+        for (i, &(offset, token)) in tokens.iter().enumerate().skip(open + 1) {
+            match token {
+                "(" => depth += 1,
+                ")" if depth == 0 => {
+                    return Some(Self {
+                        open: tokens[open].0,
+                        items,
+                        tail: tokens[i + 1..].to_vec(),
+                    });
+                }
+                ")" => depth -= 1,
+                "," if depth == 0 => {
+                    items.push(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+            items.last_mut()?.push((offset, token));
+        }
+
+        None
+    }
+
+    /// The text of `item` in `sql`, from its first token to its last.
+    fn text(sql: &'sql str, item: &Item<'sql>) -> &'sql str {
+        match (item.first(), item.last()) {
+            (Some((start, _)), Some((end, token))) => &sql[*start..end + token.len()],
+            _ => "",
+        }
+    }
+}
+
+/// The name of the column defined by `item`, or `None` for a table constraint.
+fn column_name(item: &Item<'_>) -> Option<String> {
+    const CONSTRAINTS: [&str; 5] = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+    let (_, first) = item.first()?;
+    if CONSTRAINTS
+        .iter()
+        .any(|keyword| first.eq_ignore_ascii_case(keyword))
+    {
+        return None;
+    }
+
+    Some(unquote(first))
+}
+
+/// Removes the quotes around an identifier, if any.
+pub(crate) fn unquote(token: &str) -> String {
+    match token.chars().next() {
+        Some(quote @ ('"' | '`' | '\'')) if token.len() >= 2 => {
+            let doubled = format!("{quote}{quote}");
+            token[1..token.len() - 1].replace(&doubled, &quote.to_string())
+        }
+        Some('[') if token.len() >= 2 => token[1..token.len() - 1].to_owned(),
+        _ => token.to_owned(),
+    }
+}
+
+/// Whether two lists of tokens are the same, ignoring the case of keywords and bare names.
+fn same_tokens(a: &[Token<'_>], b: &[Token<'_>]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|((_, a), (_, b))| {
+            let quoted = a.starts_with(['"', '\'', '`', '[']);
+            match quoted {
+                true => a == b,
+                false => a.eq_ignore_ascii_case(b),
+            }
+        })
+}
+
+/// Splits `sql` into its tokens, leaving out whitespace and comments, or returns `None` for
+/// an unterminated string or comment.
+pub(crate) fn tokenize(sql: &str) -> Option<Vec<Token<'_>>> {
+    let bytes = sql.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80;
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = sql[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += sql[i + 2..].find("*/")? + 4;
+                continue;
+            }
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                loop {
+                    i += sql[i..].find(char::from(quote))? + 1;
+                    // A doubled quote stands for itself.
+                    if bytes.get(i) != Some(&quote) {
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            b'[' => i += sql[i..].find(']')? + 1,
+            b if is_word(b) => {
+                while i < bytes.len() && is_word(bytes[i]) {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+        tokens.push((start, &sql[start..i]));
+    }
+
+    Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Definition};
+
+    #[test]
+    fn test_definition() {
+        let sql = r#"CREATE TABLE "t" (a INT DEFAULT (1 + 2), "b, c" TEXT -- note
+, CHECK (a > 0) /* done */) WITHOUT ROWID"#;
+        let definition = Definition::parse(sql).unwrap();
+
+        let items = definition
+            .items
+            .iter()
+            .map(|item| Definition::text(sql, item))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["a INT DEFAULT (1 + 2)", "\"b, c\" TEXT", "CHECK (a > 0)"],
+            items
+        );
+        assert_eq!(
+            vec!["WITHOUT", "ROWID"],
+            definition
+                .tail
+                .iter()
+                .map(|(_, token)| *token)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(17, definition.open);
+
+        assert!(tokenize("CREATE TABLE 't").is_none());
+    }
+}
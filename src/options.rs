@@ -0,0 +1,1060 @@
+use std::{fmt, sync::Arc};
+
+use rusqlite::types::ValueRef;
+
+use crate::{warning::Warnings, CancellationToken, Checkpoint, Dialect, DumpMetrics, DumpProgress};
+
+/// The output format of a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DumpFormat {
+    /// SQL statements, as produced by the SQLite CLI's `.dump` command.
+    #[default]
+    Sql,
+    /// One RFC 4180 CSV block per table, each introduced by a record holding the table name.
+    Csv,
+    /// A JSON object mapping each table name to an array of row objects.
+    Json,
+    /// One GitHub-flavored Markdown table per table, under a heading with its name.
+    Markdown,
+    /// A standalone HTML document with one `<table>` per table.
+    Html,
+    /// An XML document with a `<table>` element per table and a `<row>` element per row.
+    Xml,
+}
+
+/// How the generated `INSERT` statements handle rows that violate a uniqueness constraint,
+/// for loading a dump into a database that already holds some of its rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+    /// Plain `INSERT`, which aborts the statement.
+    #[default]
+    Abort,
+    /// `INSERT OR IGNORE`, which keeps the existing row.
+    Ignore,
+    /// `INSERT OR REPLACE`, which replaces the existing row.
+    Replace,
+}
+
+/// How the SQL output writes TEXT values that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidUtf8 {
+    /// Replaces the invalid bytes with U+FFFD, losing them.
+    #[default]
+    Replace,
+    /// Fails the dump, or skips the row if the dump is not [`strict`](DumpOptions::strict).
+    Error,
+    /// Writes the bytes as they are, as a `CAST(X'..' AS TEXT)` expression.
+    Cast,
+}
+
+/// What is written in place of the BLOB values larger than [`DumpOptions::max_blob_size`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OversizedBlob {
+    /// Writes NULL, with a warning.
+    Null,
+    /// Writes a TEXT placeholder such as `'[BLOB of 1048576 bytes]'`, with a warning.
+    Placeholder,
+    /// Writes each value to a file of its own inside the directory, creating it if needed,
+    /// and the name of the file as TEXT.
+    ///
+    /// The files are listed in `manifest.csv` inside the directory, along with the table and
+    /// column of their value and its size, which each dump writes anew. Files are named after
+    /// the table, the column and the rowid of their value, or the index of its row for a table
+    /// without rowid.
+    External(std::path::PathBuf),
+}
+
+/// How the JSON and CSV formats write BLOB values as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlobEncoding {
+    /// The standard, padded base64 alphabet of RFC 4648.
+    #[default]
+    Base64,
+    /// Lowercase hex digits.
+    Hex,
+}
+
+impl BlobEncoding {
+    /// The name of the encoding, as recorded in the output.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            BlobEncoding::Base64 => "base64",
+            BlobEncoding::Hex => "hex",
+        }
+    }
+
+    pub(crate) fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BlobEncoding::Base64 => crate::base64::encode(bytes),
+            BlobEncoding::Hex => crate::dump::hex(bytes),
+        }
+    }
+}
+
+/// How the output of a dump is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Compression {
+    /// The output is written as it is.
+    #[default]
+    None,
+    /// The output is streamed through a gzip encoder with the given level, from 0 to 9.
+    #[cfg(feature = "gzip")]
+    Gzip(u32),
+    /// The output is streamed through a zstd encoder with the given level, from 1 to 22,
+    /// or 0 for zstd's default.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+/// How the output of a dump is encrypted, after any [`Compression`], so that it never sits
+/// on disk in plain text. Encrypted dumps are read back with [`decrypt`](crate::decrypt).
+#[derive(Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Encryption {
+    /// The output is written as it is.
+    #[default]
+    None,
+    /// The output is written as an age file encrypted with a passphrase, which takes a second
+    /// or so to derive its key.
+    #[cfg(feature = "age")]
+    Passphrase(String),
+    /// The output is written as an age file encrypted to each of the age recipients given,
+    /// such as `age1pgs460cefrkf84e2spx65t0t2xs5lgnyp0075xwdhzpsfu3ykplqa0nvrt`.
+    #[cfg(feature = "age")]
+    Recipients(Vec<String>),
+}
+
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encryption::None => f.write_str("None"),
+            // The passphrase is kept out of logs.
+            #[cfg(feature = "age")]
+            Encryption::Passphrase(_) => f.write_str("Passphrase(..)"),
+            #[cfg(feature = "age")]
+            Encryption::Recipients(recipients) => {
+                f.debug_tuple("Recipients").field(recipients).finish()
+            }
+        }
+    }
+}
+
+/// How the values of a redacted column are replaced, see [`DumpOptions::redact`].
+#[derive(Clone)]
+pub enum Redaction {
+    /// Replaces every value with NULL.
+    Null,
+    /// Replaces every value with the given constant.
+    Constant(rusqlite::types::Value),
+    /// Replaces every value with the result of calling the function on it.
+    Custom(Arc<dyn Fn(ValueRef<'_>) -> rusqlite::types::Value + Send + Sync>),
+}
+
+impl Redaction {
+    /// Replaces every value with the result of calling `f` on it.
+    pub fn with<F>(f: F) -> Self
+    where
+        F: Fn(ValueRef<'_>) -> rusqlite::types::Value + Send + Sync + 'static,
+    {
+        Redaction::Custom(Arc::new(f))
+    }
+
+    pub(crate) fn apply(&self, value: ValueRef<'_>) -> rusqlite::types::Value {
+        match self {
+            Redaction::Null => rusqlite::types::Value::Null,
+            Redaction::Constant(constant) => constant.clone(),
+            Redaction::Custom(f) => f(value),
+        }
+    }
+}
+
+impl fmt::Debug for Redaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Redaction::Null => f.write_str("Null"),
+            Redaction::Constant(constant) => f.debug_tuple("Constant").field(constant).finish(),
+            Redaction::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// A function rewriting the values of a dump, see [`DumpOptions::transform`].
+#[derive(Clone)]
+pub(crate) struct Transform(pub(crate) Arc<TransformFn>);
+
+type TransformFn = dyn Fn(&str, &str, ValueRef<'_>) -> Option<rusqlite::types::Value> + Send + Sync;
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Transform(..)")
+    }
+}
+
+/// A callback receiving the progress of a dump, see [`DumpOptions::on_progress`].
+#[derive(Clone)]
+pub(crate) struct OnProgress(pub(crate) Arc<dyn Fn(&DumpProgress<'_>) + Send + Sync>);
+
+impl fmt::Debug for OnProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnProgress(..)")
+    }
+}
+
+/// A callback receiving the metrics of a dump, see [`DumpOptions::on_metrics`].
+#[derive(Clone)]
+pub(crate) struct OnMetrics(pub(crate) Arc<dyn Fn(&DumpMetrics<'_>) + Send + Sync>);
+
+impl fmt::Debug for OnMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnMetrics(..)")
+    }
+}
+
+/// Options controlling what [`ConnectionExt::dump_with`](crate::ConnectionExt::dump_with) emits.
+///
+/// The defaults reproduce the output of the SQLite CLI's `.dump` command.
+///
+/// ```
+/// use dumpqlite::DumpOptions;
+///
+/// let options = DumpOptions::new().data_only(true).transaction(false);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DumpOptions {
+    pub(crate) format: DumpFormat,
+    pub(crate) data_only: bool,
+    pub(crate) schema_only: bool,
+    pub(crate) indexes: bool,
+    pub(crate) triggers: bool,
+    pub(crate) transaction: bool,
+    pub(crate) commit_every: Option<usize>,
+    pub(crate) transaction_per_table: bool,
+    pub(crate) snapshot: bool,
+    pub(crate) verify_source: bool,
+    pub(crate) backup_to_memory: bool,
+    pub(crate) strict: bool,
+    pub(crate) deterministic: bool,
+    pub(crate) schema: String,
+    pub(crate) header_pragmas: bool,
+    pub(crate) metadata_header: bool,
+    pub(crate) clean: bool,
+    pub(crate) if_not_exists: bool,
+    pub(crate) pretty_schema: bool,
+    pub(crate) crlf: bool,
+    pub(crate) statement_terminator: String,
+    pub(crate) blank_lines: bool,
+    pub(crate) analyze: bool,
+    pub(crate) checksum: bool,
+    pub(crate) preserve_rowids: bool,
+    pub(crate) include_tables: Option<Vec<String>>,
+    pub(crate) exclude_tables: Vec<String>,
+    pub(crate) where_clauses: Vec<(String, String)>,
+    pub(crate) since: Option<Checkpoint>,
+    pub(crate) incremental_columns: Vec<(String, String)>,
+    /// Whether the dump is one of the dumps of [`ConnectionExt::dump_per_table`], or of the
+    /// files of an archive, which must not reset the state of the other tables.
+    ///
+    /// [`ConnectionExt::dump_per_table`]: crate::ConnectionExt::dump_per_table
+    pub(crate) split: bool,
+    pub(crate) sample_fraction: Option<f64>,
+    pub(crate) max_rows_per_table: Option<usize>,
+    pub(crate) sample_seed: u32,
+    pub(crate) follow_foreign_keys: bool,
+    pub(crate) redactions: Vec<(String, Redaction)>,
+    pub(crate) transform: Option<Transform>,
+    pub(crate) on_progress: Option<OnProgress>,
+    pub(crate) on_metrics: Option<OnMetrics>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    pub(crate) warnings: Option<Warnings>,
+    pub(crate) compression: Compression,
+    pub(crate) encryption: Encryption,
+    pub(crate) buffer_size: usize,
+    pub(crate) rows_per_insert: usize,
+    pub(crate) insert_column_names: bool,
+    pub(crate) on_conflict: OnConflict,
+    pub(crate) invalid_utf8: InvalidUtf8,
+    pub(crate) newlines: bool,
+    pub(crate) max_blob_size: Option<(usize, OversizedBlob)>,
+    pub(crate) blob_encoding: BlobEncoding,
+    pub(crate) dialect: Dialect,
+    pub(crate) batch_size: usize,
+    pub(crate) max_display_rows: Option<usize>,
+}
+
+impl Default for DumpOptions {
+    fn default() -> Self {
+        Self {
+            format: DumpFormat::Sql,
+            data_only: false,
+            schema_only: false,
+            indexes: true,
+            triggers: true,
+            transaction: true,
+            commit_every: None,
+            transaction_per_table: false,
+            snapshot: false,
+            verify_source: false,
+            backup_to_memory: false,
+            strict: true,
+            deterministic: false,
+            schema: "main".to_owned(),
+            header_pragmas: false,
+            metadata_header: false,
+            clean: false,
+            if_not_exists: false,
+            pretty_schema: false,
+            crlf: false,
+            statement_terminator: ";".to_owned(),
+            blank_lines: false,
+            analyze: false,
+            checksum: false,
+            preserve_rowids: false,
+            include_tables: None,
+            exclude_tables: Vec::new(),
+            where_clauses: Vec::new(),
+            since: None,
+            incremental_columns: Vec::new(),
+            split: false,
+            sample_fraction: None,
+            max_rows_per_table: None,
+            sample_seed: 0,
+            follow_foreign_keys: false,
+            redactions: Vec::new(),
+            transform: None,
+            on_progress: None,
+            on_metrics: None,
+            cancellation: None,
+            warnings: None,
+            compression: Compression::None,
+            encryption: Encryption::None,
+            buffer_size: 64 * 1024,
+            rows_per_insert: 1,
+            insert_column_names: false,
+            on_conflict: OnConflict::Abort,
+            invalid_utf8: InvalidUtf8::Replace,
+            newlines: false,
+            max_blob_size: None,
+            blob_encoding: BlobEncoding::Base64,
+            dialect: Dialect::Sqlite,
+            batch_size: 8192,
+            max_display_rows: None,
+        }
+    }
+}
+
+impl DumpOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Selects the output format. Defaults to [`DumpFormat::Sql`].
+    pub fn format(mut self, format: DumpFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Emits only the `INSERT` statements, like `.dump --data-only`.
+    ///
+    /// Enabling this disables [`schema_only`](Self::schema_only).
+    pub fn data_only(mut self, data_only: bool) -> Self {
+        self.data_only = data_only;
+        if data_only {
+            self.schema_only = false;
+        }
+        self
+    }
+
+    /// Emits only the `CREATE` statements, like `.schema`.
+    ///
+    /// Combine with `transaction(false)` to get nothing but the schema.
+    /// Enabling this disables [`data_only`](Self::data_only).
+    pub fn schema_only(mut self, schema_only: bool) -> Self {
+        self.schema_only = schema_only;
+        if schema_only {
+            self.data_only = false;
+        }
+        self
+    }
+
+    /// Emits the `CREATE INDEX` statements of the dumped tables. Enabled by default.
+    ///
+    /// The indexes of a restored table are then left to be created separately, such as once its
+    /// rows are in.
+    pub fn indexes(mut self, indexes: bool) -> Self {
+        self.indexes = indexes;
+        self
+    }
+
+    /// Emits the `CREATE TRIGGER` statements of the dumped tables. Enabled by default.
+    pub fn triggers(mut self, triggers: bool) -> Self {
+        self.triggers = triggers;
+        self
+    }
+
+    /// Wraps the dump in `BEGIN TRANSACTION;` ... `COMMIT;`. Enabled by default.
+    pub fn transaction(mut self, transaction: bool) -> Self {
+        self.transaction = transaction;
+        self
+    }
+
+    /// Commits the transaction of the SQL output and opens a new one once every `rows`
+    /// inserted rows, so that a restore neither holds the whole dump in one transaction nor
+    /// loses the rows already committed when it fails. Disabled by default.
+    ///
+    /// Transactions are only split between `INSERT` statements, and each one opened by the
+    /// SQLite dialect sets `PRAGMA defer_foreign_keys`, which every commit turns off, so that
+    /// foreign keys are still only checked when it commits. Has no effect without
+    /// [`transaction`](Self::transaction).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows` is zero.
+    pub fn commit_every(mut self, rows: usize) -> Self {
+        assert!(rows > 0, "rows per transaction must be positive");
+        self.commit_every = Some(rows);
+        self
+    }
+
+    /// Wraps the rows of each table in a transaction of their own, preceded by a
+    /// `-- Data for table` comment, instead of wrapping the whole dump in a single one.
+    /// Disabled by default.
+    ///
+    /// A restore that fails then keeps the tables already committed and can resume from the
+    /// table that failed, while the comments show how far it got. The schema is created
+    /// outside of any transaction. Has no effect without [`transaction`](Self::transaction).
+    pub fn transaction_per_table(mut self, transaction_per_table: bool) -> Self {
+        self.transaction_per_table = transaction_per_table;
+        self
+    }
+
+    /// Aborts the dump when a table or row fails to read. Enabled by default.
+    ///
+    /// When disabled, tables that cannot be read and rows whose values cannot be read are left
+    /// out of the dump instead, and the dump goes on with the next table or row. The Arrow and
+    /// Parquet outputs still abort on rows that fail to read.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Dumps tables in the order of their names and rows in the order of their rowid, or of
+    /// their primary key in tables without one.
+    ///
+    /// Two dumps of the same content are then byte-identical no matter the order the tables
+    /// were created in or how SQLite chooses to scan them, which makes them fit for diffing.
+    /// Disabled by default.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Keeps the rowid of every row in the SQL output, like `.dump --preserve-rowids`.
+    ///
+    /// The rowid of a table without an `INTEGER PRIMARY KEY` column is otherwise renumbered on
+    /// restore. When enabled, it is read and inserted as an extra first column, and the column
+    /// names are listed in the `INSERT` statements of such tables. Tables without a rowid, such
+    /// as `WITHOUT ROWID` tables, are dumped as usual.
+    pub fn preserve_rowids(mut self, preserve_rowids: bool) -> Self {
+        self.preserve_rowids = preserve_rowids;
+        self
+    }
+
+    /// Dumps the schema named `schema`, such as `temp` or the name of an attached database.
+    /// Defaults to `main`.
+    ///
+    /// The dump restores the tables of that schema into the database it is restored into,
+    /// under their own names.
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.schema = schema.into();
+        self
+    }
+
+    /// Emits `PRAGMA user_version` and `PRAGMA application_id` with the values of the dumped
+    /// database at the top of the SQL output, so that the restored database keeps its migration
+    /// state and file type. Disabled by default, like the SQLite CLI.
+    pub fn header_pragmas(mut self, header_pragmas: bool) -> Self {
+        self.header_pragmas = header_pragmas;
+        self
+    }
+
+    /// Starts the SQL output with comment lines recording how the dump was made: the version
+    /// of dumpqlite, the time it was generated, the version of SQLite, the page size and text
+    /// encoding of the dumped database, and the options shaping the SQL. Disabled by default.
+    ///
+    /// Each line is of the form `-- key: value`, with the options as a JSON object:
+    ///
+    /// ```sql
+    /// -- dumpqlite: 0.1.0
+    /// -- generated_at: 2024-05-01T12:00:00Z
+    /// -- sqlite_version: 3.45.1
+    /// -- page_size: 4096
+    /// -- encoding: UTF-8
+    /// -- options: {"dialect":"sqlite","schema":"main","data_only":false,...}
+    /// ```
+    ///
+    /// The time is left out of [`deterministic`](Self::deterministic) dumps.
+    pub fn metadata_header(mut self, metadata_header: bool) -> Self {
+        self.metadata_header = metadata_header;
+        self
+    }
+
+    /// Drops the tables, views, indexes and triggers of the dump with `DROP ... IF EXISTS`
+    /// before creating them, like `pg_dump --clean --if-exists`, so that the dump replaces
+    /// them when restored into an existing database. Disabled by default.
+    ///
+    /// The objects of the database that are not in the dump are left as they are.
+    pub fn clean(mut self, clean: bool) -> Self {
+        self.clean = clean;
+        self
+    }
+
+    /// Creates the tables, indexes, views and triggers of the dump with `IF NOT EXISTS`, so
+    /// that the schema of the dump restores into a database already holding part of it, such
+    /// as a test database seeded by migrations. Disabled by default.
+    ///
+    /// The rows of the dump are inserted either way; combine with
+    /// [`on_conflict`](Self::on_conflict) to keep those already there.
+    pub fn if_not_exists(mut self, if_not_exists: bool) -> Self {
+        self.if_not_exists = if_not_exists;
+        self
+    }
+
+    /// Reformats the statements creating tables, indexes, views and triggers, rather than
+    /// writing them with the whitespace they were created with, so that schema dumps diff
+    /// cleanly whoever wrote the schema. Disabled by default.
+    ///
+    /// Runs of whitespace become a single space, and the columns and constraints of a table,
+    /// like the statements of a trigger, go on an indented line each:
+    ///
+    /// ```sql
+    /// CREATE TABLE users (
+    ///     id INTEGER PRIMARY KEY,
+    ///     name TEXT NOT NULL
+    /// );
+    /// ```
+    ///
+    /// Statements holding comments are kept as they are, as are virtual tables.
+    pub fn pretty_schema(mut self, pretty_schema: bool) -> Self {
+        self.pretty_schema = pretty_schema;
+        self
+    }
+
+    /// Ends the SQL output with `ANALYZE sqlite_schema;` when it restores the statistics of the
+    /// query planner, so that the restoring connection loads them right away. Disabled by
+    /// default.
+    ///
+    /// Like the SQLite CLI, the rows of the `sqlite_stat1` and `sqlite_stat4` tables are always
+    /// dumped; they can be left out with [`exclude_tables`](Self::exclude_tables).
+    pub fn analyze(mut self, analyze: bool) -> Self {
+        self.analyze = analyze;
+        self
+    }
+
+    /// Ends the SQL output with a `-- sha256: ...` comment holding the SHA-256 hash of
+    /// everything before it, to check an archived dump with
+    /// [`verify_checksum`](crate::verify_checksum). Disabled by default.
+    ///
+    /// The hash is of the SQL as written, before any [`compression`](Self::compression) or
+    /// [`encryption`](Self::encryption). It is only written by the SQL format, and not by
+    /// [`dump_with_manifest`](crate::ConnectionExt::dump_with_manifest),
+    /// [`dump_in_parts`](crate::ConnectionExt::dump_in_parts) or
+    /// [`dump_statements`](crate::ConnectionExt::dump_statements), which do not write the
+    /// dump as a whole.
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Restricts the dump to the given tables. Can be called repeatedly to add more tables.
+    pub fn include_tables<I, S>(mut self, tables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include_tables
+            .get_or_insert_with(Vec::new)
+            .extend(tables.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leaves the given tables out of the dump, even if they were included.
+    pub fn exclude_tables<I, S>(mut self, tables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_tables
+            .extend(tables.into_iter().map(Into::into));
+        self
+    }
+
+    /// The number of rows added by each `INSERT` statement, as in
+    /// `INSERT INTO t VALUES(...),(...);`. Defaults to one row per statement, like the SQLite CLI.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows_per_insert` is zero.
+    pub fn rows_per_insert(mut self, rows_per_insert: usize) -> Self {
+        assert!(rows_per_insert > 0, "rows per insert must be positive");
+        self.rows_per_insert = rows_per_insert;
+        self
+    }
+
+    /// Lists the columns in every `INSERT` statement, as in `INSERT INTO t(a,b) VALUES(...);`,
+    /// so that the dump can be loaded into a table that has since gained columns.
+    pub fn insert_column_names(mut self, insert_column_names: bool) -> Self {
+        self.insert_column_names = insert_column_names;
+        self
+    }
+
+    /// Dumps only the rows of `table` matching `predicate`, an SQL expression such as
+    /// `created_at > '2024-01-01'`. Setting a predicate again for the same table replaces it.
+    ///
+    /// The predicate is inserted into the data queries verbatim, so it must come from a trusted
+    /// source.
+    pub fn where_clause(mut self, table: impl Into<String>, predicate: impl Into<String>) -> Self {
+        let table = table.into();
+        self.where_clauses
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(&table));
+        self.where_clauses.push((table, predicate.into()));
+        self
+    }
+
+    /// Dumps only the rows added since `checkpoint` was taken with
+    /// [`ConnectionExt::checkpoint_with`](crate::ConnectionExt::checkpoint_with), as a delta
+    /// script to append to the dumps before it.
+    ///
+    /// The rows of a table are those whose rowid, or incremental column, is greater than the
+    /// mark of the table. Rows updated or deleted since are not dumped, unless their
+    /// incremental column is a timestamp bumped by updates, in which case
+    /// [`OnConflict::Replace`] restores them over their older version. Tables without a mark,
+    /// such as those created since, are dumped whole. Combine with
+    /// [`data_only`](Self::data_only) so that the delta does not create the tables again.
+    pub fn since(mut self, checkpoint: Checkpoint) -> Self {
+        self.since = Some(checkpoint);
+        self
+    }
+
+    /// Marks the rows of `table` by `column`, such as an `updated_at` timestamp, in the
+    /// checkpoints taken for [`since`](Self::since), instead of by rowid. Setting a column
+    /// again for the same table replaces it.
+    pub fn incremental_column(
+        mut self,
+        table: impl Into<String>,
+        column: impl Into<String>,
+    ) -> Self {
+        let table = table.into();
+        self.incremental_columns
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(&table));
+        self.incremental_columns.push((table, column.into()));
+        self
+    }
+
+    /// Dumps a pseudo-random sample of about `fraction` of the rows of every table.
+    ///
+    /// Rows are picked by a hash of their rowid, so repeated dumps with the same
+    /// [`sample_seed`](Self::sample_seed) hold the same rows. Tables without a rowid, such as
+    /// `WITHOUT ROWID` tables, cannot be sampled and are dumped in full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1.
+    pub fn sample_fraction(mut self, fraction: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "sample fraction must be between 0 and 1"
+        );
+        self.sample_fraction = Some(fraction);
+        self
+    }
+
+    /// Dumps at most `max_rows` pseudo-random rows of every table, picked the same way as
+    /// [`sample_fraction`](Self::sample_fraction). Tables without a rowid are dumped in full.
+    pub fn max_rows_per_table(mut self, max_rows: usize) -> Self {
+        self.max_rows_per_table = Some(max_rows);
+        self
+    }
+
+    /// Seeds the choice of sampled rows, so that different seeds pick different rows.
+    /// Defaults to 0.
+    pub fn sample_seed(mut self, seed: u32) -> Self {
+        self.sample_seed = seed;
+        self
+    }
+
+    /// Also dumps every row referenced through a foreign key by a dumped row, so that a dump
+    /// narrowed by [`where_clause`](Self::where_clause) or sampling restores without violations
+    /// when `PRAGMA foreign_keys=ON`.
+    ///
+    /// Parent rows are only added to tables that are part of the dump and have a rowid.
+    pub fn follow_foreign_keys(mut self, follow_foreign_keys: bool) -> Self {
+        self.follow_foreign_keys = follow_foreign_keys;
+        self
+    }
+
+    /// Replaces the values of the columns matching `pattern` before they are written.
+    ///
+    /// The pattern has the form `table.column`, where either part may contain `*` wildcards,
+    /// as in `*.email` or `users.password_hash`. A pattern without a table, such as `email`,
+    /// matches the column in every table. Names are matched case-insensitively, and the first
+    /// matching rule applies.
+    ///
+    /// Replacements that do not fit the column type of a typed output, such as Parquet,
+    /// are written as NULL.
+    ///
+    /// ```
+    /// use dumpqlite::{DumpOptions, Redaction};
+    ///
+    /// let options = DumpOptions::new()
+    ///     .redact("users.password_hash", Redaction::Null)
+    ///     .redact("*.email", Redaction::Constant("redacted@example.com".to_owned().into()));
+    /// ```
+    pub fn redact(mut self, pattern: impl Into<String>, redaction: Redaction) -> Self {
+        self.redactions.push((pattern.into(), redaction));
+        self
+    }
+
+    /// Calls `f` with the table name, column name and value of every value before it is written.
+    ///
+    /// Returning `Some` replaces the value, while `None` keeps it. Redactions apply to the
+    /// transformed values.
+    ///
+    /// ```
+    /// use dumpqlite::DumpOptions;
+    /// use rusqlite::types::{Value, ValueRef};
+    ///
+    /// let options = DumpOptions::new().transform(|_, column, value| match value {
+    ///     ValueRef::Integer(cents) if column.ends_with("_cents") => Some(Value::Real(cents as f64 / 100.0)),
+    ///     _ => None,
+    /// });
+    /// ```
+    pub fn transform<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, &str, ValueRef<'_>) -> Option<rusqlite::types::Value> + Send + Sync + 'static,
+    {
+        self.transform = Some(Transform(Arc::new(f)));
+        self
+    }
+
+    /// Calls `f` when a table starts being dumped and after every row written,
+    /// for example to drive a progress bar. `f` should return quickly, as it runs on the
+    /// thread performing the dump.
+    ///
+    /// Progress is reported by [`dump_with`](crate::ConnectionExt::dump_with) and, without
+    /// bytes, by `dump_parquet`.
+    pub fn on_progress<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&DumpProgress<'_>) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(OnProgress(Arc::new(f)));
+        self
+    }
+
+    /// Calls `f` each time the rows of a table are written, with how long they took and how
+    /// fast the dump goes, for example to export to a monitoring system.
+    ///
+    /// Metrics are reported by [`dump_with`](crate::ConnectionExt::dump_with) and, without
+    /// bytes, by `dump_parquet`. Bytes are counted as written, after any compression.
+    pub fn on_metrics<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&DumpMetrics<'_>) + Send + Sync + 'static,
+    {
+        self.on_metrics = Some(OnMetrics(Arc::new(f)));
+        self
+    }
+
+    /// Aborts the dump with [`Error::Cancelled`](crate::Error::Cancelled) once `token` is
+    /// cancelled.
+    ///
+    /// ```
+    /// use dumpqlite::{CancellationToken, ConnectionExt, DumpOptions};
+    ///
+    /// let conn = rusqlite::Connection::open_in_memory()?;
+    /// let token = CancellationToken::for_connection(&conn);
+    /// let options = DumpOptions::new().cancellation(token.clone());
+    ///
+    /// token.cancel();
+    /// assert!(matches!(
+    ///     conn.dump_with(&options, &mut std::io::sink()),
+    ///     Err(dumpqlite::Error::Cancelled)
+    /// ));
+    /// # Ok::<(), dumpqlite::Error>(())
+    /// ```
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Compresses the output as it is produced. Defaults to [`Compression::None`].
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Encrypts the output as it is produced, after compressing it. Defaults to
+    /// [`Encryption::None`].
+    pub fn encryption(mut self, encryption: Encryption) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// The number of bytes buffered before they are passed to the writer, so that the many
+    /// small writes of a dump reach it coalesced, whatever the writer. Defaults to 64 KiB.
+    ///
+    /// A size of zero passes every write straight through, for writers that already buffer.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Selects the conflict clause of the `INSERT` statements. Defaults to [`OnConflict::Abort`].
+    pub fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+        self.on_conflict = on_conflict;
+        self
+    }
+
+    /// Chooses how TEXT values that are not valid UTF-8 are written.
+    /// Defaults to [`InvalidUtf8::Replace`].
+    ///
+    /// The non-SQL formats write such values with replaced bytes, unless the policy is
+    /// [`InvalidUtf8::Error`].
+    pub fn invalid_utf8(mut self, invalid_utf8: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+
+    /// Writes the newlines and carriage returns of TEXT values as they are, like
+    /// `.dump --newlines`.
+    ///
+    /// By default, like the SQLite CLI, values holding them are written as `replace()`
+    /// expressions putting them back, to keep every statement on a single line.
+    pub fn newlines(mut self, newlines: bool) -> Self {
+        self.newlines = newlines;
+        self
+    }
+
+    /// Ends the lines of the SQL output with `\r\n` rather than `\n`, for Windows tools.
+    ///
+    /// Only the line breaks between statements change: those inside a statement, such as in a
+    /// `CREATE` statement written over several lines, are kept as they are stored.
+    pub fn crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+
+    /// Ends the statements of the SQL output with `terminator` in place of their semicolon,
+    /// such as `;;` or `\n/` for loaders splitting statements their own way. Defaults to `;`.
+    ///
+    /// The semicolons inside a statement, such as those ending the statements of a trigger,
+    /// are kept.
+    pub fn statement_terminator(mut self, terminator: impl Into<String>) -> Self {
+        self.statement_terminator = terminator.into();
+        self
+    }
+
+    /// Writes a blank line before the statements of each table in the SQL output, to read or
+    /// diff it table by table. Disabled by default.
+    pub fn blank_lines(mut self, blank_lines: bool) -> Self {
+        self.blank_lines = blank_lines;
+        self
+    }
+
+    /// Leaves the BLOB values larger than `max_blob_size` bytes out of the dump, writing
+    /// what `policy` selects in their place, so that dumps read by people or diffed are not
+    /// dominated by binary data. Unlimited by default.
+    ///
+    /// The warnings are returned by
+    /// [`dump_with_warnings`](crate::ConnectionExt::dump_with_warnings). The values left out
+    /// of a table with a rowid are never read whole by
+    /// [`dump_with`](crate::ConnectionExt::dump_with). Arrow record batches and Parquet files
+    /// hold every value as it is.
+    pub fn max_blob_size(mut self, max_blob_size: usize, policy: OversizedBlob) -> Self {
+        self.max_blob_size = Some((max_blob_size, policy));
+        self
+    }
+
+    /// Chooses how the JSON and CSV formats write BLOB values, which they have no type for.
+    /// Defaults to [`BlobEncoding::Base64`].
+    ///
+    /// The encoding is recorded at the start of the output, under the name `sqlite_metadata`
+    /// that no table can have, so that importers know how to decode the values.
+    pub fn blob_encoding(mut self, blob_encoding: BlobEncoding) -> Self {
+        self.blob_encoding = blob_encoding;
+        self
+    }
+
+    /// Selects the SQL dialect of the SQL format. Defaults to [`Dialect::Sqlite`].
+    ///
+    /// Other dialects ignore [`on_conflict`](Self::on_conflict),
+    /// [`preserve_rowids`](Self::preserve_rowids), [`header_pragmas`](Self::header_pragmas),
+    /// [`clean`](Self::clean), [`if_not_exists`](Self::if_not_exists),
+    /// [`pretty_schema`](Self::pretty_schema), [`analyze`](Self::analyze) and
+    /// [`newlines`](Self::newlines), and write TEXT values that are not valid UTF-8 with
+    /// replaced bytes unless the policy is [`InvalidUtf8::Error`].
+    /// The other formats ignore the dialect.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// The number of rows grouped together by batched outputs,
+    /// such as Arrow record batches or the row groups of Parquet files. Defaults to 8192.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `batch_size` is zero.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Caps the number of rows rendered per table by the formats meant to be read by people,
+    /// such as [`DumpFormat::Markdown`] and [`DumpFormat::Html`]. Unlimited by default.
+    pub fn max_display_rows(mut self, max_display_rows: usize) -> Self {
+        self.max_display_rows = Some(max_display_rows);
+        self
+    }
+
+    /// Reads the whole dump within a single read transaction, so that it holds the state of the
+    /// database at one point in time even while other connections write to it. Disabled by
+    /// default, in which case each table is read at the time it is dumped.
+    ///
+    /// The transaction lasts until the dump ends, or until the last statement of
+    /// [`dump_statements_with`](crate::ConnectionExt::dump_statements_with) is read. In WAL
+    /// mode it does not block writers, while in rollback journal mode writers wait for it to end.
+    /// A connection already inside a transaction is dumped as it is, and Arrow record batches
+    /// are left for the caller to wrap in a transaction.
+    pub fn snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /// Runs `PRAGMA integrity_check` on the database before dumping it, and fails with
+    /// [`Error::Corrupt`](crate::Error::Corrupt) if it finds a problem, rather than writing a
+    /// backup that silently misses the rows SQLite cannot read. Disabled by default.
+    ///
+    /// The check reads the whole database, which takes about as long as the dump itself. It
+    /// runs within the [`snapshot`](Self::snapshot) if any, so that it checks the state of
+    /// the database that is dumped.
+    pub fn verify_source(mut self, verify_source: bool) -> Self {
+        self.verify_source = verify_source;
+        self
+    }
+
+    /// Copies the database into memory with SQLite's online backup API before dumping the
+    /// copy, so that readers of a busy database file only lock it while the copy is taken.
+    /// Disabled by default.
+    ///
+    /// The copy is taken a few pages at a time, and starts over when another connection
+    /// writes to the database in between, so that it holds a single point in time like a
+    /// [`snapshot`](Self::snapshot). Its progress is reported to the callback of
+    /// [`on_progress`](Self::on_progress). The whole database must fit in memory, and Arrow
+    /// record batches are still read from the database itself.
+    pub fn backup_to_memory(mut self, backup_to_memory: bool) -> Self {
+        self.backup_to_memory = backup_to_memory;
+        self
+    }
+
+    /// Whether the whole dump is wrapped in a single transaction.
+    pub(crate) fn single_transaction(&self) -> bool {
+        self.transaction && !self.transaction_per_table
+    }
+
+    /// The end of the lines of the SQL output, see [`crlf`](Self::crlf).
+    pub(crate) fn line_ending(&self) -> &'static str {
+        match self.crlf {
+            true => "\r\n",
+            false => "\n",
+        }
+    }
+
+    /// Qualifies `name` with the dumped schema, both quoted as identifiers.
+    pub(crate) fn qualified(&self, name: &str) -> String {
+        format!(
+            "{}.{}",
+            crate::quote::identifier(&self.schema),
+            crate::quote::identifier(name)
+        )
+    }
+
+    pub(crate) fn redaction(&self, table: &str, column: &str) -> Option<&Redaction> {
+        self.redactions
+            .iter()
+            .find(|(pattern, _)| {
+                let (table_pattern, column_pattern) =
+                    pattern.rsplit_once('.').unwrap_or(("*", pattern));
+                glob_matches(table_pattern, table) && glob_matches(column_pattern, column)
+            })
+            .map(|(_, redaction)| redaction)
+    }
+
+    pub(crate) fn predicate(&self, table: &str) -> Option<&str> {
+        self.where_clauses
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(table))
+            .map(|(_, predicate)| predicate.as_str())
+    }
+
+    pub(crate) fn marked_column(&self, table: &str) -> Option<&str> {
+        self.incremental_columns
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(table))
+            .map(|(_, column)| column.as_str())
+    }
+
+    /// Table names are matched case-insensitively, as SQLite does.
+    pub(crate) fn includes_table(&self, name: &str) -> bool {
+        let matches = |table: &String| table.eq_ignore_ascii_case(name);
+
+        let included = match &self.include_tables {
+            Some(tables) => tables.iter().any(matches),
+            None => true,
+        };
+
+        included && !self.exclude_tables.iter().any(matches)
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters,
+/// ignoring ASCII case.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_glob_matches() {
+        assert!(super::glob_matches("*", "users"));
+        assert!(super::glob_matches("Users", "users"));
+        assert!(super::glob_matches("*_hash", "password_hash"));
+        assert!(super::glob_matches("user*", "users"));
+        assert!(super::glob_matches("a*b*c", "abbc"));
+        assert!(!super::glob_matches("a*b*c", "acb"));
+        assert!(!super::glob_matches("ab*ba", "aba"));
+        assert!(!super::glob_matches("user", "users"));
+    }
+}
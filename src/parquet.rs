@@ -0,0 +1,238 @@
+use std::{fs, path::Path, sync::Arc};
+
+use ::parquet::{
+    basic::{LogicalType, Repetition, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+
+use rusqlite::types::ValueRef;
+
+use crate::{affinity::Affinity, DumpOptions, Error, StatementKind};
+
+/// Values of one column, buffered until a row group is written.
+enum Column {
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    ByteArray(Vec<ByteArray>),
+}
+
+impl Column {
+    fn clear(&mut self) {
+        match self {
+            Column::Int64(values) => values.clear(),
+            Column::Double(values) => values.clear(),
+            Column::ByteArray(values) => values.clear(),
+        }
+    }
+}
+
+/// Maps a column affinity to its Parquet type and the SQL expression reading the column,
+/// which casts every value to that type.
+fn column_type(name: &str, affinity: Affinity) -> Result<(Type, String), crate::Error> {
+    let (physical_type, logical_type) = match affinity {
+        Affinity::Integer => (PhysicalType::INT64, None),
+        Affinity::Real | Affinity::Numeric => (PhysicalType::DOUBLE, None),
+        Affinity::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        Affinity::Blob => (PhysicalType::BYTE_ARRAY, None),
+    };
+
+    let field = Type::primitive_type_builder(name, physical_type)
+        .with_repetition(Repetition::OPTIONAL)
+        .with_logical_type(logical_type)
+        .build()?;
+
+    Ok((field, affinity.cast(&crate::quote::identifier(name))))
+}
+
+/// Writes every table to `{dir}/{table}.parquet`, one row group per `batch_size` rows.
+pub(crate) fn dump(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    dir: &Path,
+) -> Result<(), crate::Error> {
+    fs::create_dir_all(dir)?;
+
+    for table_name in crate::dump::data_tables(conn, options)? {
+        let column_types = crate::dump::column_types(conn, options, &table_name)?;
+        let rewriter = crate::dump::Rewriter::new(
+            options,
+            &table_name,
+            column_types.iter().map(|(name, _)| name),
+        );
+
+        let (fields, expressions): (Vec<_>, Vec<_>) = column_types
+            .iter()
+            .map(|(name, declared_type)| {
+                column_type(name, Affinity::from_declared_type(declared_type))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+
+        let mut columns = fields
+            .iter()
+            .map(|field| match field.get_physical_type() {
+                PhysicalType::INT64 => Column::Int64(Vec::new()),
+                PhysicalType::DOUBLE => Column::Double(Vec::new()),
+                _ => Column::ByteArray(Vec::new()),
+            })
+            .collect::<Vec<_>>();
+        let mut definition_levels = vec![Vec::new(); columns.len()];
+
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields.into_iter().map(Arc::new).collect())
+            .build()?;
+        let file = fs::File::create(dir.join(format!("{table_name}.parquet")))?;
+        let properties = WriterProperties::builder().build();
+        let mut writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))?;
+
+        let mut write_row_group = |columns: &mut [Column], definition_levels: &mut [Vec<i16>]| {
+            let mut row_group = writer.next_row_group()?;
+            for (column, levels) in columns.iter_mut().zip(definition_levels.iter_mut()) {
+                let Some(mut column_writer) = row_group.next_column()? else {
+                    break;
+                };
+                match column {
+                    Column::Int64(values) => column_writer.typed::<Int64Type>().write_batch(
+                        values,
+                        Some(levels),
+                        None,
+                    )?,
+                    Column::Double(values) => column_writer.typed::<DoubleType>().write_batch(
+                        values,
+                        Some(levels),
+                        None,
+                    )?,
+                    Column::ByteArray(values) => column_writer
+                        .typed::<ByteArrayType>()
+                        .write_batch(values, Some(levels), None)?,
+                };
+                column_writer.close()?;
+
+                column.clear();
+                levels.clear();
+            }
+            row_group.close()?;
+
+            Ok::<_, crate::Error>(())
+        };
+
+        if !options.schema_only {
+            let in_table = |row| Error::in_table(&table_name, StatementKind::Data, row);
+
+            let mut stmt = conn
+                .prepare(&format!(
+                    "{}{}",
+                    crate::dump::select(conn, options, &table_name, &expressions.join(", ")),
+                    crate::dump::order_by(conn, options, &table_name),
+                ))
+                .map_err(in_table(None))?;
+            let mut rows = stmt.query([]).map_err(in_table(None))?;
+            let mut buffered = 0;
+            let mut index = 0;
+            progress.start_table(&table_name);
+
+            while let Some(row) = rows.next().map_err(in_table(Some(index)))? {
+                crate::cancel::check(options.cancellation.as_ref())?;
+                for (i, (column, levels)) in
+                    columns.iter_mut().zip(&mut definition_levels).enumerate()
+                {
+                    let value = row.get_ref(i).map_err(in_table(Some(index)))?;
+                    let rewritten = rewriter.rewrite(i, value);
+                    let value = rewritten.as_ref().map_or(value, ValueRef::from);
+
+                    let defined = match (column, value) {
+                        (Column::Int64(values), ValueRef::Integer(value)) => {
+                            values.push(value);
+                            true
+                        }
+                        (Column::Double(values), ValueRef::Real(value)) => {
+                            values.push(value);
+                            true
+                        }
+                        (Column::Double(values), ValueRef::Integer(value)) => {
+                            values.push(value as f64);
+                            true
+                        }
+                        (
+                            Column::ByteArray(values),
+                            ValueRef::Text(bytes) | ValueRef::Blob(bytes),
+                        ) => {
+                            values.push(ByteArray::from(bytes.to_vec()));
+                            true
+                        }
+                        _ => false,
+                    };
+                    levels.push(i16::from(defined));
+                }
+
+                progress.row();
+                index += 1;
+                buffered += 1;
+                if buffered == options.batch_size {
+                    write_row_group(&mut columns, &mut definition_levels)?;
+                    buffered = 0;
+                }
+            }
+
+            if buffered > 0 {
+                write_row_group(&mut columns, &mut definition_levels)?;
+            }
+            progress.end_table();
+        }
+
+        writer.close()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use crate::{ConnectionExt, DumpOptions};
+
+    #[test]
+    fn test_dump_parquet() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB, score REAL, balance DECIMAL);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users VALUES (1, 'alice', X'cafe', 1.5, 10);
+INSERT INTO users VALUES (2, NULL, NULL, NULL, NULL);
+INSERT INTO users VALUES (3, 42, 'text', '2.5', 0.5);"#,
+        )?;
+
+        let dir =
+            std::env::temp_dir().join(format!("dumpqlite-test-parquet-{}", std::process::id()));
+        let options = DumpOptions::new().batch_size(2);
+        conn.dump_parquet(&options, &dir)?;
+
+        let reader = SerializedFileReader::new(std::fs::File::open(dir.join("users.parquet"))?)?;
+        assert_eq!(2, reader.metadata().num_row_groups());
+
+        let rows = reader
+            .get_row_iter(None)?
+            .map(|row| row.map(|row| row.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected = vec![
+            "{id: 1, name: \"alice\", avatar: [202, 254], score: 1.5, balance: 10.0}",
+            "{id: 2, name: null, avatar: null, score: null, balance: null}",
+            "{id: 3, name: \"42\", avatar: [116, 101, 120, 116], score: 2.5, balance: 0.5}",
+        ];
+        assert_eq!(expected, rows);
+
+        let reader = SerializedFileReader::new(std::fs::File::open(dir.join("tags.parquet"))?)?;
+        assert_eq!(0, reader.metadata().file_metadata().num_rows());
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
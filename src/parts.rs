@@ -0,0 +1,121 @@
+use std::io::{self, Write as _};
+
+use crate::{dump::Kind, DumpOptions, DumpStatements};
+
+/// What the statements written so far left open, to reopen it at the start of the next part.
+#[derive(Debug, Default)]
+struct State {
+    /// The statements of the prologue setting up the connection before the transaction opens,
+    /// such as `PRAGMA foreign_keys=OFF;`.
+    setup: Vec<String>,
+    transaction: bool,
+    writable_schema: bool,
+}
+
+impl State {
+    fn observe(&mut self, options: &DumpOptions, kind: Kind, sql: &str) {
+        // A statement may be announced by a comment on the lines before it.
+        let statement = sql.rsplit('\n').next().unwrap_or(sql);
+        if statement == options.dialect.begin() {
+            self.transaction = true;
+        } else if statement == "COMMIT;" {
+            self.transaction = false;
+        } else if statement == "PRAGMA writable_schema=ON;" {
+            self.writable_schema = true;
+        } else if statement.starts_with("PRAGMA writable_schema=") {
+            self.writable_schema = false;
+        } else if kind == Kind::Prologue && !self.transaction && !sql.starts_with("--") {
+            self.setup.push(sql.to_owned());
+        }
+    }
+
+    /// The statements ending a part cut at this point.
+    fn close(&self) -> Vec<String> {
+        match self.transaction {
+            true => vec!["COMMIT;".to_owned()],
+            false => Vec::new(),
+        }
+    }
+
+    /// The statements starting part `part`, cut at this point.
+    fn reopen(&self, options: &DumpOptions, part: usize) -> Vec<String> {
+        let mut statements = vec![format!(
+            "-- Part {part} of the dump, continuing part {}",
+            part - 1
+        )];
+        statements.extend(self.setup.iter().cloned());
+        if self.transaction {
+            statements.extend(crate::dump::begin(options));
+        }
+        if self.writable_schema {
+            statements.push("PRAGMA writable_schema=ON;".to_owned());
+        }
+        statements
+    }
+}
+
+/// The size of `statements` once written, one per line.
+fn size<S: AsRef<str>>(statements: &[S]) -> u64 {
+    statements
+        .iter()
+        .map(|sql| sql.as_ref().len() as u64 + 1)
+        .sum()
+}
+
+/// Writes the statements of `statements` to the writers `factory` returns for parts 1, 2 and
+/// so on, starting the next part before a statement that would take the current one past
+/// `max_bytes`.
+pub(crate) fn dump<W, F>(
+    mut statements: DumpStatements<'_>,
+    options: &DumpOptions,
+    max_bytes: u64,
+    mut factory: F,
+) -> Result<(), crate::Error>
+where
+    W: io::Write,
+    F: FnMut(usize) -> io::Result<W>,
+{
+    let mut state = State::default();
+    let mut part = 1;
+    let mut writer = io::BufWriter::with_capacity(options.buffer_size, factory(part)?);
+    // The bytes of the current part, and whether it holds a statement past the prologue yet.
+    let mut written = 0;
+    let mut started = false;
+
+    while let Some(statement) = statements.next_with_kind() {
+        let (kind, sql) = statement?;
+
+        // Room for the statements closing the part is kept as it grows, so the statement
+        // closing it anyway always fits.
+        let closing = state.close();
+        let closes = closing.iter().eq([&sql]);
+        if started && !closes && written + size(&[&sql]) + size(&closing) > max_bytes {
+            for sql in closing {
+                writeln!(writer, "{sql}")?;
+            }
+            writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .flush()?;
+
+            part += 1;
+            writer = io::BufWriter::with_capacity(options.buffer_size, factory(part)?);
+            let opening = state.reopen(options, part);
+            for sql in &opening {
+                writeln!(writer, "{sql}")?;
+            }
+            written = size(&opening);
+        }
+
+        writeln!(writer, "{sql}")?;
+        written += size(&[&sql]);
+        started |= kind != Kind::Prologue;
+        state.observe(options, kind, &sql);
+    }
+
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())?
+        .flush()?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+
+use crate::migration::{tokenize, Token};
+
+/// The indentation of the definitions of a table and of the statements of a trigger.
+const INDENT: &str = "    ";
+
+/// Reformats the statement `sql` creating a table, an index, a view or a trigger, as written
+/// with [`DumpOptions::pretty_schema`](crate::DumpOptions::pretty_schema).
+///
+/// Runs of whitespace become a single space, with a space after each comma and none inside
+/// parentheses. The columns and constraints of a table then go on a line each, as do the
+/// statements of a trigger. Statements holding comments, which would be lost, and those
+/// creating virtual tables, whose arguments belong to their module, are left as they are.
+pub(crate) fn create(sql: &str) -> Cow<'_, str> {
+    let Some(tokens) = tokenize(sql) else {
+        return Cow::Borrowed(sql);
+    };
+    if has_comments(sql, &tokens) {
+        return Cow::Borrowed(sql);
+    }
+
+    let formatted = if sql.starts_with("CREATE TABLE ") {
+        table(&tokens)
+    } else if sql.starts_with("CREATE TRIGGER ") {
+        trigger(&tokens)
+    } else if sql.starts_with("CREATE VIRTUAL TABLE ") {
+        None
+    } else {
+        Some(join(&tokens))
+    };
+    formatted.map_or(Cow::Borrowed(sql), Cow::Owned)
+}
+
+/// Whether anything but whitespace lies between the tokens of `sql`.
+fn has_comments(sql: &str, tokens: &[Token<'_>]) -> bool {
+    let mut end = 0;
+    for &(offset, token) in tokens {
+        if !sql[end..offset].trim().is_empty() {
+            return true;
+        }
+        end = offset + token.len();
+    }
+    !sql[end..].trim().is_empty()
+}
+
+/// The keywords followed by a space before a parenthesis, unlike the names of functions and
+/// types.
+const KEYWORDS: [&str; 9] = [
+    "AS", "CHECK", "DEFAULT", "EXISTS", "IN", "KEY", "UNIQUE", "USING", "VALUES",
+];
+
+/// Joins `tokens` on a single line.
+fn join(tokens: &[Token<'_>]) -> String {
+    let mut out = String::new();
+    for (i, &(_, token)) in tokens.iter().enumerate() {
+        if i > 0 && space(tokens, i) {
+            out.push(' ');
+        }
+        out.push_str(token);
+    }
+    out
+}
+
+/// Whether a space goes before the token `i` of `tokens`: after a comma, never inside
+/// parentheses or before a comma, before a parenthesis after a keyword or the table named
+/// by `ON` or `REFERENCES`, and otherwise if the tokens were apart in the first place.
+fn space(tokens: &[Token<'_>], i: usize) -> bool {
+    let (end, prev) = tokens[i - 1];
+    let (start, next) = tokens[i];
+    let is_word =
+        |token: &str| token.starts_with(|c: char| c.is_alphanumeric() || "_$\"`'[".contains(c));
+
+    match (prev, next) {
+        (_, "," | ")") | ("(", _) => false,
+        (",", _) => true,
+        (prev, "(") if is_word(prev) => {
+            KEYWORDS
+                .iter()
+                .any(|keyword| prev.eq_ignore_ascii_case(keyword))
+                || i >= 2 && {
+                    let (_, before) = tokens[i - 2];
+                    before.eq_ignore_ascii_case("ON") || before.eq_ignore_ascii_case("REFERENCES")
+                }
+        }
+        _ => end + prev.len() < start,
+    }
+}
+
+/// Lays out `CREATE TABLE name (...)` with a column or constraint per line, or returns `None`
+/// for `CREATE TABLE ... AS SELECT`.
+fn table(tokens: &[Token<'_>]) -> Option<String> {
+    let open = tokens.iter().position(|&(_, token)| token == "(")?;
+    let head = &tokens[..open];
+    if head
+        .iter()
+        .any(|(_, token)| token.eq_ignore_ascii_case("AS"))
+    {
+        return None;
+    }
+
+    let mut items = Vec::new();
+    let mut start = open + 1;
+    let mut depth = 0;
+    for (i, &(_, token)) in tokens.iter().enumerate().skip(open + 1) {
+        match token {
+            "(" => depth += 1,
+            ")" if depth > 0 => depth -= 1,
+            "," | ")" if depth == 0 => {
+                items.push(format!("{INDENT}{}", join(&tokens[start..i])));
+                start = i + 1;
+                if token == ")" {
+                    let mut out = format!("{} (\n{}\n)", join(head), items.join(",\n"));
+                    if start < tokens.len() {
+                        out.push(' ');
+                        out.push_str(&join(&tokens[start..]));
+                    }
+                    return Some(out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Lays out `CREATE TRIGGER ... BEGIN ... END` with a statement per line.
+fn trigger(tokens: &[Token<'_>]) -> Option<String> {
+    let begin = tokens
+        .iter()
+        .position(|(_, token)| token.eq_ignore_ascii_case("BEGIN"))?;
+    let (_, end) = tokens.last()?;
+    if !end.eq_ignore_ascii_case("END") {
+        return None;
+    }
+
+    let mut out = format!("{} BEGIN\n", join(&tokens[..begin]));
+    let body = &tokens[begin + 1..tokens.len() - 1];
+    for statement in body.split_inclusive(|&(_, token)| token == ";") {
+        let (_, last) = statement.last()?;
+        if *last != ";" {
+            return None;
+        }
+        out.push_str(INDENT);
+        out.push_str(&join(&statement[..statement.len() - 1]));
+        out.push_str(";\n");
+    }
+    out.push_str(end);
+    Some(out)
+}
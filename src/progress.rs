@@ -0,0 +1,247 @@
+use std::{
+    cell::{Cell, RefCell},
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dump::Kind,
+    options::{OnMetrics, OnProgress},
+    trace::Trace,
+    DumpOptions,
+};
+
+/// A snapshot of the progress of a dump, passed to the callback set with
+/// [`DumpOptions::on_progress`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct DumpProgress<'a> {
+    /// The table whose rows are being written.
+    pub table: &'a str,
+    /// The number of rows of `table` written so far.
+    pub table_rows_written: u64,
+    /// The number of rows of every table written so far.
+    pub rows_written: u64,
+    /// The number of tables left to dump after `table`.
+    pub tables_remaining: usize,
+    /// The number of bytes written to the writer so far.
+    pub bytes_written: u64,
+    /// The number of pages of the database copied so far, while it is copied into memory
+    /// with [`DumpOptions::backup_to_memory`]. Zero once the dump itself starts.
+    pub pages_copied: u64,
+    /// The number of pages of the database to copy into memory, or zero when not copying.
+    pub page_count: u64,
+}
+
+/// The metrics of a dump once the rows of a table are written, passed to the callback set
+/// with [`DumpOptions::on_metrics`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct DumpMetrics<'a> {
+    /// The table whose rows were written.
+    pub table: &'a str,
+    /// The number of rows of `table` written.
+    pub table_rows: u64,
+    /// The number of bytes written for the rows of `table`.
+    pub table_bytes: u64,
+    /// How long the rows of `table` took to read and write.
+    pub table_duration: Duration,
+    /// The number of rows of every table written so far.
+    pub rows_written: u64,
+    /// The number of bytes written to the writer so far.
+    pub bytes_written: u64,
+    /// How long the dump has been running.
+    pub elapsed: Duration,
+}
+
+impl DumpMetrics<'_> {
+    /// The number of rows written per second since the dump started.
+    pub fn rows_per_second(&self) -> f64 {
+        per_second(self.rows_written, self.elapsed)
+    }
+
+    /// The number of bytes written per second since the dump started.
+    pub fn bytes_per_second(&self) -> f64 {
+        per_second(self.bytes_written, self.elapsed)
+    }
+
+    /// The number of rows of `table` written per second.
+    pub fn table_rows_per_second(&self) -> f64 {
+        per_second(self.table_rows, self.table_duration)
+    }
+}
+
+fn per_second(count: u64, duration: Duration) -> f64 {
+    match duration.as_secs_f64() {
+        0.0 => 0.0,
+        seconds => count as f64 / seconds,
+    }
+}
+
+/// Tracks the progress of a dump and reports it to the callback of the options, if any.
+pub(crate) struct Progress {
+    callback: Option<OnProgress>,
+    metrics: Option<OnMetrics>,
+    table: RefCell<String>,
+    table_rows: Cell<u64>,
+    rows: Cell<u64>,
+    tables_remaining: Cell<usize>,
+    bytes: Cell<u64>,
+    started: Instant,
+    /// When the current table started, and the bytes written before it.
+    table_started: Cell<(Instant, u64)>,
+    trace: Trace,
+}
+
+impl Progress {
+    pub(crate) fn new(
+        conn: &rusqlite::Connection,
+        options: &DumpOptions,
+    ) -> Result<Self, crate::Error> {
+        let tables = match options.on_progress {
+            Some(_) if !options.schema_only => crate::dump::data_tables(conn, options)?.len(),
+            _ => 0,
+        };
+
+        let started = Instant::now();
+        Ok(Self {
+            callback: options.on_progress.clone(),
+            metrics: options.on_metrics.clone(),
+            table: RefCell::new(String::new()),
+            table_rows: Cell::new(0),
+            rows: Cell::new(0),
+            tables_remaining: Cell::new(tables),
+            bytes: Cell::new(0),
+            started,
+            table_started: Cell::new((started, 0)),
+            trace: Trace::new(options),
+        })
+    }
+
+    /// Reports that `copied` of the `pages` pages of the database were copied into memory,
+    /// before any table is dumped.
+    pub(crate) fn backup(options: &DumpOptions, copied: u64, pages: u64) {
+        if let Some(callback) = &options.on_progress {
+            (callback.0)(&DumpProgress {
+                table: "",
+                table_rows_written: 0,
+                rows_written: 0,
+                tables_remaining: 0,
+                bytes_written: 0,
+                pages_copied: copied,
+                page_count: pages,
+            });
+        }
+    }
+
+    /// Wraps `writer` so that the bytes written through it are counted.
+    pub(crate) fn count<'a, W: io::Write>(&'a self, writer: &'a mut W) -> Counted<'a, W> {
+        Counted::new(writer, &self.bytes)
+    }
+
+    /// Whether anything follows the progress of the dump.
+    fn tracked(&self) -> bool {
+        self.callback.is_some() || self.metrics.is_some() || Trace::ENABLED
+    }
+
+    pub(crate) fn start_table(&self, table_name: &str) {
+        if !self.tracked() {
+            return;
+        }
+
+        table_name.clone_into(&mut self.table.borrow_mut());
+        self.table_rows.set(0);
+        self.tables_remaining
+            .set(self.tables_remaining.get().saturating_sub(1));
+        self.table_started.set((Instant::now(), self.bytes.get()));
+        self.trace.start_table(table_name);
+        self.report();
+    }
+
+    /// Reports the metrics of the table started last, once its rows are written.
+    pub(crate) fn end_table(&self) {
+        if !self.tracked() {
+            return;
+        }
+
+        let (table_started, table_bytes) = self.table_started.get();
+        let metrics = DumpMetrics {
+            table: &self.table.borrow(),
+            table_rows: self.table_rows.get(),
+            table_bytes: self.bytes.get() - table_bytes,
+            table_duration: table_started.elapsed(),
+            rows_written: self.rows.get(),
+            bytes_written: self.bytes.get(),
+            elapsed: self.started.elapsed(),
+        };
+        self.trace.end_table(
+            metrics.table,
+            metrics.table_rows,
+            metrics.table_bytes,
+            metrics.table_duration,
+        );
+        if let Some(callback) = &self.metrics {
+            (callback.0)(&metrics);
+        }
+    }
+
+    /// Follows the statement `sql` of an SQL dump as it is written.
+    pub(crate) fn statement(&self, kind: Kind, sql: &str) {
+        self.trace.statement(kind, sql, self.rows.get());
+    }
+
+    /// Follows the end of the dump, once everything is written.
+    pub(crate) fn finish(&self) {
+        self.trace
+            .finish(self.rows.get(), self.bytes.get(), self.started.elapsed());
+    }
+
+    pub(crate) fn row(&self) {
+        if !self.tracked() {
+            return;
+        }
+
+        self.table_rows.set(self.table_rows.get() + 1);
+        self.rows.set(self.rows.get() + 1);
+        self.report();
+    }
+
+    fn report(&self) {
+        if let Some(callback) = &self.callback {
+            (callback.0)(&DumpProgress {
+                table: &self.table.borrow(),
+                table_rows_written: self.table_rows.get(),
+                rows_written: self.rows.get(),
+                tables_remaining: self.tables_remaining.get(),
+                bytes_written: self.bytes.get(),
+                pages_copied: 0,
+                page_count: 0,
+            });
+        }
+    }
+}
+
+/// A writer counting the bytes written to the writer it wraps.
+pub(crate) struct Counted<'a, W> {
+    writer: &'a mut W,
+    bytes: &'a Cell<u64>,
+}
+
+impl<'a, W> Counted<'a, W> {
+    /// Wraps `writer` so that the bytes written through it are added to `bytes`.
+    pub(crate) fn new(writer: &'a mut W, bytes: &'a Cell<u64>) -> Self {
+        Self { writer, bytes }
+    }
+}
+
+impl<W: io::Write> io::Write for Counted<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.bytes.set(self.bytes.get() + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,91 @@
+use std::io;
+
+use rusqlite::types::ToSqlOutput;
+
+use crate::{backup, cancel, quote, ConnectionExt as _, DumpOptions};
+
+/// Writes the rows returned by the `SELECT` statement `sql` as the dump of a table named
+/// `table_name`.
+///
+/// The rows are copied into a table of an in-memory database, declared with the columns of the
+/// result and their declared types, which is then dumped as any other table.
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    sql: &str,
+    table_name: &str,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let token = options.cancellation.as_ref();
+    let mut copy = rusqlite::Connection::open_in_memory()?;
+
+    let result = (|| {
+        cancel::check(token)?;
+        let mut stmt = conn.prepare(sql)?;
+        if stmt.column_count() == 0 || !stmt.readonly() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the statement to dump is not a query",
+            )
+            .into());
+        }
+
+        let columns = columns(&stmt);
+        let tx = copy.transaction()?;
+        tx.execute_batch(&format!(
+            "CREATE TABLE {}({});",
+            quote::identifier(table_name),
+            columns
+                .iter()
+                .map(|(name, decl_type)| match decl_type {
+                    Some(decl_type) => format!("{} {decl_type}", quote::identifier(name)),
+                    None => quote::identifier(name).into_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+        ))?;
+        {
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO {} VALUES({});",
+                quote::identifier(table_name),
+                vec!["?"; columns.len()].join(","),
+            ))?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                cancel::check(token)?;
+                let values = (0..columns.len())
+                    .map(|i| row.get_ref(i).map(ToSqlOutput::Borrowed))
+                    .collect::<Result<Vec<_>, _>>()?;
+                insert.execute(rusqlite::params_from_iter(values))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    })();
+    result.map_err(|err| cancel::map_interrupted(token, err))?;
+
+    let mut options = backup::copy_options(options);
+    options.include_tables = Some(vec![table_name.to_owned()]);
+    options.exclude_tables.clear();
+    copy.dump_with(&options, writer)
+}
+
+/// The names and declared types of the columns returned by `stmt`, with the names repeated in
+/// the result numbered as `CREATE TABLE ... AS SELECT` does, such as `x:1`.
+fn columns(stmt: &rusqlite::Statement<'_>) -> Vec<(String, Option<String>)> {
+    let mut columns: Vec<(String, Option<String>)> = Vec::new();
+    for column in stmt.columns() {
+        let mut name = column.name().to_owned();
+        let mut n = 0;
+        while columns
+            .iter()
+            .any(|(other, _)| other.eq_ignore_ascii_case(&name))
+        {
+            n += 1;
+            name = format!("{}:{n}", column.name());
+        }
+        columns.push((name, column.decl_type().map(str::to_owned)));
+    }
+
+    columns
+}
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+
+/// The keywords of SQLite, which cannot be used as bare identifiers.
+const KEYWORDS: &str = "\
+ABORT ACTION ADD AFTER ALL ALTER ALWAYS ANALYZE AND AS ASC ATTACH AUTOINCREMENT BEFORE \
+BEGIN BETWEEN BY CASCADE CASE CAST CHECK COLLATE COLUMN COMMIT CONFLICT CONSTRAINT CREATE \
+CROSS CURRENT CURRENT_DATE CURRENT_TIME CURRENT_TIMESTAMP DATABASE DEFAULT DEFERRABLE \
+DEFERRED DELETE DESC DETACH DISTINCT DO DROP EACH ELSE END ESCAPE EXCEPT EXCLUDE EXCLUSIVE \
+EXISTS EXPLAIN FAIL FILTER FIRST FOLLOWING FOR FOREIGN FROM FULL GENERATED GLOB GROUP \
+GROUPS HAVING IF IGNORE IMMEDIATE IN INDEX INDEXED INITIALLY INNER INSERT INSTEAD \
+INTERSECT INTO IS ISNULL JOIN KEY LAST LEFT LIKE LIMIT MATCH MATERIALIZED NATURAL NO NOT \
+NOTHING NOTNULL NULL NULLS OF OFFSET ON OR ORDER OTHERS OUTER OVER PARTITION PLAN PRAGMA \
+PRECEDING PRIMARY QUERY RAISE RANGE RECURSIVE REFERENCES REGEXP REINDEX RELEASE RENAME \
+REPLACE RESTRICT RETURNING RIGHT ROLLBACK ROW ROWS SAVEPOINT SELECT SET TABLE TEMP \
+TEMPORARY THEN TIES TO TRANSACTION TRIGGER UNBOUNDED UNION UNIQUE UPDATE USING VACUUM \
+VALUES VIEW VIRTUAL WHEN WHERE WINDOW WITH WITHOUT";
+
+/// Quotes `name` for use as an identifier in SQL.
+///
+/// Like the SQLite CLI, names made of letters, digits and underscores that are not keywords are
+/// left bare, while every other name is wrapped in double quotes.
+pub(crate) fn identifier(name: &str) -> Cow<'_, str> {
+    let bare = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS
+            .split_ascii_whitespace()
+            .any(|keyword| keyword.eq_ignore_ascii_case(name));
+
+    match bare {
+        true => Cow::Borrowed(name),
+        false => Cow::Owned(format!("\"{}\"", name.replace('"', "\"\""))),
+    }
+}
+
+/// Quotes every name of `names` as an identifier, separated by `separator`.
+pub(crate) fn identifiers<S: AsRef<str>>(names: &[S], separator: &str) -> String {
+    names
+        .iter()
+        .map(|name| identifier(name.as_ref()))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Quotes `text` as an SQL string literal.
+pub(crate) fn text(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Quotes `text` as an SQL expression evaluating to `text` that fits on a single line, like
+/// the SQLite CLI.
+///
+/// Newlines and carriage returns are written as placeholders put back with `replace()` and
+/// `char()`, unless `newlines` allows them as they are. NUL characters, which would end the
+/// statement, are always replaced the same way.
+pub(crate) fn escaped_text(text: &str, newlines: bool) -> String {
+    // From the innermost to the outermost `replace()`.
+    let escapes = [
+        ('\0', "\\000", "\\x00", 0),
+        ('\r', "\\r", "\\015", 13),
+        ('\n', "\\n", "\\012", 10),
+    ]
+    .into_iter()
+    .filter(|(c, ..)| *c == '\0' || !newlines)
+    .filter(|(c, ..)| text.contains(*c))
+    .map(|(c, short, long, code)| (c, unused(text, short, long), code))
+    .collect::<Vec<_>>();
+
+    let mut quoted = self::text(text);
+    for (c, placeholder, _) in &escapes {
+        quoted = quoted.replace(*c, placeholder);
+    }
+    for (_, placeholder, code) in &escapes {
+        quoted = format!("replace({quoted},'{placeholder}',char({code}))");
+    }
+
+    quoted
+}
+
+/// Appends [`escaped_text`] of `text` to `out`, without allocating for the text that needs no
+/// placeholders.
+pub(crate) fn push_escaped_text(out: &mut String, text: &str, newlines: bool) {
+    let placeholders = text.contains('\0') || (!newlines && text.contains(['\r', '\n']));
+    if placeholders {
+        out.push_str(&escaped_text(text, newlines));
+        return;
+    }
+
+    out.push('\'');
+    for (i, part) in text.split('\'').enumerate() {
+        if i > 0 {
+            out.push_str("''");
+        }
+        out.push_str(part);
+    }
+    out.push('\'');
+}
+
+/// Returns the first of `short`, `long` and numbered variants of `short` that `text` does not
+/// contain.
+fn unused(text: &str, short: &str, long: &str) -> String {
+    [short.to_owned(), long.to_owned()]
+        .into_iter()
+        .chain((0..).map(|i| format!("({short}{i})")))
+        .find(|placeholder| !text.contains(placeholder.as_str()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_identifier() {
+        assert_eq!("users", super::identifier("users"));
+        assert_eq!("_users2", super::identifier("_users2"));
+        assert_eq!("\"order\"", super::identifier("order"));
+        assert_eq!("\"Order\"", super::identifier("Order"));
+        assert_eq!("\"my table\"", super::identifier("my table"));
+        assert_eq!("\"2fa\"", super::identifier("2fa"));
+        assert_eq!("\"say \"\"hi\"\"\"", super::identifier("say \"hi\""));
+        assert_eq!("\"\"", super::identifier(""));
+        assert_eq!("\"naïve\"", super::identifier("naïve"));
+    }
+
+    #[test]
+    fn test_escaped_text() {
+        assert_eq!("'it''s'", super::escaped_text("it's", false));
+        assert_eq!(
+            r"replace('a\nb','\n',char(10))",
+            super::escaped_text("a\nb", false)
+        );
+        assert_eq!(
+            r"replace(replace('a\r\nb','\r',char(13)),'\n',char(10))",
+            super::escaped_text("a\r\nb", false)
+        );
+        assert_eq!(
+            r"replace('\n, \012 and (\n0)','(\n0)',char(10))",
+            super::escaped_text("\\n, \\012 and \n", false)
+        );
+        assert_eq!("'a\nb'", super::escaped_text("a\nb", true));
+        assert_eq!(
+            r"replace('a\000b','\000',char(0))",
+            super::escaped_text("a\0b", true)
+        );
+    }
+
+    #[test]
+    fn test_push_escaped_text() {
+        for text in ["", "plain", "it's ''", "a\nb", "a\r\nb", "a\0b"] {
+            for newlines in [false, true] {
+                let mut out = "x,".to_owned();
+                super::push_escaped_text(&mut out, text, newlines);
+                assert_eq!(format!("x,{}", super::escaped_text(text, newlines)), out);
+            }
+        }
+    }
+}
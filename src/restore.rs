@@ -0,0 +1,228 @@
+use std::{ffi, io};
+
+/// Splits SQL text read from `reader` into complete statements.
+///
+/// A chunk may hold more than one statement when they share a line,
+/// and leading blank lines are dropped.
+pub(crate) struct Statements<R> {
+    reader: R,
+    /// The number of lines read so far.
+    lines: u64,
+    /// The line the last statement returned starts on, counting from one.
+    line: u64,
+}
+
+impl<R: io::BufRead> Statements<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            lines: 0,
+            line: 0,
+        }
+    }
+
+    /// The line the last statement returned starts on, counting from one.
+    pub(crate) fn line(&self) -> u64 {
+        self.line
+    }
+}
+
+impl<R: io::BufRead> Iterator for Statements<R> {
+    type Item = Result<String, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut sql = String::new();
+
+        loop {
+            let len = sql.len();
+            match self.reader.read_line(&mut sql) {
+                Ok(0) => break,
+                Ok(_) => self.lines += 1,
+                Err(err) => return Some(Err(err)),
+            }
+
+            if sql.trim().is_empty() {
+                sql.clear();
+                continue;
+            }
+            if len == 0 {
+                self.line = self.lines;
+            }
+
+            if sql[len..].trim_end().ends_with(';') && is_complete(&sql) {
+                break;
+            }
+        }
+
+        (!sql.is_empty()).then_some(Ok(sql))
+    }
+}
+
+fn is_complete(sql: &str) -> bool {
+    let Ok(sql) = ffi::CString::new(sql) else {
+        return false;
+    };
+
+    // SAFETY: `sql` is a valid NUL-terminated string that outlives the call.
+    unsafe { rusqlite::ffi::sqlite3_complete(sql.as_ptr()) != 0 }
+}
+
+/// Whether `sql` only begins or ends a transaction, which the restore manages by itself.
+fn is_transaction_control(sql: &str) -> bool {
+    // Such as the comment naming the table of a transaction per table.
+    let sql = sql
+        .lines()
+        .skip_while(|line| line.trim_start().starts_with("--"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    let mut words = sql.split_whitespace().map(str::to_ascii_uppercase);
+
+    let statement = match (words.next(), words.next(), words.next()) {
+        (Some(first), None, None) => first,
+        (Some(first), Some(second), None) if second == "TRANSACTION" => first,
+        _ => return false,
+    };
+
+    matches!(statement.as_str(), "BEGIN" | "COMMIT" | "END")
+}
+
+pub(crate) fn restore<R: io::Read>(
+    conn: &mut rusqlite::Connection,
+    reader: &mut R,
+) -> Result<(), crate::Error> {
+    // Foreign keys cannot be toggled inside a transaction, so the `PRAGMA foreign_keys=OFF;`
+    // at the top of a dump is applied here instead.
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys;", [], |row| row.get(0))?;
+    conn.execute_batch("PRAGMA foreign_keys=OFF;")?;
+
+    let result = (|| {
+        let tx = conn.transaction()?;
+        execute(&tx, reader)?;
+        tx.commit()?;
+        Ok(())
+    })();
+
+    if foreign_keys {
+        conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+    }
+
+    result
+}
+
+/// Restores a dump inside the savepoint `sp`, releasing it once every statement succeeded.
+///
+/// Foreign keys cannot be turned off within the enclosing transaction, so their checks are
+/// deferred to its commit instead, by when every referenced row has been restored.
+pub(crate) fn restore_savepoint<R: io::Read>(
+    sp: rusqlite::Savepoint<'_>,
+    reader: &mut R,
+) -> Result<(), crate::Error> {
+    sp.execute_batch("PRAGMA defer_foreign_keys=ON;")?;
+    execute(&sp, reader)?;
+    sp.commit()?;
+    Ok(())
+}
+
+/// Executes the statements read from `reader`, leaving out those controlling transactions.
+fn execute<R: io::Read>(conn: &rusqlite::Connection, reader: &mut R) -> Result<(), crate::Error> {
+    for statement in Statements::new(io::BufReader::new(reader)) {
+        let statement = statement?;
+        if !is_transaction_control(&statement) {
+            conn.execute_batch(&statement)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ConnectionExt;
+
+    #[test]
+    fn test_statements() -> Result<(), crate::Error> {
+        let sql = r#"
+INSERT INTO t VALUES('a;b');
+INSERT INTO t VALUES('multi
+line;
+text'); INSERT INTO t VALUES(2);
+
+CREATE TRIGGER t_audit AFTER INSERT ON t BEGIN
+    INSERT INTO audit VALUES(new.rowid);
+END;
+"#;
+
+        let statements = super::Statements::new(sql.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+
+        let expected = vec![
+            "INSERT INTO t VALUES('a;b');\n",
+            "INSERT INTO t VALUES('multi\nline;\ntext'); INSERT INTO t VALUES(2);\n",
+            "CREATE TRIGGER t_audit AFTER INSERT ON t BEGIN\n    INSERT INTO audit VALUES(new.rowid);\nEND;\n",
+        ];
+        assert_eq!(expected, statements);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+CREATE TABLE tasks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES users(id),
+    title TEXT
+);
+CREATE TRIGGER users_cleanup AFTER DELETE ON users BEGIN
+    DELETE FROM tasks WHERE user_id = old.id;
+END;
+INSERT INTO users (name) VALUES ('alice');
+INSERT INTO tasks (user_id, title) VALUES (1, 'semi;colon'), (1, 'new
+line');"#,
+        )?;
+
+        let mut dump = Vec::new();
+        conn.dump(&mut dump)?;
+
+        let mut restored = rusqlite::Connection::open_in_memory()?;
+        restored.execute_batch("PRAGMA foreign_keys=ON;")?;
+        crate::ConnectionExt::restore(&mut restored, &mut dump.as_slice())?;
+
+        let mut redump = Vec::new();
+        restored.dump(&mut redump)?;
+        assert_eq!(
+            std::str::from_utf8(&dump).unwrap(),
+            std::str::from_utf8(&redump).unwrap()
+        );
+
+        let foreign_keys: bool =
+            restored.query_row("PRAGMA foreign_keys;", [], |row| row.get(0))?;
+        assert!(foreign_keys);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_rollback() -> Result<(), crate::Error> {
+        let mut conn = rusqlite::Connection::open_in_memory()?;
+
+        let sql = r#"
+BEGIN TRANSACTION;
+CREATE TABLE users (name TEXT);
+INSERT INTO users VALUES('alice');
+INSERT INTO missing VALUES('bob');
+COMMIT;
+"#;
+        assert!(crate::ConnectionExt::restore(&mut conn, &mut sql.as_bytes()).is_err());
+
+        let tables: i64 =
+            conn.query_row("SELECT count(*) FROM sqlite_schema;", [], |row| row.get(0))?;
+        assert_eq!(0, tables);
+
+        Ok(())
+    }
+}
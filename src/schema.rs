@@ -0,0 +1,330 @@
+use rusqlite::OptionalExtension as _;
+
+use crate::{warning::skip, DumpOptions, Error, StatementKind};
+
+/// The schema of a database, as returned by
+/// [`ConnectionExt::schema`](crate::ConnectionExt::schema).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Schema {
+    /// The tables, in the order they are dumped, leaving out SQLite's own tables.
+    pub tables: Vec<Table>,
+    /// The views, in the order they were created, but for each view coming after the views it
+    /// selects from.
+    pub views: Vec<View>,
+    /// The triggers, in the order they were created.
+    pub triggers: Vec<Trigger>,
+}
+
+/// A table, including virtual tables and the shadow tables holding their rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Table {
+    pub name: String,
+    /// The `CREATE TABLE` statement of the table, without a terminating semicolon.
+    pub sql: String,
+    pub columns: Vec<Column>,
+    /// The columns of the primary key, in the order of the key.
+    pub primary_key: Vec<String>,
+    pub foreign_keys: Vec<ForeignKey>,
+    /// The indexes of the table, by name, including those SQLite creates for its `UNIQUE` and
+    /// `PRIMARY KEY` constraints.
+    pub indexes: Vec<Index>,
+}
+
+/// A column of a table, as listed by `PRAGMA table_xinfo`.
+///
+/// The hidden columns of virtual tables are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Column {
+    pub name: String,
+    /// The type the column was declared with, or an empty string.
+    pub declared_type: String,
+    pub not_null: bool,
+    /// The SQL expression of the default value, if any.
+    pub default: Option<String>,
+    /// Whether the column is generated from the others, and so has no values of its own to
+    /// dump.
+    pub generated: bool,
+}
+
+/// A foreign key of a table, as listed by `PRAGMA foreign_key_list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    /// The table referenced.
+    pub parent: String,
+    /// The columns referenced, which are the primary key of `parent` when the key does not
+    /// name them.
+    pub parent_columns: Vec<String>,
+    /// The action on update, such as `NO ACTION` or `CASCADE`.
+    pub on_update: String,
+    /// The action on delete, such as `NO ACTION` or `CASCADE`.
+    pub on_delete: String,
+}
+
+/// An index of a table, as listed by `PRAGMA index_list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Index {
+    pub name: String,
+    /// The `CREATE INDEX` statement of the index, or `None` for the indexes SQLite creates for
+    /// constraints.
+    pub sql: Option<String>,
+    pub unique: bool,
+    pub origin: IndexOrigin,
+    /// Whether the index only holds the rows matching its `WHERE` clause.
+    pub partial: bool,
+    /// The indexed columns, in the order of the index.
+    pub columns: Vec<IndexColumn>,
+}
+
+/// What created an index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IndexOrigin {
+    /// A `CREATE INDEX` statement.
+    CreateIndex,
+    /// A `UNIQUE` constraint.
+    Unique,
+    /// A `PRIMARY KEY` constraint.
+    PrimaryKey,
+}
+
+/// A column of an index, as listed by `PRAGMA index_xinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IndexColumn {
+    /// The name of the column, or `None` for an expression.
+    pub name: Option<String>,
+    /// Whether the column is sorted in descending order.
+    pub descending: bool,
+}
+
+/// A view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct View {
+    pub name: String,
+    /// The `CREATE VIEW` statement of the view, without a terminating semicolon.
+    pub sql: String,
+}
+
+/// A trigger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Trigger {
+    pub name: String,
+    /// The table or view the trigger fires on.
+    pub table: String,
+    /// The `CREATE TRIGGER` statement of the trigger, without a terminating semicolon.
+    pub sql: String,
+}
+
+/// Reads the schema of the tables selected by `options`, along with their views and triggers.
+///
+/// Like a dump, a lenient read leaves out the tables whose schema cannot be read.
+pub(crate) fn read(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Schema, crate::Error> {
+    let mut tables = Vec::new();
+    for (table_name, create_sql) in crate::dump::tables(conn, options)? {
+        match Table::read(conn, options, &table_name, &create_sql) {
+            Ok(table) => tables.push(table),
+            Err(err) => skip(options, &table_name, None, err).map_err(Error::in_table(
+                &table_name,
+                StatementKind::Schema,
+                None,
+            ))?,
+        }
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        r#"
+            SELECT type, name, tbl_name, sql
+            FROM {}
+            WHERE sql NOT NULL
+                AND type IN ('view', 'trigger')
+            ORDER BY rowid;"#,
+        options.qualified("sqlite_schema")
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut views = Vec::new();
+    let mut triggers = Vec::new();
+    while let Some(row) = rows.next()? {
+        let table: String = row.get(2)?;
+        if !options.includes_table(&table) {
+            continue;
+        }
+
+        match row.get::<_, String>(0)?.as_str() {
+            "view" => views.push(View {
+                name: row.get(1)?,
+                sql: row.get(3)?,
+            }),
+            _ => triggers.push(Trigger {
+                name: row.get(1)?,
+                table,
+                sql: row.get(3)?,
+            }),
+        }
+    }
+
+    Ok(Schema {
+        tables,
+        views: crate::views::in_dependency_order(views, |view| (&view.name, &view.sql)),
+        triggers,
+    })
+}
+
+impl Table {
+    /// Reads the schema of `table_name`, created by `create_sql`, from the pragmas describing it.
+    pub(crate) fn read(
+        conn: &rusqlite::Connection,
+        options: &DumpOptions,
+        table_name: &str,
+        create_sql: &str,
+    ) -> rusqlite::Result<Self> {
+        let schema = &options.schema;
+
+        let mut stmt = conn.prepare(
+            r#"
+                SELECT name, type, "notnull", dflt_value, hidden
+                FROM pragma_table_xinfo(?1, ?2)
+                WHERE hidden <> 1
+                ORDER BY cid;"#,
+        )?;
+        let columns = stmt
+            .query_map([table_name, schema], |row| {
+                Ok(Column {
+                    name: row.get(0)?,
+                    declared_type: row.get(1)?,
+                    not_null: row.get(2)?,
+                    default: row.get(3)?,
+                    generated: row.get::<_, i64>(4)? != 0,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut stmt = conn
+            .prepare("SELECT name, \"unique\", origin, partial FROM pragma_index_list(?1, ?2);")?;
+        let listed = stmt
+            .query_map([table_name, schema], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, bool>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, bool>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut indexes = Vec::new();
+        for (name, unique, origin, partial) in listed {
+            let mut stmt = conn.prepare(
+                r#"
+                    SELECT name, "desc"
+                    FROM pragma_index_xinfo(?1, ?2)
+                    WHERE key
+                    ORDER BY seqno;"#,
+            )?;
+            let columns = stmt
+                .query_map([&name, schema], |row| {
+                    Ok(IndexColumn {
+                        name: row.get(0)?,
+                        descending: row.get(1)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            // The primary key index of a `WITHOUT ROWID` table has no row in the schema.
+            let sql = conn
+                .query_row(
+                    &format!(
+                        "SELECT sql FROM {} WHERE type == 'index' AND name == ?1;",
+                        options.qualified("sqlite_schema")
+                    ),
+                    [&name],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .flatten();
+
+            indexes.push(Index {
+                name,
+                sql,
+                unique,
+                origin: match origin.as_str() {
+                    "u" => IndexOrigin::Unique,
+                    "pk" => IndexOrigin::PrimaryKey,
+                    _ => IndexOrigin::CreateIndex,
+                },
+                partial,
+                columns,
+            });
+        }
+        indexes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut stmt = conn.prepare(
+            r#"
+                SELECT id, "table", "from", "to", on_update, on_delete
+                FROM pragma_foreign_key_list(?1, ?2)
+                ORDER BY id, seq;"#,
+        )?;
+        let rows = stmt
+            .query_map([table_name, schema], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut foreign_keys: Vec<(i64, ForeignKey)> = Vec::new();
+        for (id, parent, from, to, on_update, on_delete) in rows {
+            let key = match foreign_keys.last_mut() {
+                Some((last, key)) if *last == id => key,
+                _ => {
+                    foreign_keys.push((
+                        id,
+                        ForeignKey {
+                            columns: Vec::new(),
+                            parent,
+                            parent_columns: Vec::new(),
+                            on_update,
+                            on_delete,
+                        },
+                    ));
+                    &mut foreign_keys.last_mut().unwrap().1
+                }
+            };
+            key.columns.push(from);
+            key.parent_columns.extend(to);
+        }
+        // A foreign key without parent columns references the primary key of its parent.
+        let mut foreign_keys = foreign_keys
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect::<Vec<_>>();
+        for key in &mut foreign_keys {
+            if key.parent_columns.is_empty() {
+                key.parent_columns = crate::dump::primary_key(conn, options, &key.parent)?;
+            }
+        }
+
+        Ok(Self {
+            name: table_name.to_owned(),
+            sql: create_sql.to_owned(),
+            columns,
+            primary_key: crate::dump::primary_key(conn, options, table_name)?,
+            foreign_keys,
+            indexes,
+        })
+    }
+}
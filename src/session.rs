@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+
+use rusqlite::session::{ConflictAction, ConflictType};
+
+use crate::DumpOptions;
+
+/// Records the changes made to the tables of a connection from when it is created, as
+/// returned by [`ConnectionExt::session`](crate::ConnectionExt::session).
+///
+/// The changes are taken as a changeset, which
+/// [`ConnectionExt::apply_changeset`](crate::ConnectionExt::apply_changeset) applies to a copy
+/// of the database, such as one restored from a dump taken when the session was created. A
+/// series of changesets makes for incremental backups far smaller than full dumps.
+///
+/// Changes made through any statement of the connection are recorded, until the session is
+/// dropped.
+pub struct Session<'conn> {
+    session: rusqlite::session::Session<'conn>,
+    schema: String,
+}
+
+impl Session<'_> {
+    /// Returns the changes recorded so far as a changeset, holding the values of the rows
+    /// before and after each change, so that conflicts are detected when it is applied.
+    pub fn changeset(&mut self) -> Result<Vec<u8>, crate::Error> {
+        let mut changes = Vec::new();
+        self.session.changeset_strm(&mut changes)?;
+        Ok(changes)
+    }
+
+    /// Returns the changes recorded so far as a patchset, which leaves out the values before
+    /// each change but those of the primary key, and is smaller than a changeset.
+    pub fn patchset(&mut self) -> Result<Vec<u8>, crate::Error> {
+        let mut changes = Vec::new();
+        self.session.patchset_strm(&mut changes)?;
+        Ok(changes)
+    }
+
+    /// Whether no change has been recorded, or every change recorded has been undone since.
+    pub fn is_empty(&self) -> bool {
+        self.session.is_empty()
+    }
+}
+
+impl core::fmt::Debug for Session<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Session")
+            .field("schema", &self.schema)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Starts recording the changes made to the tables of `conn` selected by `options`, including
+/// those created later on.
+///
+/// Tables without a `PRIMARY KEY` are left out, as SQLite matches the rows of a changeset by
+/// it, and so are the internal `sqlite_` tables.
+pub(crate) fn begin<'conn>(
+    conn: &'conn rusqlite::Connection,
+    options: &DumpOptions,
+) -> Result<Session<'conn>, crate::Error> {
+    let mut session = rusqlite::session::Session::new_with_name(conn, options.schema.as_str())?;
+    let filter = options.clone();
+    session.table_filter(Some(move |table: &str| {
+        !table.starts_with("sqlite_") && filter.includes_table(table)
+    }));
+    session.attach(None::<&str>)?;
+
+    Ok(Session {
+        session,
+        schema: options.schema.clone(),
+    })
+}
+
+/// Applies `changeset`, a changeset or patchset, to `conn` within a savepoint, which is rolled
+/// back on the first change conflicting with the rows of `conn`.
+pub(crate) fn apply(conn: &rusqlite::Connection, mut changeset: &[u8]) -> Result<(), crate::Error> {
+    let conflict = Arc::new(Mutex::new(Conflict::None));
+    let result = conn.apply_strm(&mut changeset, None::<fn(&str) -> bool>, {
+        let conflict = conflict.clone();
+        move |kind, item| {
+            *conflict.lock().unwrap_or_else(|err| err.into_inner()) = match kind {
+                ConflictType::SQLITE_CHANGESET_FOREIGN_KEY => Conflict::ForeignKey,
+                _ => Conflict::Table(
+                    item.op()
+                        .map(|op| op.table_name().to_owned())
+                        .unwrap_or_default(),
+                ),
+            };
+            ConflictAction::SQLITE_CHANGESET_ABORT
+        }
+    });
+
+    let conflict = std::mem::replace(
+        &mut *conflict.lock().unwrap_or_else(|err| err.into_inner()),
+        Conflict::None,
+    );
+    let message = match conflict {
+        Conflict::None => return Ok(result?),
+        Conflict::Table(table) => format!("changeset conflicts with the rows of table {table}"),
+        Conflict::ForeignKey => "changeset violates foreign key constraints".to_owned(),
+    };
+    let code = match result {
+        Err(rusqlite::Error::SqliteFailure(err, _)) => err,
+        _ => rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ABORT),
+    };
+    Err(rusqlite::Error::SqliteFailure(code, Some(message)).into())
+}
+
+/// The first conflict met while applying a changeset.
+enum Conflict {
+    None,
+    /// A change to the table did not match its rows.
+    Table(String),
+    /// The rows left foreign keys unsatisfied.
+    ForeignKey,
+}
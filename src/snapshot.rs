@@ -0,0 +1,45 @@
+use crate::{quote, DumpOptions};
+
+/// A read transaction holding the state of the database a dump reads, so that every table is
+/// read at the same point in time while other connections write to it.
+///
+/// The transaction is rolled back when the snapshot is dropped, as it writes nothing.
+pub(crate) struct Snapshot<'conn> {
+    conn: Option<&'conn rusqlite::Connection>,
+}
+
+impl<'conn> Snapshot<'conn> {
+    /// Opens a read transaction on `conn` if [`DumpOptions::snapshot`] is enabled.
+    ///
+    /// A connection already inside a transaction reads from it, and is left alone.
+    pub(crate) fn begin(
+        conn: &'conn rusqlite::Connection,
+        options: &DumpOptions,
+    ) -> rusqlite::Result<Self> {
+        if !options.snapshot || !conn.is_autocommit() {
+            return Ok(Self { conn: None });
+        }
+
+        conn.execute_batch("BEGIN DEFERRED;")?;
+        let snapshot = Self { conn: Some(conn) };
+        // A deferred transaction only takes its snapshot once it first reads.
+        conn.query_row(
+            &format!(
+                "SELECT count(*) FROM {}.sqlite_schema;",
+                quote::identifier(&options.schema)
+            ),
+            [],
+            |_| Ok(()),
+        )?;
+
+        Ok(snapshot)
+    }
+}
+
+impl Drop for Snapshot<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn {
+            let _ = conn.execute_batch("ROLLBACK;");
+        }
+    }
+}
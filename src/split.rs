@@ -0,0 +1,119 @@
+use std::io;
+
+use crate::{ConnectionExt, DumpFormat, DumpOptions};
+
+/// Writes a dump of each table, and of each view, to the writer `factory` returns for its name.
+///
+/// Each dump holds the definition of the table, its indexes and triggers, and its rows, or
+/// only the rows for the formats other than SQL, so that any of them restores on its own.
+/// Virtual tables are dumped along with their shadow tables.
+pub(crate) fn dump<W, F>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    mut factory: F,
+) -> Result<(), crate::Error>
+where
+    W: io::Write,
+    F: FnMut(&str) -> io::Result<W>,
+{
+    // Referenced rows are resolved across every table up front, since each dump is of its
+    // own table only.
+    let mut options = crate::subset::close(conn, options)?.into_owned();
+    options.follow_foreign_keys = false;
+    // The database was checked once for every dump.
+    options.verify_source = false;
+    crate::blob::reset_manifest(&options)?;
+    options.split = true;
+
+    let tables = crate::dump::tables(conn, &options)?;
+    let mut names = crate::dump::data_tables(conn, &options)?;
+    if options.format == DumpFormat::Sql && !options.data_only {
+        names.extend(views(conn, &options)?);
+    }
+
+    for name in names {
+        let table = table_options(&options, &tables, &name);
+
+        let mut writer = factory(&name)?;
+        conn.dump_with(&table, &mut writer)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Writes the dump of the table or view `name` alone to `writer`, as a part of a split dump.
+pub(crate) fn dump_table<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    name: &str,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let exists: bool = conn.query_row(
+        &format!(
+            r#"
+                SELECT EXISTS (
+                    SELECT 1
+                    FROM {}
+                    WHERE type IN ('table', 'view')
+                        AND name == ?1 COLLATE NOCASE
+                );"#,
+            options.qualified("sqlite_schema")
+        ),
+        [name],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(
+            io::Error::new(io::ErrorKind::NotFound, format!("no such table: {name}")).into(),
+        );
+    }
+
+    let mut options = options.clone();
+    options.include_tables = None;
+    options.exclude_tables.clear();
+    options.split = true;
+    let tables = crate::dump::tables(conn, &options)?;
+
+    conn.dump_with(&table_options(&options, &tables, name), writer)
+}
+
+/// The options dumping the table or view `name` of `tables` alone, along with its shadow
+/// tables if it is a virtual table.
+fn table_options(options: &DumpOptions, tables: &[(String, String)], name: &str) -> DumpOptions {
+    let shadow_tables = tables
+        .iter()
+        .filter(|(table_name, create_sql)| {
+            options.format == DumpFormat::Sql
+                && crate::dump::is_shadow_table(table_name, create_sql, name)
+        })
+        .map(|(table_name, _)| table_name.clone());
+
+    let mut table = options.clone();
+    table.include_tables = Some(
+        std::iter::once(name.to_owned())
+            .chain(shadow_tables)
+            .collect(),
+    );
+    table
+}
+
+/// Lists the views selected by `options`.
+fn views(conn: &rusqlite::Connection, options: &DumpOptions) -> Result<Vec<String>, crate::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM {} WHERE type == 'view'{};",
+        options.qualified("sqlite_schema"),
+        match options.deterministic {
+            true => " ORDER BY name",
+            false => " ORDER BY rowid",
+        },
+    ))?;
+    let views = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(views
+        .into_iter()
+        .filter(|view| options.includes_table(view))
+        .collect())
+}
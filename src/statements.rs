@@ -0,0 +1,329 @@
+use crate::{
+    dump::{Chunks, Insert, Kind, Rewriter, Step, TableRows},
+    progress::Progress,
+    snapshot::Snapshot,
+    warning::skip,
+    DumpOptions, Error, StatementKind,
+};
+
+/// A statement of a dump, passed to the sink of
+/// [`ConnectionExt::dump_with_sink`](crate::ConnectionExt::dump_with_sink) along with the part
+/// of the dump it belongs to.
+///
+/// Each statement includes its terminating semicolon, without a trailing newline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DumpChunk<'a> {
+    /// A statement preparing the restore, such as `BEGIN TRANSACTION;`.
+    Prologue(&'a str),
+    /// A statement creating a table, an index, a trigger or a view.
+    Schema(&'a str),
+    /// A statement adding rows.
+    Data(&'a str),
+    /// A statement finishing the restore, such as `COMMIT;`.
+    Epilogue(&'a str),
+}
+
+impl<'a> DumpChunk<'a> {
+    pub(crate) fn new(kind: Kind, sql: &'a str) -> Self {
+        match kind {
+            Kind::Prologue => Self::Prologue(sql),
+            Kind::Schema => Self::Schema(sql),
+            Kind::Data => Self::Data(sql),
+            Kind::Epilogue => Self::Epilogue(sql),
+        }
+    }
+
+    /// The statement itself.
+    pub fn sql(&self) -> &'a str {
+        match *self {
+            Self::Prologue(sql) | Self::Schema(sql) | Self::Data(sql) | Self::Epilogue(sql) => sql,
+        }
+    }
+}
+
+/// An iterator over the statements of an SQL dump, returned by
+/// [`ConnectionExt::dump_statements_with`](crate::ConnectionExt::dump_statements_with).
+///
+/// Each item is a single statement with its terminating semicolon and without a trailing
+/// newline, in the order [`ConnectionExt::dump_with`](crate::ConnectionExt::dump_with) writes
+/// them with the SQL format. The rows of a table are read lazily, one `INSERT` statement of
+/// `rows_per_insert` rows at a time, each by its own query.
+pub struct DumpStatements<'conn> {
+    conn: &'conn rusqlite::Connection,
+    options: DumpOptions,
+    state: State,
+    /// The read transaction held until the last statement.
+    snapshot: Option<Snapshot<'conn>>,
+    /// The in-memory copy dumped in place of `conn`, if any.
+    copy: Option<rusqlite::Connection>,
+}
+
+enum State {
+    Pending,
+    Running {
+        progress: Box<Progress>,
+        steps: std::vec::IntoIter<Step>,
+        table: Option<Box<Table>>,
+        chunks: Chunks,
+        /// The statements splitting the transaction after the last `INSERT` statement.
+        pending: std::vec::IntoIter<String>,
+    },
+    Done,
+}
+
+/// The table whose rows are being read.
+struct Table {
+    rows: TableRows,
+    sql: String,
+    rewriter: Rewriter,
+    keyset: bool,
+    position: i64,
+    /// The number of rows read so far.
+    read: u64,
+    done: bool,
+}
+
+impl<'conn> DumpStatements<'conn> {
+    pub(crate) fn new(conn: &'conn rusqlite::Connection, options: &DumpOptions) -> Self {
+        Self {
+            conn,
+            options: options.clone(),
+            state: State::Pending,
+            snapshot: None,
+            copy: None,
+        }
+    }
+
+    fn next_statement(&mut self) -> Result<Option<(Kind, String)>, crate::Error> {
+        crate::cancel::check(self.options.cancellation.as_ref())?;
+
+        if let State::Pending = self.state {
+            match crate::backup::to_memory(self.conn, &self.options)? {
+                Some(copy) => {
+                    self.copy = Some(copy);
+                    self.options = crate::backup::copy_options(&self.options);
+                }
+                None => self.snapshot = Some(Snapshot::begin(self.conn, &self.options)?),
+            }
+            let conn = self.copy.as_ref().unwrap_or(self.conn);
+            crate::integrity::check(conn, &self.options)?;
+            self.options = crate::subset::close(conn, &self.options)?.into_owned();
+            crate::blob::reset_manifest(&self.options)?;
+            self.state = State::Running {
+                progress: Box::new(Progress::new(conn, &self.options)?),
+                steps: crate::dump::plan(conn, &self.options)?.into_iter(),
+                table: None,
+                chunks: Chunks::default(),
+                pending: Vec::new().into_iter(),
+            };
+        }
+        let State::Running {
+            progress,
+            steps,
+            table,
+            chunks,
+            pending,
+        } = &mut self.state
+        else {
+            return Ok(None);
+        };
+        let conn = self.copy.as_ref().unwrap_or(self.conn);
+
+        loop {
+            if let Some(sql) = pending.next() {
+                progress.statement(Kind::Data, &sql);
+                return Ok(Some((Kind::Data, sql)));
+            }
+
+            if let Some(current) = table {
+                match next_insert(conn, &self.options, progress, current)? {
+                    Some((statement, rows)) => {
+                        *pending = chunks.add(&self.options, rows).into_iter();
+                        return Ok(Some((Kind::Data, statement)));
+                    }
+                    None => {
+                        *table = None;
+                        progress.end_table();
+                    }
+                }
+            }
+
+            match steps.next() {
+                Some(Step::Statement(kind, sql)) => {
+                    progress.statement(kind, &sql);
+                    return Ok(Some((kind, sql)));
+                }
+                Some(Step::Rows(rows)) => {
+                    *table = Some(Box::new(Table::new(conn, &self.options, rows)));
+                    progress.start_table(&table.as_ref().unwrap().rows.table_name);
+                }
+                None => {
+                    progress.finish();
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+impl Table {
+    fn new(conn: &rusqlite::Connection, options: &DumpOptions, rows: TableRows) -> Self {
+        let table_name = &rows.table_name;
+        let rewriter = Rewriter::new(options, table_name, &rows.columns);
+        let columns = crate::quote::identifiers(&rows.columns, ", ");
+        let (sql, keyset) = crate::dump::page_query(conn, options, table_name, &columns);
+
+        Self {
+            rows,
+            sql,
+            rewriter,
+            keyset,
+            position: if keyset { i64::MIN } else { 0 },
+            read: 0,
+            done: false,
+        }
+    }
+}
+
+/// Reads the next `rows_per_insert` rows of `table` as a single `INSERT` statement, along with
+/// the number of rows it adds.
+fn next_insert(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &Progress,
+    table: &mut Table,
+) -> Result<Option<(String, usize)>, crate::Error> {
+    let mut insert = Insert::default();
+    // A page whose every row was skipped does not end the table.
+    while !table.done {
+        read_page(conn, options, progress, table, &mut insert)?;
+        if insert.rows > 0 {
+            let rows = insert.rows;
+            return Ok(Some((insert.take(), rows)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reads the next page of `rows_per_insert` rows of `table` into `insert`.
+///
+/// Like `dump_with`, a lenient dump leaves out the tables that cannot be read, the rows whose
+/// values cannot be read, and the rows past one that cannot be stepped over.
+fn read_page(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &Progress,
+    table: &mut Table,
+    insert: &mut Insert,
+) -> Result<(), crate::Error> {
+    let table_name = &table.rows.table_name;
+    let in_table = |row| Error::in_table(table_name, StatementKind::Data, row);
+
+    let mut stmt = match conn.prepare_cached(&table.sql) {
+        Ok(stmt) => stmt,
+        Err(err) => {
+            skip(options, table_name, None, err).map_err(in_table(None))?;
+            table.done = true;
+            return Ok(());
+        }
+    };
+    let mut rows = stmt
+        .query((table.position, options.rows_per_insert as i64))
+        .map_err(in_table(None))?;
+    let len = table.rows.columns.len();
+    let mut read = 0;
+
+    loop {
+        let index = table.read + read;
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(err) => {
+                skip(options, table_name, None, err).map_err(in_table(Some(index)))?;
+                table.done = true;
+                break;
+            }
+        };
+        crate::cancel::check(options.cancellation.as_ref())?;
+
+        read += 1;
+        if table.keyset {
+            table.position = row.get(len).map_err(in_table(Some(index)))?;
+        }
+
+        let row_values = match (0..len)
+            .map(|i| crate::dump::read_value(options, row, i))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(row_values) => row_values,
+            Err(err) => {
+                let rowid = table.keyset.then_some(table.position);
+                skip(options, table_name, rowid, err).map_err(in_table(Some(index)))?;
+                continue;
+            }
+        };
+        let blob_row = crate::blob::Row {
+            table_name,
+            rowid: table.keyset.then_some(table.position),
+            index,
+            flags: None,
+        };
+        let rewritten = table
+            .rewriter
+            .rewrite_row(conn, options, &blob_row, &row_values)?;
+        let row_values = row_values
+            .into_iter()
+            .zip(&rewritten)
+            .map(|(value, rewritten)| {
+                rewritten
+                    .as_ref()
+                    .map_or(value, rusqlite::types::ValueRef::from)
+            })
+            .collect::<Vec<_>>();
+
+        insert.push_row(options, &table.rows, &row_values);
+        progress.row();
+    }
+
+    table.read += read;
+    if !table.keyset {
+        table.position += read as i64;
+    }
+    if read < options.rows_per_insert as u64 {
+        table.done = true;
+    }
+
+    Ok(())
+}
+
+impl DumpStatements<'_> {
+    /// Returns the next statement along with the part of the dump it belongs to.
+    pub(crate) fn next_with_kind(&mut self) -> Option<Result<(Kind, String), crate::Error>> {
+        if let State::Done = self.state {
+            return None;
+        }
+
+        let token = self.options.cancellation.clone();
+        let statement = self
+            .next_statement()
+            .map_err(|err| crate::cancel::map_interrupted(token.as_ref(), err));
+        if !matches!(statement, Ok(Some(_))) {
+            self.state = State::Done;
+            self.snapshot = None;
+            self.copy = None;
+        }
+
+        statement.transpose()
+    }
+}
+
+impl Iterator for DumpStatements<'_> {
+    type Item = Result<String, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_kind()
+            .map(|statement| statement.map(|(_, sql)| sql))
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{progress::Counted, warning::Warnings, ConnectionExt, DumpOptions, DumpWarning};
+
+/// A summary of a dump, as returned by
+/// [`ConnectionExt::dump_with_stats`](crate::ConnectionExt::dump_with_stats), to log and alert
+/// on, such as when a table suddenly dumps no rows.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DumpStats {
+    /// The number of tables whose rows were dumped.
+    pub tables: usize,
+    /// The number of rows dumped from each table, including those left with none.
+    pub rows_per_table: HashMap<String, u64>,
+    /// The number of bytes written to the writer, after any compression or encryption.
+    pub bytes_written: u64,
+    /// How long the dump took.
+    pub duration: Duration,
+    /// The tables, rows and values left out of the dump, as returned by
+    /// [`ConnectionExt::dump_with_warnings`](crate::ConnectionExt::dump_with_warnings).
+    pub warnings: Vec<DumpWarning>,
+}
+
+/// Writes the dump of `conn` selected by `options` to `writer`, collecting its [`DumpStats`].
+pub(crate) fn dump<C, W>(
+    conn: &C,
+    options: &DumpOptions,
+    writer: &mut W,
+) -> Result<DumpStats, crate::Error>
+where
+    C: ConnectionExt + ?Sized,
+    W: io::Write,
+{
+    let warnings = Warnings::default();
+    let rows_per_table = Arc::new(Mutex::new(HashMap::new()));
+    let mut options = options.clone();
+    options.warnings = Some(warnings.clone());
+    // The metrics of each table are still passed on to the callback of the caller, if any.
+    let on_metrics = options.on_metrics.take();
+    let options = options.on_metrics({
+        let rows_per_table = rows_per_table.clone();
+        move |metrics| {
+            rows_per_table
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .insert(metrics.table.to_owned(), metrics.table_rows);
+            if let Some(on_metrics) = &on_metrics {
+                (on_metrics.0)(metrics);
+            }
+        }
+    });
+
+    let started = Instant::now();
+    let bytes_written = Cell::new(0);
+    conn.dump_with(&options, &mut Counted::new(writer, &bytes_written))?;
+
+    let rows_per_table =
+        std::mem::take(&mut *rows_per_table.lock().unwrap_or_else(|err| err.into_inner()));
+    Ok(DumpStats {
+        tables: rows_per_table.len(),
+        rows_per_table,
+        bytes_written: bytes_written.get(),
+        duration: started.elapsed(),
+        warnings: warnings.take(),
+    })
+}
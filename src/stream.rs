@@ -0,0 +1,116 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+};
+
+use bytes::Bytes;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::DumpOptions;
+
+/// A dump produced as a [`Stream`](futures_core::Stream) of chunks of output,
+/// returned by [`dump_stream`].
+///
+/// The dump starts on tokio's blocking thread pool when the stream is first polled, and stays
+/// at most a few chunks ahead of the consumer. Dropping the stream stops the dump.
+pub struct DumpStream {
+    state: State,
+}
+
+enum State {
+    Pending(Arc<Mutex<rusqlite::Connection>>, Box<DumpOptions>),
+    Running(
+        mpsc::Receiver<Vec<u8>>,
+        JoinHandle<Result<(), crate::Error>>,
+    ),
+    Done,
+}
+
+/// Returns the dump of `conn` as a stream of chunks, as written by
+/// [`ConnectionExt::dump_with`](crate::ConnectionExt::dump_with).
+///
+/// The connection is locked from the first poll until the dump ends.
+/// The stream must be polled from within a tokio runtime.
+pub fn dump_stream(conn: Arc<Mutex<rusqlite::Connection>>, options: DumpOptions) -> DumpStream {
+    DumpStream {
+        state: State::Pending(conn, Box::new(options)),
+    }
+}
+
+impl futures_core::Stream for DumpStream {
+    type Item = Result<Bytes, crate::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match &mut self.state {
+                State::Pending(..) => {
+                    let State::Pending(conn, options) =
+                        std::mem::replace(&mut self.state, State::Done)
+                    else {
+                        unreachable!()
+                    };
+                    let (chunks, task) = crate::async_tokio::spawn_dump(conn, *options);
+                    self.state = State::Running(chunks, task);
+                }
+                State::Running(chunks, task) => {
+                    if let Some(chunk) = ready!(chunks.poll_recv(cx)) {
+                        return Poll::Ready(Some(Ok(Bytes::from(chunk))));
+                    }
+
+                    let result = crate::async_tokio::join(ready!(Pin::new(task).poll(cx)));
+                    self.state = State::Done;
+                    return Poll::Ready(result.err().map(Err));
+                }
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        sync::{Arc, Mutex},
+    };
+
+    use futures_core::Stream;
+
+    use crate::{ConnectionExt, DumpOptions};
+
+    #[test]
+    fn test_dump_stream() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5000)
+INSERT INTO users (name) SELECT 'user ' || i FROM n;"#,
+        )?;
+
+        let mut expected = Vec::new();
+        conn.dump(&mut expected)?;
+
+        let conn = Arc::new(Mutex::new(conn));
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+        let mut stream = super::dump_stream(Arc::clone(&conn), DumpOptions::new());
+        let chunks = runtime.block_on(async {
+            let mut chunks = Vec::new();
+            while let Some(chunk) =
+                std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+            {
+                chunks.push(chunk?);
+            }
+            Ok::<_, crate::Error>(chunks)
+        })?;
+
+        assert!(chunks.len() > 1);
+        assert_eq!(expected, chunks.concat());
+
+        Ok(())
+    }
+}
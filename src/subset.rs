@@ -0,0 +1,191 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+};
+
+use crate::{quote, DumpOptions};
+
+/// A foreign key of a child table, as the pairs of child and parent columns it matches.
+struct ForeignKey {
+    parent: String,
+    columns: Vec<(String, String)>,
+}
+
+/// Rewrites the row filters of `options` so that every row referenced through a foreign key
+/// by a dumped row is dumped as well, when
+/// [`follow_foreign_keys`](DumpOptions::follow_foreign_keys) is enabled.
+///
+/// Starting from the rows selected by the filters and samples, the parent rows they reference
+/// are added until no new row is found. Tables that gain rows get their filter replaced by the
+/// list of their selected rowids.
+pub(crate) fn close<'a>(
+    conn: &rusqlite::Connection,
+    options: &'a DumpOptions,
+) -> Result<Cow<'a, DumpOptions>, crate::Error> {
+    if !options.follow_foreign_keys || options.schema_only {
+        return Ok(Cow::Borrowed(options));
+    }
+
+    let tables = crate::dump::data_tables(conn, options)?;
+    let find = |name: &str| tables.iter().find(|table| table.eq_ignore_ascii_case(name));
+
+    let mut foreign_keys = HashMap::new();
+    for table in &tables {
+        let keys = foreign_keys_of(conn, options, table)?
+            .into_iter()
+            .filter_map(|key| {
+                let parent = find(&key.parent)?.clone();
+                Some(ForeignKey { parent, ..key })
+            })
+            .collect::<Vec<_>>();
+        foreign_keys.insert(table.as_str(), keys);
+    }
+
+    let mut selected: HashMap<&str, BTreeSet<i64>> = HashMap::new();
+    let mut pending = Vec::new();
+    for table in &tables {
+        if !crate::dump::has_rowid(conn, options, table) {
+            continue;
+        }
+
+        let sql = crate::dump::select(conn, options, table, "rowid");
+        let mut stmt = conn.prepare(&sql)?;
+        let rowids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<BTreeSet<i64>, _>>()?;
+
+        pending.push((table.as_str(), rowids.clone()));
+        selected.insert(table.as_str(), rowids);
+    }
+    let initial = selected.clone();
+
+    while let Some((table, rowids)) = pending.pop() {
+        if rowids.is_empty() {
+            continue;
+        }
+        let list = rowid_list(&rowids);
+
+        for key in &foreign_keys[table] {
+            let Some(parent_rowids) = selected.get_mut(key.parent.as_str()) else {
+                continue;
+            };
+
+            let on = key
+                .columns
+                .iter()
+                .map(|(from, to)| {
+                    format!(
+                        "p.{} = c.{}",
+                        quote::identifier(to),
+                        quote::identifier(from)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let sql = format!(
+                "SELECT p.rowid FROM {} AS p JOIN {} AS c ON {on} WHERE c.rowid IN ({list});",
+                options.qualified(&key.parent),
+                options.qualified(table)
+            );
+
+            let mut stmt = conn.prepare(&sql)?;
+            let mut added = BTreeSet::new();
+            for rowid in stmt.query_map([], |row| row.get(0))? {
+                let rowid = rowid?;
+                if parent_rowids.insert(rowid) {
+                    added.insert(rowid);
+                }
+            }
+
+            if !added.is_empty() {
+                pending.push((key.parent.as_str(), added));
+            }
+        }
+    }
+
+    // The filters are replaced by conditions that already account for any sample.
+    let mut closed = options.clone();
+    closed.where_clauses.clear();
+    closed.since = None;
+    closed.sample_fraction = None;
+    closed.max_rows_per_table = None;
+
+    for table in &tables {
+        let filter = match selected.get(table.as_str()) {
+            Some(rowids) if initial[table.as_str()] != *rowids => {
+                Some(format!("rowid IN ({})", rowid_list(rowids)))
+            }
+            _ => crate::dump::filter(conn, options, table),
+        };
+
+        if let Some(filter) = filter {
+            closed.where_clauses.push((table.clone(), filter));
+        }
+    }
+
+    Ok(Cow::Owned(closed))
+}
+
+fn foreign_keys_of(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table: &str,
+) -> Result<Vec<ForeignKey>, crate::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "PRAGMA {}.foreign_key_list({});",
+        quote::identifier(&options.schema),
+        quote::identifier(table)
+    ))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>("id")?,
+                row.get::<_, String>("table")?,
+                row.get::<_, String>("from")?,
+                row.get::<_, Option<String>>("to")?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut keys: Vec<(i64, ForeignKey)> = Vec::new();
+    for (id, parent, from, to) in rows {
+        // A foreign key without parent columns references the parent's primary key.
+        let to = match to {
+            Some(to) => to,
+            None => {
+                let seq = keys
+                    .iter()
+                    .find(|(key, _)| *key == id)
+                    .map_or(0, |(_, key)| key.columns.len());
+                match crate::dump::primary_key(conn, options, &parent)?
+                    .into_iter()
+                    .nth(seq)
+                {
+                    Some(to) => to,
+                    None => "rowid".to_string(),
+                }
+            }
+        };
+
+        match keys.iter_mut().find(|(key, _)| *key == id) {
+            Some((_, key)) => key.columns.push((from, to)),
+            None => keys.push((
+                id,
+                ForeignKey {
+                    parent,
+                    columns: vec![(from, to)],
+                },
+            )),
+        }
+    }
+
+    Ok(keys.into_iter().map(|(_, key)| key).collect())
+}
+
+fn rowid_list(rowids: &BTreeSet<i64>) -> String {
+    rowids
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
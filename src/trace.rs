@@ -0,0 +1,101 @@
+#[cfg(feature = "tracing")]
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use crate::{dump::Kind, DumpOptions};
+
+/// The spans and events of a dump, emitted through `tracing` when the `tracing` feature is
+/// enabled: a `dump` span, a `table` span within it for the rows of each table, and events
+/// once the schema is written, once a table is written, on each commit and once the dump is
+/// finished.
+///
+/// The spans are never entered, so that a dump read statement by statement does not leave
+/// them open over the code of the caller. They start when created and end when dropped.
+pub(crate) struct Trace {
+    #[cfg(feature = "tracing")]
+    dump: tracing::Span,
+    #[cfg(feature = "tracing")]
+    table: RefCell<tracing::Span>,
+    /// Whether the dump writes a schema not written yet.
+    #[cfg(feature = "tracing")]
+    schema_pending: Cell<bool>,
+}
+
+#[cfg(feature = "tracing")]
+impl Trace {
+    pub(crate) fn new(options: &DumpOptions) -> Self {
+        Self {
+            dump: tracing::info_span!(
+                "dump",
+                schema = %options.schema,
+                format = ?options.format,
+            ),
+            table: RefCell::new(tracing::Span::none()),
+            schema_pending: Cell::new(
+                options.format == crate::DumpFormat::Sql && !options.data_only,
+            ),
+        }
+    }
+
+    pub(crate) const ENABLED: bool = true;
+
+    pub(crate) fn start_table(&self, table_name: &str) {
+        *self.table.borrow_mut() =
+            tracing::info_span!(parent: &self.dump, "table", table = %table_name);
+    }
+
+    pub(crate) fn end_table(&self, table_name: &str, rows: u64, bytes: u64, duration: Duration) {
+        let span = self.table.replace(tracing::Span::none());
+        tracing::info!(
+            parent: &span,
+            table = %table_name,
+            rows,
+            bytes,
+            duration_ms = duration.as_millis() as u64,
+            "table written"
+        );
+    }
+
+    pub(crate) fn statement(&self, kind: Kind, sql: &str, rows_written: u64) {
+        if kind == Kind::Epilogue {
+            self.schema_written();
+        }
+        if sql == "COMMIT;" {
+            tracing::debug!(parent: &self.dump, rows_written, "commit");
+        }
+    }
+
+    pub(crate) fn finish(&self, rows_written: u64, bytes_written: u64, elapsed: Duration) {
+        self.schema_written();
+        tracing::info!(
+            parent: &self.dump,
+            rows_written,
+            bytes_written,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "dump finished"
+        );
+    }
+
+    fn schema_written(&self) {
+        if self.schema_pending.replace(false) {
+            tracing::info!(parent: &self.dump, "schema written");
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Trace {
+    pub(crate) fn new(_: &DumpOptions) -> Self {
+        Self {}
+    }
+
+    pub(crate) const ENABLED: bool = false;
+
+    pub(crate) fn start_table(&self, _: &str) {}
+
+    pub(crate) fn end_table(&self, _: &str, _: u64, _: u64, _: Duration) {}
+
+    pub(crate) fn statement(&self, _: Kind, _: &str, _: u64) {}
+
+    pub(crate) fn finish(&self, _: u64, _: u64, _: Duration) {}
+}
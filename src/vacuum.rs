@@ -0,0 +1,27 @@
+use std::{io, path::Path};
+
+use crate::{cancel, integrity, quote, DumpOptions};
+
+/// Writes a compacted copy of the dumped schema to a new database file at `path` with
+/// `VACUUM INTO`.
+pub(crate) fn vacuum_into(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    path: &Path,
+) -> Result<(), crate::Error> {
+    let token = options.cancellation.as_ref();
+    let result = (|| {
+        cancel::check(token)?;
+        let path = path.to_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8")
+        })?;
+        integrity::check(conn, options)?;
+        conn.execute(
+            &format!("VACUUM {} INTO ?1;", quote::identifier(&options.schema)),
+            [path],
+        )?;
+        Ok(())
+    })();
+
+    result.map_err(|err| cancel::map_interrupted(token, err))
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashSet, io};
+
+use rusqlite::fallible_iterator::FallibleIterator as _;
+
+use crate::restore::Statements;
+
+/// Checks that every statement of the SQL dump read from `reader` is valid, without restoring
+/// it, and fails with [`Error::Statement`](crate::Error::Statement) on the first one that is
+/// not.
+///
+/// Each statement is prepared against an empty in-memory database. Statements changing the
+/// schema, such as `CREATE TABLE`, `PRAGMA writable_schema` and `ANALYZE`, which creates the
+/// statistics tables, are executed there, so that the tables they create are known to the
+/// statements inserting rows into them, as are those inserting into the shadow tables of
+/// virtual tables, which read their configuration from them. No other rows are inserted, so
+/// constraints on the values of rows are not checked.
+///
+/// ```
+/// let dump = "CREATE TABLE t (a);\nINSERT INTO t VALUES(1);\nINSERT INTO u VALUES(2);\n";
+///
+/// let err = dumpqlite::validate(&mut dump.as_bytes()).unwrap_err();
+/// assert_eq!(
+///     "invalid statement at line 3: no such table: u",
+///     err.to_string()
+/// );
+/// # Ok::<(), dumpqlite::Error>(())
+/// ```
+pub fn validate<R: io::Read>(reader: &mut R) -> Result<(), crate::Error> {
+    let mut checker = Checker {
+        conn: rusqlite::Connection::open_in_memory()?,
+        shadow_tables: HashSet::new(),
+    };
+
+    let mut statements = Statements::new(io::BufReader::new(reader));
+    while let Some(sql) = statements.next() {
+        let sql = sql?;
+        checker
+            .check(&sql)
+            .map_err(|source| crate::Error::Statement {
+                line: statements.line(),
+                sql,
+                source,
+            })?;
+    }
+
+    Ok(())
+}
+
+/// The empty database statements are prepared against.
+struct Checker {
+    conn: rusqlite::Connection,
+    /// The lowercase names of the shadow tables of the virtual tables created so far.
+    shadow_tables: HashSet<String>,
+}
+
+impl Checker {
+    /// Prepares every statement of `sql`, executing those changing the schema.
+    fn check(&mut self, sql: &str) -> rusqlite::Result<()> {
+        let mut batch = rusqlite::Batch::new(&self.conn, sql);
+        let mut schema_changed = false;
+        while let Some(mut stmt) = batch.next()? {
+            let Some(sql) = stmt.expanded_sql() else {
+                continue;
+            };
+            // Leading comments are skipped, such as the one naming the table of a transaction
+            // per table.
+            let words = sql
+                .lines()
+                .filter(|line| !line.trim_start().starts_with("--"))
+                .flat_map(str::split_whitespace)
+                .take(3)
+                .collect::<Vec<_>>();
+            let execute = match words[..] {
+                [keyword, ..]
+                    if ["CREATE", "DROP", "ALTER", "PRAGMA", "ANALYZE"]
+                        .iter()
+                        .any(|schema| keyword.eq_ignore_ascii_case(schema)) =>
+                {
+                    schema_changed = true;
+                    true
+                }
+                [insert, into, table]
+                    if insert.eq_ignore_ascii_case("INSERT")
+                        && into.eq_ignore_ascii_case("INTO") =>
+                {
+                    let table = table
+                        .split('(')
+                        .next()
+                        .unwrap_or_default()
+                        .trim_matches(['"', '\'', '`', '[', ']'])
+                        .to_ascii_lowercase();
+                    // Virtual tables are restored by inserting their schema rows directly, and
+                    // read their configuration from their shadow tables once they are used.
+                    let schema = table == "sqlite_schema" || table == "sqlite_master";
+                    schema_changed |= schema;
+                    schema || self.shadow_tables.contains(&table)
+                }
+                _ => false,
+            };
+
+            if execute {
+                let mut rows = stmt.raw_query();
+                while rows.next()?.is_some() {}
+            }
+        }
+
+        if schema_changed {
+            self.shadow_tables = shadow_tables(&self.conn)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the lowercase names of the shadow tables of the virtual tables of `conn`.
+fn shadow_tables(conn: &rusqlite::Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_schema WHERE type = 'table';")?;
+    let tables = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let virtual_tables = tables
+        .iter()
+        .filter(|(_, sql)| {
+            sql.as_deref()
+                .is_some_and(|sql| sql.starts_with("CREATE VIRTUAL TABLE"))
+        })
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+
+    Ok(tables
+        .iter()
+        .filter(|(name, sql)| {
+            let sql = sql.as_deref().unwrap_or_default();
+            virtual_tables
+                .iter()
+                .any(|virtual_table| crate::dump::is_shadow_table(name, sql, virtual_table))
+        })
+        .map(|(name, _)| name.to_ascii_lowercase())
+        .collect())
+}
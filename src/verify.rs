@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use crate::{dump, hash, sha256::Sha256, ConnectionExt, DumpOptions, Error, StatementKind};
+
+/// The outcome of checking a dump against its database, as returned by
+/// [`ConnectionExt::verify_dump`](crate::ConnectionExt::verify_dump).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct VerifyReport {
+    /// The tables whose restored schema or rows differ from those of the database, by name.
+    pub differences: Vec<TableDifference>,
+}
+
+impl VerifyReport {
+    /// Whether the dump restores every table as it is in the database.
+    pub fn is_ok(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// How a table restored from a dump differs from the table of the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TableDifference {
+    /// The table is not restored by the dump.
+    Missing { table: String },
+    /// The table is restored by the dump but is not in the database.
+    Unexpected { table: String },
+    /// The table is restored with another `CREATE TABLE` statement.
+    Schema {
+        table: String,
+        expected: String,
+        actual: String,
+    },
+    /// The table is restored with other rows. The counts may be equal when only values differ.
+    Rows {
+        table: String,
+        expected_rows: u64,
+        actual_rows: u64,
+    },
+}
+
+impl TableDifference {
+    /// The table that differs.
+    pub fn table(&self) -> &str {
+        match self {
+            TableDifference::Missing { table }
+            | TableDifference::Unexpected { table }
+            | TableDifference::Schema { table, .. }
+            | TableDifference::Rows { table, .. } => table,
+        }
+    }
+}
+
+/// Restores `dump` into an in-memory database and compares each table selected by `options`
+/// with the one restored, by schema, then by rows in the order of their key.
+pub(crate) fn verify(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    dump: &str,
+) -> Result<VerifyReport, crate::Error> {
+    let mut restored = rusqlite::Connection::open_in_memory()?;
+    ConnectionExt::restore(&mut restored, &mut dump.as_bytes())?;
+    // The restored database holds the tables of the dump only, with their rows already
+    // filtered, so it is read whole.
+    let restored_options = DumpOptions::default();
+
+    let expected = dump::tables(conn, options)?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    let actual = dump::tables(&restored, &restored_options)?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+    let mut differences = Vec::new();
+    for (table, expected_sql) in &expected {
+        let Some(actual_sql) = actual.get(table) else {
+            differences.push(TableDifference::Missing {
+                table: table.clone(),
+            });
+            continue;
+        };
+        if actual_sql != expected_sql {
+            differences.push(TableDifference::Schema {
+                table: table.clone(),
+                expected: expected_sql.clone(),
+                actual: actual_sql.clone(),
+            });
+            continue;
+        }
+        // The rows of virtual tables are compared through their shadow tables.
+        if expected_sql.starts_with("CREATE VIRTUAL TABLE") {
+            continue;
+        }
+
+        let (expected_hash, expected_rows) = hash_table(conn, options, table)?;
+        let (actual_hash, actual_rows) = hash_table(&restored, &restored_options, table)?;
+        if expected_hash != actual_hash {
+            differences.push(TableDifference::Rows {
+                table: table.clone(),
+                expected_rows,
+                actual_rows,
+            });
+        }
+    }
+    for table in actual.keys() {
+        if !expected.contains_key(table) {
+            differences.push(TableDifference::Unexpected {
+                table: table.clone(),
+            });
+        }
+    }
+    differences.sort_by(|a, b| a.table().cmp(b.table()));
+
+    Ok(VerifyReport { differences })
+}
+
+/// Returns the hash of the rows of `table_name` and their number.
+fn hash_table(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    table_name: &str,
+) -> Result<([u8; 32], u64), crate::Error> {
+    let mut hash = Sha256::new();
+    let rows = hash::hash_rows(conn, options, table_name, &mut hash).map_err(Error::in_table(
+        table_name,
+        StatementKind::Data,
+        None,
+    ))?;
+
+    Ok((hash.finish(), rows))
+}
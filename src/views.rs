@@ -0,0 +1,61 @@
+use crate::migration::{tokenize, unquote};
+
+/// Orders `views` so that each view comes after the views it selects from, keeping them in
+/// the order they were given otherwise. `view` returns the name and the `CREATE VIEW`
+/// statement of a view.
+///
+/// The views a view selects from are the names of the other views among the identifiers of its
+/// statement. A column sharing the name of a view may add a dependency that is not there, and
+/// views that depend on each other that way stay in the order they were given.
+pub(crate) fn in_dependency_order<T>(views: Vec<T>, view: impl Fn(&T) -> (&str, &str)) -> Vec<T> {
+    let names = views
+        .iter()
+        .map(|v| view(v).0.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let dependencies = views
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let identifiers = tokenize(view(v).1)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(_, token)| !token.starts_with('\''))
+                .map(|(_, token)| unquote(token).to_ascii_lowercase())
+                .collect::<Vec<_>>();
+            (0..names.len())
+                .filter(|&j| j != i && identifiers.contains(&names[j]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut state = vec![State::Pending; views.len()];
+    let mut order = Vec::with_capacity(views.len());
+    for i in 0..views.len() {
+        visit(i, &dependencies, &mut state, &mut order);
+    }
+
+    let mut views = views.into_iter().map(Some).collect::<Vec<_>>();
+    order.into_iter().filter_map(|i| views[i].take()).collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    Visiting,
+    Done,
+}
+
+/// Appends the views `i` depends on to `order`, then `i` itself, leaving out the dependencies
+/// that lead back to a view being visited.
+fn visit(i: usize, dependencies: &[Vec<usize>], state: &mut [State], order: &mut Vec<usize>) {
+    if state[i] != State::Pending {
+        return;
+    }
+
+    state[i] = State::Visiting;
+    for &j in &dependencies[i] {
+        visit(j, dependencies, state, order);
+    }
+    state[i] = State::Done;
+    order.push(i);
+}
@@ -0,0 +1,87 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use crate::DumpOptions;
+
+/// Something left out of a lenient dump because it failed to read, or of any dump because it
+/// is a BLOB larger than [`DumpOptions::max_blob_size`],
+/// as returned by [`ConnectionExt::dump_with_warnings`](crate::ConnectionExt::dump_with_warnings).
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DumpWarning {
+    /// The table that was left out, or whose row was left out.
+    pub table: String,
+    /// The rowid of the row that was left out, if known.
+    ///
+    /// `None` when the whole table, or the rest of it, was left out.
+    pub rowid: Option<i64>,
+    /// Why the table or row could not be read, or the value was left out.
+    pub cause: rusqlite::Error,
+}
+
+/// The warnings collected by a lenient dump.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Warnings(Arc<Mutex<Collected>>);
+
+#[derive(Debug, Default)]
+struct Collected {
+    warnings: Vec<DumpWarning>,
+    // Tables are read more than once, to count or filter their rows,
+    // so the same failure is reported once only.
+    seen: HashSet<(String, Option<i64>, String)>,
+}
+
+impl Warnings {
+    fn push(&self, warning: DumpWarning) {
+        let mut collected = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        let key = (
+            warning.table.clone(),
+            warning.rowid,
+            warning.cause.to_string(),
+        );
+        if collected.seen.insert(key) {
+            collected.warnings.push(warning);
+        }
+    }
+
+    pub(crate) fn take(&self) -> Vec<DumpWarning> {
+        let mut collected = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        std::mem::take(&mut collected.warnings)
+    }
+}
+
+/// Goes on past the table or row of `table` that failed to read with `err`, if the dump
+/// is not [`strict`](DumpOptions::strict), and returns `err` for the caller to fail with otherwise.
+///
+/// Statements interrupted by a cancellation are never skipped, so that the dump still stops.
+pub(crate) fn skip(
+    options: &DumpOptions,
+    table: &str,
+    rowid: Option<i64>,
+    err: rusqlite::Error,
+) -> Result<(), rusqlite::Error> {
+    let interrupted = matches!(
+        &err,
+        rusqlite::Error::SqliteFailure(failure, _)
+            if failure.code == rusqlite::ErrorCode::OperationInterrupted
+    );
+    if options.strict || interrupted {
+        return Err(err);
+    }
+
+    warn(options, table, rowid, err);
+    Ok(())
+}
+
+/// Reports that something of `table` was left out of the dump because of `cause`.
+pub(crate) fn warn(options: &DumpOptions, table: &str, rowid: Option<i64>, cause: rusqlite::Error) {
+    if let Some(warnings) = &options.warnings {
+        warnings.push(DumpWarning {
+            table: table.to_owned(),
+            rowid,
+            cause,
+        });
+    }
+}
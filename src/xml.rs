@@ -0,0 +1,126 @@
+use std::io;
+
+use crate::DumpOptions;
+
+/// Writes the database as an XML document, streaming one `<row>` element at a time:
+///
+/// ```xml
+/// <database>
+///   <table name="users">
+///     <row><col name="id">1</col><col name="name" null="true"/></row>
+///   </table>
+/// </database>
+/// ```
+pub(crate) fn dump<W: io::Write>(
+    conn: &rusqlite::Connection,
+    options: &DumpOptions,
+    progress: &crate::progress::Progress,
+    writer: &mut W,
+) -> Result<(), crate::Error> {
+    let tables = crate::dump::data_tables(conn, options)?;
+
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(writer, "<database>")?;
+
+    for table_name in &tables {
+        let columns = crate::dump::columns(conn, options, table_name)?;
+        let names = columns
+            .iter()
+            .map(|column| escape(column))
+            .collect::<Vec<_>>();
+
+        if options.schema_only {
+            writeln!(writer, "  <table name=\"{}\"/>", escape(table_name))?;
+            continue;
+        }
+
+        writeln!(writer, "  <table name=\"{}\">", escape(table_name))?;
+
+        crate::dump::for_each_row(
+            conn,
+            options,
+            progress,
+            table_name,
+            &columns,
+            None,
+            |values| {
+                write!(writer, "    <row>")?;
+                for (name, &value) in names.iter().zip(values) {
+                    if value == rusqlite::types::ValueRef::Null {
+                        write!(writer, "<col name=\"{name}\" null=\"true\"/>")?;
+                    } else {
+                        let value = crate::dump::format_plain_value(value);
+                        write!(writer, "<col name=\"{name}\">{}</col>", escape(&value))?;
+                    }
+                }
+                writeln!(writer, "</row>")?;
+
+                Ok(())
+            },
+        )?;
+
+        writeln!(writer, "  </table>")?;
+    }
+
+    writeln!(writer, "</database>")?;
+
+    Ok(())
+}
+
+/// Escapes the characters that are special in XML text and attribute values.
+///
+/// Control characters that XML 1.0 cannot represent are replaced with U+FFFD.
+fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            '\t' | '\n' => output.push(c),
+            '\r' => output.push_str("&#13;"),
+            c if c < ' ' => output.push(char::REPLACEMENT_CHARACTER),
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConnectionExt, DumpFormat, DumpOptions};
+
+    #[test]
+    fn test_dump_xml() -> Result<(), crate::Error> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            r#"
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, avatar BLOB);
+CREATE TABLE tags (name TEXT);
+INSERT INTO users VALUES (1, 'Tom & "Jerry" <3', X'cafe'), (2, char(13, 10, 0), NULL);"#,
+        )?;
+
+        let mut writer = Vec::new();
+        let options = DumpOptions::new().format(DumpFormat::Xml);
+        conn.dump_with(&options, &mut writer)?;
+
+        let expected = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<database>
+  <table name=\"users\">
+    <row><col name=\"id\">1</col><col name=\"name\">Tom &amp; &quot;Jerry&quot; &lt;3</col><col name=\"avatar\">cafe</col></row>
+    <row><col name=\"id\">2</col><col name=\"name\">&#13;\n\u{fffd}</col><col name=\"avatar\" null=\"true\"/></row>
+  </table>
+  <table name=\"tags\">
+  </table>
+</database>
+";
+        assert_eq!(expected, std::str::from_utf8(&writer).unwrap());
+
+        Ok(())
+    }
+}